@@ -0,0 +1,36 @@
+use ai_playground::{anchors::AnchorConfig, nms::non_max_suppression, palm};
+use criterion::{criterion_group, criterion_main, Criterion};
+use ndarray::Array3;
+
+/// Deterministic stand-in for model output, spread over `-1..1`.
+fn noise(shape: (usize, usize, usize), seed: f32) -> Array3<f32> {
+    let mut i = 0.0;
+    Array3::from_shape_simple_fn(shape, || {
+        i += 1.0;
+        (i * 12.9898 + seed).sin()
+    })
+}
+
+fn postprocess(c: &mut Criterion) {
+    let config = AnchorConfig::palm_detection();
+    c.bench_function("palm anchor generation", |b| b.iter(|| config.generate()));
+
+    let anchors = config.generate();
+    let n = anchors.len();
+    let input = (config.input_width, config.input_height);
+    let regressors = noise((1, n, 18), 0.0) * 20.0;
+    let scores = noise((1, n, 1), 1.0);
+    c.bench_function("palm decode", |b| {
+        b.iter(|| palm::decode(regressors.view(), scores.view(), &anchors, input))
+    });
+
+    let palms = palm::decode(regressors.view(), scores.view(), &anchors, input);
+    for threshold in [0.9, 0.5, 0.0] {
+        c.bench_function(&format!("nms score >= {}", threshold), |b| {
+            b.iter(|| non_max_suppression(palms.clone(), threshold, 0.25))
+        });
+    }
+}
+
+criterion_group!(benches, postprocess);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+use ai_playground::preprocess::{image_to_tensor, Normalization, Resizer};
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::RgbImage;
+
+fn frame(w: u32, h: u32) -> RgbImage {
+    RgbImage::from_fn(w, h, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+    })
+}
+
+fn preprocess(c: &mut Criterion) {
+    for (w, h) in [(640, 480), (1280, 720), (1920, 1080)] {
+        let img = frame(w, h);
+        let mut group = c.benchmark_group(format!("preprocess {}x{}", w, h));
+        group.bench_function("image resize + normalize", |b| {
+            b.iter(|| image_to_tensor(&img, 192, 192, Normalization::UNIT))
+        });
+        let mut resizer = Resizer::new(192, 192);
+        group.bench_function("fused simd", |b| {
+            b.iter(|| resizer.resize(&img, Normalization::UNIT))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, preprocess);
+criterion_main!(benches);
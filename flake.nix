@@ -58,6 +58,10 @@
                 wayland
 
                 libv4l libv4l.dev
+                xdotool
+                dbus
+                xorg.libxcb
+                alsa-lib
                 opencv4
                 linuxHeaders
               ] ++ (with pkgs.xorg; [
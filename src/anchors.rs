@@ -0,0 +1,160 @@
+//! SSD anchor generation, following MediaPipe's `SsdAnchorsCalculator` for
+//! models with fixed-size anchors (BlazePalm, BlazeFace and friends).
+
+/// Anchor center in coordinates normalized to the model input, with its size
+/// as a multiple of the input size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    pub x_center: f32,
+    pub y_center: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnchorConfig {
+    pub input_width: usize,
+    pub input_height: usize,
+    /// Stride of every output layer. Consecutive layers with the same stride
+    /// share a feature map, so their anchors are emitted cell by cell together.
+    pub strides: Vec<usize>,
+    /// Anchors each layer contributes to every cell of its feature map.
+    pub anchors_per_layer: usize,
+    /// Position of the anchor center within its cell, in cell units.
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl AnchorConfig {
+    /// `palm_detection_lite` and `palm_detection_full`: 192×192, 2016 anchors.
+    pub fn palm_detection() -> Self {
+        Self {
+            input_width: 192,
+            input_height: 192,
+            strides: vec![8, 16, 16, 16],
+            anchors_per_layer: 2,
+            offset_x: 0.5,
+            offset_y: 0.5,
+        }
+    }
+
+    /// BlazePose person detector: 224×224, 2254 anchors.
+    pub fn pose_detection() -> Self {
+        Self {
+            input_width: 224,
+            input_height: 224,
+            strides: vec![8, 16, 32, 32, 32],
+            anchors_per_layer: 2,
+            offset_x: 0.5,
+            offset_y: 0.5,
+        }
+    }
+
+    /// Short-range BlazeFace: 128×128, 896 anchors.
+    pub fn face_detection_short_range() -> Self {
+        Self {
+            input_width: 128,
+            input_height: 128,
+            strides: vec![8, 16, 16, 16],
+            anchors_per_layer: 2,
+            offset_x: 0.5,
+            offset_y: 0.5,
+        }
+    }
+
+    pub fn generate(&self) -> Vec<Anchor> {
+        let mut anchors = vec![];
+        let mut layer = 0;
+        while layer < self.strides.len() {
+            let stride = self.strides[layer];
+            let mut repeats = 0;
+            while layer < self.strides.len() && self.strides[layer] == stride {
+                repeats += self.anchors_per_layer;
+                layer += 1;
+            }
+
+            let rows = self.input_height.div_ceil(stride);
+            let cols = self.input_width.div_ceil(stride);
+            for y in 0..rows {
+                for x in 0..cols {
+                    let anchor = Anchor {
+                        x_center: (x as f32 + self.offset_x) / cols as f32,
+                        y_center: (y as f32 + self.offset_y) / rows as f32,
+                        w: 1.0,
+                        h: 1.0,
+                    };
+                    anchors.extend(std::iter::repeat_n(anchor, repeats));
+                }
+            }
+        }
+        anchors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn anchor_counts() {
+        assert_eq!(AnchorConfig::palm_detection().generate().len(), 2016);
+        assert_eq!(AnchorConfig::pose_detection().generate().len(), 2254);
+        assert_eq!(
+            AnchorConfig::face_detection_short_range().generate().len(),
+            896
+        );
+    }
+
+    #[test]
+    fn palm_reference_values() {
+        let anchors = AnchorConfig::palm_detection().generate();
+        // First feature map: 24×24 cells, 2 anchors each.
+        for anchor in &anchors[0..2] {
+            assert_close(anchor.x_center, 0.5 / 24.0);
+            assert_close(anchor.y_center, 0.5 / 24.0);
+        }
+        assert_close(anchors[2].x_center, 1.5 / 24.0);
+        assert_close(anchors[2 * 24].y_center, 1.5 / 24.0);
+        // Second feature map: 12×12 cells, 3 layers of 2 anchors each.
+        for anchor in &anchors[1152..1158] {
+            assert_close(anchor.x_center, 0.5 / 12.0);
+            assert_close(anchor.y_center, 0.5 / 12.0);
+        }
+        assert_close(anchors[1158].x_center, 1.5 / 12.0);
+        let last = anchors[2015];
+        assert_close(last.x_center, 11.5 / 12.0);
+        assert_close(last.y_center, 11.5 / 12.0);
+        assert!(anchors.iter().all(|a| a.w == 1.0 && a.h == 1.0));
+    }
+
+    /// The grid that was hard-coded for the palm model before anchors were
+    /// configurable, in pixels relative to the input center.
+    fn legacy_palm_grid() -> Vec<(f32, f32)> {
+        let mut grid = vec![];
+        for (rows, repeats, cell_width) in [(24usize, 2usize, 8.0f32), (12, 6, 16.0)] {
+            for j in 0..repeats * rows * rows {
+                let half = (rows - 1) as f32 * 0.5;
+                grid.push((
+                    cell_width * (((j / repeats) % rows) as f32 - half),
+                    cell_width * ((j / repeats / rows) as f32 - half),
+                ));
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn palm_matches_legacy_grid() {
+        let anchors = AnchorConfig::palm_detection().generate();
+        let legacy = legacy_palm_grid();
+        assert_eq!(anchors.len(), legacy.len());
+        for (anchor, (x, y)) in anchors.iter().zip(legacy) {
+            assert_close(anchor.x_center * 192.0 - 96.0, x);
+            assert_close(anchor.y_center * 192.0 - 96.0, y);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BBox {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl BBox {
+    fn to_xyxy(self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.x + self.w, self.y + self.h)
+    }
+
+    pub fn intersection(&self, other: BBox) -> f32 {
+        let (x1, y1, x2, y2) = self.to_xyxy();
+        let (x3, y3, x4, y4) = other.to_xyxy();
+        let w = x2.min(x4) - x1.max(x3);
+        let h = y2.min(y4) - y1.max(y3);
+        if w < 0.0 || h < 0.0 {
+            return 0.0;
+        }
+        w * h
+    }
+
+    pub fn area(&self) -> f32 {
+        self.w * self.h
+    }
+
+    pub fn union(&self, other: BBox) -> f32 {
+        self.area() + other.area() - self.intersection(other)
+    }
+
+    pub fn iou(&self, other: BBox) -> f32 {
+        self.intersection(other) / self.union(other)
+    }
+
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            x: self.x + x,
+            y: self.y + y,
+            w: self.w,
+            h: self.h,
+        }
+    }
+
+    pub fn scale(&self, scale_x: f32, scale_y: f32) -> Self {
+        Self {
+            x: self.x * scale_x,
+            y: self.y * scale_y,
+            w: self.w * scale_x,
+            h: self.h * scale_y,
+        }
+    }
+}
+
+/// Box rotated by `angle` radians around its center. Positive angles turn
+/// the box's up direction clockwise on screen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct RotatedRect {
+    pub cx: f32,
+    pub cy: f32,
+    pub w: f32,
+    pub h: f32,
+    pub angle: f32,
+}
+
+impl From<BBox> for RotatedRect {
+    fn from(bbox: BBox) -> Self {
+        Self {
+            cx: bbox.x + bbox.w / 2.0,
+            cy: bbox.y + bbox.h / 2.0,
+            w: bbox.w,
+            h: bbox.h,
+            angle: 0.0,
+        }
+    }
+}
+
+impl RotatedRect {
+    /// Maps `(u, v)` in `0..1` across the rect onto the image.
+    pub fn to_image(&self, u: f32, v: f32) -> (f32, f32) {
+        let (dx, dy) = ((u - 0.5) * self.w, (v - 0.5) * self.h);
+        let (sin, cos) = self.angle.sin_cos();
+        (self.cx + dx * cos - dy * sin, self.cy + dx * sin + dy * cos)
+    }
+
+    /// Top left, top right, bottom right and bottom left, as rotated.
+    pub fn corners(&self) -> [(f32, f32); 4] {
+        [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(|(u, v)| self.to_image(u, v))
+    }
+}
+
+/// Anything with a box and a confidence, so it can go through NMS.
+pub trait Detection: Copy {
+    fn bbox(&self) -> BBox;
+    fn score(&self) -> f32;
+    fn with_score(self, score: f32) -> Self;
+    /// One detection from overlapping ones, best first, with their boxes
+    /// and keypoints averaged by score and the best one's score.
+    fn blend(cluster: &[Self]) -> Self;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn rotated_corners() {
+        let bbox = BBox {
+            x: 10.0,
+            y: 20.0,
+            w: 4.0,
+            h: 2.0,
+        };
+        let rect = RotatedRect::from(bbox);
+        assert_eq!(rect.corners()[0], (10.0, 20.0));
+        assert_eq!(rect.corners()[2], (14.0, 22.0));
+
+        // A quarter turn clockwise puts the top edge on the right.
+        let rect = RotatedRect {
+            angle: FRAC_PI_2,
+            ..rect
+        };
+        let [tl, tr, ..] = rect.corners();
+        assert!(close(tl, (13.0, 19.0)), "{:?}", tl);
+        assert!(close(tr, (13.0, 23.0)), "{:?}", tr);
+    }
+}
@@ -0,0 +1,89 @@
+//! `--bench` mode: end-to-end palm detection latency per execution provider.
+
+use image::RgbImage;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    inference::{create_session_on, Provider},
+    models::{ModelManager, ModelSpec, PALM_DETECTION_LITE},
+    nms::NmsMode,
+    palm::PalmDetector,
+    report::report,
+    worker::Thresholds,
+};
+
+/// Untimed iterations before measuring, to let providers allocate and tune.
+const WARMUP: usize = 5;
+
+/// Runs `iterations` detections on `image`, or a synthetic camera-sized
+/// frame, and prints latency percentiles and throughput for every provider.
+pub fn run(iterations: usize, image: Option<&Path>) -> Result<(), String> {
+    let img = match image {
+        Some(path) => image::open(path).map_err(|e| e.to_string())?.to_rgb8(),
+        None => RgbImage::from_fn(640, 480, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x ^ y) % 256) as u8])
+        }),
+    };
+    let mut models = ModelManager::new(vec![ModelSpec::palm_detection_lite()]);
+    let model = models.wait(PALM_DETECTION_LITE)?;
+    report(format_args!(
+        "Palm detection on {}x{}, {} iterations",
+        img.width(),
+        img.height(),
+        iterations
+    ))?;
+
+    for provider in Provider::ALL {
+        let session = match create_session_on(&model, provider) {
+            Ok(session) => session,
+            Err(e) => {
+                report(format_args!("{:?}: skipped, {}", provider, e))?;
+                continue;
+            }
+        };
+        let mut detector = PalmDetector::from_session(session);
+        let t = Thresholds::default();
+        for _ in 0..WARMUP {
+            detector.detect(&img, t.palm_score, t.palm_iou, NmsMode::Hard);
+        }
+        let mut latencies = (0..iterations.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                detector.detect(&img, t.palm_score, t.palm_iou, NmsMode::Hard);
+                start.elapsed()
+            })
+            .collect::<Vec<_>>();
+        latencies.sort();
+        let total = latencies.iter().sum::<Duration>();
+        report(format_args!(
+            "{:?}: p50 {:.2} ms, p95 {:.2} ms, {:.1} frames/s",
+            provider,
+            percentile(&latencies, 0.5).as_secs_f64() * 1e3,
+            percentile(&latencies, 0.95).as_secs_f64() * 1e3,
+            latencies.len() as f64 / total.as_secs_f64(),
+        ))?;
+    }
+    Ok(())
+}
+
+/// Nearest-rank percentile of ascending `sorted`.
+pub(crate) fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank() {
+        let sorted = (1..=20).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 0.5), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 0.95), Duration::from_millis(19));
+        assert_eq!(percentile(&sorted[..1], 0.95), Duration::from_millis(1));
+    }
+}
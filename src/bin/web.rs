@@ -0,0 +1,11 @@
+//! Entry point of the browser build, see [`ai_playground::web`].
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    ai_playground::web::start("app");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("The web build runs in the browser: build it for wasm32 with `trunk serve`");
+}
@@ -0,0 +1,189 @@
+//! Air canvas: pinching draws with the index finger on a layer over the
+//! video. The height of the hand when a stroke starts picks its color, and
+//! an open palm held still wipes the canvas.
+
+use std::time::Instant;
+
+use crate::{
+    gesture::{Gesture, GestureRecognizer},
+    hand::{Hand, INDEX_TIP},
+    palm::{Palm, INDEX_BASE},
+};
+
+/// Pinch ratio below which the pen touches the canvas, see
+/// [`Palm::pinch_ratio`] and [`Hand::pinch_ratio`].
+const PINCH_THRESHOLD: f32 = 0.35;
+/// Exponential smoothing factor of the pen, against detection jitter.
+const SMOOTHING: f32 = 0.5;
+/// Pen moves shorter than this many frame pixels add no point.
+const MIN_STEP: f32 = 2.0;
+
+#[derive(Clone, Debug)]
+pub struct Stroke {
+    pub color: [u8; 3],
+    /// Frame pixels, in drawing order.
+    pub points: Vec<(f32, f32)>,
+}
+
+/// Color of a stroke started at `height`, from red at the top of the frame
+/// through the rainbow to violet at the bottom.
+pub fn color_at(height: f32) -> [u8; 3] {
+    egui::ecolor::Hsva::new(height.clamp(0.0, 1.0) * 0.8, 1.0, 1.0, 1.0).to_srgb()
+}
+
+pub struct AirCanvas {
+    pub enabled: bool,
+    /// Stroke width in frame pixels.
+    pub width: f32,
+    strokes: Vec<Stroke>,
+    /// Smoothed pen position, while a hand is seen.
+    pen: Option<(f32, f32)>,
+    /// Color of the next stroke.
+    color: [u8; 3],
+    drawing: bool,
+    gestures: GestureRecognizer,
+}
+
+impl Default for AirCanvas {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 6.0,
+            strokes: vec![],
+            pen: None,
+            color: color_at(0.0),
+            drawing: false,
+            gestures: GestureRecognizer::default(),
+        }
+    }
+}
+
+impl AirCanvas {
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+
+    /// Where the pen is, the color it draws with and whether it is drawing.
+    pub fn pen(&self) -> Option<((f32, f32), [u8; 3], bool)> {
+        self.pen.map(|pen| (pen, self.color, self.drawing))
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.drawing = false;
+    }
+
+    /// Feeds the palms and hands detected in a frame of `(w, h)` pixels.
+    /// The first hand's index fingertip is the pen; without hand landmarks
+    /// the first palm's index finger base stands in for it.
+    pub fn update(&mut self, palms: &[Palm], hands: &[Hand], (w, h): (u32, u32), now: Instant) {
+        if !self.enabled {
+            return;
+        }
+        if self.gestures.update(palms.first(), (w, h), now) == Some(Gesture::OpenPalm) {
+            self.clear();
+        }
+        let pen = match (hands.first(), palms.first()) {
+            (Some(hand), _) => {
+                let (x, y, _) = hand.landmarks[INDEX_TIP];
+                Some(((x, y), hand.pinch_ratio()))
+            }
+            (None, Some(palm)) => Some((palm.tips[INDEX_BASE], palm.pinch_ratio())),
+            (None, None) => None,
+        };
+        let Some((target, pinch)) = pen else {
+            self.pen = None;
+            self.drawing = false;
+            return;
+        };
+        let (x, y) = match self.pen {
+            Some((px, py)) => (
+                px + (1.0 - SMOOTHING) * (target.0 - px),
+                py + (1.0 - SMOOTHING) * (target.1 - py),
+            ),
+            None => target,
+        };
+        self.pen = Some((x, y));
+
+        // Hysteresis keeps a borderline pinch from breaking up strokes.
+        if !self.drawing && pinch < PINCH_THRESHOLD {
+            self.drawing = true;
+            self.strokes.push(Stroke {
+                color: self.color,
+                points: vec![(x, y)],
+            });
+        } else if self.drawing && pinch > PINCH_THRESHOLD * 1.2 {
+            self.drawing = false;
+        } else if let Some(stroke) = self.strokes.last_mut().filter(|_| self.drawing) {
+            let (lx, ly) = *stroke.points.last().unwrap();
+            if (x - lx).hypot(y - ly) >= MIN_STEP {
+                stroke.points.push((x, y));
+            }
+        }
+        if !self.drawing {
+            self.color = color_at(y / h as f32);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add(egui::Slider::new(&mut self.width, 1.0..=32.0).text("Stroke width"));
+        if ui.button("Clear").clicked() {
+            self.clear();
+        }
+        ui.label("Pinch to draw with the index finger. Hand height picks the color, an open palm held still clears.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        palm::{MIDDLE_BASE, THUMB, WRIST},
+        test_util::palm,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn pinching_draws_and_holding_open_clears() {
+        let mut canvas = AirCanvas {
+            enabled: true,
+            ..AirCanvas::default()
+        };
+        let start = Instant::now();
+        // A palm at `(x, y)` in a 100×100 frame, pinching or open.
+        let feed = |canvas: &mut AirCanvas, ms: u64, x: f32, y: f32, pinching: bool| {
+            let now = start + Duration::from_millis(ms);
+            let thumb = if pinching {
+                (x - 6.0, y - 9.0)
+            } else {
+                (x - 15.0, y)
+            };
+            let palm = palm(x, y)
+                .tip(WRIST, (x, y + 10.0))
+                .tip(MIDDLE_BASE, (x, y - 10.0))
+                .tip(INDEX_BASE, (x - 5.0, y - 10.0))
+                .tip(THUMB, thumb)
+                .build();
+            canvas.update(&[palm], &[], (100, 100), now);
+        };
+        // Moving down the frame while pinching, with a gap in between.
+        for i in 0..5 {
+            feed(&mut canvas, i * 30, 20.0 + i as f32 * 5.0, 80.0, true);
+        }
+        feed(&mut canvas, 150, 50.0, 80.0, false);
+        for i in 0..3 {
+            feed(&mut canvas, 180 + i * 30, 50.0, 80.0 - i as f32 * 5.0, true);
+        }
+        assert_eq!(canvas.strokes().len(), 2);
+        assert!(canvas.strokes()[0].points.len() > 2);
+        // The color was picked where the pen hovered before the stroke.
+        assert_eq!(canvas.strokes()[0].color, color_at(0.0));
+        assert_ne!(canvas.strokes()[1].color, color_at(0.0));
+
+        for i in 0..20 {
+            feed(&mut canvas, 300 + i * 100, 50.0, 50.0, false);
+        }
+        assert!(canvas.strokes().is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+//! Short clips of the annotated first stream, for sharing. The last few
+//! seconds are always kept in memory, downscaled, and saved on request as
+//! an animated GIF or a numbered PNG sequence.
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbImage,
+};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+/// GIF quantization speed from 1 to 30. Faster is a little blotchier, but
+/// a clip saves in seconds rather than a minute.
+const GIF_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClipFormat {
+    #[default]
+    Gif,
+    Png,
+}
+
+impl ClipFormat {
+    pub const ALL: [Self; 2] = [Self::Gif, Self::Png];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gif => "Animated GIF",
+            Self::Png => "PNG sequence",
+        }
+    }
+}
+
+/// Ring buffer of the last frames, and clips being saved from it.
+pub struct ClipRecorder {
+    pub dir: String,
+    pub format: ClipFormat,
+    /// Length of the clips, in seconds.
+    pub seconds: f32,
+    /// Frames wider than this are scaled down as they are buffered.
+    pub max_width: u32,
+    frames: VecDeque<(Instant, RgbImage)>,
+    /// Result of the clip being encoded, if one is.
+    saving: Option<Receiver<Result<PathBuf, String>>>,
+    pending: bool,
+    status: String,
+}
+
+impl ClipRecorder {
+    pub fn new(dir: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            format: ClipFormat::default(),
+            seconds: 5.0,
+            max_width: 480,
+            frames: VecDeque::new(),
+            saving: None,
+            pending: false,
+            status: String::new(),
+        }
+    }
+
+    /// Buffers `frame`, shown at `now`, and forgets frames too old to be
+    /// in a clip.
+    pub fn push(&mut self, frame: &RgbImage, now: Instant) {
+        let (w, h) = frame.dimensions();
+        let frame = if w > self.max_width {
+            let height = (h as u64 * self.max_width as u64 / w as u64).max(1) as u32;
+            image::imageops::thumbnail(frame, self.max_width, height)
+        } else {
+            frame.clone()
+        };
+        // Frames of another size are from another source; they don't mix.
+        if self
+            .frames
+            .back()
+            .is_some_and(|(_, last)| last.dimensions() != frame.dimensions())
+        {
+            self.frames.clear();
+        }
+        self.frames.push_back((now, frame));
+        let max_age = Duration::from_secs_f32(self.seconds);
+        while self
+            .frames
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > max_age)
+        {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Saves the buffered frames once [`Self::update`] runs next.
+    pub fn request(&mut self) {
+        self.pending = true;
+    }
+
+    /// Starts saving a requested clip, named after `source`, and reports
+    /// clips that finished saving.
+    pub fn update(&mut self, source: &str) {
+        if let Some(saving) = &self.saving {
+            match saving.try_recv() {
+                Ok(result) => {
+                    self.status = match result {
+                        Ok(path) => format!("Saved {}", path.display()),
+                        Err(e) => {
+                            error!("Failed to save clip: {}", e);
+                            format!("Failed: {}", e)
+                        }
+                    };
+                    self.saving = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.saving = None,
+            }
+        }
+        if !std::mem::take(&mut self.pending) {
+            return;
+        }
+        if self.saving.is_some() {
+            self.status = "Still saving the last clip".into();
+            return;
+        }
+        if self.frames.is_empty() {
+            self.status = "No frames to save".into();
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let base = format!("{}_{}", now.as_millis(), source);
+        let path = match self.format {
+            ClipFormat::Gif => Path::new(&self.dir).join(format!("{}.gif", base)),
+            ClipFormat::Png => Path::new(&self.dir).join(base),
+        };
+        let frames = self.frames.iter().cloned().collect::<Vec<_>>();
+        let format = self.format;
+        let (tx, rx) = mpsc::channel();
+        // Encoding a GIF takes a while; the app keeps running meanwhile.
+        thread::spawn(move || {
+            let _ = tx.send(save(&frames, &path, format).map(|()| path));
+        });
+        self.saving = Some(rx);
+        self.status = "Saving…".into();
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Directory");
+            ui.text_edit_singleline(&mut self.dir);
+        });
+        egui::ComboBox::from_label("Format")
+            .selected_text(self.format.name())
+            .show_ui(ui, |ui| {
+                for format in ClipFormat::ALL {
+                    ui.selectable_value(&mut self.format, format, format.name());
+                }
+            });
+        ui.add(egui::Slider::new(&mut self.seconds, 1.0..=15.0).text("Length (s)"));
+        ui.add(
+            egui::Slider::new(&mut self.max_width, 160..=1280)
+                .text("Max width")
+                .suffix(" px"),
+        );
+        let label = format!("Save last {:.0} s (F10)", self.seconds);
+        if ui.button(label).clicked() {
+            self.request();
+        }
+        ui.weak(format!("{} frames buffered", self.frames.len()));
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+}
+
+/// Writes `frames` to `path` in `format`: a GIF file, or a directory of
+/// PNGs numbered from 0.
+fn save(frames: &[(Instant, RgbImage)], path: &Path, format: ClipFormat) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    match format {
+        ClipFormat::Gif => {
+            let file = File::create(path).map_err(|e| e.to_string())?;
+            let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), GIF_SPEED);
+            encoder
+                .set_repeat(Repeat::Infinite)
+                .map_err(|e| e.to_string())?;
+            let delays = delays(frames);
+            let frames = frames.iter().zip(delays).map(|((_, frame), delay)| {
+                let rgba = image::DynamicImage::ImageRgb8(frame.clone()).into_rgba8();
+                Frame::from_parts(rgba, 0, 0, Delay::from_saturating_duration(delay))
+            });
+            encoder.encode_frames(frames).map_err(|e| e.to_string())
+        }
+        ClipFormat::Png => {
+            std::fs::create_dir_all(path).map_err(|e| e.to_string())?;
+            for (i, (_, frame)) in frames.iter().enumerate() {
+                frame
+                    .save(path.join(format!("{:04}.png", i)))
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// How long each frame is shown: until the next one came, and the last one
+/// as long as the one before it.
+fn delays(frames: &[(Instant, RgbImage)]) -> Vec<Duration> {
+    let mut delays = frames
+        .windows(2)
+        .map(|pair| pair[1].0.duration_since(pair[0].0))
+        .collect::<Vec<_>>();
+    let last = delays.last().copied().unwrap_or(Duration::from_millis(100));
+    delays.push(last);
+    delays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+
+    #[test]
+    fn only_the_last_seconds_are_kept_downscaled() {
+        let mut clips = ClipRecorder::new("clips");
+        clips.seconds = 1.0;
+        let start = Instant::now();
+        for i in 0..20 {
+            let now = start + Duration::from_millis(i * 100);
+            clips.push(&RgbImage::new(960, 720), now);
+        }
+        // Frames of the last second, ends included.
+        assert_eq!(clips.frames.len(), 11);
+        assert_eq!(clips.frames[0].1.dimensions(), (480, 360));
+    }
+
+    #[test]
+    fn gifs_keep_every_frame_and_its_timing() {
+        let start = Instant::now();
+        let frames = (0..3)
+            .map(|i| {
+                let frame = RgbImage::from_pixel(8, 8, image::Rgb([i * 100, 0, 0]));
+                (start + Duration::from_millis(i as u64 * 40), frame)
+            })
+            .collect::<Vec<_>>();
+        let path = std::env::temp_dir().join(format!("clip-{}.gif", std::process::id()));
+        save(&frames, &path, ClipFormat::Gif).unwrap();
+        let file = std::io::BufReader::new(File::open(&path).unwrap());
+        let decoded = image::codecs::gif::GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(decoded.len(), 3);
+        for frame in &decoded {
+            assert_eq!(Duration::from(frame.delay()), Duration::from_millis(40));
+        }
+        assert_eq!(decoded[2].buffer().get_pixel(0, 0)[0], 200);
+    }
+}
@@ -0,0 +1,275 @@
+//! Pixel formats cameras deliver and their conversion to the RGB the models
+//! take. Cameras are asked for the best format they offer, see
+//! [`negotiate`], rather than for RGB, which many only fake by decoding
+//! MJPEG or YUYV themselves, and some don't offer at all.
+
+use image::{ImageFormat, RgbImage};
+use std::cmp::Reverse;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb,
+    /// RGB with red and blue swapped, as some Windows drivers send it.
+    Bgr,
+    /// 4:2:2 YUV, two pixels in `Y0 U Y1 V`. Rows of odd width end in a
+    /// pair with an unused second Y.
+    Yuyv,
+    /// 4:2:0 YUV, a plane of Y followed by one of interleaved U and V at
+    /// half resolution, rounded up for odd sizes.
+    Nv12,
+    Gray,
+    Mjpeg,
+}
+
+impl PixelFormat {
+    /// Formats from the cheapest to convert to the most expensive.
+    pub const PREFERENCE: [Self; 6] = [
+        Self::Rgb,
+        Self::Bgr,
+        Self::Gray,
+        Self::Yuyv,
+        Self::Nv12,
+        Self::Mjpeg,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgb => "RGB",
+            Self::Bgr => "BGR",
+            Self::Yuyv => "YUYV",
+            Self::Nv12 => "NV12",
+            Self::Gray => "grayscale",
+            Self::Mjpeg => "MJPEG",
+        }
+    }
+
+    /// Bytes of a `w`×`h` frame, or `None` for compressed frames.
+    fn frame_len(self, w: u32, h: u32) -> Option<usize> {
+        let (w, h) = (w as usize, h as usize);
+        let pixels = w * h;
+        match self {
+            Self::Rgb | Self::Bgr => Some(pixels * 3),
+            Self::Yuyv => Some(w.div_ceil(2) * 4 * h),
+            Self::Nv12 => Some(pixels + w.div_ceil(2) * 2 * h.div_ceil(2)),
+            Self::Gray => Some(pixels),
+            Self::Mjpeg => None,
+        }
+    }
+}
+
+/// A way a camera can capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mode {
+    pub format: PixelFormat,
+    pub size: (u32, u32),
+    pub fps: u32,
+}
+
+/// Index of the mode to capture in: one no wider than `max_width` if there
+/// is any, then color, the highest frame rate, the largest frame and the
+/// cheapest conversion.
+pub fn negotiate(modes: &[Mode], max_width: Option<u32>) -> Option<usize> {
+    let cost = |format| PixelFormat::PREFERENCE.iter().position(|&f| f == format);
+    modes
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, mode)| {
+            let (w, h) = mode.size;
+            (
+                max_width.is_none_or(|max| w <= max),
+                mode.format != PixelFormat::Gray,
+                mode.fps,
+                w as u64 * h as u64,
+                Reverse(cost(mode.format)),
+            )
+        })
+        .map(|(i, _)| i)
+}
+
+/// Converts a `w`×`h` frame of `format` to RGB in `frame`, reallocating it
+/// if the size changed. MJPEG frames take the size they were encoded at.
+pub fn to_rgb(
+    format: PixelFormat,
+    data: &[u8],
+    (w, h): (u32, u32),
+    frame: &mut RgbImage,
+) -> Result<(), String> {
+    if format == PixelFormat::Mjpeg {
+        let decoded = image::load_from_memory_with_format(data, ImageFormat::Jpeg)
+            .map_err(|e| e.to_string())?;
+        *frame = decoded.into_rgb8();
+        return Ok(());
+    }
+    let len = format.frame_len(w, h).unwrap();
+    if data.len() < len {
+        return Err(format!(
+            "{} frame of {} bytes, expected {}",
+            format.name(),
+            data.len(),
+            len
+        ));
+    }
+    if frame.dimensions() != (w, h) {
+        *frame = RgbImage::new(w, h);
+    }
+    let data = &data[..len];
+    let out: &mut [u8] = frame;
+    match format {
+        PixelFormat::Rgb => out.copy_from_slice(data),
+        PixelFormat::Bgr => {
+            for (rgb, bgr) in out.chunks_exact_mut(3).zip(data.chunks_exact(3)) {
+                rgb.copy_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+            }
+        }
+        PixelFormat::Gray => {
+            for (rgb, &v) in out.chunks_exact_mut(3).zip(data) {
+                rgb.fill(v);
+            }
+        }
+        PixelFormat::Yuyv => {
+            let stride = (w as usize).div_ceil(2) * 4;
+            for (row, yuyv) in out
+                .chunks_exact_mut(w as usize * 3)
+                .zip(data.chunks_exact(stride))
+            {
+                for (x, rgb) in row.chunks_exact_mut(3).enumerate() {
+                    let pair = &yuyv[x / 2 * 4..];
+                    rgb.copy_from_slice(&yuv_to_rgb(pair[x % 2 * 2], pair[1], pair[3]));
+                }
+            }
+        }
+        PixelFormat::Nv12 => {
+            let (w, h) = (w as usize, h as usize);
+            let (luma, chroma) = data.split_at(w * h);
+            let stride = w.div_ceil(2) * 2;
+            for y in 0..h {
+                let row = &chroma[y / 2 * stride..];
+                for x in 0..w {
+                    let (u, v) = (row[x / 2 * 2], row[x / 2 * 2 + 1]);
+                    let i = y * w + x;
+                    out[i * 3..i * 3 + 3].copy_from_slice(&yuv_to_rgb(luma[i], u, v));
+                }
+            }
+        }
+        PixelFormat::Mjpeg => unreachable!(),
+    }
+    Ok(())
+}
+
+/// BT.601 studio range YUV to RGB, in integers.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = 298 * (y as i32 - 16);
+    let (d, e) = (u as i32 - 128, v as i32 - 128);
+    let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+    [
+        clamp(c + 409 * e),
+        clamp(c - 100 * d - 208 * e),
+        clamp(c + 516 * d),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(format: PixelFormat, data: &[u8], size: (u32, u32)) -> RgbImage {
+        let mut frame = RgbImage::new(0, 0);
+        to_rgb(format, data, size, &mut frame).unwrap();
+        frame
+    }
+
+    #[test]
+    fn channels_come_out_in_rgb_order() {
+        // Red then blue.
+        let rgb = [255, 0, 0, 0, 0, 255];
+        let bgr = [0, 0, 255, 255, 0, 0];
+        for (format, data) in [(PixelFormat::Rgb, rgb), (PixelFormat::Bgr, bgr)] {
+            let frame = convert(format, &data, (2, 1));
+            assert_eq!(frame.get_pixel(0, 0).0, [255, 0, 0], "{:?}", format);
+            assert_eq!(frame.get_pixel(1, 0).0, [0, 0, 255], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn gray_is_spread_over_all_channels() {
+        let frame = convert(PixelFormat::Gray, &[0, 128, 255], (3, 1));
+        assert_eq!(frame.into_raw(), [0, 0, 0, 128, 128, 128, 255, 255, 255]);
+    }
+
+    #[test]
+    fn yuv_frames_keep_their_colors() {
+        // Studio range black, white, pure red and pure blue.
+        let black = (16, 128, 128);
+        let white = (235, 128, 128);
+        let red = (81, 90, 240);
+        let blue = (41, 240, 110);
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 2);
+
+        let yuyv = [
+            black.0, black.1, white.0, black.2, red.0, red.1, red.0, red.2,
+        ];
+        let frame = convert(PixelFormat::Yuyv, &yuyv, (4, 1));
+        assert!(close(frame.get_pixel(0, 0).0, [0, 0, 0]));
+        assert!(close(frame.get_pixel(1, 0).0, [255, 255, 255]));
+        assert!(close(frame.get_pixel(2, 0).0, [255, 0, 0]));
+        assert!(close(frame.get_pixel(3, 0).0, [255, 0, 0]));
+
+        // A 4×2 frame, red on the left and blue on the right.
+        let luma = [red.0, red.0, blue.0, blue.0];
+        let mut nv12 = [luma, luma].concat();
+        nv12.extend([red.1, red.2, blue.1, blue.2]);
+        let frame = convert(PixelFormat::Nv12, &nv12, (4, 2));
+        for y in 0..2 {
+            assert!(close(frame.get_pixel(1, y).0, [255, 0, 0]));
+            assert!(close(frame.get_pixel(2, y).0, [0, 0, 255]));
+        }
+    }
+
+    #[test]
+    fn odd_sizes_round_chroma_up() {
+        let red = (81, 90, 240);
+        let blue = (41, 240, 110);
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 2);
+
+        // Two rows of 3 pixels, each row ending in a half-used pair.
+        let row = [red.0, red.1, red.0, red.2, blue.0, blue.1, 0, blue.2];
+        let frame = convert(PixelFormat::Yuyv, &row.repeat(2), (3, 2));
+        for y in 0..2 {
+            assert!(close(frame.get_pixel(1, y).0, [255, 0, 0]));
+            assert!(close(frame.get_pixel(2, y).0, [0, 0, 255]));
+        }
+
+        // 3×3, with 2×2 chroma: red on the left, blue in the last column.
+        let mut nv12 = [red.0, red.0, blue.0].repeat(3);
+        nv12.extend([red.1, red.2, blue.1, blue.2].repeat(2));
+        let frame = convert(PixelFormat::Nv12, &nv12, (3, 3));
+        for y in 0..3 {
+            assert!(close(frame.get_pixel(0, y).0, [255, 0, 0]));
+            assert!(close(frame.get_pixel(2, y).0, [0, 0, 255]));
+        }
+        let mut frame = RgbImage::new(0, 0);
+        assert!(to_rgb(PixelFormat::Nv12, &nv12[..15], (3, 3), &mut frame).is_err());
+    }
+
+    #[test]
+    fn short_frames_are_rejected() {
+        let mut frame = RgbImage::new(0, 0);
+        assert!(to_rgb(PixelFormat::Yuyv, &[0; 6], (2, 2), &mut frame).is_err());
+    }
+
+    #[test]
+    fn color_and_frame_rate_come_first() {
+        let mode = |format, size, fps| Mode { format, size, fps };
+        let modes = [
+            mode(PixelFormat::Gray, (1920, 1080), 60),
+            mode(PixelFormat::Mjpeg, (1280, 720), 30),
+            mode(PixelFormat::Yuyv, (1280, 720), 30),
+            mode(PixelFormat::Yuyv, (640, 480), 30),
+            mode(PixelFormat::Mjpeg, (1920, 1080), 15),
+        ];
+        assert_eq!(negotiate(&modes, None), Some(2));
+        assert_eq!(negotiate(&modes, Some(640)), Some(3));
+        assert_eq!(negotiate(&modes[..1], None), Some(0));
+        assert_eq!(negotiate(&[], None), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! How well two models agree on the same frames, to see what running a
+//! quantized model costs.
+
+use crate::bbox::Detection;
+
+/// Detections of a reference and a candidate model, matched by IoU. Sums
+/// over any number of frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Agreement {
+    pub frames: usize,
+    /// Pairs of detections overlapping enough to be the same object.
+    pub matched: usize,
+    pub iou_sum: f32,
+    /// Reference detections the candidate has no match for.
+    pub missed: usize,
+    /// Candidate detections the reference has no match for.
+    pub extra: usize,
+}
+
+impl Agreement {
+    /// Matches `reference` and `candidate` detections of one frame, best
+    /// overlap first. Pairs below `iou_threshold` don't match.
+    pub fn of_frame<T: Detection>(reference: &[T], candidate: &[T], iou_threshold: f32) -> Self {
+        let mut pairs = reference
+            .iter()
+            .enumerate()
+            .flat_map(|(i, r)| {
+                candidate
+                    .iter()
+                    .enumerate()
+                    .map(move |(j, c)| (i, j, r.bbox().iou(c.bbox())))
+            })
+            .filter(|(_, _, iou)| *iou >= iou_threshold)
+            .collect::<Vec<_>>();
+        pairs.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+        let mut reference_used = vec![false; reference.len()];
+        let mut candidate_used = vec![false; candidate.len()];
+        let mut agreement = Self {
+            frames: 1,
+            ..Default::default()
+        };
+        for (i, j, iou) in pairs {
+            if reference_used[i] || candidate_used[j] {
+                continue;
+            }
+            reference_used[i] = true;
+            candidate_used[j] = true;
+            agreement.matched += 1;
+            agreement.iou_sum += iou;
+        }
+        agreement.missed = reference.len() - agreement.matched;
+        agreement.extra = candidate.len() - agreement.matched;
+        agreement
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.frames += other.frames;
+        self.matched += other.matched;
+        self.iou_sum += other.iou_sum;
+        self.missed += other.missed;
+        self.extra += other.extra;
+    }
+
+    /// Mean IoU of matched pairs.
+    pub fn mean_iou(&self) -> Option<f32> {
+        (self.matched > 0).then(|| self.iou_sum / self.matched as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bbox::BBox, yolo::Object};
+
+    fn object(x: f32) -> Object {
+        Object {
+            bbox: BBox {
+                x,
+                y: 0.0,
+                w: 10.0,
+                h: 10.0,
+            },
+            class: 0,
+            score: 1.0,
+        }
+    }
+
+    #[test]
+    fn matches_best_overlap_once() {
+        let reference = [object(0.0), object(100.0)];
+        let candidate = [object(1.0), object(0.0), object(50.0)];
+        let agreement = Agreement::of_frame(&reference, &candidate, 0.5);
+        assert_eq!(agreement.matched, 1);
+        assert_eq!(agreement.mean_iou(), Some(1.0));
+        assert_eq!((agreement.missed, agreement.extra), (1, 2));
+
+        let mut total = agreement;
+        total.add(&Agreement::of_frame(&reference, &reference, 0.5));
+        assert_eq!((total.frames, total.matched, total.missed), (2, 3, 1));
+    }
+}
@@ -0,0 +1,259 @@
+//! Named sets of settings, such as a fast CPU setup and an accurate GPU
+//! one, persisted as JSON in the platform config directory.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    enhance::Enhancement,
+    inference::Provider,
+    math::sigmoid,
+    overlay::Overlay,
+    pipeline::{PipelineKind, PipelineSpec, Precision},
+    track::Schedule,
+    worker::Thresholds,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Indices of the cameras to open.
+    pub cameras: Vec<u32>,
+    /// URLs of the network streams to open.
+    pub urls: Vec<String>,
+    /// Pipelines run together on every frame.
+    pub pipelines: Vec<PipelineSpec>,
+    /// Model files used instead of the managed copies, by model file name.
+    pub model_paths: BTreeMap<String, PathBuf>,
+    pub thresholds: Thresholds,
+    /// Enhancement of frames before detection.
+    pub enhancement: Enhancement,
+    pub schedule: Schedule,
+    pub overlay: Overlay,
+    /// Where models run. `None` prefers CUDA and falls back to the CPU.
+    pub provider: Option<Provider>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            cameras: vec![0],
+            urls: vec![],
+            pipelines: vec![PipelineSpec::new(PipelineKind::Palm(Precision::Fp32))],
+            model_paths: BTreeMap::new(),
+            thresholds: Thresholds::default(),
+            enhancement: Enhancement::default(),
+            schedule: Schedule::default(),
+            overlay: Overlay::default(),
+            provider: None,
+        }
+    }
+}
+
+/// Format of saved profiles. Version 1 has palm score thresholds as
+/// probabilities rather than logits.
+const VERSION: u32 = 1;
+
+/// All profiles and which one is in use.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profiles {
+    /// Format the profiles were saved in. Files from before it was saved
+    /// are version 0.
+    #[serde(default)]
+    pub version: u32,
+    pub active: String,
+    pub profiles: BTreeMap<String, AppConfig>,
+}
+
+impl Default for Profiles {
+    /// The default settings, plus detection skipping frames to keep up on
+    /// a CPU, and detection on every frame on a GPU.
+    fn default() -> Self {
+        let low_latency = AppConfig {
+            provider: Some(Provider::Cpu),
+            schedule: Schedule {
+                adaptive: true,
+                interpolate: true,
+                ..Schedule::default()
+            },
+            ..AppConfig::default()
+        };
+        let accuracy = AppConfig {
+            provider: Some(Provider::Cuda),
+            thresholds: Thresholds {
+                palm_score: 0.62,
+                palm_iou: 0.3,
+                ..Thresholds::default()
+            },
+            schedule: Schedule {
+                every: 1,
+                adaptive: false,
+                interpolate: false,
+                ..Schedule::default()
+            },
+            ..AppConfig::default()
+        };
+        Self {
+            version: VERSION,
+            active: "Default".to_owned(),
+            profiles: BTreeMap::from([
+                ("Default".to_owned(), AppConfig::default()),
+                ("Low-latency CPU".to_owned(), low_latency),
+                ("Accuracy GPU".to_owned(), accuracy),
+            ]),
+        }
+    }
+}
+
+impl Profiles {
+    /// `profiles.json` in the user's config directory.
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ai-playground")
+            .join("profiles.json")
+    }
+
+    /// Reads the profiles at `path`, or `None` if there are none yet. A file
+    /// that doesn't parse is moved to `profiles.json.bak`, so that saving
+    /// the defaults in its place doesn't lose it, and the error says where
+    /// it went.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let parsed = serde_json::from_str(&json).and_then(|mut value| {
+            migrate(&mut value);
+            serde_json::from_value::<Self>(value)
+        });
+        let mut profiles = match parsed {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                let backup = path.with_extension("json.bak");
+                return Err(match std::fs::rename(path, &backup) {
+                    Ok(()) => format!(
+                        "{}: {}, moved it to {}",
+                        path.display(),
+                        e,
+                        backup.display()
+                    ),
+                    Err(rename) => format!(
+                        "{}: {}, and failed to move it aside: {}",
+                        path.display(),
+                        e,
+                        rename
+                    ),
+                });
+            }
+        };
+        if !profiles.profiles.contains_key(&profiles.active) {
+            let config = profiles.profiles.values().next().cloned();
+            profiles
+                .profiles
+                .insert(profiles.active.clone(), config.unwrap_or_default());
+        }
+        Ok(Some(profiles))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Settings of the active profile.
+    pub fn active(&self) -> AppConfig {
+        self.profiles.get(&self.active).cloned().unwrap_or_default()
+    }
+}
+
+/// Brings profiles saved in an older format up to [`VERSION`], keeping the
+/// settings they were saved with.
+fn migrate(profiles: &mut serde_json::Value) {
+    let version = profiles.get("version").and_then(|v| v.as_u64());
+    if version.unwrap_or(0) < 1 {
+        // Palm scores were compared with thresholds as logits.
+        let configs = profiles
+            .get_mut("profiles")
+            .and_then(|configs| configs.as_object_mut());
+        for config in configs.into_iter().flat_map(|configs| configs.values_mut()) {
+            if let Some(score) = config.pointer_mut("/thresholds/palm_score") {
+                if let Some(logit) = score.as_f64() {
+                    *score = sigmoid(logit as f32).into();
+                }
+            }
+        }
+    }
+    if let Some(profiles) = profiles.as_object_mut() {
+        profiles.insert("version".to_owned(), VERSION.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_round_trip() {
+        let dir = std::env::temp_dir().join(format!("ai-playground-config-{}", std::process::id()));
+        let path = dir.join("profiles.json");
+        assert_eq!(Profiles::load(&path), Ok(None));
+
+        let profiles = Profiles {
+            active: "Accuracy GPU".to_owned(),
+            ..Profiles::default()
+        };
+        profiles.save(&path).unwrap();
+        assert_eq!(Profiles::load(&path), Ok(Some(profiles.clone())));
+        assert_eq!(profiles.active().provider, Some(Provider::Cuda));
+
+        // Missing fields take their defaults and a missing active profile
+        // is recreated.
+        std::fs::write(
+            &path,
+            r#"{"active": "Mine", "profiles": {"Old": {"cameras": [2]}}}"#,
+        )
+        .unwrap();
+        let loaded = Profiles::load(&path).unwrap().unwrap();
+        assert_eq!(loaded.active().cameras, [2]);
+        assert_eq!(loaded.active().thresholds, Thresholds::default());
+
+        // A broken file is kept aside rather than left to be overwritten.
+        std::fs::write(&path, "not json").unwrap();
+        assert!(Profiles::load(&path).is_err());
+        assert!(!path.exists());
+        let backup = dir.join("profiles.json.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "not json");
+        assert_eq!(Profiles::load(&path), Ok(None));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn palm_score_logits_become_probabilities() {
+        let old = r#"{"active": "A", "profiles": {
+            "A": {"thresholds": {"palm_score": 0.0, "face_score": 0.4}},
+            "B": {"cameras": [1]}
+        }}"#;
+        let mut value = serde_json::from_str(old).unwrap();
+        migrate(&mut value);
+        let profiles = serde_json::from_value::<Profiles>(value).unwrap();
+        assert_eq!(profiles.version, VERSION);
+        let thresholds = profiles.active().thresholds;
+        assert_eq!(thresholds.palm_score, 0.5);
+        assert_eq!(thresholds.face_score, 0.4);
+        assert_eq!(profiles.profiles["B"].thresholds, Thresholds::default());
+
+        // Saved probabilities are left as they are.
+        let mut value = serde_json::to_value(&profiles).unwrap();
+        migrate(&mut value);
+        assert_eq!(serde_json::from_value::<Profiles>(value).unwrap(), profiles);
+    }
+}
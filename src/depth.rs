@@ -0,0 +1,234 @@
+//! Monocular depth estimation with MiDaS small. The model predicts relative
+//! inverse depth: larger is nearer, on a scale of its own for every frame,
+//! so maps are normalized per frame and only compare within one.
+
+use egui::{Color32, ColorImage, TextureHandle};
+use image::{GrayImage, RgbImage};
+use ndarray::arr1;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    bbox::BBox,
+    inference::{self, Session},
+    preprocess::{Normalization, Resizer},
+};
+
+const INPUT_SIZE: usize = 256;
+/// ImageNet channel means and deviations MiDaS was trained with.
+const MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+const STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// Stops of the colormap near to far, evenly spaced: pale yellow through
+/// orange and purple to black, as in inferno.
+const COLORMAP: [[u8; 3]; 5] = [
+    [252, 255, 164],
+    [249, 142, 9],
+    [188, 55, 84],
+    [87, 16, 110],
+    [0, 0, 4],
+];
+
+/// Where depth maps are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepthDisplay {
+    /// Blended over the frame, under the detections.
+    #[default]
+    Overlay,
+    /// Next to the streams, leaving the frame as it is.
+    Panel,
+}
+
+impl DepthDisplay {
+    pub const ALL: [Self; 2] = [Self::Overlay, Self::Panel];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Overlay => "Over the frame",
+            Self::Panel => "Separate panel",
+        }
+    }
+}
+
+/// Relative depth of a frame, or of its ROI.
+#[derive(Clone, Debug)]
+pub struct DepthMap {
+    /// Part of the frame the map covers, in frame pixels.
+    pub area: BBox,
+    /// Inverse depth at model resolution, 255 at the nearest point of the
+    /// frame and 0 at the farthest.
+    pub nearness: GrayImage,
+}
+
+impl DepthMap {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            area: self.area.shift(x, y),
+            nearness: self.nearness.clone(),
+        }
+    }
+
+    /// Nearness from 0 to 1 at frame point `(x, y)`, if the map covers it.
+    pub fn at(&self, (x, y): (f32, f32)) -> Option<f32> {
+        let a = self.area;
+        let u = (x - a.x) / a.w;
+        let v = (y - a.y) / a.h;
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+        let (w, h) = self.nearness.dimensions();
+        let pixel = self
+            .nearness
+            .get_pixel((u * w as f32) as u32, (v * h as f32) as u32);
+        Some(pixel[0] as f32 / 255.0)
+    }
+
+    /// The map through [`colormap`], at model resolution.
+    pub fn colorized(&self) -> RgbImage {
+        let (w, h) = self.nearness.dimensions();
+        RgbImage::from_fn(w, h, |x, y| {
+            image::Rgb(colormap(self.nearness.get_pixel(x, y)[0]))
+        })
+    }
+}
+
+/// Color of `nearness`, bright for near and dark for far.
+pub fn colormap(nearness: u8) -> [u8; 3] {
+    let t = (255 - nearness) as f32 / 255.0 * (COLORMAP.len() - 1) as f32;
+    let i = (t as usize).min(COLORMAP.len() - 2);
+    let f = t - i as f32;
+    let (a, b) = (COLORMAP[i], COLORMAP[i + 1]);
+    std::array::from_fn(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f).round() as u8)
+}
+
+pub struct DepthEstimator {
+    session: Session,
+    resizer: Resizer,
+}
+
+impl DepthEstimator {
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, String> {
+        Ok(Self {
+            session: inference::create_session(model_path)?,
+            resizer: Resizer::new(INPUT_SIZE, INPUT_SIZE),
+        })
+    }
+
+    /// Depth of all of `img`.
+    pub fn estimate(&mut self, img: &RgbImage) -> Option<DepthMap> {
+        let mut input = self.resizer.resize(img, Normalization::UNIT);
+        input -= &arr1(&MEAN);
+        input /= &arr1(&STD);
+        // MiDaS takes NCHW.
+        let input = input
+            .permuted_axes([0, 3, 1, 2])
+            .as_standard_layout()
+            .into_owned();
+        let mut outputs = inference::run(&*self.session, input);
+        let Some(output) = outputs.pop().filter(|o| o.len() == INPUT_SIZE * INPUT_SIZE) else {
+            error!("Depth model has no {0}×{0} output", INPUT_SIZE);
+            return None;
+        };
+        let (min, max) = output
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+        let range = (max - min).max(1e-6);
+        let nearness = GrayImage::from_raw(
+            INPUT_SIZE as u32,
+            INPUT_SIZE as u32,
+            output
+                .iter()
+                .map(|v| ((v - min) / range * 255.0).round() as u8)
+                .collect(),
+        )
+        .unwrap();
+        Some(DepthMap {
+            area: BBox {
+                x: 0.0,
+                y: 0.0,
+                w: img.width() as f32,
+                h: img.height() as f32,
+            },
+            nearness,
+        })
+    }
+}
+
+/// Colorized depth map of the first stream, in a panel of its own.
+#[derive(Default)]
+pub struct DepthPanel {
+    texture: Option<TextureHandle>,
+}
+
+impl DepthPanel {
+    /// Shows `map` from now on, or nothing for `None`.
+    pub fn update(&mut self, ctx: &egui::Context, map: Option<&DepthMap>) {
+        let Some(map) = map else {
+            self.texture = None;
+            return;
+        };
+        let colorized = map.colorized();
+        let image = ColorImage {
+            size: [colorized.width() as usize, colorized.height() as usize],
+            pixels: colorized
+                .pixels()
+                .map(|p| Color32::from_rgb(p[0], p[1], p[2]))
+                .collect(),
+        };
+        match &mut self.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+            None => {
+                self.texture = Some(ctx.load_texture("depth", image, egui::TextureOptions::LINEAR));
+            }
+        }
+    }
+
+    /// The map stretched back to the aspect of the area it covers.
+    pub fn ui(&self, ui: &mut egui::Ui, area: Option<BBox>) {
+        let (Some(texture), Some(area)) = (&self.texture, area) else {
+            ui.label("No depth map; tick \"Depth estimation\" in the \"Pipelines\" section");
+            return;
+        };
+        let width = ui.available_width();
+        let size = egui::vec2(width, width * area.h / area.w.max(1.0));
+        let texture = egui::load::SizedTexture::new(texture.id(), size);
+        ui.add(egui::Image::from_texture(texture).max_size(ui.available_size()));
+        ui.label("Bright is near, dark is far");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colormap_darkens_with_distance() {
+        assert_eq!(colormap(255), COLORMAP[0]);
+        assert_eq!(colormap(0), COLORMAP[4]);
+        let brightness = |c: [u8; 3]| c.iter().map(|&v| v as u32).sum::<u32>();
+        let colors = (0..=255).rev().step_by(8).map(colormap).collect::<Vec<_>>();
+        assert!(colors
+            .windows(2)
+            .all(|pair| brightness(pair[0]) >= brightness(pair[1])));
+    }
+
+    #[test]
+    fn lookup_maps_frame_points_into_the_area() {
+        let mut nearness = GrayImage::new(2, 2);
+        nearness.put_pixel(1, 0, image::Luma([255]));
+        let map = DepthMap {
+            area: BBox {
+                x: 100.0,
+                y: 50.0,
+                w: 200.0,
+                h: 100.0,
+            },
+            nearness,
+        };
+        assert_eq!(map.at((250.0, 60.0)), Some(1.0));
+        assert_eq!(map.at((150.0, 60.0)), Some(0.0));
+        assert_eq!(map.at((50.0, 60.0)), None);
+        assert_eq!(map.shift(-100.0, 0.0).at((150.0, 60.0)), Some(1.0));
+    }
+}
@@ -0,0 +1,311 @@
+//! How far hands are from the camera, from how big their palms look. A palm
+//! twice as far away looks half as big, so one calibration at a known
+//! distance is enough. Crossing the near or far threshold is reported as a
+//! [`MotionEvent`], like the motion gestures.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    gesture::Gesture,
+    motion::MotionEvent,
+    palm::{Palm, INDEX_BASE, MIDDLE_BASE, PINKY_BASE, WRIST},
+    trails::Trails,
+};
+
+/// Weight of the newest estimate in each hand's smoothed distance.
+const SMOOTHING: f32 = 0.3;
+
+/// Apparent palm size at a known distance.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// Distance the palm was held at, in cm.
+    pub distance: f32,
+    /// Palm box side then, relative to the frame diagonal.
+    pub box_size: f32,
+    /// Keypoint span then, see [`palm_size`].
+    pub keypoint_size: f32,
+}
+
+impl Calibration {
+    pub fn of(palm: &Palm, distance: f32, frame_size: (u32, u32)) -> Self {
+        Self {
+            distance,
+            box_size: palm_size(palm, false, frame_size),
+            keypoint_size: palm_size(palm, true, frame_size),
+        }
+    }
+
+    /// Distance in cm of a palm measuring `size`, by `keypoints` or not.
+    pub fn distance(&self, size: f32, keypoints: bool) -> f32 {
+        let reference = if keypoints {
+            self.keypoint_size
+        } else {
+            self.box_size
+        };
+        self.distance * reference / size.max(1e-6)
+    }
+}
+
+/// Size of `palm` relative to the frame diagonal: the side of its box, or
+/// with `keypoints` the longer of its length, wrist to middle finger base,
+/// and its width across the finger bases. Turning the hand shortens only
+/// one of those, while the box shrinks with either.
+pub fn palm_size(palm: &Palm, keypoints: bool, (w, h): (u32, u32)) -> f32 {
+    let size = if keypoints {
+        let dist = |a: usize, b: usize| {
+            let ((ax, ay), (bx, by)) = (palm.tips[a], palm.tips[b]);
+            (ax - bx).hypot(ay - by)
+        };
+        dist(WRIST, MIDDLE_BASE).max(dist(INDEX_BASE, PINKY_BASE))
+    } else {
+        palm.bbox.w.max(palm.bbox.h)
+    };
+    size / (w as f32).hypot(h as f32)
+}
+
+/// Persisted across runs.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DistanceSettings {
+    pub enabled: bool,
+    /// Measure palms by their keypoints rather than their boxes.
+    pub keypoints: bool,
+    /// Distance to hold the palm at while calibrating, in cm.
+    pub reference: f32,
+    pub calibration: Option<Calibration>,
+    /// Hands get near below `near` cm and far beyond `far` cm. In between
+    /// they stay what they were, so a hand at a threshold doesn't flicker.
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for DistanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keypoints: true,
+            reference: 50.0,
+            calibration: None,
+            near: 35.0,
+            far: 70.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Zone {
+    Near,
+    Between,
+    Far,
+}
+
+/// Zone of a hand at `distance` cm that was in `previous`.
+fn zone(distance: f32, previous: Zone, near: f32, far: f32) -> Zone {
+    if distance < near {
+        Zone::Near
+    } else if distance > far {
+        Zone::Far
+    } else {
+        previous
+    }
+}
+
+struct Track {
+    zone: Zone,
+    /// Smoothed distance, in cm.
+    distance: f32,
+}
+
+/// Estimates the distance of every palm of the first stream and reports
+/// hands crossing the thresholds.
+#[derive(Default)]
+pub struct HandDistance {
+    pub settings: DistanceSettings,
+    tracks: HashMap<u64, Track>,
+    /// Smoothed distance of each palm of the last frame, in cm.
+    distances: Vec<f32>,
+    calibrating: bool,
+    status: String,
+}
+
+impl HandDistance {
+    pub fn new(settings: DistanceSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    /// Distance of each palm passed to the last update, in cm. Empty until
+    /// calibrated.
+    pub fn distances(&self) -> &[f32] {
+        &self.distances
+    }
+
+    /// Estimates the distances of `palms`, detected at `now` in a frame of
+    /// `frame_size` pixels and already added to `trails`, and returns the
+    /// hands that got near or far. Hands start out where they are first
+    /// seen without an event; only crossing a threshold fires one.
+    pub fn update(
+        &mut self,
+        palms: &[Palm],
+        trails: &Trails,
+        frame_size: (u32, u32),
+        now: Instant,
+    ) -> Vec<MotionEvent> {
+        self.distances.clear();
+        if !self.settings.enabled {
+            self.tracks.clear();
+            return vec![];
+        }
+        if self.calibrating {
+            if let Some(palm) = palms.first() {
+                let calibration = Calibration::of(palm, self.settings.reference, frame_size);
+                self.settings.calibration = Some(calibration);
+                self.calibrating = false;
+                self.status = format!("Calibrated at {:.0} cm", self.settings.reference);
+            }
+        }
+        let Some(calibration) = self.settings.calibration else {
+            return vec![];
+        };
+        let (w, h) = frame_size;
+        let ids = trails.tracks().map(|(id, _)| id).collect::<Vec<_>>();
+        self.tracks.retain(|id, _| ids.contains(id));
+        let mut events = vec![];
+        for palm in palms {
+            let size = palm_size(palm, self.settings.keypoints, frame_size);
+            let distance = calibration.distance(size, self.settings.keypoints);
+            let b = palm.bbox;
+            let center = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+            let Some(id) = trails
+                .tracks()
+                .find(|(_, trail)| trail.back() == Some(&(center, now)))
+                .map(|(id, _)| id)
+            else {
+                self.distances.push(distance);
+                continue;
+            };
+            let (near, far) = (self.settings.near, self.settings.far);
+            let track = self.tracks.entry(id).or_insert_with(|| Track {
+                zone: zone(distance, Zone::Between, near, far),
+                distance,
+            });
+            track.distance += (distance - track.distance) * SMOOTHING;
+            self.distances.push(track.distance);
+            let zone = zone(track.distance, track.zone, near, far);
+            if zone == track.zone {
+                continue;
+            }
+            track.zone = zone;
+            let gesture = match zone {
+                Zone::Near => Gesture::HandNear,
+                Zone::Far => Gesture::HandFar,
+                Zone::Between => continue,
+            };
+            events.push(MotionEvent {
+                track: id,
+                gesture,
+                position: (center.0 / w as f32, center.1 / h as f32),
+            });
+        }
+        events
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings;
+        ui.checkbox(&mut settings.enabled, "Enabled");
+        ui.checkbox(&mut settings.keypoints, "Measure by keypoints")
+            .on_hover_text("Palm length and width instead of its box; steadier as the hand turns");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut settings.reference)
+                    .range(5.0..=300.0)
+                    .suffix(" cm"),
+            );
+            let label = if self.calibrating {
+                "Show a palm…"
+            } else {
+                "Calibrate"
+            };
+            if ui
+                .add_enabled(settings.enabled, egui::Button::new(label))
+                .on_hover_text("Hold an open palm facing the camera at this distance")
+                .clicked()
+            {
+                self.calibrating = true;
+            }
+        });
+        if settings.calibration.is_none() && !self.calibrating {
+            ui.weak("Not calibrated");
+        }
+        ui.add(
+            egui::Slider::new(&mut settings.near, 5.0..=300.0)
+                .text("Near below (cm)")
+                .integer(),
+        );
+        ui.add(
+            egui::Slider::new(&mut settings.far, 5.0..=300.0)
+                .text("Far beyond (cm)")
+                .integer(),
+        );
+        settings.far = settings.far.max(settings.near);
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::palm;
+    use std::time::Duration;
+
+    /// A palm centered at `(50, 50)` whose box and keypoint span are `side`.
+    fn spanning(side: f32) -> Palm {
+        let (c, r) = (50.0, side / 2.0);
+        palm(c, c)
+            .size(side, side)
+            .tip(WRIST, (c, c + r))
+            .tip(MIDDLE_BASE, (c, c - r))
+            .build()
+    }
+
+    #[test]
+    fn half_the_size_is_twice_as_far() {
+        let calibration = Calibration::of(&spanning(20.0), 50.0, (100, 100));
+        for keypoints in [false, true] {
+            let size = palm_size(&spanning(10.0), keypoints, (100, 100));
+            assert!((calibration.distance(size, keypoints) - 100.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn crossing_thresholds_fires_once() {
+        let mut distance = HandDistance::new(DistanceSettings {
+            enabled: true,
+            calibration: Some(Calibration::of(&spanning(20.0), 50.0, (100, 100))),
+            ..DistanceSettings::default()
+        });
+        let mut trails = Trails::default();
+        let start = Instant::now();
+        // From 50 cm to 25, back out to 100 and in to 60, where it stays far.
+        let sides = [20.0; 10]
+            .into_iter()
+            .chain([40.0; 10])
+            .chain([10.0; 15])
+            .chain([16.7; 15]);
+        let mut gestures = vec![];
+        for (i, side) in sides.enumerate() {
+            let now = start + Duration::from_millis(i as u64 * 30);
+            let palms = [spanning(side)];
+            trails.update(&palms, (100, 100), now, Duration::from_secs(2));
+            let events = distance.update(&palms, &trails, (100, 100), now);
+            gestures.extend(events.iter().map(|e| e.gesture));
+        }
+        assert_eq!(gestures, [Gesture::HandNear, Gesture::HandFar]);
+        assert!((distance.distances()[0] - 60.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,328 @@
+//! Image enhancement applied to frames before detection, for scenes too dark
+//! or flat for the models. Each step is a [`FrameFilter`]; [`Enhancement`]
+//! holds the user's settings and chains the filters they turn on.
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+
+/// An in-place change of a frame's pixels.
+pub trait FrameFilter {
+    fn apply(&self, frame: &mut RgbImage);
+}
+
+fn luma(p: &image::Rgb<u8>) -> f32 {
+    0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32
+}
+
+/// Maps every channel of every pixel through `lut`.
+fn map_channels(frame: &mut RgbImage, lut: &[u8; 256]) {
+    for v in frame.iter_mut() {
+        *v = lut[*v as usize];
+    }
+}
+
+fn lut(f: impl Fn(f32) -> f32) -> [u8; 256] {
+    std::array::from_fn(|v| f(v as f32).round().clamp(0.0, 255.0) as u8)
+}
+
+/// Raises pixel values to `1 / gamma`, so that a gamma above 1 brightens
+/// the shadows while keeping black and white.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gamma(pub f32);
+
+impl FrameFilter for Gamma {
+    fn apply(&self, frame: &mut RgbImage) {
+        let exponent = 1.0 / self.0.max(0.01);
+        map_channels(frame, &lut(|v| 255.0 * (v / 255.0).powf(exponent)));
+    }
+}
+
+/// Scales pixel values around mid gray by `contrast`, then adds
+/// `brightness` times the full range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BrightnessContrast {
+    pub brightness: f32,
+    pub contrast: f32,
+}
+
+impl FrameFilter for BrightnessContrast {
+    fn apply(&self, frame: &mut RgbImage) {
+        map_channels(
+            frame,
+            &lut(|v| (v - 127.5) * self.contrast + 127.5 + self.brightness * 255.0),
+        );
+    }
+}
+
+/// Histogram equalization of the luminance, in a grid of `tiles`×`tiles`
+/// regions blended bilinearly (CLAHE). Counts above `clip_limit` times the
+/// mean of a histogram bin are spread over all bins, which keeps noise in
+/// flat regions from being amplified. One tile without a clip limit is
+/// plain global equalization.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Equalize {
+    pub tiles: u32,
+    pub clip_limit: Option<f32>,
+}
+
+impl Equalize {
+    /// Luminance mapping equalizing `histogram`.
+    fn lut(&self, mut histogram: [u32; 256]) -> [f32; 256] {
+        let total = histogram.iter().sum::<u32>().max(1);
+        if let Some(limit) = self.clip_limit {
+            let limit = ((limit * total as f32 / 256.0) as u32).max(1);
+            let excess = histogram
+                .iter_mut()
+                .map(|count| {
+                    let clipped = count.saturating_sub(limit);
+                    *count -= clipped;
+                    clipped
+                })
+                .sum::<u32>();
+            for (i, count) in histogram.iter_mut().enumerate() {
+                *count += excess / 256 + u32::from((i as u32) < excess % 256);
+            }
+        }
+        let mut lut = [0.0; 256];
+        let mut cumulative = 0;
+        for (out, count) in lut.iter_mut().zip(histogram) {
+            cumulative += count;
+            *out = cumulative as f32 / total as f32 * 255.0;
+        }
+        lut
+    }
+}
+
+impl FrameFilter for Equalize {
+    fn apply(&self, frame: &mut RgbImage) {
+        let (w, h) = frame.dimensions();
+        let tiles = self.tiles.clamp(1, w.min(h).max(1));
+        let (tile_w, tile_h) = (w.div_ceil(tiles), h.div_ceil(tiles));
+        let mut histograms = vec![[0u32; 256]; (tiles * tiles) as usize];
+        for (x, y, p) in frame.enumerate_pixels() {
+            let tile = (y / tile_h * tiles + x / tile_w) as usize;
+            histograms[tile][luma(p) as usize] += 1;
+        }
+        let luts = histograms
+            .into_iter()
+            .map(|histogram| self.lut(histogram))
+            .collect::<Vec<_>>();
+
+        // Position of a pixel in tile units, relative to the tile centers.
+        let grid = |v: u32, size: u32| {
+            let t = ((v as f32 + 0.5) / size as f32 - 0.5).clamp(0.0, (tiles - 1) as f32);
+            let i = (t as u32).min(tiles.saturating_sub(2));
+            (i, (i + 1).min(tiles - 1), t - i as f32)
+        };
+        for (x, y, p) in frame.enumerate_pixels_mut() {
+            let (x0, x1, fx) = grid(x, tile_w);
+            let (y0, y1, fy) = grid(y, tile_h);
+            let y_old = luma(p);
+            let at = |tx: u32, ty: u32| luts[(ty * tiles + tx) as usize][y_old as usize];
+            let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+            let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+            let y_new = top * (1.0 - fy) + bottom * fy;
+            // Scaling the channels keeps the hue; black becomes gray.
+            for c in p.0.iter_mut() {
+                let v = if y_old < 1.0 {
+                    y_new
+                } else {
+                    *c as f32 * y_new / y_old
+                };
+                *c = v.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Equalization {
+    #[default]
+    Off,
+    Global,
+    Adaptive,
+}
+
+impl Equalization {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Global, Self::Adaptive];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::Global => "Histogram",
+            Self::Adaptive => "CLAHE",
+        }
+    }
+}
+
+/// Enhancement of frames before detection.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Enhancement {
+    pub enabled: bool,
+    pub equalization: Equalization,
+    /// Clip limit and tiles per side of [`Equalization::Adaptive`].
+    pub clip_limit: f32,
+    pub tiles: u32,
+    pub gamma: f32,
+    pub brightness: f32,
+    pub contrast: f32,
+    /// Shows the frame as captured left of this fraction of its width, and
+    /// enhanced right of it.
+    pub split: Option<f32>,
+}
+
+impl Default for Enhancement {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            equalization: Equalization::Off,
+            clip_limit: 2.0,
+            tiles: 8,
+            gamma: 1.0,
+            brightness: 0.0,
+            contrast: 1.0,
+            split: None,
+        }
+    }
+}
+
+impl Enhancement {
+    /// Filters to apply, in order. Empty when nothing would change.
+    pub fn filters(&self) -> Vec<Box<dyn FrameFilter>> {
+        let mut filters: Vec<Box<dyn FrameFilter>> = vec![];
+        if !self.enabled {
+            return filters;
+        }
+        match self.equalization {
+            Equalization::Off => {}
+            Equalization::Global => filters.push(Box::new(Equalize {
+                tiles: 1,
+                clip_limit: None,
+            })),
+            Equalization::Adaptive => filters.push(Box::new(Equalize {
+                tiles: self.tiles,
+                clip_limit: Some(self.clip_limit),
+            })),
+        }
+        if self.gamma != 1.0 {
+            filters.push(Box::new(Gamma(self.gamma)));
+        }
+        if self.brightness != 0.0 || self.contrast != 1.0 {
+            filters.push(Box::new(BrightnessContrast {
+                brightness: self.brightness,
+                contrast: self.contrast,
+            }));
+        }
+        filters
+    }
+
+    pub fn apply(&self, frame: &mut RgbImage) {
+        for filter in self.filters() {
+            filter.apply(frame);
+        }
+    }
+
+    /// Replaces the part of `enhanced` left of the split with `original`,
+    /// and marks the split with a line. Does nothing without a split.
+    pub fn split_view(&self, original: &RgbImage, enhanced: &mut RgbImage) {
+        let Some(split) = self.split.filter(|_| self.enabled) else {
+            return;
+        };
+        let x_split = (split.clamp(0.0, 1.0) * enhanced.width() as f32) as u32;
+        for (x, y, p) in enhanced.enumerate_pixels_mut() {
+            if x + 1 < x_split {
+                *p = original[(x, y)];
+            } else if x <= x_split {
+                *p = image::Rgb([255, 255, 255]);
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.add_enabled_ui(self.enabled, |ui| {
+            egui::ComboBox::from_label("Equalization")
+                .selected_text(self.equalization.name())
+                .show_ui(ui, |ui| {
+                    for mode in Equalization::ALL {
+                        ui.selectable_value(&mut self.equalization, mode, mode.name());
+                    }
+                });
+            if self.equalization == Equalization::Adaptive {
+                ui.add(egui::Slider::new(&mut self.clip_limit, 1.0..=8.0).text("Clip limit"));
+                ui.add(egui::Slider::new(&mut self.tiles, 1..=16).text("Tiles"));
+            }
+            ui.add(
+                egui::Slider::new(&mut self.gamma, 0.2..=3.0)
+                    .logarithmic(true)
+                    .text("Gamma"),
+            );
+            ui.add(egui::Slider::new(&mut self.brightness, -0.5..=0.5).text("Brightness"));
+            ui.add(egui::Slider::new(&mut self.contrast, 0.2..=3.0).text("Contrast"));
+            let mut split = self.split.is_some();
+            if ui.checkbox(&mut split, "Before/after split").changed() {
+                self.split = split.then_some(0.5);
+            }
+            if let Some(at) = &mut self.split {
+                ui.add(egui::Slider::new(at, 0.0..=1.0).text("Split at"));
+            }
+            if ui.button("Reset").clicked() {
+                *self = Self {
+                    enabled: true,
+                    ..Self::default()
+                };
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(w: u32, h: u32, f: impl Fn(u32, u32) -> u8) -> RgbImage {
+        RgbImage::from_fn(w, h, |x, y| image::Rgb([f(x, y); 3]))
+    }
+
+    #[test]
+    fn gamma_and_contrast_keep_their_neutral_values() {
+        let mut frame = gray(16, 16, |x, y| (x * 16 + y) as u8);
+        let original = frame.clone();
+        Gamma(1.0).apply(&mut frame);
+        BrightnessContrast {
+            brightness: 0.0,
+            contrast: 1.0,
+        }
+        .apply(&mut frame);
+        assert_eq!(frame, original);
+
+        Gamma(2.0).apply(&mut frame);
+        assert_eq!(frame[(0, 0)], original[(0, 0)]);
+        assert_eq!(frame[(15, 15)], original[(15, 15)]);
+        assert!(frame[(4, 0)][0] > original[(4, 0)][0]);
+    }
+
+    #[test]
+    fn equalization_stretches_a_dark_frame() {
+        let mut frame = gray(32, 32, |x, _| 10 + (x % 8) as u8);
+        Equalize {
+            tiles: 1,
+            clip_limit: None,
+        }
+        .apply(&mut frame);
+        let values = frame.pixels().map(|p| p[0]).collect::<Vec<_>>();
+        assert_eq!(values.iter().max(), Some(&255));
+        assert!(*values.iter().min().unwrap() < 40);
+
+        // Clipping limits how far a flat frame is stretched.
+        let mut clipped = gray(32, 32, |x, _| 10 + (x % 8) as u8);
+        Equalize {
+            tiles: 4,
+            clip_limit: Some(2.0),
+        }
+        .apply(&mut clipped);
+        let max = clipped.pixels().map(|p| p[0]).max().unwrap();
+        assert!(max > 18 && max < 255, "{}", max);
+    }
+}
@@ -0,0 +1,381 @@
+//! `eval` mode: precision, recall and average precision of a pipeline on a
+//! directory of images with COCO-style box annotations, so model and
+//! preprocessing changes can be compared by numbers.
+
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+use tracing::warn;
+
+use crate::{
+    bbox::BBox,
+    models::{ModelManager, ModelSpec},
+    nms::NmsMode,
+    pipeline::{PipelineKind, PipelineSpec, Precision},
+    report::report,
+    scheduler::Scheduler,
+    worker::Thresholds,
+};
+
+const USAGE: &str = "eval <image dir> <annotations.json> [--pipeline palm|face|yolo] \
+    [--precision fp32|fp16|int8] [--nms hard|soft|weighted] [--iou 0.5,0.75] [--score 0.65]";
+
+/// Detections scoring less aren't counted, not even for average precision.
+const MIN_SCORE: f32 = 0.05;
+
+#[derive(Deserialize)]
+struct Coco {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    #[serde(default)]
+    categories: Vec<CocoCategory>,
+}
+
+#[derive(Deserialize)]
+struct CocoImage {
+    id: u64,
+    file_name: String,
+}
+
+#[derive(Deserialize)]
+struct CocoAnnotation {
+    image_id: u64,
+    category_id: u64,
+    /// Left, top, width and height in pixels.
+    bbox: [f32; 4],
+    #[serde(default)]
+    iscrowd: u8,
+}
+
+#[derive(Deserialize)]
+struct CocoCategory {
+    id: u64,
+    name: String,
+}
+
+/// A detected or annotated box of class `class` in image `image`.
+#[derive(Clone, Copy, Debug)]
+pub struct Labeled {
+    pub image: usize,
+    pub class: usize,
+    pub bbox: BBox,
+    /// One for annotations.
+    pub score: f32,
+}
+
+/// Score of each of `detections`, best first, and whether it found one of
+/// `truth` by `iou` or more, as in COCO: each annotation is taken by the
+/// best detection overlapping it most that isn't matched yet.
+pub fn match_detections(detections: &[Labeled], truth: &[Labeled], iou: f32) -> Vec<(f32, bool)> {
+    let mut detections = detections.to_vec();
+    detections.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let mut taken = vec![false; truth.len()];
+    detections
+        .iter()
+        .map(|d| {
+            let best = truth
+                .iter()
+                .enumerate()
+                .filter(|&(i, t)| !taken[i] && t.image == d.image && t.class == d.class)
+                .map(|(i, t)| (i, t.bbox.iou(d.bbox)))
+                .filter(|&(_, overlap)| overlap >= iou)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((i, _)) = best {
+                taken[i] = true;
+            }
+            (d.score, best.is_some())
+        })
+        .collect()
+}
+
+/// Area under the precision-recall curve of `matches`, best first, out of
+/// `positives` annotations. As in COCO, precision at a recall is the best
+/// precision at that recall or more, sampled at 101 recalls.
+pub fn average_precision(matches: &[(f32, bool)], positives: usize) -> f32 {
+    if positives == 0 {
+        return 0.0;
+    }
+    let mut hits = 0;
+    let mut curve = matches
+        .iter()
+        .enumerate()
+        .map(|(i, &(_, hit))| {
+            hits += usize::from(hit);
+            (hits as f32 / positives as f32, hits as f32 / (i + 1) as f32)
+        })
+        .collect::<Vec<_>>();
+    for i in (1..curve.len()).rev() {
+        curve[i - 1].1 = curve[i - 1].1.max(curve[i].1);
+    }
+    let sampled = (0..=100).map(|r| {
+        let recall = r as f32 / 100.0;
+        curve
+            .iter()
+            .find(|&&(r, _)| r >= recall)
+            .map_or(0.0, |&(_, precision)| precision)
+    });
+    sampled.sum::<f32>() / 101.0
+}
+
+/// How many of the `matches` scoring `threshold` or more are hits, and how
+/// many there are, for precision and recall at that score.
+fn hits_above(matches: &[(f32, bool)], threshold: f32) -> (usize, usize) {
+    let kept = matches.iter().filter(|(score, _)| *score >= threshold);
+    (kept.clone().filter(|(_, hit)| *hit).count(), kept.count())
+}
+
+struct Options {
+    kind: PipelineKind,
+    nms: NmsMode,
+    ious: Vec<f32>,
+    score: Option<f32>,
+}
+
+fn parse(args: &[String]) -> Result<(&Path, &Path, Options), String> {
+    let [images, annotations, flags @ ..] = args else {
+        return Err(format!("usage: {}", USAGE));
+    };
+    let mut options = Options {
+        kind: PipelineKind::Palm(Precision::Fp32),
+        nms: NmsMode::default(),
+        // COCO's 0.5 to 0.95 in steps of 0.05.
+        ious: (0..10).map(|i| 0.5 + 0.05 * i as f32).collect(),
+        score: None,
+    };
+    let mut precision = Precision::Fp32;
+    for pair in flags.chunks(2) {
+        let [flag, value] = pair else {
+            return Err(format!("{} needs a value", pair[0]));
+        };
+        let bad = || format!("bad {} {}, usage: {}", flag, value, USAGE);
+        match flag.as_str() {
+            "--pipeline" => {
+                options.kind = match value.as_str() {
+                    "palm" => PipelineKind::Palm(precision),
+                    "face" => PipelineKind::Iris,
+                    "yolo" => PipelineKind::Yolo,
+                    _ => return Err(bad()),
+                }
+            }
+            "--precision" => {
+                precision = Precision::ALL
+                    .into_iter()
+                    .find(|p| p.name() == value)
+                    .ok_or_else(bad)?;
+            }
+            "--nms" => {
+                options.nms = NmsMode::ALL
+                    .into_iter()
+                    .find(|m| m.name().eq_ignore_ascii_case(value))
+                    .ok_or_else(bad)?;
+            }
+            "--iou" => {
+                options.ious = value
+                    .split(',')
+                    .map(|iou| iou.parse().map_err(|_| bad()))
+                    .collect::<Result<_, _>>()?;
+            }
+            "--score" => options.score = Some(value.parse().map_err(|_| bad())?),
+            _ => return Err(bad()),
+        }
+    }
+    options.kind = options.kind.with_precision(precision);
+    Ok((Path::new(images), Path::new(annotations), options))
+}
+
+/// Runs the pipeline given by `args` on every annotated image and prints
+/// how well its boxes match the annotations.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (dir, annotations, options) = parse(args)?;
+    let coco = fs::read_to_string(annotations).map_err(|e| e.to_string())?;
+    let coco: Coco = serde_json::from_str(&coco).map_err(|e| e.to_string())?;
+
+    let mut models = ModelManager::new(ModelSpec::all());
+    let paths = options
+        .kind
+        .models()
+        .into_iter()
+        .map(|model| models.wait(model))
+        .collect::<Result<Vec<_>, _>>()?;
+    let spec = PipelineSpec::new(options.kind);
+    let mut scheduler = Scheduler::new(vec![(spec, spec.load(&paths)?)]);
+
+    // Palms and faces have no class, so annotations of every category are
+    // theirs. Object classes are matched to categories by name.
+    let by_class = options.kind == PipelineKind::Yolo;
+    let class_names = match by_class {
+        true => coco.categories.iter().map(|c| c.name.clone()).collect(),
+        false => vec!["all".to_owned()],
+    };
+    let category = coco
+        .categories
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.id, i))
+        .collect::<BTreeMap<_, _>>();
+
+    let defaults = Thresholds::default();
+    let (thresholds, operating) = match options.kind {
+        PipelineKind::Yolo => (
+            Thresholds {
+                object_score: MIN_SCORE,
+                ..defaults
+            },
+            defaults.object_score,
+        ),
+        PipelineKind::Iris => (
+            Thresholds {
+                face_score: MIN_SCORE,
+                ..defaults
+            },
+            defaults.face_score,
+        ),
+        _ => (
+            Thresholds {
+                palm_score: MIN_SCORE,
+                ..defaults
+            },
+            defaults.palm_score,
+        ),
+    };
+    let thresholds = Thresholds {
+        nms: options.nms,
+        ..thresholds
+    };
+    let operating = options.score.unwrap_or(operating);
+
+    let mut detections = vec![];
+    let mut truth = vec![];
+    for (i, image) in coco.images.iter().enumerate() {
+        let path = dir.join(&image.file_name);
+        let frame = match image::open(&path) {
+            Ok(frame) => frame.to_rgb8(),
+            Err(e) => {
+                warn!("Failed to read {}, skipping it: {}", path.display(), e);
+                continue;
+            }
+        };
+        truth.extend(
+            coco.annotations
+                .iter()
+                .filter(|a| a.image_id == image.id && a.iscrowd == 0)
+                .filter_map(|a| {
+                    let [x, y, w, h] = a.bbox;
+                    let class = match by_class {
+                        true => *category.get(&a.category_id)?,
+                        false => 0,
+                    };
+                    Some(Labeled {
+                        image: i,
+                        class,
+                        bbox: BBox { x, y, w, h },
+                        score: 1.0,
+                    })
+                }),
+        );
+        let found = scheduler.run(&frame, None, &thresholds);
+        let names = scheduler.class_names();
+        let labeled = |bbox, score, class| Labeled {
+            image: i,
+            class,
+            bbox,
+            score,
+        };
+        detections.extend(found.palms.iter().map(|p| labeled(p.bbox, p.score, 0)));
+        detections.extend(found.faces.iter().map(|f| labeled(f.bbox, f.score, 0)));
+        detections.extend(found.objects.iter().filter_map(|o| {
+            let name = names.get(o.class)?;
+            let class = class_names.iter().position(|c| c == name)?;
+            Some(labeled(o.bbox, o.score, class))
+        }));
+        if (i + 1) % 100 == 0 {
+            report(format_args!("{}/{} images", i + 1, coco.images.len()))?;
+        }
+    }
+
+    report(format_args!(
+        "{} on {} images, {} boxes, NMS {}",
+        options.kind.name(),
+        coco.images.len(),
+        truth.len(),
+        options.nms.name()
+    ))?;
+    let mut aps = vec![];
+    for &iou in &options.ious {
+        let (mut hits, mut kept, mut class_aps) = (0, 0, vec![]);
+        for class in 0..class_names.len() {
+            let of_class = |l: &&Labeled| l.class == class;
+            let class_truth = truth.iter().filter(of_class).copied().collect::<Vec<_>>();
+            if class_truth.is_empty() {
+                continue;
+            }
+            let class_detections = detections
+                .iter()
+                .filter(of_class)
+                .copied()
+                .collect::<Vec<_>>();
+            let matches = match_detections(&class_detections, &class_truth, iou);
+            let (class_hits, class_kept) = hits_above(&matches, operating);
+            (hits, kept) = (hits + class_hits, kept + class_kept);
+            class_aps.push((class, average_precision(&matches, class_truth.len())));
+        }
+        let ap = class_aps.iter().map(|(_, ap)| ap).sum::<f32>() / class_aps.len().max(1) as f32;
+        report(format_args!(
+            "IoU {:.2}: AP {:.3}, precision {:.3} and recall {:.3} at score {:.2}",
+            iou,
+            ap,
+            hits as f32 / kept.max(1) as f32,
+            hits as f32 / truth.len().max(1) as f32,
+            operating
+        ))?;
+        if class_aps.len() > 1 {
+            for (class, ap) in class_aps {
+                report(format_args!("  {}: AP {:.3}", class_names[class], ap))?;
+            }
+        }
+        aps.push(ap);
+    }
+    if aps.len() > 1 {
+        let (first, last) = (options.ious[0], options.ious[aps.len() - 1]);
+        let map = aps.iter().sum::<f32>() / aps.len() as f32;
+        report(format_args!("mAP@[{:.2}:{:.2}]: {:.3}", first, last, map))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labeled(x: f32, score: f32) -> Labeled {
+        Labeled {
+            image: 0,
+            class: 0,
+            bbox: BBox {
+                x,
+                y: 0.0,
+                w: 10.0,
+                h: 10.0,
+            },
+            score,
+        }
+    }
+
+    #[test]
+    fn duplicates_lower_average_precision() {
+        let truth = [labeled(0.0, 1.0), labeled(50.0, 1.0)];
+        // The second detection of the first box is a false positive.
+        let detections = [labeled(1.0, 0.9), labeled(0.0, 0.8), labeled(50.0, 0.7)];
+        let matches = match_detections(&detections, &truth, 0.5);
+        assert_eq!(matches, [(0.9, true), (0.8, false), (0.7, true)]);
+        // Recall up to 0.5 at precision 1, then up to 1 at precision 2/3.
+        let ap = average_precision(&matches, truth.len());
+        assert!(
+            (ap - (51.0 + 50.0 * 2.0 / 3.0) / 101.0).abs() < 1e-5,
+            "{}",
+            ap
+        );
+
+        assert_eq!(average_precision(&[(0.9, true), (0.7, true)], 2), 1.0);
+        assert_eq!(hits_above(&matches, 0.75), (1, 2));
+    }
+}
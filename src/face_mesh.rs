@@ -0,0 +1,286 @@
+//! Face mesh: BlazeFace finds faces, then MediaPipe's face landmark model
+//! regresses 468 points with depth inside a rotated crop around each.
+//! Expression metrics are derived from a few of them: the eye aspect ratio
+//! of each eye, which drops when blinking, and how far the mouth is open.
+
+use image::RgbImage;
+use ndarray::ArrayD;
+use serde::{Serialize, Serializer};
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    bbox::{BBox, RotatedRect},
+    inference::{self, Session},
+    iris::{Face, FaceDetector, LEFT_EYE, RIGHT_EYE},
+    math::sigmoid,
+    nms::NmsMode,
+    preprocess::{rotated_crop_to_tensor, Normalization},
+};
+
+pub const NUM_LANDMARKS: usize = 468;
+const LANDMARK_INPUT: usize = 192;
+const LANDMARKS_LEN: usize = NUM_LANDMARKS * 3;
+/// Crop side relative to the larger side of the face box, as in MediaPipe.
+const FACE_ROI_SCALE: f32 = 1.5;
+/// Faces the landmark model is less sure than this of are left out.
+const PRESENCE_THRESHOLD: f32 = 0.5;
+
+/// Mesh landmarks of each eye for its aspect ratio, right eye then left:
+/// outer corner, two points on the upper lid, inner corner and the two
+/// points on the lower lid below them.
+pub const EYE_LANDMARKS: [[usize; 6]; 2] = [
+    [33, 160, 158, 133, 153, 144],
+    [263, 387, 385, 362, 380, 373],
+];
+/// Middle of the upper and lower inner lip, then the mouth corners.
+pub const MOUTH_LANDMARKS: [usize; 4] = [13, 14, 78, 308];
+/// Eye aspect ratio below which an eye counts as closed. Open eyes are at
+/// about 0.3.
+pub const BLINK_RATIO: f32 = 0.2;
+
+/// What the face is doing, from its mesh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Expression {
+    /// Eyelid opening relative to eye width, right eye then left.
+    pub eye_aspect_ratio: [f32; 2],
+    /// Lip opening relative to mouth width, zero with the mouth closed.
+    pub mouth_openness: f32,
+}
+
+impl Expression {
+    pub fn of(landmarks: &[(f32, f32, f32)]) -> Self {
+        let dist = |a: usize, b: usize| {
+            let ((ax, ay, _), (bx, by, _)) = (landmarks[a], landmarks[b]);
+            (ax - bx).hypot(ay - by)
+        };
+        let eye_aspect_ratio = EYE_LANDMARKS.map(|[p1, p2, p3, p4, p5, p6]| {
+            (dist(p2, p6) + dist(p3, p5)) / (2.0 * dist(p1, p4)).max(1e-3)
+        });
+        let [upper, lower, left, right] = MOUTH_LANDMARKS;
+        Self {
+            eye_aspect_ratio,
+            mouth_openness: dist(upper, lower) / dist(left, right).max(1e-3),
+        }
+    }
+
+    /// Whether each eye is closed, right eye then left.
+    pub fn closed_eyes(&self) -> [bool; 2] {
+        self.eye_aspect_ratio.map(|ratio| ratio < BLINK_RATIO)
+    }
+
+    /// Both eyes closed at once, rather than a wink.
+    pub fn blinking(&self) -> bool {
+        self.closed_eyes() == [true, true]
+    }
+}
+
+fn serialize_landmarks<S: Serializer>(
+    landmarks: &[(f32, f32, f32); NUM_LANDMARKS],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    landmarks[..].serialize(serializer)
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct FaceMesh {
+    /// Crop the landmarks were regressed in.
+    pub roi: RotatedRect,
+    /// Frame pixels, with depth on the same scale. Smaller depths are closer
+    /// to the camera.
+    #[serde(serialize_with = "serialize_landmarks")]
+    pub landmarks: [(f32, f32, f32); NUM_LANDMARKS],
+    /// Probability that the crop holds a face at all.
+    pub presence: f32,
+    pub expression: Expression,
+}
+
+impl FaceMesh {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            roi: RotatedRect {
+                cx: self.roi.cx + x,
+                cy: self.roi.cy + y,
+                ..self.roi
+            },
+            landmarks: self.landmarks.map(|(px, py, z)| (px + x, py + y, z)),
+            ..*self
+        }
+    }
+
+    /// Smallest box around the landmarks.
+    pub fn bbox(&self) -> BBox {
+        let (mut x0, mut y0) = (f32::MAX, f32::MAX);
+        let (mut x1, mut y1) = (f32::MIN, f32::MIN);
+        for (x, y, _) in self.landmarks {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x), y1.max(y));
+        }
+        BBox {
+            x: x0,
+            y: y0,
+            w: x1 - x0,
+            h: y1 - y0,
+        }
+    }
+}
+
+/// Square crop around a detected face, turned so that the eyes are level.
+pub fn mesh_roi(face: &Face) -> RotatedRect {
+    let (rx, ry) = face.keypoints[RIGHT_EYE];
+    let (lx, ly) = face.keypoints[LEFT_EYE];
+    let side = face.bbox.w.max(face.bbox.h) * FACE_ROI_SCALE;
+    RotatedRect {
+        cx: face.bbox.x + face.bbox.w / 2.0,
+        cy: face.bbox.y + face.bbox.h / 2.0,
+        w: side,
+        h: side,
+        angle: (ly - ry).atan2(lx - rx),
+    }
+}
+
+pub struct FaceMesher {
+    detector: FaceDetector,
+    landmarker: Session,
+    /// Whether the model takes all crops of a frame in one batch.
+    batched: bool,
+}
+
+impl FaceMesher {
+    pub fn new(
+        detector_path: impl AsRef<Path>,
+        landmarker_path: impl AsRef<Path>,
+    ) -> Result<Self, String> {
+        let landmarker = inference::create_session(landmarker_path)?;
+        Ok(Self {
+            detector: FaceDetector::new(detector_path)?,
+            batched: landmarker.dynamic_batch(),
+            landmarker,
+        })
+    }
+
+    /// Finds faces and regresses the meshes of the most confident ones.
+    pub fn track(&mut self, img: &RgbImage, score_threshold: f32, nms: NmsMode) -> Vec<FaceMesh> {
+        let rois = self
+            .detector
+            .detect(img, score_threshold, nms)
+            .iter()
+            .map(mesh_roi)
+            .collect::<Vec<_>>();
+        let inputs = rois
+            .iter()
+            .map(|&roi| {
+                rotated_crop_to_tensor(
+                    img,
+                    roi,
+                    LANDMARK_INPUT,
+                    LANDMARK_INPUT,
+                    Normalization::UNIT,
+                )
+            })
+            .collect();
+        let outputs = inference::run_batch(&*self.landmarker, inputs, self.batched);
+        rois.into_iter()
+            .zip(outputs)
+            .filter_map(|(roi, outputs)| Self::decode(roi, &outputs))
+            .collect()
+    }
+
+    /// Mesh from the model outputs for the crop `roi`.
+    fn decode(roi: RotatedRect, outputs: &[ArrayD<f32>]) -> Option<FaceMesh> {
+        let (Some(landmarks), Some(presence)) = (
+            outputs.iter().find(|o| o.len() == LANDMARKS_LEN),
+            outputs.iter().find(|o| o.len() == 1),
+        ) else {
+            error!("Face landmark model has no landmark and face flag outputs");
+            return None;
+        };
+        let presence = sigmoid(presence.iter().next().copied().unwrap_or_default());
+        if presence < PRESENCE_THRESHOLD {
+            return None;
+        }
+        let side = LANDMARK_INPUT as f32;
+        let landmarks = landmarks
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|v| {
+                let (x, y) = roi.to_image(v[0] / side, v[1] / side);
+                (x, y, v[2] / side * roi.w)
+            })
+            .collect::<Vec<_>>();
+        Some(FaceMesh {
+            roi,
+            expression: Expression::of(&landmarks),
+            landmarks: landmarks.try_into().unwrap(),
+            presence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mesh with every landmark at the origin but those of the metrics:
+    /// eyes `eye_height` tall and 10 wide, and the lips `mouth_height` apart
+    /// on a mouth 20 wide.
+    fn landmarks(eye_height: [f32; 2], mouth_height: f32) -> Vec<(f32, f32, f32)> {
+        let mut landmarks = vec![(0.0, 0.0, 0.0); NUM_LANDMARKS];
+        for (eye, h) in EYE_LANDMARKS.iter().zip(eye_height) {
+            let [p1, p2, p3, p4, p5, p6] = *eye;
+            landmarks[p1] = (0.0, 0.0, 0.0);
+            landmarks[p4] = (10.0, 0.0, 0.0);
+            landmarks[p2] = (3.0, -h / 2.0, 0.0);
+            landmarks[p6] = (3.0, h / 2.0, 0.0);
+            landmarks[p3] = (7.0, -h / 2.0, 0.0);
+            landmarks[p5] = (7.0, h / 2.0, 0.0);
+        }
+        let [upper, lower, left, right] = MOUTH_LANDMARKS;
+        landmarks[upper] = (10.0, 50.0 - mouth_height / 2.0, 0.0);
+        landmarks[lower] = (10.0, 50.0 + mouth_height / 2.0, 0.0);
+        landmarks[left] = (0.0, 50.0, 0.0);
+        landmarks[right] = (20.0, 50.0, 0.0);
+        landmarks
+    }
+
+    #[test]
+    fn expression_follows_eyes_and_mouth() {
+        let open = Expression::of(&landmarks([3.0, 3.0], 0.0));
+        assert_eq!(open.eye_aspect_ratio, [0.3, 0.3]);
+        assert_eq!(open.mouth_openness, 0.0);
+        assert!(!open.blinking());
+
+        let wink = Expression::of(&landmarks([3.0, 1.0], 10.0));
+        assert_eq!(wink.closed_eyes(), [false, true]);
+        assert!(!wink.blinking());
+        assert_eq!(wink.mouth_openness, 0.5);
+
+        assert!(Expression::of(&landmarks([0.5, 1.0], 0.0)).blinking());
+    }
+
+    #[test]
+    fn crop_levels_the_eyes() {
+        let face = Face {
+            bbox: BBox {
+                x: 0.0,
+                y: 0.0,
+                w: 100.0,
+                h: 80.0,
+            },
+            keypoints: [
+                (30.0, 40.0),
+                (70.0, 80.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+            ],
+            score: 1.0,
+            eyes: Default::default(),
+        };
+        let roi = mesh_roi(&face);
+        assert_eq!((roi.cx, roi.cy, roi.w, roi.h), (50.0, 40.0, 150.0, 150.0));
+        assert!((roi.angle - std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,154 @@
+//! Moving regions, from the difference between consecutive frames. Needs no
+//! model, and is a plugin [`Pipeline`] rather than a built-in one, as an
+//! example of one.
+
+use image::{GrayImage, RgbImage};
+use std::path::PathBuf;
+
+use crate::{
+    bbox::BBox,
+    plugin::{InputSpec, Pipeline, PluginDetection},
+};
+
+/// Frames are compared at this size, which is plenty for motion and cheap.
+const INPUT: (u32, u32) = (160, 120);
+/// Side of the grid cells motion is counted in, in input pixels.
+const CELL: u32 = 8;
+/// Gray level change of a pixel that counts as motion.
+const CHANGE: u8 = 24;
+/// Share of changed pixels a cell needs to be moving.
+const MIN_CHANGED: f32 = 0.2;
+
+/// Boxes around groups of adjacent moving cells, scored by the share of
+/// their pixels that changed.
+#[derive(Default)]
+pub struct MovingRegions {
+    previous: Option<GrayImage>,
+}
+
+impl Pipeline for MovingRegions {
+    fn name() -> &'static str {
+        "Moving regions"
+    }
+
+    fn load(_: &[PathBuf]) -> Result<Self, String> {
+        Ok(Self::default())
+    }
+
+    fn input(&self) -> InputSpec {
+        InputSpec { size: Some(INPUT) }
+    }
+
+    fn process(&mut self, frame: &RgbImage) -> Vec<PluginDetection> {
+        let gray = image::imageops::grayscale(frame);
+        let previous = self.previous.replace(gray);
+        let (Some(previous), Some(gray)) = (previous, &self.previous) else {
+            return vec![];
+        };
+        if previous.dimensions() != gray.dimensions() {
+            return vec![];
+        }
+        let (cols, rows) = (gray.width() / CELL, gray.height() / CELL);
+        let changed = (0..rows * cols)
+            .map(|i| {
+                let (cx, cy) = (i % cols * CELL, i / cols * CELL);
+                let count = (cy..cy + CELL)
+                    .flat_map(|y| (cx..cx + CELL).map(move |x| (x, y)))
+                    .filter(|&(x, y)| {
+                        gray.get_pixel(x, y)[0].abs_diff(previous.get_pixel(x, y)[0]) >= CHANGE
+                    })
+                    .count();
+                count as f32 / (CELL * CELL) as f32
+            })
+            .collect::<Vec<_>>();
+        regions(&changed, cols, rows)
+            .into_iter()
+            .map(|cells| {
+                let (mut x0, mut y0, mut x1, mut y1) = (cols, rows, 0, 0);
+                for &i in &cells {
+                    let (x, y) = (i as u32 % cols, i as u32 / cols);
+                    (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1));
+                }
+                let score = cells.iter().map(|&i| changed[i]).sum::<f32>() / cells.len() as f32;
+                PluginDetection {
+                    bbox: BBox {
+                        x: (x0 * CELL) as f32,
+                        y: (y0 * CELL) as f32,
+                        w: ((x1 - x0) * CELL) as f32,
+                        h: ((y1 - y0) * CELL) as f32,
+                    },
+                    label: "moving".into(),
+                    score,
+                    keypoints: vec![],
+                }
+            })
+            .collect()
+    }
+}
+
+/// Indices of the moving cells of each 4-connected group, given the share
+/// of changed pixels of every cell of a `cols`×`rows` grid.
+fn regions(changed: &[f32], cols: u32, rows: u32) -> Vec<Vec<usize>> {
+    let (cols, rows) = (cols as usize, rows as usize);
+    let mut seen = vec![false; changed.len()];
+    let mut regions = vec![];
+    for start in 0..changed.len() {
+        if seen[start] || changed[start] < MIN_CHANGED {
+            continue;
+        }
+        seen[start] = true;
+        let (mut region, mut queue) = (vec![], vec![start]);
+        while let Some(i) = queue.pop() {
+            region.push(i);
+            let (x, y) = (i % cols, i / cols);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < cols).then(|| i + 1),
+                (y > 0).then(|| i - cols),
+                (y + 1 < rows).then(|| i + cols),
+            ];
+            for n in neighbors.into_iter().flatten() {
+                if !seen[n] && changed[n] >= MIN_CHANGED {
+                    seen[n] = true;
+                    queue.push(n);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An input-sized frame, black but for a white cell-sized square at
+    /// `(x, y)`.
+    fn frame(x: u32, y: u32) -> RgbImage {
+        RgbImage::from_fn(INPUT.0, INPUT.1, |px, py| {
+            let inside = (x..x + CELL).contains(&px) && (y..y + CELL).contains(&py);
+            image::Rgb(if inside { [255; 3] } else { [0; 3] })
+        })
+    }
+
+    #[test]
+    fn moving_squares_are_found_and_still_ones_are_not() {
+        let mut plugin = MovingRegions::default();
+        assert!(plugin.process(&frame(16, 16)).is_empty());
+        assert!(plugin.process(&frame(16, 16)).is_empty());
+        let found = plugin.process(&frame(24, 16));
+        assert_eq!(found.len(), 1);
+        // Where the square left and where it arrived, side by side.
+        assert_eq!(
+            found[0].bbox,
+            BBox {
+                x: 16.0,
+                y: 16.0,
+                w: 16.0,
+                h: 8.0
+            }
+        );
+        assert_eq!(found[0].score, 1.0);
+    }
+}
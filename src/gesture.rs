@@ -0,0 +1,257 @@
+//! Discrete hand gestures read from the palm keypoints of consecutive frames.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::palm::Palm;
+
+/// Swipes are measured over this much recent motion.
+const SWIPE_WINDOW: Duration = Duration::from_millis(400);
+/// Fraction of the frame a swipe has to cover within the window.
+const SWIPE_DISTANCE: f32 = 0.25;
+/// A palm moving less than this fraction of the frame counts as held still.
+const HOLD_TOLERANCE: f32 = 0.04;
+const HOLD_TIME: Duration = Duration::from_millis(800);
+/// Quiet time after a gesture, so one motion doesn't fire several.
+const COOLDOWN: Duration = Duration::from_millis(600);
+/// Pinch ratio below which the hand pinches, see [`Palm::pinch_ratio`].
+const PINCH_THRESHOLD: f32 = 0.35;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Gesture {
+    /// Open hand held still.
+    OpenPalm,
+    Pinch,
+    SwipeLeft,
+    SwipeRight,
+    SwipeUp,
+    SwipeDown,
+    /// Hand raised into the top of the frame and held there.
+    HandRaise,
+    /// Hand brought closer to the camera than the near distance.
+    HandNear,
+    /// Hand moved farther from the camera than the far distance.
+    HandFar,
+}
+
+impl Gesture {
+    pub const ALL: [Self; 9] = [
+        Self::OpenPalm,
+        Self::Pinch,
+        Self::SwipeLeft,
+        Self::SwipeRight,
+        Self::SwipeUp,
+        Self::SwipeDown,
+        Self::HandRaise,
+        Self::HandNear,
+        Self::HandFar,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::OpenPalm => "Open palm",
+            Self::Pinch => "Pinch",
+            Self::SwipeLeft => "Swipe left",
+            Self::SwipeRight => "Swipe right",
+            Self::SwipeUp => "Swipe up",
+            Self::SwipeDown => "Swipe down",
+            Self::HandRaise => "Hand raise",
+            Self::HandNear => "Hand near",
+            Self::HandFar => "Hand far",
+        }
+    }
+
+    /// Whether the gesture is a motion of the hand, recognized from its
+    /// track by [`crate::motion::MotionGestures`] or
+    /// [`crate::distance::HandDistance`] rather than from single frames.
+    pub fn is_motion(self) -> bool {
+        matches!(
+            self,
+            Self::SwipeLeft
+                | Self::SwipeRight
+                | Self::SwipeUp
+                | Self::SwipeDown
+                | Self::HandRaise
+                | Self::HandNear
+                | Self::HandFar
+        )
+    }
+}
+
+/// Turns the first palm of every frame into gesture events. Each gesture is
+/// reported once, when it completes.
+#[derive(Default)]
+pub struct GestureRecognizer {
+    /// Mirror left and right, as when facing the camera.
+    pub mirror: bool,
+    /// Recent palm centers, normalized to the frame.
+    history: VecDeque<(Instant, (f32, f32))>,
+    cooldown_until: Option<Instant>,
+    pinching: bool,
+    /// When the palm came to rest, and whether that hold already fired.
+    held: Option<(Instant, bool)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(mirror: bool) -> Self {
+        Self {
+            mirror,
+            ..Default::default()
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        palm: Option<&Palm>,
+        (w, h): (u32, u32),
+        now: Instant,
+    ) -> Option<Gesture> {
+        let Some(palm) = palm else {
+            self.history.clear();
+            self.pinching = false;
+            self.held = None;
+            return None;
+        };
+        let bbox = palm.bbox;
+        let center = (
+            (bbox.x + bbox.w / 2.0) / w as f32,
+            (bbox.y + bbox.h / 2.0) / h as f32,
+        );
+        self.history.push_back((now, center));
+        while self
+            .history
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > SWIPE_WINDOW)
+        {
+            self.history.pop_front();
+        }
+
+        let gesture = self
+            .pinch(palm)
+            .or_else(|| self.swipe())
+            .or_else(|| self.hold(now));
+        if self.cooldown_until.is_some_and(|until| now < until) {
+            return None;
+        }
+        if gesture.is_some() {
+            self.cooldown_until = Some(now + COOLDOWN);
+            self.history.clear();
+            self.history.push_back((now, center));
+        }
+        gesture
+    }
+
+    fn pinch(&mut self, palm: &Palm) -> Option<Gesture> {
+        let ratio = palm.pinch_ratio();
+        if !self.pinching && ratio < PINCH_THRESHOLD {
+            self.pinching = true;
+            return Some(Gesture::Pinch);
+        }
+        if self.pinching && ratio > PINCH_THRESHOLD * 1.2 {
+            self.pinching = false;
+        }
+        None
+    }
+
+    fn swipe(&self) -> Option<Gesture> {
+        let (_, (x0, y0)) = self.history.front()?;
+        let (_, (x1, y1)) = self.history.back()?;
+        let (mut dx, dy) = (x1 - x0, y1 - y0);
+        if self.mirror {
+            dx = -dx;
+        }
+        if dx.abs().max(dy.abs()) < SWIPE_DISTANCE {
+            return None;
+        }
+        Some(match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+            (true, true, _) => Gesture::SwipeRight,
+            (true, false, _) => Gesture::SwipeLeft,
+            (false, _, true) => Gesture::SwipeDown,
+            (false, _, false) => Gesture::SwipeUp,
+        })
+    }
+
+    fn hold(&mut self, now: Instant) -> Option<Gesture> {
+        let (_, (x1, y1)) = *self.history.back()?;
+        let still = !self.pinching
+            && self
+                .history
+                .iter()
+                .all(|(_, (x, y))| (x - x1).hypot(y - y1) < HOLD_TOLERANCE);
+        if !still {
+            self.held = None;
+            return None;
+        }
+        match self.held {
+            None => {
+                self.held = Some((now, false));
+                None
+            }
+            Some((since, false)) if now.duration_since(since) >= HOLD_TIME => {
+                self.held = Some((since, true));
+                Some(Gesture::OpenPalm)
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        palm::{INDEX_BASE, MIDDLE_BASE, THUMB, WRIST},
+        test_util::palm,
+    };
+
+    /// Feeds an open palm centered at each `(x, y)` in a 100×100 frame.
+    fn feed(recognizer: &mut GestureRecognizer, frames: &[(u64, f32, f32)]) -> Vec<Gesture> {
+        let start = Instant::now();
+        frames
+            .iter()
+            .filter_map(|&(ms, x, y)| {
+                let now = start + Duration::from_millis(ms);
+                let palm = palm(x, y)
+                    .tip(WRIST, (x, y + 10.0))
+                    .tip(MIDDLE_BASE, (x, y - 10.0))
+                    .tip(INDEX_BASE, (x - 5.0, y - 10.0))
+                    .tip(THUMB, (x - 15.0, y))
+                    .build();
+                recognizer.update(Some(&palm), (100, 100), now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn swipes() {
+        let mut recognizer = GestureRecognizer::default();
+        let frames = [(0, 80.0, 50.0), (100, 65.0, 50.0), (200, 50.0, 50.0)];
+        assert_eq!(feed(&mut recognizer, &frames), [Gesture::SwipeLeft]);
+
+        let mut recognizer = GestureRecognizer::new(true);
+        assert_eq!(feed(&mut recognizer, &frames), [Gesture::SwipeRight]);
+
+        let mut recognizer = GestureRecognizer::default();
+        let frames = [(0, 50.0, 20.0), (300, 50.0, 60.0)];
+        assert_eq!(feed(&mut recognizer, &frames), [Gesture::SwipeDown]);
+    }
+
+    #[test]
+    fn slow_motion_is_no_swipe() {
+        let mut recognizer = GestureRecognizer::default();
+        let frames = (0..10)
+            .map(|i| (i * 200, 80.0 - i as f32 * 5.0, 50.0))
+            .collect::<Vec<_>>();
+        assert_eq!(feed(&mut recognizer, &frames), []);
+    }
+
+    #[test]
+    fn holding_still_fires_once() {
+        let mut recognizer = GestureRecognizer::default();
+        let frames = (0..30).map(|i| (i * 100, 50.0, 50.0)).collect::<Vec<_>>();
+        assert_eq!(feed(&mut recognizer, &frames), [Gesture::OpenPalm]);
+    }
+}
@@ -0,0 +1,210 @@
+//! MediaPipe's hand landmark model: 21 keypoints with relative depth,
+//! regressed inside the rotated crop around a detected palm.
+
+use image::RgbImage;
+use ndarray::ArrayD;
+use serde::Serialize;
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    bbox::{BBox, RotatedRect},
+    inference::{self, Session},
+    palm::{Handedness, Palm},
+    preprocess::{rotated_crop_to_tensor, Normalization},
+};
+
+pub const NUM_LANDMARKS: usize = 21;
+pub const WRIST: usize = 0;
+pub const THUMB_TIP: usize = 4;
+pub const INDEX_BASE: usize = 5;
+pub const INDEX_TIP: usize = 8;
+pub const MIDDLE_BASE: usize = 9;
+pub const MIDDLE_TIP: usize = 12;
+pub const RING_TIP: usize = 16;
+pub const PINKY_BASE: usize = 17;
+pub const PINKY_TIP: usize = 20;
+
+/// Pairs of landmarks connected when drawing the skeleton, as in MediaPipe's
+/// `HAND_CONNECTIONS`.
+pub const HAND_EDGES: [(usize, usize); 21] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 4),
+    (0, 5),
+    (5, 6),
+    (6, 7),
+    (7, 8),
+    (5, 9),
+    (9, 10),
+    (10, 11),
+    (11, 12),
+    (9, 13),
+    (13, 14),
+    (14, 15),
+    (15, 16),
+    (13, 17),
+    (0, 17),
+    (17, 18),
+    (18, 19),
+    (19, 20),
+];
+
+/// Fingertip distance from the wrist, relative to palm length, below which
+/// a finger counts as curled.
+const FIST_REACH: f32 = 1.2;
+
+const LANDMARK_INPUT: usize = 224;
+const LANDMARKS_LEN: usize = NUM_LANDMARKS * 3;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Hand {
+    /// Crop the landmarks were regressed in.
+    pub roi: RotatedRect,
+    /// Frame pixels, with depth relative to the wrist on the same scale.
+    /// Smaller depths are closer to the camera.
+    pub landmarks: [(f32, f32, f32); NUM_LANDMARKS],
+    /// Probability that the crop holds a hand at all.
+    pub presence: f32,
+    pub handedness: Handedness,
+}
+
+impl Hand {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            roi: RotatedRect {
+                cx: self.roi.cx + x,
+                cy: self.roi.cy + y,
+                ..self.roi
+            },
+            landmarks: self.landmarks.map(|(px, py, z)| (px + x, py + y, z)),
+            ..*self
+        }
+    }
+
+    /// Thumb-to-index fingertip distance relative to palm length; small when
+    /// pinching.
+    pub fn pinch_ratio(&self) -> f32 {
+        let dist = |a: usize, b: usize| {
+            let ((ax, ay, _), (bx, by, _)) = (self.landmarks[a], self.landmarks[b]);
+            (ax - bx).hypot(ay - by)
+        };
+        dist(THUMB_TIP, INDEX_TIP) / dist(WRIST, MIDDLE_BASE).max(1.0)
+    }
+
+    /// Whether all four fingers are curled in, their tips no farther from
+    /// the wrist than the palm is long.
+    pub fn is_fist(&self) -> bool {
+        let dist = |a: usize, b: usize| {
+            let ((ax, ay, _), (bx, by, _)) = (self.landmarks[a], self.landmarks[b]);
+            (ax - bx).hypot(ay - by)
+        };
+        let palm = dist(WRIST, MIDDLE_BASE);
+        [INDEX_TIP, MIDDLE_TIP, RING_TIP, PINKY_TIP]
+            .into_iter()
+            .all(|tip| dist(WRIST, tip) < palm * FIST_REACH)
+    }
+
+    /// Smallest box around the landmarks.
+    pub fn bbox(&self) -> BBox {
+        let (mut x0, mut y0) = (f32::MAX, f32::MAX);
+        let (mut x1, mut y1) = (f32::MIN, f32::MIN);
+        for (x, y, _) in self.landmarks {
+            (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x), y1.max(y));
+        }
+        BBox {
+            x: x0,
+            y: y0,
+            w: x1 - x0,
+            h: y1 - y0,
+        }
+    }
+}
+
+pub struct HandLandmarker {
+    session: Session,
+    /// Whether the model takes all crops of a frame in one batch.
+    batched: bool,
+}
+
+impl HandLandmarker {
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, String> {
+        let session = inference::create_session(model_path)?;
+        Ok(Self {
+            batched: session.dynamic_batch(),
+            session,
+        })
+    }
+
+    /// Landmarks of the hands around `palms`, leaving out those the model is
+    /// less than `presence_threshold` sure are there. The crops run as one
+    /// batch if the model allows.
+    pub fn landmarks(
+        &mut self,
+        img: &RgbImage,
+        palms: &[Palm],
+        presence_threshold: f32,
+    ) -> Vec<Hand> {
+        let rois = palms.iter().map(Palm::hand_roi).collect::<Vec<_>>();
+        let inputs = rois
+            .iter()
+            .map(|&roi| {
+                rotated_crop_to_tensor(
+                    img,
+                    roi,
+                    LANDMARK_INPUT,
+                    LANDMARK_INPUT,
+                    Normalization::UNIT,
+                )
+            })
+            .collect();
+        let outputs = inference::run_batch(&*self.session, inputs, self.batched);
+        rois.into_iter()
+            .zip(outputs)
+            .filter_map(|(roi, outputs)| Self::decode(roi, &outputs, presence_threshold))
+            .collect()
+    }
+
+    /// Hand from the model outputs for the crop `roi`.
+    fn decode(roi: RotatedRect, outputs: &[ArrayD<f32>], presence_threshold: f32) -> Option<Hand> {
+        // In name order: landmarks, presence, handedness, world landmarks.
+        let landmarks = outputs.iter().find(|o| o.len() == LANDMARKS_LEN);
+        let mut scalars = outputs.iter().filter(|o| o.len() == 1);
+        let (Some(landmarks), Some(presence), Some(right)) =
+            (landmarks, scalars.next(), scalars.next())
+        else {
+            error!("Hand model has no landmark, presence and handedness outputs");
+            return None;
+        };
+        let presence = presence.iter().next().copied().unwrap_or_default();
+        if presence < presence_threshold {
+            return None;
+        }
+        let side = LANDMARK_INPUT as f32;
+        let landmarks = landmarks
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|v| {
+                let (x, y) = roi.to_image(v[0] / side, v[1] / side);
+                (x, y, v[2] / side * roi.w)
+            })
+            .collect::<Vec<_>>();
+        // The model was trained on mirrored selfies, so on an unmirrored
+        // frame its right hand is the person's left.
+        let right = right.iter().next().copied().unwrap_or_default();
+        let handedness = if right > 0.5 {
+            Handedness::Left
+        } else {
+            Handedness::Right
+        };
+        Some(Hand {
+            roi,
+            landmarks: landmarks.try_into().unwrap(),
+            presence,
+            handedness,
+        })
+    }
+}
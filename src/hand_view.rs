@@ -0,0 +1,154 @@
+//! Hand landmarks in 3D, turning slowly or orbited with the mouse, to see
+//! the depth the landmark model predicts and not just its projection.
+
+use egui::{pos2, vec2, Color32, Sense, Stroke};
+
+use crate::hand::{Hand, HAND_EDGES, WRIST};
+
+/// Turn per second while spinning, in radians.
+const SPIN_SPEED: f32 = 0.6;
+/// Turn per dragged point, in radians.
+const ORBIT_SPEED: f32 = 0.01;
+/// Distance of the camera from the hand, in hand sizes.
+const CAMERA_DISTANCE: f32 = 3.0;
+
+pub struct HandView {
+    /// Whether the panel is shown.
+    pub open: bool,
+    /// Turn the view on its own while it isn't dragged.
+    pub spin: bool,
+    /// Rotation around the vertical axis, then around the horizontal one.
+    yaw: f32,
+    pitch: f32,
+    zoom: f32,
+}
+
+impl Default for HandView {
+    fn default() -> Self {
+        Self {
+            open: false,
+            spin: true,
+            yaw: 0.0,
+            pitch: 0.0,
+            zoom: 1.0,
+        }
+    }
+}
+
+/// Turns `(x, y, z)` by `yaw` around the y axis, then by `pitch` around the
+/// x axis.
+pub fn rotate((x, y, z): (f32, f32, f32), yaw: f32, pitch: f32) -> (f32, f32, f32) {
+    let (sin, cos) = yaw.sin_cos();
+    let (x, z) = (x * cos + z * sin, z * cos - x * sin);
+    let (sin, cos) = pitch.sin_cos();
+    (x, y * cos - z * sin, y * sin + z * cos)
+}
+
+impl HandView {
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.open, "Show panel");
+        ui.checkbox(&mut self.spin, "Spin");
+        if ui.button("Reset view").clicked() {
+            *self = Self {
+                open: self.open,
+                spin: self.spin,
+                ..Self::default()
+            };
+        }
+    }
+
+    /// Draws `hands` side by side, each around its wrist and scaled to its
+    /// crop. Dragging orbits, scrolling zooms and double-clicking
+    /// faces the camera again.
+    pub fn ui(&mut self, ui: &mut egui::Ui, hands: &[Hand]) {
+        ui.label("Drag to orbit, scroll to zoom");
+        let side = ui.available_width().min(ui.available_height());
+        let (response, painter) = ui.allocate_painter(vec2(side, side), Sense::click_and_drag());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, Color32::from_gray(24));
+
+        let delta = response.drag_delta();
+        self.yaw += delta.x * ORBIT_SPEED;
+        self.pitch = (self.pitch - delta.y * ORBIT_SPEED).clamp(-1.5, 1.5);
+        if response.hovered() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+            self.zoom = (self.zoom * (scroll * 0.002).exp()).clamp(0.2, 5.0);
+        }
+        if response.double_clicked() {
+            (self.yaw, self.pitch) = (0.0, 0.0);
+        }
+        if self.spin && !response.dragged() {
+            let dt = ui.input(|i| i.stable_dt).min(0.1);
+            self.yaw = (self.yaw + SPIN_SPEED * dt) % std::f32::consts::TAU;
+        }
+
+        if hands.is_empty() {
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "No hand landmarks",
+                egui::FontId::proportional(16.0),
+                Color32::GRAY,
+            );
+            return;
+        }
+        let n = hands.len() as f32;
+        for (i, hand) in hands.iter().enumerate() {
+            let slot = vec2((i as f32 + 0.5 - n / 2.0) * side / n, side * 0.2);
+            let (wx, wy, wz) = hand.landmarks[WRIST];
+            let size = hand.roi.w.max(1.0);
+            let points = hand.landmarks.map(|(x, y, z)| {
+                let p = ((x - wx) / size, (y - wy) / size, (z - wz) / size);
+                let (x, y, z) = rotate(p, self.yaw, self.pitch);
+                // Perspective, so nearer joints look bigger.
+                let scale = CAMERA_DISTANCE / (CAMERA_DISTANCE + z).max(0.1);
+                let screen = rect.center() + slot + vec2(x, y) * scale * side * 0.8 * self.zoom / n;
+                (screen, z)
+            });
+            let hue = (i as f32 * 0.618_034).fract();
+            let color = |z: f32| {
+                let value = (0.8 - z).clamp(0.3, 1.0);
+                Color32::from(egui::ecolor::Hsva::new(hue, 0.7, value, 1.0))
+            };
+            for (f, t) in HAND_EDGES {
+                let ((a, za), (b, zb)) = (points[f], points[t]);
+                painter.line_segment([a, b], Stroke::new(2.0, color((za + zb) / 2.0)));
+            }
+            for (p, z) in points {
+                painter.circle_filled(p, 4.0 - z.clamp(-1.0, 1.0) * 2.0, color(z));
+            }
+            let label = pos2(rect.min.x + 8.0, rect.min.y + 8.0 + 16.0 * i as f32);
+            painter.text(
+                label,
+                egui::Align2::LEFT_TOP,
+                format!("{} {:.2}", hand.handedness.label(), hand.presence),
+                egui::FontId::proportional(14.0),
+                color(0.0),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarter_turn_swaps_axes() {
+        let close = |(a, b, c): (f32, f32, f32), (x, y, z): (f32, f32, f32)| {
+            (a - x).abs() < 1e-6 && (b - y).abs() < 1e-6 && (c - z).abs() < 1e-6
+        };
+        let quarter = std::f32::consts::FRAC_PI_2;
+        // Turning right brings points on the right towards the viewer.
+        assert!(close(
+            rotate((1.0, 0.0, 0.0), quarter, 0.0),
+            (0.0, 0.0, -1.0)
+        ));
+        // Tilting back moves points in front of the wrist down.
+        assert!(close(
+            rotate((0.0, 0.0, -1.0), 0.0, quarter),
+            (0.0, 1.0, 0.0)
+        ));
+        assert!(close(rotate((0.3, -0.2, 0.5), 0.0, 0.0), (0.3, -0.2, 0.5)));
+    }
+}
@@ -0,0 +1,269 @@
+//! Model sessions and the provider they load on. Sessions are native ONNX
+//! Runtime ones, which the `native` feature brings; the browser build runs
+//! its model through onnxruntime-web instead, see `src/web.rs`.
+
+use ndarray::{Array4, ArrayD, Axis, Slice};
+#[cfg(feature = "native")]
+use ort::{ExecutionProvider, SessionBuilder, Tensor};
+use serde::{Deserialize, Serialize};
+use std::{cell::Cell, path::Path, sync::Mutex};
+#[cfg(feature = "native")]
+use tracing::info;
+use tracing::{debug_span, warn};
+
+/// Provider [`create_session`] loads models on. `None` prefers CUDA.
+static DEFAULT_PROVIDER: Mutex<Option<Provider>> = Mutex::new(None);
+
+/// A loaded model with a single input.
+pub trait InferenceSession: Send {
+    /// Runs the model on `input` and returns its outputs in name order.
+    fn run(&self, input: Array4<f32>) -> Vec<ArrayD<f32>>;
+
+    /// Whether the input takes any batch size. Models exported with a
+    /// fixed batch of 1 don't.
+    fn dynamic_batch(&self) -> bool;
+}
+
+pub type Session = Box<dyn InferenceSession>;
+
+#[cfg(feature = "native")]
+impl InferenceSession for ort::Session {
+    fn run(&self, input: Array4<f32>) -> Vec<ArrayD<f32>> {
+        let tensor = Tensor::from_array(input).unwrap();
+        let outputs = ort::Session::run(self, ort::inputs![tensor].unwrap()).unwrap();
+        outputs
+            .iter()
+            .map(|o| o.1.try_extract_tensor::<f32>().unwrap().view().into_owned())
+            .collect()
+    }
+
+    fn dynamic_batch(&self) -> bool {
+        self.inputs
+            .first()
+            .and_then(|input| input.input_type.tensor_dimensions())
+            .and_then(|dimensions| dimensions.first())
+            .is_some_and(|&batch| batch < 0)
+    }
+}
+
+thread_local! {
+    /// Provider [`with_provider`] loads models on, over the default one.
+    static PROVIDER: Cell<Option<Provider>> = const { Cell::new(None) };
+}
+
+/// Makes [`create_session`] load models on `provider` only, or on CUDA if
+/// available and the CPU otherwise for `None`.
+pub fn set_default_provider(provider: Option<Provider>) {
+    *DEFAULT_PROVIDER.lock().unwrap() = provider;
+}
+
+pub fn default_provider() -> Option<Provider> {
+    *DEFAULT_PROVIDER.lock().unwrap()
+}
+
+/// Calls `load` with [`create_session`] loading models on `provider`, or
+/// the default provider for `None`.
+pub fn with_provider<T>(provider: Option<Provider>, load: impl FnOnce() -> T) -> T {
+    let previous = PROVIDER.replace(provider);
+    let loaded = load();
+    PROVIDER.set(previous);
+    loaded
+}
+
+/// Loads a model on the default provider, preferring CUDA when it is
+/// available unless one was set.
+#[cfg(feature = "native")]
+pub fn create_session(model_path: impl AsRef<Path>) -> Result<Session, String> {
+    let model_path = model_path.as_ref();
+    if let Some(provider) = PROVIDER.get().or_else(default_provider) {
+        return create_session_on(model_path, provider)
+            .map_err(|e| format!("{}: {}", model_path.display(), e));
+    }
+    let builder = SessionBuilder::new().map_err(|e| e.to_string())?;
+    let cuda = ort::CUDAExecutionProvider::default();
+    match cuda.register(&builder) {
+        Ok(_) => info!("CUDA found"),
+        Err(e) => warn!("CUDA not found: {}", e),
+    }
+    builder
+        .commit_from_file(model_path)
+        .map(|session| Box::new(session) as Session)
+        .map_err(|e| format!("{}: {}", model_path.display(), e))
+}
+
+/// Builds without `native` have no ONNX Runtime to load models with.
+#[cfg(not(feature = "native"))]
+pub fn create_session(model_path: impl AsRef<Path>) -> Result<Session, String> {
+    Err(format!(
+        "{}: models only load in the native build",
+        model_path.as_ref().display()
+    ))
+}
+
+/// Dimensions of the inputs and outputs of a model, in the order the model
+/// declares them. Dynamic dimensions are negative.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModelShapes {
+    pub inputs: Vec<Vec<i64>>,
+    pub outputs: Vec<Vec<i64>>,
+}
+
+/// Reads the shapes of the model at `model_path`, loading it on the CPU.
+#[cfg(feature = "native")]
+pub fn model_shapes(model_path: impl AsRef<Path>) -> Result<ModelShapes, String> {
+    let session = SessionBuilder::new()
+        .and_then(|builder| builder.commit_from_file(model_path))
+        .map_err(|e| e.to_string())?;
+    let dimensions = |ty: &ort::ValueType| ty.tensor_dimensions().cloned().unwrap_or_default();
+    Ok(ModelShapes {
+        inputs: session
+            .inputs
+            .iter()
+            .map(|input| dimensions(&input.input_type))
+            .collect(),
+        outputs: session
+            .outputs
+            .iter()
+            .map(|output| dimensions(&output.output_type))
+            .collect(),
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Provider {
+    Cpu,
+    Cuda,
+}
+
+impl Provider {
+    pub const ALL: [Self; 2] = [Self::Cpu, Self::Cuda];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Cuda => "CUDA",
+        }
+    }
+}
+
+/// Loads a model on `provider` only, failing if it isn't available.
+#[cfg(feature = "native")]
+pub fn create_session_on(
+    model_path: impl AsRef<Path>,
+    provider: Provider,
+) -> Result<Session, String> {
+    let builder = SessionBuilder::new().map_err(|e| e.to_string())?;
+    if provider == Provider::Cuda {
+        let cuda = ort::CUDAExecutionProvider::default();
+        if !cuda.is_available().map_err(|e| e.to_string())? {
+            return Err("CUDA is not available".to_owned());
+        }
+        cuda.register(&builder).map_err(|e| e.to_string())?;
+    }
+    builder
+        .commit_from_file(model_path)
+        .map(|session| Box::new(session) as Session)
+        .map_err(|e| e.to_string())
+}
+
+/// Runs a single-input model and returns its outputs in name order.
+pub fn run(session: &dyn InferenceSession, input: Array4<f32>) -> Vec<ArrayD<f32>> {
+    let _span = debug_span!("inference").entered();
+    session.run(input)
+}
+
+/// Runs a single-input model on each of `inputs` and returns the outputs of
+/// each, as [`run`] does. With `batched`, the inputs are stacked into one
+/// tensor and run at once; otherwise, or if the outputs don't have a batch
+/// dimension of the input count, they are run one after the other.
+pub fn run_batch(
+    session: &dyn InferenceSession,
+    inputs: Vec<Array4<f32>>,
+    batched: bool,
+) -> Vec<Vec<ArrayD<f32>>> {
+    if batched && inputs.len() > 1 {
+        let views = inputs.iter().map(|input| input.view()).collect::<Vec<_>>();
+        let stacked = ndarray::concatenate(Axis(0), &views).unwrap();
+        if let Some(outputs) = split_batch(&run(session, stacked), inputs.len()) {
+            return outputs;
+        }
+        warn!("Batched outputs don't match the inputs, running them one by one");
+    }
+    inputs
+        .into_iter()
+        .map(|input| run(session, input))
+        .collect()
+}
+
+/// Splits each of the batched `outputs` into `n` outputs with a batch of
+/// one, grouped by batch item. `None` if an output has another batch size.
+fn split_batch(outputs: &[ArrayD<f32>], n: usize) -> Option<Vec<Vec<ArrayD<f32>>>> {
+    if outputs
+        .iter()
+        .any(|output| output.shape().first() != Some(&n))
+    {
+        return None;
+    }
+    let item = |i: usize| {
+        outputs
+            .iter()
+            .map(|output| output.slice_axis(Axis(0), Slice::from(i..i + 1)).to_owned())
+            .collect()
+    };
+    Some((0..n).map(item).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::IxDyn;
+
+    #[test]
+    fn batched_outputs_are_split_per_item() {
+        let landmarks = ArrayD::from_shape_fn(IxDyn(&[3, 63]), |i| (i[0] * 100 + i[1]) as f32);
+        let presence = ArrayD::from_shape_fn(IxDyn(&[3, 1]), |i| i[0] as f32);
+        let items = split_batch(&[landmarks, presence], 3).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[2][0].shape(), [1, 63]);
+        assert_eq!(items[2][0][[0, 5]], 205.0);
+        assert_eq!(items[1][1].iter().copied().collect::<Vec<_>>(), [1.0]);
+
+        let fixed = ArrayD::zeros(IxDyn(&[1, 63]));
+        assert!(split_batch(&[fixed], 3).is_none());
+    }
+
+    /// Sums each batch item of its input, counting its runs.
+    #[derive(Default)]
+    struct Sum {
+        runs: Cell<usize>,
+    }
+
+    impl InferenceSession for Sum {
+        fn run(&self, input: Array4<f32>) -> Vec<ArrayD<f32>> {
+            self.runs.set(self.runs.get() + 1);
+            let sums = input
+                .outer_iter()
+                .map(|item| item.sum())
+                .collect::<Vec<_>>();
+            vec![ArrayD::from_shape_vec(IxDyn(&[sums.len(), 1]), sums).unwrap()]
+        }
+
+        fn dynamic_batch(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn batches_run_at_once_or_one_by_one() {
+        let inputs = (1..=3)
+            .map(|i| Array4::from_elem((1, 1, 2, 2), i as f32))
+            .collect::<Vec<_>>();
+        for (batched, runs) in [(true, 1), (false, 3)] {
+            let session = Sum::default();
+            let outputs = run_batch(&session, inputs.clone(), batched);
+            let sums = outputs.iter().map(|o| o[0][[0, 0]]).collect::<Vec<_>>();
+            assert_eq!(sums, [4.0, 8.0, 12.0]);
+            assert_eq!(session.runs.get(), runs);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Iris tracking: BlazeFace finds faces, then MediaPipe's iris model
+//! regresses eyelids and iris inside a crop around each eye. Gaze is
+//! estimated from where the iris sits within the eyelids.
+
+use image::RgbImage;
+use serde::Serialize;
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    anchors::{Anchor, AnchorConfig},
+    bbox::{BBox, Detection, RotatedRect},
+    inference::{self, Session},
+    math::sigmoid,
+    nms::{suppress, weighted_bbox, weighted_mean, NmsMode},
+    preprocess::{rotated_crop_to_tensor, Normalization, Resizer},
+};
+
+/// BlazeFace keypoints, named from the person's point of view.
+pub const RIGHT_EYE: usize = 0;
+pub const LEFT_EYE: usize = 1;
+pub const KEYPOINT_NAMES: [&str; 6] = [
+    "right eye",
+    "left eye",
+    "nose tip",
+    "mouth",
+    "right ear",
+    "left ear",
+];
+const FACE_STRIDE: usize = 16;
+const MAX_FACES: usize = 4;
+
+const IRIS_INPUT: usize = 64;
+/// Eyelid landmarks at the start of the iris model's 71 eye and brow
+/// landmarks, each as x, y and z.
+pub const EYELID_LANDMARKS: usize = 16;
+const CONTOUR_LEN: usize = 71 * 3;
+/// Iris center followed by four points on its edge.
+const IRIS_LEN: usize = 5 * 3;
+/// Eye crop side relative to the distance between the eyes. MediaPipe
+/// crops 2.3 eye widths, which comes to about this much.
+const EYE_CROP_SCALE: f32 = 1.1;
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Eye {
+    pub eyelids: [(f32, f32); EYELID_LANDMARKS],
+    pub iris: (f32, f32),
+    pub iris_radius: f32,
+    /// Offset of the iris from the middle of the eyelids, relative to the
+    /// eye's width, in frame directions. Zero looking straight ahead.
+    pub gaze: (f32, f32),
+}
+
+impl Eye {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            eyelids: self.eyelids.map(|(px, py)| (px + x, py + y)),
+            iris: (self.iris.0 + x, self.iris.1 + y),
+            ..*self
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Face {
+    pub bbox: BBox,
+    /// Eyes, nose tip, mouth and ear tragions, see [`RIGHT_EYE`].
+    pub keypoints: [(f32, f32); 6],
+    pub score: f32,
+    /// Right eye, then left eye.
+    pub eyes: [Eye; 2],
+}
+
+impl Detection for Face {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn with_score(self, score: f32) -> Self {
+        Self { score, ..self }
+    }
+
+    /// Blends the detected boxes and keypoints; eyes are tracked after NMS.
+    fn blend(cluster: &[Self]) -> Self {
+        Self {
+            bbox: weighted_bbox(cluster),
+            keypoints: std::array::from_fn(|j| weighted_mean(cluster, |f| f.keypoints[j])),
+            ..cluster[0]
+        }
+    }
+}
+
+impl Face {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            bbox: self.bbox.shift(x, y),
+            keypoints: self.keypoints.map(|(px, py)| (px + x, py + y)),
+            eyes: self.eyes.map(|eye| eye.shift(x, y)),
+            ..*self
+        }
+    }
+
+    /// Crop around one eye, turned with the face. The left eye is mirrored,
+    /// as the iris model only knows right eyes.
+    pub fn eye_roi(&self, eye: usize) -> RotatedRect {
+        let (rx, ry) = self.keypoints[RIGHT_EYE];
+        let (lx, ly) = self.keypoints[LEFT_EYE];
+        let side = (lx - rx).hypot(ly - ry) * EYE_CROP_SCALE;
+        let (cx, cy) = self.keypoints[eye];
+        RotatedRect {
+            cx,
+            cy,
+            w: if eye == LEFT_EYE { -side } else { side },
+            h: side,
+            angle: (ly - ry).atan2(lx - rx),
+        }
+    }
+}
+
+/// Iris center and radius, and the gaze offset of the iris from the middle
+/// of `eyelids`.
+pub fn gaze(eyelids: &[(f32, f32)], iris: &[(f32, f32)]) -> ((f32, f32), f32, (f32, f32)) {
+    let center = iris[0];
+    let radius = iris[1..]
+        .iter()
+        .map(|(x, y)| (x - center.0).hypot(y - center.1))
+        .sum::<f32>()
+        / (iris.len() - 1) as f32;
+    let n = eyelids.len() as f32;
+    let middle = eyelids
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x / n, sy + y / n));
+    let width = eyelids
+        .iter()
+        .flat_map(|a| eyelids.iter().map(move |b| (a.0 - b.0).hypot(a.1 - b.1)))
+        .fold(0.0, f32::max)
+        .max(1.0);
+    let offset = ((center.0 - middle.0) / width, (center.1 - middle.1) / width);
+    (center, radius, offset)
+}
+
+/// Short-range BlazeFace, the first stage of every face pipeline.
+pub struct FaceDetector {
+    session: Session,
+    anchor_config: AnchorConfig,
+    anchors: Vec<Anchor>,
+    resizer: Resizer,
+}
+
+impl FaceDetector {
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, String> {
+        let anchor_config = AnchorConfig::face_detection_short_range();
+        Ok(Self {
+            session: inference::create_session(model_path)?,
+            anchors: anchor_config.generate(),
+            resizer: Resizer::new(anchor_config.input_width, anchor_config.input_height),
+            anchor_config,
+        })
+    }
+
+    /// Faces above `score_threshold`, at most [`MAX_FACES`] of the most
+    /// confident ones. Their eyes are left empty.
+    pub fn detect(&mut self, img: &RgbImage, score_threshold: f32, nms: NmsMode) -> Vec<Face> {
+        let (input_w, input_h) = (
+            self.anchor_config.input_width,
+            self.anchor_config.input_height,
+        );
+        let input = self.resizer.resize(img, Normalization::SIGNED);
+        let outputs = inference::run(&*self.session, input);
+        let n = self.anchors.len();
+        let (Some(regressors), Some(scores)) = (
+            outputs.iter().find(|o| o.len() == n * FACE_STRIDE),
+            outputs.iter().find(|o| o.len() == n),
+        ) else {
+            error!("Face model has no box and score outputs");
+            return vec![];
+        };
+        let regressors = regressors.iter().copied().collect::<Vec<_>>();
+        let scores = scores.iter().copied().collect::<Vec<_>>();
+
+        let scale_x = img.width() as f32 / input_w as f32;
+        let scale_y = img.height() as f32 / input_h as f32;
+        let faces = self
+            .anchors
+            .iter()
+            .zip(regressors.chunks_exact(FACE_STRIDE))
+            .zip(&scores)
+            .map(|((anchor, r), score)| {
+                let (ax, ay) = (
+                    anchor.x_center * input_w as f32,
+                    anchor.y_center * input_h as f32,
+                );
+                let x = r[0] * anchor.w + ax;
+                let y = r[1] * anchor.h + ay;
+                let w = r[2] * anchor.w;
+                let h = r[3] * anchor.h;
+                let keypoint = |j: usize| {
+                    (
+                        (r[4 + j * 2] * anchor.w + ax) * scale_x,
+                        (r[4 + j * 2 + 1] * anchor.h + ay) * scale_y,
+                    )
+                };
+                Face {
+                    bbox: BBox {
+                        x: x - w / 2.0,
+                        y: y - h / 2.0,
+                        w,
+                        h,
+                    }
+                    .scale(scale_x, scale_y),
+                    keypoints: [0, 1, 2, 3, 4, 5].map(keypoint),
+                    score: sigmoid(*score),
+                    eyes: [Eye::default(); 2],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut faces = suppress(faces, nms, score_threshold, 0.3);
+        faces.truncate(MAX_FACES);
+        faces
+    }
+}
+
+pub struct IrisTracker {
+    detector: FaceDetector,
+    landmarker: Session,
+}
+
+impl IrisTracker {
+    pub fn new(
+        detector_path: impl AsRef<Path>,
+        landmarker_path: impl AsRef<Path>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            detector: FaceDetector::new(detector_path)?,
+            landmarker: inference::create_session(landmarker_path)?,
+        })
+    }
+
+    /// Finds faces and tracks the irises of the most confident ones.
+    pub fn track(&mut self, img: &RgbImage, score_threshold: f32, nms: NmsMode) -> Vec<Face> {
+        let faces = self.detector.detect(img, score_threshold, nms);
+        faces
+            .into_iter()
+            .filter_map(|mut face| {
+                for (i, eye) in [RIGHT_EYE, LEFT_EYE].into_iter().enumerate() {
+                    face.eyes[i] = self.track_eye(img, face.eye_roi(eye))?;
+                }
+                Some(face)
+            })
+            .collect()
+    }
+
+    fn track_eye(&mut self, img: &RgbImage, roi: RotatedRect) -> Option<Eye> {
+        let input = rotated_crop_to_tensor(img, roi, IRIS_INPUT, IRIS_INPUT, Normalization::UNIT);
+        let outputs = inference::run(&*self.landmarker, input);
+        let (Some(contour), Some(iris)) = (
+            outputs.iter().find(|o| o.len() == CONTOUR_LEN),
+            outputs.iter().find(|o| o.len() == IRIS_LEN),
+        ) else {
+            error!("Iris model has no eye contour and iris outputs");
+            return None;
+        };
+        let to_frame = |v: &[f32]| roi.to_image(v[0] / IRIS_INPUT as f32, v[1] / IRIS_INPUT as f32);
+        let contour = contour.iter().copied().collect::<Vec<_>>();
+        let iris = iris.iter().copied().collect::<Vec<_>>();
+        let eyelids = contour
+            .chunks_exact(3)
+            .take(EYELID_LANDMARKS)
+            .map(to_frame)
+            .collect::<Vec<_>>();
+        let iris = iris.chunks_exact(3).map(to_frame).collect::<Vec<_>>();
+        let (center, radius, offset) = gaze(&eyelids, &iris);
+        Some(Eye {
+            eyelids: eyelids.try_into().unwrap(),
+            iris: center,
+            iris_radius: radius,
+            gaze: offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaze_follows_iris() {
+        let eyelids = [(0.0, 0.0), (20.0, 0.0), (10.0, -4.0), (10.0, 4.0)];
+        let iris = [
+            (15.0, 0.0),
+            (17.0, 0.0),
+            (13.0, 0.0),
+            (15.0, 2.0),
+            (15.0, -2.0),
+        ];
+        let (center, radius, offset) = gaze(&eyelids, &iris);
+        assert_eq!(center, (15.0, 0.0));
+        assert_eq!(radius, 2.0);
+        assert_eq!(offset, (0.25, 0.0));
+    }
+
+    #[test]
+    fn left_eye_crop_is_mirrored() {
+        let face = Face {
+            bbox: BBox {
+                x: 0.0,
+                y: 0.0,
+                w: 100.0,
+                h: 100.0,
+            },
+            keypoints: [
+                (30.0, 40.0),
+                (70.0, 40.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+                (0.0, 0.0),
+            ],
+            score: 1.0,
+            eyes: [Eye::default(); 2],
+        };
+        // Both crops have the nose side of the eye on the same side.
+        let right = face.eye_roi(RIGHT_EYE);
+        let left = face.eye_roi(LEFT_EYE);
+        assert_eq!(right.to_image(1.0, 0.5), (52.0, 40.0));
+        assert_eq!(left.to_image(1.0, 0.5), (48.0, 40.0));
+    }
+}
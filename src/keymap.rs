@@ -0,0 +1,227 @@
+//! Global keyboard shortcuts triggered by gestures.
+
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::warn;
+
+use crate::{
+    gesture::{Gesture, GestureRecognizer},
+    motion::MotionEvent,
+    palm::Palm,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub gesture: Gesture,
+    /// Keys joined by `+`, e.g. `ctrl+shift+t`, `pagedown` or `playpause`.
+    pub shortcut: String,
+}
+
+pub fn default_bindings() -> Vec<Binding> {
+    let binding = |gesture, shortcut: &str| Binding {
+        gesture,
+        shortcut: shortcut.to_owned(),
+    };
+    vec![
+        binding(Gesture::OpenPalm, "playpause"),
+        binding(Gesture::SwipeLeft, "pagedown"),
+        binding(Gesture::SwipeRight, "pageup"),
+    ]
+}
+
+/// Parses a `+`-separated shortcut into the keys to hold down, in order.
+pub fn parse_shortcut(shortcut: &str) -> Result<Vec<Key>, String> {
+    shortcut
+        .split('+')
+        .map(|name| {
+            let name = name.trim();
+            let mut chars = name.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                return Ok(Key::Unicode(c.to_ascii_lowercase()));
+            }
+            Ok(match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Key::Control,
+                "alt" => Key::Alt,
+                "shift" => Key::Shift,
+                "meta" | "super" | "win" | "cmd" => Key::Meta,
+                "space" => Key::Space,
+                "enter" | "return" => Key::Return,
+                "tab" => Key::Tab,
+                "esc" | "escape" => Key::Escape,
+                "backspace" => Key::Backspace,
+                "delete" => Key::Delete,
+                "home" => Key::Home,
+                "end" => Key::End,
+                "pageup" => Key::PageUp,
+                "pagedown" => Key::PageDown,
+                "left" => Key::LeftArrow,
+                "right" => Key::RightArrow,
+                "up" => Key::UpArrow,
+                "down" => Key::DownArrow,
+                "playpause" => Key::MediaPlayPause,
+                "next" => Key::MediaNextTrack,
+                "prev" => Key::MediaPrevTrack,
+                "volumeup" => Key::VolumeUp,
+                "volumedown" => Key::VolumeDown,
+                "mute" => Key::VolumeMute,
+                "f1" => Key::F1,
+                "f2" => Key::F2,
+                "f3" => Key::F3,
+                "f4" => Key::F4,
+                "f5" => Key::F5,
+                "f6" => Key::F6,
+                "f7" => Key::F7,
+                "f8" => Key::F8,
+                "f9" => Key::F9,
+                "f10" => Key::F10,
+                "f11" => Key::F11,
+                "f12" => Key::F12,
+                "" => return Err("empty key".to_owned()),
+                _ => return Err(format!("unknown key \"{}\"", name)),
+            })
+        })
+        .collect()
+}
+
+/// Recognizes gestures on the first palm and presses the shortcuts bound to
+/// them, and to the motion gestures of any hand.
+pub struct GestureKeys {
+    pub enabled: bool,
+    pub bindings: Vec<Binding>,
+    recognizer: GestureRecognizer,
+    enigo: Option<Enigo>,
+    /// Most recent gesture and what it triggered, for the UI.
+    last: Option<(Gesture, String)>,
+}
+
+impl GestureKeys {
+    pub fn new(bindings: Vec<Binding>) -> Self {
+        let enigo = match Enigo::new(&Settings::default()) {
+            Ok(enigo) => Some(enigo),
+            Err(e) => {
+                warn!("Gesture shortcuts unavailable: {}", e);
+                None
+            }
+        };
+        Self {
+            enabled: false,
+            bindings,
+            recognizer: GestureRecognizer::new(true),
+            enigo,
+            last: None,
+        }
+    }
+
+    /// Feeds the palm detected in a frame of `frame_size` pixels, if any,
+    /// and the motion gestures completed in the frame. Motion gestures only
+    /// come from `motion`, which follows every hand.
+    pub fn update(&mut self, palm: Option<&Palm>, frame_size: (u32, u32), motion: &[MotionEvent]) {
+        if !self.enabled {
+            return;
+        }
+        let still = self
+            .recognizer
+            .update(palm, frame_size, Instant::now())
+            .filter(|gesture| !gesture.is_motion());
+        for gesture in still.into_iter().chain(motion.iter().map(|e| e.gesture)) {
+            self.trigger(gesture);
+        }
+    }
+
+    fn trigger(&mut self, gesture: Gesture) {
+        let shortcuts = self
+            .bindings
+            .iter()
+            .filter(|b| b.gesture == gesture)
+            .map(|b| b.shortcut.clone())
+            .collect::<Vec<_>>();
+        for shortcut in &shortcuts {
+            match parse_shortcut(shortcut) {
+                Ok(keys) => self.press(&keys),
+                Err(e) => warn!("Invalid shortcut {}: {}", shortcut, e),
+            }
+        }
+        self.last = Some((gesture, shortcuts.join(", ")));
+    }
+
+    fn press(&mut self, keys: &[Key]) {
+        let Some(enigo) = self.enigo.as_mut() else {
+            return;
+        };
+        let result = keys
+            .iter()
+            .try_for_each(|key| enigo.key(*key, Direction::Press))
+            .and_then(|_| {
+                keys.iter()
+                    .rev()
+                    .try_for_each(|key| enigo.key(*key, Direction::Release))
+            });
+        if let Err(e) = result {
+            warn!("Key event failed: {}", e);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.enigo.is_none() {
+            ui.label("No input backend available");
+            return;
+        }
+        ui.checkbox(&mut self.enabled, "Enabled");
+        let mut remove = None;
+        egui::Grid::new("gesture bindings").show(ui, |ui| {
+            for (i, binding) in self.bindings.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("gesture", i))
+                    .selected_text(binding.gesture.name())
+                    .show_ui(ui, |ui| {
+                        for gesture in Gesture::ALL {
+                            ui.selectable_value(&mut binding.gesture, gesture, gesture.name());
+                        }
+                    });
+                let valid = parse_shortcut(&binding.shortcut).is_ok();
+                ui.add(
+                    egui::TextEdit::singleline(&mut binding.shortcut)
+                        .desired_width(120.0)
+                        .text_color_opt((!valid).then_some(egui::Color32::RED)),
+                );
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.bindings.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add").clicked() {
+                self.bindings.push(Binding {
+                    gesture: Gesture::OpenPalm,
+                    shortcut: String::new(),
+                });
+            }
+            if ui.button("Defaults").clicked() {
+                self.bindings = default_bindings();
+            }
+        });
+        if let Some((gesture, shortcuts)) = &self.last {
+            ui.label(format!("Last: {} → {}", gesture.name(), shortcuts));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shortcuts() {
+        assert_eq!(
+            parse_shortcut("Ctrl+Shift+T"),
+            Ok(vec![Key::Control, Key::Shift, Key::Unicode('t')])
+        );
+        assert_eq!(parse_shortcut(" pagedown "), Ok(vec![Key::PageDown]));
+        assert!(parse_shortcut("ctrl+").is_err());
+        assert!(parse_shortcut("hyper+x").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+//! Building blocks of the playground: model pipelines, pre- and
+//! postprocessing, camera streams and outputs. The desktop app lives in
+//! `main.rs`, the browser app in [`web`].
+
+pub mod anchors;
+pub mod bbox;
+#[cfg(feature = "native")]
+pub mod bench;
+pub mod canvas;
+pub mod clip;
+pub mod color;
+pub mod compare;
+#[cfg(feature = "native")]
+pub mod config;
+pub mod depth;
+pub mod distance;
+pub mod enhance;
+#[cfg(feature = "native")]
+pub mod eval;
+pub mod face_mesh;
+pub mod frame_diff;
+pub mod gesture;
+pub mod hand;
+pub mod hand_view;
+pub mod inference;
+pub mod iris;
+#[cfg(feature = "native")]
+pub mod keymap;
+pub mod logging;
+pub mod math;
+#[cfg(feature = "native")]
+pub mod midi;
+pub mod models;
+pub mod motion;
+#[cfg(feature = "native")]
+pub mod mouse;
+#[cfg(feature = "native")]
+pub mod network;
+pub mod nms;
+pub mod osc;
+pub mod overlay;
+pub mod palm;
+pub mod pipeline;
+pub mod plugin;
+pub mod pose;
+pub mod power;
+pub mod preprocess;
+pub mod recorder;
+#[cfg(feature = "native")]
+pub mod reload;
+#[cfg(feature = "native")]
+pub mod replay;
+#[cfg(feature = "native")]
+pub mod report;
+pub mod scheduler;
+#[cfg(feature = "native")]
+pub mod segmentation;
+pub mod sink;
+pub mod snapshot;
+pub mod source;
+#[cfg(feature = "native")]
+pub mod split;
+pub mod stream;
+#[cfg(test)]
+pub(crate) mod test_util;
+pub mod track;
+pub mod trails;
+pub mod two_hands;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod worker;
+pub mod yolo;
+#[cfg(feature = "native")]
+pub mod zoo;
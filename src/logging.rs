@@ -0,0 +1,218 @@
+//! Diagnostics through `tracing`, printed to stdout and kept for the log
+//! panel, since release builds on Windows have no console to print to.
+
+use egui::{Color32, RichText};
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tracing::{field::Field, Event, Level, Subscriber};
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+/// Entries kept for the panel; older ones are dropped.
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    /// Seconds since logging started.
+    pub time: f32,
+    pub level: Level,
+    pub target: String,
+    /// Names of the spans the event happened in, outermost first, e.g.
+    /// `capture` or `detect:inference`.
+    pub spans: String,
+    /// The message, followed by any other fields.
+    pub message: String,
+}
+
+/// Most recent log entries, shared between the logging layer and the panel.
+#[derive(Clone, Default)]
+pub struct LogBuffer(Arc<Mutex<VecDeque<LogEntry>>>);
+
+impl LogBuffer {
+    fn push(&self, entry: LogEntry) {
+        let mut entries = self.0.lock().unwrap();
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Entries at `level` or more severe, oldest first.
+    pub fn entries(&self, level: Level) -> Vec<LogEntry> {
+        let entries = self.0.lock().unwrap();
+        entries
+            .iter()
+            .filter(|e| e.level <= level)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+/// Collects the message and fields of an event into one line.
+#[derive(Default)]
+struct Message(String);
+
+impl tracing::field::Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Layer appending every event to a [`LogBuffer`].
+pub struct BufferLayer {
+    buffer: LogBuffer,
+    start: Instant,
+}
+
+impl BufferLayer {
+    pub fn new(buffer: LogBuffer) -> Self {
+        Self {
+            buffer,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl<S> Layer<S> for BufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut message = Message::default();
+        event.record(&mut message);
+        let spans = ctx.event_scope(event).map_or(String::new(), |scope| {
+            scope
+                .from_root()
+                .map(|span| span.name())
+                .collect::<Vec<_>>()
+                .join(":")
+        });
+        let metadata = event.metadata();
+        self.buffer.push(LogEntry {
+            time: self.start.elapsed().as_secs_f32(),
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            spans,
+            message: message.0,
+        });
+    }
+}
+
+/// Logs this crate's debug messages and other crates' warnings into the
+/// returned buffer, and prints those of level info and above.
+pub fn init() -> LogBuffer {
+    let buffer = LogBuffer::default();
+    let targets = Targets::new()
+        .with_default(Level::WARN)
+        .with_target("ai_playground", Level::DEBUG);
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .with(BufferLayer::new(buffer.clone()))
+        .with(targets)
+        .init();
+    buffer
+}
+
+/// The log panel, with the entries of a [`LogBuffer`] filtered by level.
+pub struct LogView {
+    /// Whether the panel is shown.
+    pub open: bool,
+    /// Least severe level shown.
+    pub level: Level,
+    buffer: LogBuffer,
+}
+
+impl LogView {
+    pub fn new(buffer: LogBuffer) -> Self {
+        Self {
+            open: false,
+            level: Level::INFO,
+            buffer,
+        }
+    }
+
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.open, "Show panel");
+        let errors = self.buffer.entries(Level::WARN).len();
+        if errors > 0 {
+            ui.weak(format!("{} warnings and errors", errors));
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("Level")
+                .selected_text(self.level.as_str())
+                .show_ui(ui, |ui| {
+                    for level in [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG] {
+                        ui.selectable_value(&mut self.level, level, level.as_str());
+                    }
+                });
+            if ui.button("Clear").clicked() {
+                self.buffer.clear();
+            }
+        });
+        egui::ScrollArea::vertical()
+            .auto_shrink(false)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in self.buffer.entries(self.level) {
+                    let color = match entry.level {
+                        Level::ERROR => Color32::LIGHT_RED,
+                        Level::WARN => Color32::YELLOW,
+                        Level::INFO => ui.visuals().text_color(),
+                        _ => ui.visuals().weak_text_color(),
+                    };
+                    let spans = if entry.spans.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {}", entry.spans)
+                    };
+                    let line = format!(
+                        "{:>9.3} {:<5} {}{}: {}",
+                        entry.time, entry.level, entry.target, spans, entry.message
+                    );
+                    ui.label(RichText::new(line).monospace().color(color));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_kept_with_their_spans() {
+        let buffer = LogBuffer::default();
+        let subscriber = tracing_subscriber::registry().with(BufferLayer::new(buffer.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let _detect = tracing::debug_span!("detect").entered();
+            let _inference = tracing::debug_span!("inference").entered();
+            tracing::warn!(stage = 2, "Model has no outputs");
+            tracing::debug!("Ran");
+        });
+        let warnings = buffer.entries(Level::WARN);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].spans, "detect:inference");
+        assert_eq!(warnings[0].message, "Model has no outputs stage=2");
+        assert_eq!(buffer.entries(Level::DEBUG).len(), 2);
+    }
+}
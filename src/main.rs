@@ -1,20 +1,82 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
-use ab_glyph::{FontRef, PxScale};
+use ai_playground::{
+    canvas::AirCanvas,
+    clip::ClipRecorder,
+    config::{AppConfig, Profiles},
+    depth::{DepthDisplay, DepthPanel},
+    distance::HandDistance,
+    enhance::Enhancement,
+    frame_diff::MovingRegions,
+    hand_view::HandView,
+    inference::{set_default_provider, Provider},
+    keymap::{default_bindings, GestureKeys},
+    logging::{self, LogBuffer, LogView},
+    midi::{default_mappings, MidiControl},
+    models::{ModelManager, ModelSpec},
+    motion::MotionGestures,
+    mouse::{MouseControl, MouseSettings},
+    network::{without_credentials, NetworkSource},
+    osc::OscOutput,
+    overlay::OverlayRenderer,
+    pipeline::{self, PipelineKind, PipelineSpec, Precision},
+    plugin,
+    power::PowerSaver,
+    recorder::Recorder,
+    reload::ModelWatcher,
+    replay::Replay,
+    segmentation::Background,
+    snapshot::Snapshotter,
+    source::{CameraSource, FrameSource, ImageFolder, LatestFrame, ScreenSource, SourceId},
+    split::SplitComparison,
+    stream::StreamState,
+    track::Schedule,
+    two_hands::TwoHands,
+    worker::Thresholds,
+    zoo::{self, ModelZoo},
+};
+
+#[cfg(target_os = "linux")]
+use ai_playground::sink::{VirtualCamera, DEFAULT_DEVICE};
 use eframe::egui;
-use egui::TextureHandle;
-use image::{imageops::FilterType, ImageBuffer, Rgb};
-use imageproc::drawing::{draw_line_segment_mut, draw_text_mut};
-use ndarray::{s, Array2, Array4};
-use nokhwa::{
-    pixel_format::RgbFormat,
-    utils::{CameraIndex, RequestedFormat, RequestedFormatType},
-    Camera,
+use nokhwa::utils::{ApiBackend, CameraIndex, CameraInfo};
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
-use ort::{ExecutionProvider, SessionBuilder, Tensor};
+use tracing::{error, info};
 
 fn main() -> eframe::Result {
+    let log = logging::init();
+    plugin::register::<MovingRegions>();
+    plugin::load_libraries(std::path::Path::new("plugins"));
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("--bench") {
+        let iterations = match args.get(1).map(|n| (n, n.parse())) {
+            None => 100,
+            Some((_, Ok(iterations))) => iterations,
+            Some((n, Err(e))) => {
+                error!("Invalid iteration count {:?}: {}", n, e);
+                std::process::exit(1);
+            }
+        };
+        let image = args.get(2).map(std::path::Path::new);
+        if let Err(e) = ai_playground::bench::run(iterations, image) {
+            error!("Benchmark failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.first().map(String::as_str) == Some("eval") {
+        if let Err(e) = ai_playground::eval::run(&args[1..]) {
+            error!("Evaluation failed: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1280.0, 960.0]),
         ..Default::default()
@@ -26,304 +88,955 @@ fn main() -> eframe::Result {
             // This gives us image support:
             egui_extras::install_image_loaders(&cc.egui_ctx);
 
-            Ok(Box::new(MyApp::new(&cc.egui_ctx)))
+            Ok(Box::new(MyApp::new(cc, camera_indices(), log)))
         }),
     )
 }
 
-struct MyApp {
-    camera: Camera,
-    session: ort::Session,
-    handle: TextureHandle,
+/// Camera indices to open at startup, given as command line arguments.
+/// Without any, the cameras of the active profile are opened.
+fn camera_indices() -> Vec<CameraIndex> {
+    std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .map(CameraIndex::Index)
+        .collect()
 }
 
-#[derive(Clone, Copy, Debug)]
-struct BBox {
-    x: f32,
-    y: f32,
-    w: f32,
-    h: f32,
+/// Storage keys of persisted settings. Overlay settings moved to profiles
+/// and are only read to seed them.
+const OVERLAY_KEY: &str = "overlay";
+const BINDINGS_KEY: &str = "gesture_bindings";
+const MIDI_KEY: &str = "midi_mappings";
+const DISTANCE_KEY: &str = "hand_distance";
+const POWER_KEY: &str = "power_saving";
+
+struct MyApp {
+    streams: Vec<StreamState>,
+    /// Cameras known to the system, for opening more streams from the UI.
+    cameras: Vec<CameraInfo>,
+    /// Monitors and windows that can be captured instead of a camera.
+    screens: Vec<ScreenSource>,
+    /// Directory of images to open as a stream.
+    folder: String,
+    /// URL of a network stream to open.
+    url: String,
+    /// Video and detection log of a session to replay.
+    replay_video: String,
+    replay_log: String,
+    profiles: Profiles,
+    profiles_path: PathBuf,
+    /// Whether saving profiles won't overwrite a file that failed to load.
+    profiles_writable: bool,
+    /// Name for saving the current settings as a new profile.
+    new_profile: String,
+    profile_status: String,
+    models: ModelManager,
+    /// Model files used instead of the managed ones, as entered.
+    model_paths: BTreeMap<String, String>,
+    /// Default provider, for pipelines that don't set their own.
+    provider: Option<Provider>,
+    /// Pipelines run together on every stream.
+    pipelines: Vec<PipelineSpec>,
+    /// Why a selected pipeline failed to load. It isn't retried until it is
+    /// reloaded or the selection changes.
+    pipeline_error: Option<String>,
+    /// Why a model of the selected pipelines can't be had.
+    model_error: Option<String>,
+    watcher: ModelWatcher,
+    schedule: Schedule,
+    thresholds: Thresholds,
+    enhancement: Enhancement,
+    overlay: OverlayRenderer,
+    background: Background,
+    mouse: MouseControl,
+    gesture_keys: GestureKeys,
+    snapshots: Snapshotter,
+    clips: ClipRecorder,
+    recorder: Recorder,
+    osc: OscOutput,
+    midi: MidiControl,
+    #[cfg(target_os = "linux")]
+    virtual_camera: VirtualCamera,
+    hand_view: HandView,
+    depth_panel: DepthPanel,
+    log: LogView,
+    canvas: AirCanvas,
+    motion: MotionGestures,
+    two_hands: TwoHands,
+    power: PowerSaver,
+    distance: HandDistance,
+    split: SplitComparison,
+    zoo: ModelZoo,
 }
 
-impl BBox {
-    fn to_xyxy(&self) -> (f32, f32, f32, f32) {
-        (self.x, self.y, self.x + self.w, self.y + self.h)
+impl MyApp {
+    fn new(cc: &eframe::CreationContext, indices: Vec<CameraIndex>, log: LogBuffer) -> Self {
+        let profiles_path = Profiles::default_path();
+        let loaded = Profiles::load(&profiles_path).unwrap_or_else(|e| {
+            error!("Failed to load profiles: {}", e);
+            None
+        });
+        // A file still there didn't load and couldn't be moved aside, so
+        // saving over it would lose it.
+        let profiles_writable = loaded.is_some() || !profiles_path.exists();
+        let profiles = loaded.unwrap_or_else(|| {
+            let mut profiles = Profiles::default();
+            let overlay = cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, OVERLAY_KEY));
+            if let (Some(overlay), Some(config)) =
+                (overlay, profiles.profiles.get_mut(&profiles.active.clone()))
+            {
+                config.overlay = overlay;
+            }
+            profiles
+        });
+        let bindings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, BINDINGS_KEY))
+            .unwrap_or_else(default_bindings);
+        let mappings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, MIDI_KEY))
+            .unwrap_or_else(default_mappings);
+        let distance = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, DISTANCE_KEY))
+            .unwrap_or_default();
+        let power = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, POWER_KEY))
+            .unwrap_or_default();
+        let mut app = Self {
+            streams: vec![],
+            cameras: vec![],
+            screens: vec![],
+            folder: String::new(),
+            url: String::new(),
+            replay_video: String::new(),
+            replay_log: "detections.jsonl".to_owned(),
+            profiles,
+            profiles_path,
+            profiles_writable,
+            new_profile: String::new(),
+            profile_status: String::new(),
+            models: ModelManager::new(ModelSpec::all()),
+            model_paths: BTreeMap::new(),
+            provider: None,
+            pipelines: vec![],
+            pipeline_error: None,
+            model_error: None,
+            watcher: ModelWatcher::new(),
+            schedule: Schedule::default(),
+            thresholds: Thresholds::default(),
+            enhancement: Enhancement::default(),
+            overlay: OverlayRenderer::new(Default::default()),
+            background: Background::default(),
+            mouse: MouseControl::new(MouseSettings::default()),
+            gesture_keys: GestureKeys::new(bindings),
+            snapshots: Snapshotter::new("snapshots"),
+            clips: ClipRecorder::new("clips"),
+            recorder: Recorder::new("detections.jsonl"),
+            osc: OscOutput::new("127.0.0.1", 9000),
+            midi: MidiControl::new(mappings),
+            #[cfg(target_os = "linux")]
+            virtual_camera: VirtualCamera::new(DEFAULT_DEVICE),
+            hand_view: HandView::default(),
+            depth_panel: DepthPanel::default(),
+            log: LogView::new(log),
+            canvas: AirCanvas::default(),
+            motion: MotionGestures::default(),
+            two_hands: TwoHands::default(),
+            power: PowerSaver::new(power),
+            distance: HandDistance::new(distance),
+            split: SplitComparison::default(),
+            zoo: ModelZoo::default(),
+        };
+        app.refresh_cameras();
+        app.refresh_screens();
+        let config = app.profiles.active();
+        app.apply(&cc.egui_ctx, config, indices.is_empty());
+        for index in indices {
+            app.open_camera(&cc.egui_ctx, index);
+        }
+        app
     }
 
-    fn intersection(&self, other: BBox) -> f32 {
-        let (x1, y1, x2, y2) = self.to_xyxy();
-        let (x3, y3, x4, y4) = other.to_xyxy();
-        let w = x2.min(x4) - x1.max(x3);
-        let h = y2.min(y4) - y1.max(y3);
-        if w < 0.0 || h < 0.0 {
-            return 0.0;
+    /// Current settings, as a profile would store them.
+    fn config(&self) -> AppConfig {
+        AppConfig {
+            cameras: self
+                .streams
+                .iter()
+                .filter_map(|stream| match &stream.id {
+                    SourceId::Camera(CameraIndex::Index(i)) => Some(*i),
+                    _ => None,
+                })
+                .collect(),
+            urls: self
+                .streams
+                .iter()
+                .filter_map(|stream| match &stream.id {
+                    SourceId::Network(url) => Some(url.clone()),
+                    _ => None,
+                })
+                .collect(),
+            pipelines: self.pipelines.clone(),
+            model_paths: self
+                .model_paths
+                .iter()
+                .filter(|(_, path)| !path.is_empty())
+                .map(|(model, path)| (model.clone(), PathBuf::from(path)))
+                .collect(),
+            thresholds: self.thresholds,
+            enhancement: self.enhancement,
+            schedule: self.schedule,
+            overlay: self.overlay.settings(),
+            provider: self.provider,
         }
-        w * h
     }
 
-    fn area(&self) -> f32 {
-        self.w * self.h
+    /// Switches to `config`, reopening cameras and network streams to match
+    /// it if `cameras` is set. Pipelines are rebuilt, as models or their provider may differ.
+    fn apply(&mut self, ctx: &egui::Context, config: AppConfig, cameras: bool) {
+        self.pipelines = config.pipelines;
+        self.model_paths = config
+            .model_paths
+            .into_iter()
+            .map(|(model, path)| (model, path.display().to_string()))
+            .collect();
+        self.thresholds = config.thresholds;
+        self.enhancement = config.enhancement;
+        self.schedule = config.schedule;
+        self.overlay.set_settings(config.overlay);
+        self.provider = config.provider;
+        set_default_provider(self.power.provider(self.provider));
+        self.unload_pipelines();
+        if cameras {
+            let wanted = config
+                .cameras
+                .into_iter()
+                .map(|i| SourceId::Camera(CameraIndex::Index(i)))
+                .chain(config.urls.into_iter().map(SourceId::Network))
+                .collect::<Vec<_>>();
+            self.streams.retain(|stream| {
+                !matches!(stream.id, SourceId::Camera(_) | SourceId::Network(_))
+                    || wanted.contains(&stream.id)
+            });
+            for id in wanted {
+                if self.streams.iter().any(|stream| stream.id == id) {
+                    continue;
+                }
+                match id {
+                    SourceId::Camera(index) => self.open_camera(ctx, index),
+                    SourceId::Network(url) => self.open_network(ctx, url),
+                    _ => {}
+                }
+            }
+        }
     }
 
-    fn union(&self, other: BBox) -> f32 {
-        self.area() + other.area() - self.intersection(other)
+    /// Keeps the current settings in the active profile.
+    fn store_profile(&mut self) {
+        let config = self.config();
+        self.profiles
+            .profiles
+            .insert(self.profiles.active.clone(), config);
     }
 
-    fn iou(&self, other: BBox) -> f32 {
-        self.intersection(other) / self.union(other)
+    fn save_profiles(&mut self) {
+        self.store_profile();
+        if !self.profiles_writable {
+            self.profile_status =
+                format!("Not saved, {} failed to load", self.profiles_path.display());
+            return;
+        }
+        self.profile_status = match self.profiles.save(&self.profiles_path) {
+            Ok(()) => format!("Saved to {}", self.profiles_path.display()),
+            Err(e) => {
+                error!("Failed to save profiles: {}", e);
+                format!("Failed: {}", e)
+            }
+        };
     }
 
-    fn shift(&self, x: f32, y: f32) -> Self {
-        Self {
-            x: self.x + x,
-            y: self.y + y,
-            w: self.w,
-            h: self.h,
+    fn profiles_ui(&mut self, ui: &mut egui::Ui) {
+        let mut selected = None;
+        egui::ComboBox::from_id_source("profile")
+            .selected_text(&self.profiles.active)
+            .show_ui(ui, |ui| {
+                for name in self.profiles.profiles.keys() {
+                    if ui
+                        .selectable_label(*name == self.profiles.active, name)
+                        .clicked()
+                    {
+                        selected = Some(name.clone());
+                    }
+                }
+            });
+        if let Some(name) = selected.filter(|name| *name != self.profiles.active) {
+            self.store_profile();
+            self.profiles.active = name;
+            let config = self.profiles.active();
+            self.apply(&ui.ctx().clone(), config, true);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.save_profiles();
+            }
+            let deletable = self.profiles.profiles.len() > 1;
+            if ui
+                .add_enabled(deletable, egui::Button::new("Delete"))
+                .clicked()
+            {
+                self.profiles.profiles.remove(&self.profiles.active);
+                self.profiles.active = self.profiles.profiles.keys().next().unwrap().clone();
+                let config = self.profiles.active();
+                self.apply(&ui.ctx().clone(), config, true);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_profile);
+            let name = self.new_profile.trim().to_owned();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save as new"))
+                .clicked()
+            {
+                self.profiles.active = name;
+                self.new_profile.clear();
+                self.save_profiles();
+            }
+        });
+        if !self.profile_status.is_empty() {
+            ui.label(&self.profile_status);
         }
     }
 
-    fn scale(&self, scale_x: f32, scale_y: f32) -> Self {
-        Self {
-            x: self.x * scale_x,
-            y: self.y * scale_y,
-            w: self.w * scale_x,
-            h: self.h * scale_y,
-        }
+    fn refresh_cameras(&mut self) {
+        self.cameras = nokhwa::query(ApiBackend::Auto).unwrap_or_else(|e| {
+            error!("Failed to list cameras: {}", e);
+            vec![]
+        });
     }
-}
 
-#[derive(Clone, Copy, Debug)]
-struct Palm {
-    bbox: BBox,
-    tips: [(f32, f32); 7],
-    score: f32,
-}
+    fn refresh_screens(&mut self) {
+        self.screens = ScreenSource::all().unwrap_or_else(|e| {
+            error!("Failed to list screens: {}", e);
+            vec![]
+        });
+    }
 
-impl Palm {
-    fn shift(&self, x: f32, y: f32) -> Self {
-        Self {
-            bbox: self.bbox.shift(x, y),
-            tips: self.tips.map(|(xt, yt)| (xt + x, yt + y)),
-            score: self.score,
+    fn open_camera(&mut self, ctx: &egui::Context, index: CameraIndex) {
+        let name = index.to_string();
+        let source = LatestFrame::spawn(move || {
+            let camera = CameraSource::open(index).map_err(|e| e.to_string())?;
+            Ok(Box::new(camera) as Box<dyn FrameSource>)
+        });
+        match source {
+            Ok(source) => self.open_stream(ctx, Box::new(source)),
+            Err(e) => error!("Failed to open camera {}: {}", name, e),
         }
     }
 
-    fn scale(&self, scale_x: f32, scale_y: f32) -> Self {
-        Self {
-            bbox: self.bbox.scale(scale_x, scale_y),
-            tips: self.tips.map(|(xt, yt)| (xt * scale_x, yt * scale_y)),
-            score: self.score,
+    fn open_screen(&mut self, ctx: &egui::Context, screen: ScreenSource) {
+        let label = screen.label();
+        match LatestFrame::spawn(move || Ok(Box::new(screen) as Box<dyn FrameSource>)) {
+            Ok(source) => self.open_stream(ctx, Box::new(source)),
+            Err(e) => error!("Failed to capture {}: {}", label, e),
         }
     }
-}
 
-impl MyApp {
-    fn get_pixel_data(camera: &mut Camera) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let frame = camera.frame().unwrap();
-        let buf = frame.decode_image::<RgbFormat>().unwrap();
-        buf
+    /// Opens a network stream, waiting for its first frame.
+    fn open_network(&mut self, ctx: &egui::Context, url: String) {
+        let name = without_credentials(&url);
+        let source = LatestFrame::spawn(move || {
+            let stream = NetworkSource::open(&url)?;
+            Ok(Box::new(stream) as Box<dyn FrameSource>)
+        });
+        match source {
+            Ok(source) => self.open_stream(ctx, Box::new(source)),
+            Err(e) => error!("Failed to open {}: {}", name, e),
+        }
     }
 
-    fn new(ctx: &egui::Context) -> Self {
-        let builder = SessionBuilder::new().unwrap();
-        let cuda = ort::CUDAExecutionProvider::default();
-        match cuda.register(&builder) {
-            Ok(_) => println!("CUDA found"),
-            Err(e) => println!("Cuda not found!\n{}", e),
-        }
-        let session = builder
-            .commit_from_file("./palm_detection_lite.onnx")
-            .unwrap();
-
-        let index = CameraIndex::Index(0);
-        let requested =
-            RequestedFormat::new::<RgbFormat>(RequestedFormatType::AbsoluteHighestFrameRate);
-        let mut camera = Camera::new(index, requested).unwrap();
-        camera.open_stream().unwrap();
-
-        let buf = Self::get_pixel_data(&mut camera);
-        let img = egui::ColorImage::from_rgb(
-            [buf.width() as usize, buf.height() as usize],
-            &buf.to_vec(),
-        );
-        Self {
-            camera,
-            session,
-            handle: ctx.load_texture("s", img, egui::TextureOptions::LINEAR),
+    fn open_stream(&mut self, ctx: &egui::Context, source: Box<dyn FrameSource>) {
+        let id = source.id();
+        match StreamState::open(ctx, source) {
+            Ok(stream) => self.streams.push(stream),
+            Err(e) => error!("Failed to open {}: {}", id, e),
         }
     }
 
-    fn get_palm(
-        &self,
-        img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
-        score_threshold: f32,
-        iou_threshold: f32,
-    ) -> Vec<Palm> {
-        let resized = image::imageops::resize(img, 192, 192, FilterType::Triangle);
-        let imgnd = Array4::from_shape_vec(
-            (1, 192, 192, 3),
-            resized
-                .iter()
-                .map(|v| *v as f32 / 255.0)
-                .collect::<Vec<_>>(),
-        )
-        .unwrap();
-        let tensor = Tensor::from_array(imgnd).unwrap();
-        let outputs = self.session.run(ort::inputs![tensor].unwrap()).unwrap();
-        let mut outputs = outputs
+    /// Checkbox that opens or closes the stream of `id`.
+    fn stream_toggle(
+        &mut self,
+        ui: &mut egui::Ui,
+        id: SourceId,
+        label: String,
+        open: impl FnOnce(&mut Self, &egui::Context),
+    ) {
+        let position = self.streams.iter().position(|s| s.id == id);
+        let mut checked = position.is_some();
+        if ui.checkbox(&mut checked, label).changed() {
+            match position {
+                Some(i) => drop(self.streams.remove(i)),
+                None => open(self, &ui.ctx().clone()),
+            }
+        }
+    }
+
+    fn pipeline_ui(&mut self, ui: &mut egui::Ui) {
+        let before = self.pipelines.clone();
+        let provider = self.provider;
+        egui::ComboBox::from_label("Default execution provider")
+            .selected_text(self.provider.map_or("Auto", Provider::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.provider, None, "Auto");
+                for p in Provider::ALL {
+                    ui.selectable_value(&mut self.provider, Some(p), p.name());
+                }
+            });
+        pipeline::specs_ui(ui, &mut self.pipelines);
+        let mut paths_changed = false;
+        let mut models = self
+            .pipelines
             .iter()
-            .map(|o| o.1.try_extract_tensor::<f32>().unwrap().view().into_owned())
+            .flat_map(|spec| spec.kind.models())
             .collect::<Vec<_>>();
+        models.sort_unstable();
+        models.dedup();
+        for model in models {
+            ui.label(model);
+            let path = self.model_paths.entry(model.to_owned()).or_default();
+            let edit = egui::TextEdit::singleline(path).hint_text("Managed copy");
+            paths_changed |= ui.add(edit).lost_focus();
+        }
+        if ui.button("Reload models").clicked()
+            || self.pipelines != before
+            || self.provider != provider
+            || paths_changed
+        {
+            set_default_provider(self.power.provider(self.provider));
+            self.unload_pipelines();
+        }
+        self.watcher.ui(ui);
+        for e in [&self.model_error, &self.pipeline_error]
+            .into_iter()
+            .flatten()
+        {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+    }
 
-        assert_eq!(outputs.len(), 2);
-        fn get_grid_box_coords() -> Vec<f32> {
-            let mut offsets = vec![0.0; 2016 * 4];
-            let mut n = 0usize;
-            add_grid(&mut offsets, 24, 2, 8, &mut n);
-            add_grid(&mut offsets, 12, 6, 16, &mut n);
-            return offsets;
-
-            fn add_grid(
-                offsets: &mut [f32],
-                rows: usize,
-                repeats: usize,
-                cell_width: usize,
-                n: &mut usize,
-            ) {
-                for j in 0..repeats * rows * rows {
-                    offsets[*n] = cell_width as f32
-                        * (((j / repeats) % rows) as f32 - (rows - 1) as f32 * 0.5);
-                    *n += 1;
-                    offsets[*n] =
-                        cell_width as f32 * ((j / repeats / rows) as f32 - (rows - 1) as f32 * 0.5);
-                    *n += 3;
-                }
+    /// Switches to the pipeline of the model picked in the model zoo, once
+    /// it is downloaded and checked, using it in place of the model it
+    /// replaces.
+    fn switch_model(&mut self) {
+        let Some((model, path)) = self.zoo.switch(&self.models) else {
+            return;
+        };
+        let path = if model.replaces == model.file_name {
+            String::new()
+        } else {
+            path.display().to_string()
+        };
+        self.model_paths.insert(model.replaces.to_owned(), path);
+        // A pipeline of the same kind at another precision is switched to
+        // the zoo model's, keeping its provider. The other pipelines stay.
+        let same = |kind: PipelineKind| kind.with_precision(Precision::Fp32);
+        if !self
+            .pipelines
+            .iter()
+            .any(|spec| spec.kind == model.pipeline)
+        {
+            match self
+                .pipelines
+                .iter_mut()
+                .find(|spec| same(spec.kind) == same(model.pipeline))
+            {
+                Some(spec) => spec.kind = model.pipeline,
+                None => self.pipelines.push(PipelineSpec::new(model.pipeline)),
             }
         }
+        info!("Switched to {}", model.name);
+        self.unload_pipelines();
+    }
+
+    /// Goes idle or wakes up from the first stream's new frame, if there is
+    /// one, lowering or restoring camera resolutions and providers.
+    fn update_power(&mut self, fresh: bool) {
+        let Some(stream) = self.streams.first().filter(|_| fresh) else {
+            return;
+        };
+        let hands = !stream.palms.is_empty() || !stream.hands.is_empty();
+        let Some(idle) = self.power.update(hands, &stream.frame, Instant::now()) else {
+            return;
+        };
+        if idle {
+            info!("No hands for a while, saving power");
+        } else {
+            info!("Waking up from power saving");
+        }
+        for stream in &mut self.streams {
+            stream.set_max_width(self.power.max_width());
+        }
+        if self.power.settings.cpu {
+            set_default_provider(self.power.provider(self.provider));
+            self.unload_pipelines();
+        }
+    }
+
+    /// Drops the pipelines of all streams, so they are built afresh from the
+    /// model files.
+    fn unload_pipelines(&mut self) {
+        self.pipeline_error = None;
+        for stream in &mut self.streams {
+            stream.set_pipelines(vec![]);
+        }
+        for side in 0..2 {
+            self.split.set_pipelines(side, Ok(vec![]));
+        }
+    }
 
-        let offsets = get_grid_box_coords();
-        let anchors = Array2::from_shape_vec((offsets.len() / 4, 4), offsets).unwrap();
-
-        let regressors = outputs.swap_remove(0).into_shape((1, 2016, 18)).unwrap();
-        let scores = outputs.swap_remove(0).into_shape((1, 2016, 1)).unwrap();
-        let box_coords = regressors
-            .slice(s![0, .., 0..4])
-            .into_owned()
-            .into_shape((2016, 4))
-            .unwrap();
-        let box_coords = box_coords + anchors;
-
-        let scale_x = img.width() as f32 / 192.0;
-        let scale_y = img.height() as f32 / 192.0;
-
-        let mut palms = (0..2016)
-            .map(|i| Palm {
-                bbox: BBox {
-                    x: box_coords[(i, 0)] - box_coords[(i, 2)] / 2.0,
-                    y: box_coords[(i, 1)] - box_coords[(i, 3)] / 2.0,
-                    w: box_coords[(i, 2)],
-                    h: box_coords[(i, 3)],
-                },
-                tips: (0..7)
-                    .map(|j| {
-                        (
-                            regressors[(0, i, 4 + j * 2)] + box_coords[(i, 0)],
-                            regressors[(0, i, 4 + j * 2 + 1)] + box_coords[(i, 1)],
-                        )
+    /// Paths of the model files of every pipeline of `specs`, or `None`
+    /// while any is still missing. Missing ones are requested, so they
+    /// download together. Models that can't be downloaded, or failed to,
+    /// are an error.
+    fn model_files(&mut self, specs: &[PipelineSpec]) -> Result<Option<Vec<Vec<PathBuf>>>, String> {
+        let paths = specs
+            .iter()
+            .map(|spec| {
+                spec.kind
+                    .models()
+                    .iter()
+                    .map(|model| match self.model_paths.get(*model) {
+                        Some(path) if !path.is_empty() => Ok(Some(PathBuf::from(path))),
+                        _ => match self.models.require(model) {
+                            Some(path) => Ok(Some(path)),
+                            None => self
+                                .models
+                                .status(model)
+                                .and_then(|status| status.error(model))
+                                .map_or(Ok(None), Err),
+                        },
                     })
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap(),
-                score: scores[(0, i, 0)],
+                    .collect::<Result<Vec<_>, _>>()
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(paths
+            .into_iter()
+            .map(|paths| paths.into_iter().collect::<Option<Vec<_>>>())
+            .collect())
+    }
+
+    /// Builds the selected pipelines for streams that don't run them yet,
+    /// once all their models are available. Rebuilds them when their model
+    /// files change.
+    fn load_pipelines(&mut self) {
+        let files = self.model_files(&self.pipelines.clone());
+        self.model_error = files.as_ref().err().cloned();
+        let Ok(Some(paths)) = files else {
+            return;
+        };
+        self.watcher.watch(&paths.concat());
+        if self.watcher.poll() {
+            info!("Model files changed, reloading");
+            self.unload_pipelines();
+        }
+        if self.pipeline_error.is_some() {
+            return;
+        }
+        let stale = |stream: &StreamState| {
+            stream.pipelines() != self.pipelines.as_slice() && !stream.replaying()
+        };
+        if !self.streams.iter().any(stale) {
+            return;
+        }
+        if let Err(e) = zoo::check_files(&self.pipelines, &paths) {
+            error!("Failed to use {}", e);
+            self.pipeline_error = Some(e);
+            return;
+        }
+        for stream in &mut self.streams {
+            if !stale(stream) {
+                continue;
+            }
+            match pipeline::load_all(&self.pipelines, &paths) {
+                Ok(pipelines) => stream.set_pipelines(pipelines),
+                Err(e) => {
+                    error!("Failed to load {}", e);
+                    self.pipeline_error = Some(e);
+                    return;
+                }
+            }
+        }
+    }
 
-        palms.sort_by(|p1, p2| p2.score.total_cmp(&p1.score));
-        let mut res = vec![];
-        loop {
-            let palm = palms[0];
-            if palm.score < score_threshold {
-                break;
+    /// Builds the pipelines of the split comparison sides that changed,
+    /// like [`Self::load_pipelines`] does for the streams.
+    fn load_split(&mut self) {
+        for side in 0..2 {
+            if !self.split.needs_load(side) {
+                continue;
+            }
+            let specs = self.split.sides[side].pipelines.clone();
+            let paths = match self.model_files(&specs) {
+                Ok(Some(paths)) => paths,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Failed to load split comparison side {}: {}", side, e);
+                    self.split.set_pipelines(side, Err(e));
+                    continue;
+                }
+            };
+            let pipelines =
+                zoo::check_files(&specs, &paths).and_then(|()| pipeline::load_all(&specs, &paths));
+            if let Err(e) = &pipelines {
+                error!("Failed to load split comparison side {}: {}", side, e);
             }
-            res.push(palm);
-            palms.retain(|p2| p2.bbox.iou(palm.bbox) < iou_threshold);
+            self.split.set_pipelines(side, pipelines);
         }
+    }
 
-        res.iter()
-            .map(|palm| palm.shift(192.0 / 2.0, 192.0 / 2.0).scale(scale_x, scale_y))
-            .collect()
+    fn cameras_ui(&mut self, ui: &mut egui::Ui) {
+        for info in self.cameras.clone() {
+            let index = info.index().clone();
+            let id = SourceId::Camera(index.clone());
+            self.stream_toggle(ui, id, info.human_name(), |app, ctx| {
+                app.open_camera(ctx, index)
+            });
+        }
+        if ui.button("Refresh").clicked() {
+            self.refresh_cameras();
+        }
     }
 
-    fn paint_palm(
-        &mut self,
-        buf: &mut imageproc::image::ImageBuffer<imageproc::image::Rgb<u8>, Vec<u8>>,
-        palm: Palm,
-    ) {
-        for (i, (x, y)) in palm.tips.iter().enumerate() {
-            let font = FontRef::try_from_slice(include_bytes!("../DejaVuSans.ttf")).unwrap();
-            let height = 28.0;
-            let scale = PxScale {
-                x: height * 2.0,
-                y: height,
-            };
+    fn screens_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for screen in self.screens.clone() {
+                    let label = screen.label();
+                    self.stream_toggle(ui, screen.id(), label, |app, ctx| {
+                        app.open_screen(ctx, screen)
+                    });
+                }
+            });
+        if ui.button("Refresh").clicked() {
+            self.refresh_screens();
+        }
+    }
 
-            draw_text_mut(
-                buf,
-                imageproc::image::Rgb([255, 0, 255]),
-                *x as i32,
-                *y as i32,
-                scale,
-                &font,
-                &format!("{}", i),
-            );
+    fn folder_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.folder);
+            if ui.button("Open").clicked() {
+                match ImageFolder::open(&self.folder) {
+                    Ok(folder) => self.open_stream(ui.ctx(), Box::new(folder)),
+                    Err(e) => error!("Failed to open {}: {}", self.folder, e),
+                }
+            }
+        });
+        ui.label("Page Up and Page Down switch images");
+    }
+
+    fn replay_ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("replay").num_columns(2).show(ui, |ui| {
+            ui.label("Video");
+            ui.text_edit_singleline(&mut self.replay_video);
+            ui.end_row();
+            ui.label("Detection log");
+            ui.text_edit_singleline(&mut self.replay_log);
+            ui.end_row();
+        });
+        if ui.button("Open").clicked() {
+            match Replay::open(&self.replay_video, &self.replay_log) {
+                Ok(replay) => self.open_stream(ui.ctx(), Box::new(replay)),
+                Err(e) => error!("Failed to open replay of {}: {}", self.replay_video, e),
+            }
         }
+        ui.label("Draws the palms of a JSON lines log over the video, without running models");
+    }
 
-        for (f, t) in [
-            (0, 5),
-            (5, 6),
-            (0, 1),
-            (0, 2),
-            (0, 3),
-            (0, 4),
-            (1, 2),
-            (2, 3),
-            (3, 4),
-            (6, 1),
-        ] {
-            draw_line_segment_mut(
-                buf,
-                palm.tips[f],
-                palm.tips[t],
-                imageproc::image::Rgb([0, 255, 0]),
-            );
+    fn network_ui(&mut self, ui: &mut egui::Ui) {
+        let open = self
+            .streams
+            .iter()
+            .map(|stream| stream.id.clone())
+            .filter(|id| matches!(id, SourceId::Network(_)))
+            .collect::<Vec<_>>();
+        for id in open {
+            let label = id.to_string();
+            self.stream_toggle(ui, id, label, |_, _| {});
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.url);
+            let url = self.url.trim().to_owned();
+            if ui
+                .add_enabled(!url.is_empty(), egui::Button::new("Open"))
+                .clicked()
+            {
+                self.open_network(&ui.ctx().clone(), url);
+            }
+        });
+        ui.label("MJPEG over HTTP, or RTSP and more through ffmpeg");
+    }
+
+    /// Lays the streams out in a grid that is as close to square as possible.
+    fn streams_ui(&mut self, ui: &mut egui::Ui) {
+        let n = self.streams.len();
+        if n == 0 {
+            ui.label("No stream open");
+            return;
+        }
+        let cols = (n as f32).sqrt().ceil() as usize;
+        let rows = n.div_ceil(cols);
+        let spacing = ui.spacing().item_spacing;
+        let avail = ui.available_size();
+        let cell = egui::vec2(
+            avail.x / cols as f32 - spacing.x,
+            avail.y / rows as f32 - spacing.y,
+        );
+        for row in self.streams.chunks_mut(cols) {
+            ui.horizontal(|ui| {
+                for stream in row {
+                    stream.ui(ui, cell);
+                }
+            });
         }
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let buf = Self::get_pixel_data(&mut self.camera);
-
-            let palms = self.get_palm(&buf, 0.6, 0.25);
-            let mut buf =
-                imageproc::image::ImageBuffer::<imageproc::image::Rgb<u8>, Vec<u8>>::from_vec(
-                    buf.width(),
-                    buf.height(),
-                    buf.to_vec(),
-                )
-                .unwrap();
-            for palm in palms {
-                self.paint_palm(&mut buf, palm);
-            }
+        egui::SidePanel::right("settings").show(ctx, |ui| {
+            ui.collapsing("Profile", |ui| self.profiles_ui(ui));
+            ui.collapsing("Pipelines", |ui| self.pipeline_ui(ui));
+            ui.collapsing("Split comparison", |ui| {
+                self.split.settings_ui(ui, &self.pipelines)
+            });
+            ui.collapsing("Thresholds", |ui| self.thresholds.ui(ui));
+            ui.collapsing("Enhancement", |ui| self.enhancement.ui(ui));
+            ui.collapsing("Inference rate", |ui| self.schedule.ui(ui));
+            ui.collapsing("Power saving", |ui| self.power.ui(ui));
+            ui.collapsing("Cameras", |ui| self.cameras_ui(ui));
+            ui.collapsing("Screen capture", |ui| self.screens_ui(ui));
+            ui.collapsing("Image folder", |ui| self.folder_ui(ui));
+            ui.collapsing("Network stream", |ui| self.network_ui(ui));
+            ui.collapsing("Replay", |ui| self.replay_ui(ui));
+            ui.collapsing("Models", |ui| self.models.ui(ui));
+            ui.collapsing("Model zoo", |ui| self.zoo.ui(ui, &mut self.models));
+            ui.collapsing("Overlay", |ui| {
+                self.overlay.ui(ui);
+                if ui.button("Clear trails and heatmaps").clicked() {
+                    for stream in &mut self.streams {
+                        stream.trails.clear();
+                    }
+                }
+            });
+            ui.collapsing("Background", |ui| self.background.ui(ui));
+            ui.collapsing("Mouse control", |ui| self.mouse.ui(ui));
+            ui.collapsing("Air canvas", |ui| self.canvas.ui(ui));
+            ui.collapsing("Motion gestures", |ui| self.motion.ui(ui));
+            ui.collapsing("Hand distance", |ui| self.distance.ui(ui));
+            ui.collapsing("Two hands", |ui| self.two_hands.ui(ui));
+            ui.collapsing("Gesture shortcuts", |ui| self.gesture_keys.ui(ui));
+            ui.collapsing("Snapshots", |ui| self.snapshots.ui(ui));
+            ui.collapsing("Clips", |ui| self.clips.ui(ui));
+            ui.collapsing("Detection log", |ui| self.recorder.ui(ui));
+            ui.collapsing("OSC output", |ui| self.osc.ui(ui));
+            ui.collapsing("MIDI output", |ui| self.midi.ui(ui));
+            #[cfg(target_os = "linux")]
+            ui.collapsing("Virtual camera", |ui| self.virtual_camera.ui(ui));
+            ui.collapsing("3D hand", |ui| self.hand_view.settings_ui(ui));
+            ui.collapsing("Log", |ui| self.log.settings_ui(ui));
+        });
+
+        if ctx.input(|i| i.key_pressed(egui::Key::F9)) {
+            self.mouse.toggle();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F10)) {
+            self.clips.request();
+        }
+        if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.snapshots.request();
+        }
+        let snapshot = self.snapshots.take_pending();
 
-            let img =
-                egui::ColorImage::from_rgb([buf.width() as usize, buf.height() as usize], &buf);
-            self.handle.set(img, egui::TextureOptions::LINEAR);
-            let txt = egui::load::SizedTexture::from_handle(&self.handle);
-            ui.add(egui::Image::from_texture(txt).shrink_to_fit());
+        self.switch_model();
+        self.load_pipelines();
+        self.load_split();
+        self.background.load(&mut self.models);
+
+        let mut fresh = vec![];
+        let mut motion = vec![];
+        for (i, stream) in self.streams.iter_mut().enumerate() {
+            let schedule = self.power.schedule(self.schedule);
+            let changed = stream.capture(&schedule, self.thresholds, self.enhancement);
+            fresh.push(changed);
+            if changed {
+                stream.annotated.clone_from(&stream.enhanced);
+                self.enhancement
+                    .split_view(&stream.frame, &mut stream.annotated);
+                self.background.apply(&mut stream.annotated);
+                if let Some(map) = &stream.depth {
+                    self.overlay.draw_depth(&mut stream.annotated, map);
+                }
+                let (now, settings) = (Instant::now(), self.overlay.settings());
+                let max_age = Duration::from_secs_f32(settings.trail_seconds);
+                let size = stream.frame.dimensions();
+                stream.trails.update(&stream.palms, size, now, max_age);
+                // Only the first stream drives the canvas and gestures, like mouse control.
+                if i == 0 {
+                    self.canvas.update(&stream.palms, &stream.hands, size, now);
+                    motion = self.motion.update(&stream.trails, size, now);
+                    let distance = self
+                        .distance
+                        .update(&stream.palms, &stream.trails, size, now);
+                    motion.extend(distance);
+                    self.two_hands.update(&stream.palms, &stream.hands, size);
+                }
+                self.overlay
+                    .draw_heatmap(&mut stream.annotated, &stream.trails.heatmap);
+                self.overlay
+                    .draw_trails(&mut stream.annotated, &stream.trails, now);
+                let names = stream.class_names();
+                self.overlay
+                    .draw_weak(&mut stream.annotated, &stream.weak, &names);
+                for (id, palm) in stream.palms.iter().enumerate() {
+                    self.overlay.draw_palm(&mut stream.annotated, id, palm);
+                }
+                if i == 0 {
+                    for (palm, &cm) in stream.palms.iter().zip(self.distance.distances()) {
+                        self.overlay.draw_distance(&mut stream.annotated, palm, cm);
+                    }
+                    if let Some(metrics) = self.two_hands.metrics() {
+                        self.overlay.draw_two_hands(&mut stream.annotated, metrics);
+                    }
+                }
+                for palm in &stream.compared {
+                    self.overlay.draw_compared_palm(&mut stream.annotated, palm);
+                }
+                for (id, pose) in stream.poses.iter().enumerate() {
+                    self.overlay.draw_pose(&mut stream.annotated, id, pose);
+                }
+                for (id, object) in stream.objects.iter().enumerate() {
+                    self.overlay
+                        .draw_object(&mut stream.annotated, id, object, &names);
+                }
+                for (id, face) in stream.faces.iter().enumerate() {
+                    self.overlay.draw_face(&mut stream.annotated, id, face);
+                }
+                for (id, mesh) in stream.meshes.iter().enumerate() {
+                    self.overlay.draw_mesh(&mut stream.annotated, id, mesh);
+                }
+                for (id, hand) in stream.hands.iter().enumerate() {
+                    self.overlay.draw_hand(&mut stream.annotated, id, hand);
+                }
+                for (plugin, found) in &stream.plugins {
+                    plugin.draw(&self.overlay, &mut stream.annotated, found);
+                }
+                if i == 0 {
+                    self.overlay
+                        .draw_canvas(&mut stream.annotated, &self.canvas);
+                    if settings.depth_display == DepthDisplay::Panel {
+                        self.depth_panel.update(ctx, stream.depth.as_ref());
+                    }
+                    self.clips.push(&stream.annotated, now);
+                }
+                stream.present();
+            }
+            if let Some(timestamp) = snapshot {
+                self.snapshots.save(timestamp, stream);
+            }
+        }
+        if fresh.contains(&true) {
+            self.recorder.record(&self.streams);
+        }
+        self.update_power(fresh.first() == Some(&true));
+        if let Some(stream) = self.streams.first() {
+            self.clips.update(&stream.id.slug());
+        }
+        if let Some(stream) = self.streams.first() {
+            self.split.update(
+                ctx,
+                &stream.enhanced,
+                fresh[0],
+                self.thresholds,
+                &self.overlay,
+            );
+        }
+        match self.streams.first() {
+            // Nothing new to react to until the first stream changes.
+            Some(_) if !fresh[0] => {}
+            Some(stream) => {
+                let size = stream.frame.dimensions();
+                self.mouse.update(
+                    stream.palms.first(),
+                    stream.hands.first(),
+                    size,
+                    Instant::now(),
+                );
+                self.gesture_keys
+                    .update(stream.palms.first(), size, &motion);
+                self.osc.send_palms(&stream.palms, size);
+                self.osc.send_gestures(&motion);
+                self.osc.send_faces(&stream.meshes);
+                if self.two_hands.enabled {
+                    self.osc.send_two_hands(self.two_hands.metrics(), size);
+                }
+                self.midi.update(stream.palms.first(), size);
+                #[cfg(target_os = "linux")]
+                self.virtual_camera.send(&stream.annotated);
+            }
+            None => {
+                self.mouse.update(None, None, (1, 1), Instant::now());
+                self.gesture_keys.update(None, (1, 1), &[]);
+            }
+        }
 
-            ctx.request_repaint();
+        if self.log.open {
+            egui::TopBottomPanel::bottom("log")
+                .resizable(true)
+                .default_height(160.0)
+                .show(ctx, |ui| self.log.ui(ui));
+        }
+        if self.hand_view.open {
+            let hands = self.streams.first().map_or(&[][..], |s| &s.hands);
+            egui::SidePanel::left("hand_view")
+                .default_width(320.0)
+                .show(ctx, |ui| self.hand_view.ui(ui, hands));
+        }
+        let depth_panel = self.overlay.settings().depth_display == DepthDisplay::Panel;
+        let depth_stream = self.streams.first().filter(|stream| {
+            let mut specs = stream.pipelines().iter();
+            specs.any(|spec| spec.kind == PipelineKind::Depth)
         });
+        if let Some(stream) = depth_stream.filter(|_| depth_panel) {
+            let area = stream.depth.as_ref().map(|map| map.area);
+            egui::SidePanel::right("depth")
+                .default_width(320.0)
+                .show(ctx, |ui| self.depth_panel.ui(ui, area));
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.split.enabled {
+                self.split.ui(ui);
+            } else {
+                self.streams_ui(ui);
+            }
+        });
+        match self.power.repaint_after() {
+            Some(wait) => ctx.request_repaint_after(wait),
+            None => ctx.request_repaint(),
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, BINDINGS_KEY, &self.gesture_keys.bindings);
+        eframe::set_value(storage, MIDI_KEY, &self.midi.mappings);
+        eframe::set_value(storage, DISTANCE_KEY, &self.distance.settings);
+        eframe::set_value(storage, POWER_KEY, &self.power.settings);
+        self.save_profiles();
     }
 }
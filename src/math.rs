@@ -0,0 +1,7 @@
+//! Small numeric helpers shared by the detectors' postprocessing.
+
+/// Turns a logit into a probability. Large logits are clamped first so
+/// that the exponent can't overflow.
+pub fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x.clamp(-100.0, 100.0)).exp())
+}
@@ -0,0 +1,292 @@
+//! Turns the first palm into MIDI control changes, for playing DAW
+//! parameters by hand.
+
+use midir::{MidiOutput, MidiOutputConnection};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::palm::Palm;
+
+/// Name the app registers with the MIDI system.
+const CLIENT_NAME: &str = "ai-playground";
+/// Pinch ratio mapped onto the top of the CC range, see
+/// [`Palm::pinch_ratio`].
+const MAX_PINCH_RATIO: f32 = 1.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HandAxis {
+    /// Horizontal palm center, 0 on the left edge of the frame.
+    X,
+    /// Vertical palm center, 0 on the bottom edge of the frame.
+    Y,
+    /// Thumb-to-index distance, 0 when pinching.
+    Pinch,
+}
+
+impl HandAxis {
+    pub const ALL: [Self; 3] = [Self::X, Self::Y, Self::Pinch];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::X => "Hand X",
+            Self::Y => "Hand Y",
+            Self::Pinch => "Pinch",
+        }
+    }
+
+    /// Position of `palm` along this axis in `0..=1`, in a frame of `w`×`h`.
+    pub fn value(self, palm: &Palm, (w, h): (u32, u32)) -> f32 {
+        let bbox = palm.bbox;
+        let value = match self {
+            Self::X => (bbox.x + bbox.w / 2.0) / w as f32,
+            Self::Y => 1.0 - (bbox.y + bbox.h / 2.0) / h as f32,
+            Self::Pinch => palm.pinch_ratio() / MAX_PINCH_RATIO,
+        };
+        value.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CcMapping {
+    pub axis: HandAxis,
+    /// MIDI channel, `1..=16`.
+    pub channel: u8,
+    pub cc: u8,
+    pub invert: bool,
+}
+
+pub fn default_mappings() -> Vec<CcMapping> {
+    let mapping = |axis, cc| CcMapping {
+        axis,
+        channel: 1,
+        cc,
+        invert: false,
+    };
+    vec![
+        mapping(HandAxis::Y, 1),
+        mapping(HandAxis::X, 10),
+        mapping(HandAxis::Pinch, 74),
+    ]
+}
+
+/// Control change message setting `cc` on `channel` (`1..=16`) to `value`.
+pub fn control_change(channel: u8, cc: u8, value: u8) -> [u8; 3] {
+    [
+        0xB0 | (channel.clamp(1, 16) - 1),
+        cc.min(127),
+        value.min(127),
+    ]
+}
+
+/// Sends the configured CCs of the first palm to a MIDI output port.
+pub struct MidiControl {
+    pub enabled: bool,
+    pub mappings: Vec<CcMapping>,
+    /// Output port names, as last listed.
+    ports: Vec<String>,
+    /// Connected port and its name.
+    connection: Option<(MidiOutputConnection, String)>,
+    /// Last value sent per mapping. Only changes are sent.
+    sent: Vec<Option<u8>>,
+    error: String,
+}
+
+impl MidiControl {
+    pub fn new(mappings: Vec<CcMapping>) -> Self {
+        let mut midi = Self {
+            enabled: false,
+            mappings,
+            ports: vec![],
+            connection: None,
+            sent: vec![],
+            error: String::new(),
+        };
+        midi.refresh_ports();
+        midi
+    }
+
+    fn refresh_ports(&mut self) {
+        let result = MidiOutput::new(CLIENT_NAME).map(|output| {
+            output
+                .ports()
+                .iter()
+                .filter_map(|port| output.port_name(port).ok())
+                .collect()
+        });
+        match result {
+            Ok(ports) => self.ports = ports,
+            Err(e) => {
+                error!("Failed to list MIDI ports: {}", e);
+                self.error = e.to_string();
+            }
+        }
+    }
+
+    fn connect(&mut self, name: &str) {
+        self.connection = None;
+        self.sent.clear();
+        let result = MidiOutput::new(CLIENT_NAME)
+            .map_err(|e| e.to_string())
+            .and_then(|output| {
+                let port = output
+                    .ports()
+                    .into_iter()
+                    .find(|port| output.port_name(port).is_ok_and(|n| n == name))
+                    .ok_or_else(|| format!("{} is gone", name))?;
+                output
+                    .connect(&port, CLIENT_NAME)
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(connection) => {
+                self.connection = Some((connection, name.to_owned()));
+                self.error.clear();
+            }
+            Err(e) => {
+                error!("Failed to open MIDI port {}: {}", name, e);
+                self.error = e;
+            }
+        }
+    }
+
+    /// Feeds the palm detected in a frame of `frame_size` pixels, if any.
+    /// Without a palm, the controllers keep their values.
+    pub fn update(&mut self, palm: Option<&Palm>, frame_size: (u32, u32)) {
+        let (Some((connection, _)), Some(palm)) = (&mut self.connection, palm) else {
+            return;
+        };
+        if !self.enabled {
+            return;
+        }
+        self.sent.resize(self.mappings.len(), None);
+        for (mapping, sent) in self.mappings.iter().zip(&mut self.sent) {
+            let mut value = mapping.axis.value(palm, frame_size);
+            if mapping.invert {
+                value = 1.0 - value;
+            }
+            let value = (value * 127.0).round() as u8;
+            if *sent == Some(value) {
+                continue;
+            }
+            match connection.send(&control_change(mapping.channel, mapping.cc, value)) {
+                Ok(()) => *sent = Some(value),
+                Err(e) => self.error = e.to_string(),
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        let current = self.connection.as_ref().map(|(_, name)| name.clone());
+        let mut selected = None;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("midi port")
+                .selected_text(current.as_deref().unwrap_or("No port"))
+                .show_ui(ui, |ui| {
+                    for name in &self.ports {
+                        if ui
+                            .selectable_label(current.as_ref() == Some(name), name)
+                            .clicked()
+                        {
+                            selected = Some(name.clone());
+                        }
+                    }
+                });
+            if ui.button("Refresh").clicked() {
+                self.refresh_ports();
+            }
+        });
+        if let Some(name) = selected {
+            self.connect(&name);
+        }
+
+        let before = self.mappings.clone();
+        let mut remove = None;
+        egui::Grid::new("midi mappings").show(ui, |ui| {
+            for (i, mapping) in self.mappings.iter_mut().enumerate() {
+                egui::ComboBox::from_id_source(("midi axis", i))
+                    .selected_text(mapping.axis.name())
+                    .show_ui(ui, |ui| {
+                        for axis in HandAxis::ALL {
+                            ui.selectable_value(&mut mapping.axis, axis, axis.name());
+                        }
+                    });
+                ui.add(
+                    egui::DragValue::new(&mut mapping.channel)
+                        .range(1..=16)
+                        .prefix("Ch "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut mapping.cc)
+                        .range(0..=127)
+                        .prefix("CC "),
+                );
+                ui.checkbox(&mut mapping.invert, "Invert");
+                if let Some(Some(value)) = self.sent.get(i) {
+                    ui.label(value.to_string());
+                } else {
+                    ui.label("");
+                }
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = remove {
+            self.mappings.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add").clicked() {
+                self.mappings.push(CcMapping {
+                    axis: HandAxis::Y,
+                    channel: 1,
+                    cc: 1,
+                    invert: false,
+                });
+            }
+            if ui.button("Defaults").clicked() {
+                self.mappings = default_mappings();
+            }
+        });
+        if self.mappings != before {
+            // Resend everything, as values no longer line up with mappings.
+            self.sent.clear();
+        }
+        if !self.error.is_empty() {
+            ui.colored_label(egui::Color32::RED, &self.error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bbox::BBox, palm::Handedness};
+
+    #[test]
+    fn encodes_control_change() {
+        assert_eq!(control_change(1, 7, 100), [0xB0, 7, 100]);
+        assert_eq!(control_change(16, 74, 0), [0xBF, 74, 0]);
+        assert_eq!(control_change(0, 200, 255), [0xB0, 127, 127]);
+    }
+
+    #[test]
+    fn axes_are_normalized() {
+        let palm = Palm {
+            bbox: BBox {
+                x: 0.0,
+                y: 60.0,
+                w: 50.0,
+                h: 20.0,
+            },
+            tips: [(0.0, 0.0); 7],
+            score: 1.0,
+            handedness: Handedness::Right,
+        };
+        assert_eq!(HandAxis::X.value(&palm, (100, 100)), 0.25);
+        assert!((HandAxis::Y.value(&palm, (100, 100)) - 0.3).abs() < 1e-6);
+        assert_eq!(HandAxis::X.value(&palm.shift(500.0, 0.0), (100, 100)), 1.0);
+        assert_eq!(HandAxis::Pinch.value(&palm, (100, 100)), 0.0);
+    }
+}
@@ -0,0 +1,493 @@
+//! Resolves ONNX models from the working directory or a per-user cache,
+//! downloading missing ones in the background. Without the `native`
+//! feature only the model specs are left, for the browser to fetch.
+
+#[cfg(feature = "native")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "native")]
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+#[cfg(feature = "native")]
+use tracing::{error, warn};
+
+pub const PALM_DETECTION_LITE: &str = "palm_detection_lite.onnx";
+pub const PALM_DETECTION_LITE_FP16: &str = "palm_detection_lite_fp16.onnx";
+pub const PALM_DETECTION_LITE_INT8: &str = "palm_detection_lite_int8.onnx";
+pub const PALM_DETECTION_FULL: &str = "palm_detection_full.onnx";
+pub const POSE_DETECTION: &str = "pose_detection.onnx";
+pub const POSE_LANDMARKS_FULL: &str = "pose_landmarks_detector_full.onnx";
+pub const YOLOV8N: &str = "yolov8n.onnx";
+pub const COCO_LABELS: &str = "coco_labels.txt";
+pub const SELFIE_SEGMENTATION: &str = "selfie_segmentation.onnx";
+pub const FACE_DETECTION_SHORT_RANGE: &str = "blaze_face_short_range.onnx";
+pub const IRIS_LANDMARK: &str = "iris_landmark.onnx";
+pub const HAND_LANDMARK_FULL: &str = "hand_landmark_full.onnx";
+pub const FACE_LANDMARK: &str = "face_landmark.onnx";
+pub const MIDAS_SMALL: &str = "midas_v21_small_256.onnx";
+
+const BLAZE_POSE_URL: &str =
+    "https://huggingface.co/unity/inference-engine-blaze-pose/resolve/main/models";
+const BLAZE_FACE_URL: &str =
+    "https://huggingface.co/unity/inference-engine-blaze-face/resolve/main/models";
+const YOLO_URL: &str = "https://huggingface.co/unity/sentis-YOLOv8n/resolve/main";
+
+#[derive(Clone, Debug)]
+pub struct ModelSpec {
+    /// File name, both in the working directory and in the cache.
+    pub file_name: &'static str,
+    pub url: String,
+    /// Expected SHA-256 of the file, hex encoded. Unpinned models are
+    /// accepted as long as the download completes.
+    pub sha256: Option<&'static str>,
+}
+
+impl ModelSpec {
+    /// Whether the model can be downloaded, which it can't without a URL.
+    pub fn downloadable(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    pub fn palm_detection_lite() -> Self {
+        Self {
+            file_name: PALM_DETECTION_LITE,
+            url: "https://huggingface.co/unity/sentis-blaze-palm/resolve/main/palm_detection_lite.onnx"
+                .to_owned(),
+            sha256: Some("de5e4de6a1728a7131811b1b6930f733f498345e5e824ea11748f09101c1d372"),
+        }
+    }
+
+    /// Half precision weights. There is no published copy; convert the fp32
+    /// model with `onnxconverter_common.float16.convert_float_to_float16`
+    /// and `keep_io_types=True`, so inputs and outputs stay `f32`, and put
+    /// it in the working directory or enter a URL.
+    pub fn palm_detection_lite_fp16() -> Self {
+        Self {
+            file_name: PALM_DETECTION_LITE_FP16,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    /// Dynamically quantized weights, made with onnxruntime's
+    /// `quantization.quantize_dynamic`. Not published either, see
+    /// [`Self::palm_detection_lite_fp16`].
+    pub fn palm_detection_lite_int8() -> Self {
+        Self {
+            file_name: PALM_DETECTION_LITE_INT8,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    /// MediaPipe's full palm model, more accurate and slower than the lite
+    /// one with the same input and anchors. Not published as ONNX; convert
+    /// `palm_detection_full.tflite` the same way as [`Self::iris_landmark`].
+    pub fn palm_detection_full() -> Self {
+        Self {
+            file_name: PALM_DETECTION_FULL,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    pub fn pose_detection() -> Self {
+        Self {
+            file_name: POSE_DETECTION,
+            url: format!("{}/{}", BLAZE_POSE_URL, POSE_DETECTION),
+            sha256: None,
+        }
+    }
+
+    pub fn pose_landmarks_full() -> Self {
+        Self {
+            file_name: POSE_LANDMARKS_FULL,
+            url: format!("{}/{}", BLAZE_POSE_URL, POSE_LANDMARKS_FULL),
+            sha256: None,
+        }
+    }
+
+    pub fn face_detection_short_range() -> Self {
+        Self {
+            file_name: FACE_DETECTION_SHORT_RANGE,
+            url: format!("{}/{}", BLAZE_FACE_URL, FACE_DETECTION_SHORT_RANGE),
+            sha256: None,
+        }
+    }
+
+    /// MediaPipe's iris model, 64×64 NHWC input. There is no published ONNX
+    /// copy; convert `iris_landmark.tflite` from the MediaPipe repository
+    /// with `python -m tf2onnx.convert --tflite iris_landmark.tflite
+    /// --output iris_landmark.onnx` and put it in the working directory or
+    /// enter a URL.
+    pub fn iris_landmark() -> Self {
+        Self {
+            file_name: IRIS_LANDMARK,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    /// MediaPipe's hand landmark model, 224×224 NHWC input. Not published as
+    /// ONNX either; convert `hand_landmark_full.tflite` the same way as
+    /// [`Self::iris_landmark`].
+    pub fn hand_landmark_full() -> Self {
+        Self {
+            file_name: HAND_LANDMARK_FULL,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    /// MediaPipe's face landmark model, 468 points from a 192×192 NHWC
+    /// input. Not published as ONNX either; convert `face_landmark.tflite`
+    /// the same way as [`Self::iris_landmark`].
+    pub fn face_landmark() -> Self {
+        Self {
+            file_name: FACE_LANDMARK,
+            url: String::new(),
+            sha256: None,
+        }
+    }
+
+    /// MiDaS v2.1 small, relative depth from a 256×256 NCHW input.
+    pub fn midas_small() -> Self {
+        Self {
+            file_name: MIDAS_SMALL,
+            url: "https://github.com/isl-org/MiDaS/releases/download/v2_1/model-small.onnx"
+                .to_owned(),
+            sha256: None,
+        }
+    }
+
+    /// Any Ultralytics YOLOv8 or YOLO11 export works, pointing the URL at it
+    /// replaces this one.
+    pub fn yolov8n() -> Self {
+        Self {
+            file_name: YOLOV8N,
+            url: format!("{}/{}", YOLO_URL, YOLOV8N),
+            sha256: None,
+        }
+    }
+
+    /// MediaPipe's general selfie segmentation model, 256×256 NHWC input.
+    pub fn selfie_segmentation() -> Self {
+        Self {
+            file_name: SELFIE_SEGMENTATION,
+            url: "https://huggingface.co/onnx-community/mediapipe_selfie_segmentation/resolve/main/onnx/model.onnx"
+                .to_owned(),
+            sha256: None,
+        }
+    }
+
+    pub fn coco_labels() -> Self {
+        Self {
+            file_name: COCO_LABELS,
+            url: format!("{}/classes.txt", YOLO_URL),
+            sha256: None,
+        }
+    }
+
+    /// Every model the app knows.
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::palm_detection_lite(),
+            Self::palm_detection_lite_fp16(),
+            Self::palm_detection_lite_int8(),
+            Self::palm_detection_full(),
+            Self::pose_detection(),
+            Self::pose_landmarks_full(),
+            Self::yolov8n(),
+            Self::coco_labels(),
+            Self::selfie_segmentation(),
+            Self::face_detection_short_range(),
+            Self::iris_landmark(),
+            Self::hand_landmark_full(),
+            Self::face_landmark(),
+            Self::midas_small(),
+        ]
+        .into_iter()
+        .chain(crate::plugin::model_specs())
+        .collect()
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(Clone, Debug)]
+pub enum ModelStatus {
+    Missing,
+    /// Missing, with no URL to download it from.
+    NotDownloadable,
+    Downloading {
+        received: u64,
+        total: Option<u64>,
+    },
+    Ready(PathBuf),
+    Failed(String),
+}
+
+#[cfg(feature = "native")]
+impl ModelStatus {
+    /// Why the model `file_name` can't be used, if it can't.
+    pub fn error(&self, file_name: &str) -> Option<String> {
+        match self {
+            Self::Failed(e) => Some(format!("{}: {}", file_name, e)),
+            Self::NotDownloadable => Some(format!(
+                "{} isn't downloadable, put it in the working directory or enter its URL in the \"Models\" section",
+                file_name
+            )),
+            Self::Missing | Self::Downloading { .. } | Self::Ready(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+struct Entry {
+    spec: ModelSpec,
+    status: Arc<Mutex<ModelStatus>>,
+}
+
+#[cfg(feature = "native")]
+pub struct ModelManager {
+    cache_dir: PathBuf,
+    entries: Vec<Entry>,
+}
+
+#[cfg(feature = "native")]
+impl ModelManager {
+    /// Registers `specs` and looks them up locally. Missing models are only
+    /// downloaded once [`Self::require`]d.
+    pub fn new(specs: Vec<ModelSpec>) -> Self {
+        let cache_dir = dirs::cache_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("ai-playground")
+            .join("models");
+        let manager = Self {
+            cache_dir,
+            entries: specs
+                .into_iter()
+                .map(|spec| Entry {
+                    spec,
+                    status: Arc::new(Mutex::new(ModelStatus::Missing)),
+                })
+                .collect(),
+        };
+        for i in 0..manager.entries.len() {
+            manager.resolve(i);
+        }
+        manager
+    }
+
+    /// Path of the model if it is available, starting its download if it is
+    /// missing.
+    pub fn require(&mut self, file_name: &str) -> Option<PathBuf> {
+        let i = self
+            .entries
+            .iter()
+            .position(|e| e.spec.file_name == file_name)?;
+        let status = self.entries[i].status.lock().unwrap().clone();
+        match status {
+            ModelStatus::Ready(path) => Some(path),
+            ModelStatus::Missing => {
+                self.download(i);
+                None
+            }
+            ModelStatus::Downloading { .. }
+            | ModelStatus::Failed(_)
+            | ModelStatus::NotDownloadable => None,
+        }
+    }
+
+    /// Status of the model, without starting its download.
+    pub fn status(&self, file_name: &str) -> Option<ModelStatus> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.spec.file_name == file_name)?;
+        let status = entry.status.lock().unwrap().clone();
+        Some(status)
+    }
+
+    /// Blocks until the model is available, downloading it if needed.
+    pub fn wait(&mut self, file_name: &str) -> Result<PathBuf, String> {
+        loop {
+            if let Some(path) = self.require(file_name) {
+                return Ok(path);
+            }
+            let entry = self
+                .entries
+                .iter()
+                .find(|e| e.spec.file_name == file_name)
+                .ok_or_else(|| format!("unknown model {}", file_name))?;
+            if let Some(e) = entry.status.lock().unwrap().error(file_name) {
+                return Err(e);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn resolve(&self, i: usize) {
+        let entry = &self.entries[i];
+        let local = PathBuf::from(entry.spec.file_name);
+        let cached = self.cache_dir.join(entry.spec.file_name);
+        for path in [local, cached] {
+            match verify(&path, entry.spec.sha256) {
+                Ok(true) => {
+                    *entry.status.lock().unwrap() = ModelStatus::Ready(path);
+                    return;
+                }
+                Ok(false) => warn!("Checksum mismatch for {}", path.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => error!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+        if !entry.spec.downloadable() {
+            *entry.status.lock().unwrap() = ModelStatus::NotDownloadable;
+        }
+    }
+
+    fn download(&self, i: usize) {
+        let spec = self.entries[i].spec.clone();
+        let status = self.entries[i].status.clone();
+        let dest = self.cache_dir.join(spec.file_name);
+        *status.lock().unwrap() = ModelStatus::Downloading {
+            received: 0,
+            total: None,
+        };
+        thread::spawn(move || {
+            let result = fetch(&spec, &dest, |received, total| {
+                *status.lock().unwrap() = ModelStatus::Downloading { received, total };
+            });
+            *status.lock().unwrap() = match result {
+                Ok(()) => ModelStatus::Ready(dest),
+                Err(e) => ModelStatus::Failed(e),
+            };
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut retry = vec![];
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            ui.label(entry.spec.file_name);
+            let status = entry.status.lock().unwrap().clone();
+            match status {
+                ModelStatus::Ready(path) => {
+                    ui.label(path.display().to_string());
+                }
+                ModelStatus::Downloading { received, total } => {
+                    let bar = match total {
+                        Some(total) => egui::ProgressBar::new(received as f32 / total as f32),
+                        None => egui::ProgressBar::new(0.0).animate(true),
+                    };
+                    ui.add(bar.text(format!("{:.1} MB", received as f32 / 1e6)));
+                }
+                ModelStatus::Missing | ModelStatus::NotDownloadable | ModelStatus::Failed(_) => {
+                    match status {
+                        ModelStatus::Failed(e) => {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                        ModelStatus::NotDownloadable => {
+                            ui.label("Not downloadable: put it in the working directory or enter its URL");
+                        }
+                        _ => {}
+                    }
+                    ui.text_edit_singleline(&mut entry.spec.url);
+                    let button = egui::Button::new("Download");
+                    if ui.add_enabled(entry.spec.downloadable(), button).clicked() {
+                        retry.push(i);
+                    }
+                }
+            }
+            ui.separator();
+        }
+        for i in retry {
+            self.download(i);
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+fn sha256_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(feature = "native")]
+/// Returns whether the file at `path` hashes to `sha256`.
+fn verify(path: &Path, sha256: Option<&str>) -> io::Result<bool> {
+    let Some(sha256) = sha256 else {
+        return path.metadata().map(|_| true);
+    };
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(sha256_hex(&hasher.finalize()) == sha256)
+}
+
+#[cfg(feature = "native")]
+/// Downloads `spec` to `dest`, hashing while streaming. The file only appears
+/// at `dest` once its checksum has been verified.
+fn fetch(spec: &ModelSpec, dest: &Path, progress: impl Fn(u64, Option<u64>)) -> Result<(), String> {
+    if spec.url.is_empty() {
+        return Err("no download URL, put the file in the working directory".to_owned());
+    }
+    let response = ureq::get(&spec.url).call().map_err(|e| e.to_string())?;
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse().ok());
+
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let partial = dest.with_extension("part");
+    let mut file = File::create(&partial).map_err(|e| e.to_string())?;
+    let mut reader = response.into_reader();
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut received = 0;
+    loop {
+        let n = reader.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+        received += n as u64;
+        progress(received, total);
+    }
+    drop(file);
+
+    let actual = sha256_hex(&hasher.finalize());
+    if let Some(expected) = spec.sha256.filter(|expected| *expected != actual) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!(
+            "checksum mismatch: expected {}, got {}",
+            expected, actual
+        ));
+    }
+    std::fs::rename(&partial, dest).map_err(|e| e.to_string())
+}
+
+#[cfg(all(test, feature = "native"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_without_a_url_are_not_downloadable() {
+        let spec = ModelSpec {
+            file_name: "not_downloadable_test.onnx",
+            url: String::new(),
+            sha256: None,
+        };
+        assert!(!spec.downloadable());
+        let mut models = ModelManager::new(vec![spec]);
+        let status = models.status("not_downloadable_test.onnx");
+        assert!(matches!(status, Some(ModelStatus::NotDownloadable)));
+        assert_eq!(models.require("not_downloadable_test.onnx"), None);
+        let e = models.wait("not_downloadable_test.onnx").unwrap_err();
+        assert!(e.contains("isn't downloadable"), "{}", e);
+    }
+}
@@ -0,0 +1,264 @@
+//! Gestures made by moving the hand, read from the tracks [`Trails`] follows
+//! palms along: swipes and raising a hand. Every tracked hand is watched, and
+//! each gesture is reported once as a [`MotionEvent`] for the outputs to act
+//! on.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{gesture::Gesture, trails::Trails};
+
+/// Swipes are measured over this much recent motion.
+const SWIPE_WINDOW: Duration = Duration::from_millis(400);
+/// Fraction of the frame a swipe has to cover within the window.
+const SWIPE_DISTANCE: f32 = 0.25;
+/// How straight a swipe has to be: its distance over the length of the path.
+const SWIPE_STRAIGHTNESS: f32 = 0.7;
+/// A hand is raised while its center is above this fraction of the frame
+/// height, and lowered again once below the line plus the margin.
+const RAISE_LINE: f32 = 0.35;
+const RAISE_MARGIN: f32 = 0.1;
+/// Time a raised hand has to stay within the hold tolerance to count.
+const RAISE_HOLD: Duration = Duration::from_millis(300);
+const HOLD_TOLERANCE: f32 = 0.05;
+/// Quiet time after a gesture of a hand, so one motion doesn't fire several.
+const COOLDOWN: Duration = Duration::from_millis(600);
+
+/// A gesture of one tracked hand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MotionEvent {
+    /// Identifier of the track, see [`Trails::tracks`].
+    pub track: u64,
+    pub gesture: Gesture,
+    /// Where the hand was when the gesture completed, normalized to the frame.
+    pub position: (f32, f32),
+}
+
+/// State of one tracked hand.
+#[derive(Default)]
+struct Track {
+    /// Positions normalized to the frame, oldest first, as far back as the
+    /// longest window.
+    history: VecDeque<(Instant, (f32, f32))>,
+    raised: bool,
+    cooldown_until: Option<Instant>,
+}
+
+impl Track {
+    fn push(&mut self, now: Instant, position: (f32, f32)) {
+        if position.1 > RAISE_LINE + RAISE_MARGIN {
+            self.raised = false;
+        }
+        self.history.push_back((now, position));
+        let window = SWIPE_WINDOW.max(RAISE_HOLD);
+        while self
+            .history
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window)
+        {
+            self.history.pop_front();
+        }
+    }
+
+    /// Positions of the last `window`.
+    fn recent(&self, now: Instant, window: Duration) -> impl Iterator<Item = (f32, f32)> + '_ {
+        self.history
+            .iter()
+            .filter(move |(at, _)| now.duration_since(*at) <= window)
+            .map(|(_, position)| *position)
+    }
+
+    /// A fast, straight move over the swipe window, classified by its
+    /// dominant direction.
+    fn swipe(&self, now: Instant, mirror: bool) -> Option<Gesture> {
+        let points = self.recent(now, SWIPE_WINDOW).collect::<Vec<_>>();
+        let ((x0, y0), (x1, y1)) = (*points.first()?, *points.last()?);
+        let (mut dx, dy) = (x1 - x0, y1 - y0);
+        let distance = dx.hypot(dy);
+        let path = points
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+            .sum::<f32>();
+        if dx.abs().max(dy.abs()) < SWIPE_DISTANCE || distance < SWIPE_STRAIGHTNESS * path {
+            return None;
+        }
+        if mirror {
+            dx = -dx;
+        }
+        Some(match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+            (true, true, _) => Gesture::SwipeRight,
+            (true, false, _) => Gesture::SwipeLeft,
+            (false, _, true) => Gesture::SwipeDown,
+            (false, _, false) => Gesture::SwipeUp,
+        })
+    }
+
+    /// Fires when the hand has been held still above the raise line for
+    /// the hold time, once until it is lowered again.
+    fn raise(&mut self, now: Instant) -> Option<Gesture> {
+        let (_, (x1, y1)) = *self.history.back()?;
+        let (since, _) = self.history.front()?;
+        let held = now.duration_since(*since) >= RAISE_HOLD
+            && self
+                .recent(now, RAISE_HOLD)
+                .all(|(x, y)| y < RAISE_LINE && (x - x1).hypot(y - y1) < HOLD_TOLERANCE);
+        if self.raised || !held {
+            return None;
+        }
+        self.raised = true;
+        Some(Gesture::HandRaise)
+    }
+}
+
+/// Recognizes swipes and hand raises of every tracked hand.
+pub struct MotionGestures {
+    pub enabled: bool,
+    /// Mirror left and right, as when facing the camera.
+    pub mirror: bool,
+    tracks: HashMap<u64, Track>,
+    /// Most recent event, for the UI.
+    last: Option<MotionEvent>,
+}
+
+impl Default for MotionGestures {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mirror: true,
+            tracks: HashMap::new(),
+            last: None,
+        }
+    }
+}
+
+impl MotionGestures {
+    /// Follows the newest positions of `trails` in a frame of `(w, h)`
+    /// pixels and returns the gestures completed at `now`.
+    pub fn update(
+        &mut self,
+        trails: &Trails,
+        (w, h): (u32, u32),
+        now: Instant,
+    ) -> Vec<MotionEvent> {
+        if !self.enabled {
+            self.tracks.clear();
+            return vec![];
+        }
+        let mut events = vec![];
+        let ids = trails.tracks().map(|(id, _)| id).collect::<Vec<_>>();
+        self.tracks.retain(|id, _| ids.contains(id));
+        for (id, trail) in trails.tracks() {
+            // Trails of palms not seen in this frame keep their last place.
+            let Some(&((x, y), _)) = trail.back().filter(|(_, at)| *at == now) else {
+                continue;
+            };
+            let position = (x / w as f32, y / h as f32);
+            let track = self.tracks.entry(id).or_default();
+            track.push(now, position);
+            if track.cooldown_until.is_some_and(|until| now < until) {
+                continue;
+            }
+            let Some(gesture) = track.swipe(now, self.mirror).or_else(|| track.raise(now)) else {
+                continue;
+            };
+            track.cooldown_until = Some(now + COOLDOWN);
+            // Start over, so the rest of the motion isn't a swipe again.
+            track.history.retain(|(at, _)| *at == now);
+            events.push(MotionEvent {
+                track: id,
+                gesture,
+                position,
+            });
+        }
+        if let Some(event) = events.last() {
+            self.last = Some(*event);
+        }
+        events
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        ui.checkbox(&mut self.mirror, "Mirror");
+        if let Some(event) = self.last {
+            ui.label(format!(
+                "Last: {} of hand {}",
+                event.gesture.name(),
+                event.track
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::palm;
+
+    /// Gestures of palms at `(ms, [(x, y)])` in a 100×100 frame.
+    fn gestures(frames: &[(u64, Vec<(f32, f32)>)]) -> Vec<(u64, Gesture)> {
+        let (mut trails, mut motion) = (Trails::default(), MotionGestures::default());
+        motion.mirror = false;
+        let start = Instant::now();
+        let mut events = vec![];
+        for (ms, centers) in frames {
+            let now = start + Duration::from_millis(*ms);
+            let palms = centers
+                .iter()
+                .map(|&(x, y)| palm(x, y).size(10.0, 10.0).build())
+                .collect::<Vec<_>>();
+            trails.update(&palms, (100, 100), now, Duration::from_secs(2));
+            let completed = motion.update(&trails, (100, 100), now);
+            events.extend(completed.iter().map(|e| (e.track, e.gesture)));
+        }
+        events
+    }
+
+    #[test]
+    fn swipes_of_two_hands() {
+        // One hand swipes right while the other swipes up.
+        let frames = (0..5)
+            .map(|i| {
+                let t = i as f32 * 8.0;
+                (i * 80, vec![(20.0 + t, 80.0), (80.0, 90.0 - t)])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gestures(&frames),
+            [(0, Gesture::SwipeRight), (1, Gesture::SwipeUp)]
+        );
+    }
+
+    #[test]
+    fn raising_a_hand_fires_once() {
+        // Raised, held up for a while, then lowered and raised again.
+        let heights = (0..8)
+            .map(|i| 80.0 - i as f32 * 7.0)
+            .chain([31.0; 22])
+            .chain((1..7).map(|i| 31.0 + i as f32 * 7.0))
+            .chain((1..7).map(|i| 73.0 - i as f32 * 7.0))
+            .chain([31.0; 25]);
+        let frames = (0..)
+            .zip(heights)
+            .map(|(i, y)| (i * 50, vec![(50.0, y)]))
+            .collect::<Vec<_>>();
+        let raises = gestures(&frames)
+            .into_iter()
+            .filter(|(_, g)| *g == Gesture::HandRaise)
+            .count();
+        assert_eq!(raises, 2);
+    }
+
+    #[test]
+    fn wandering_is_no_swipe() {
+        // Back and forth across the frame, but never straight.
+        let frames = (0..20)
+            .map(|i| {
+                let x = if i % 2 == 0 { 30.0 } else { 45.0 };
+                (i * 40, vec![(x, 60.0 + (i % 3) as f32)])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(gestures(&frames), []);
+    }
+}
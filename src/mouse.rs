@@ -0,0 +1,281 @@
+use enigo::{Button, Coordinate, Direction, Enigo, Mouse, Settings};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::{
+    hand::{Hand, INDEX_TIP},
+    palm::{Palm, INDEX_BASE},
+};
+
+/// How long a fist has to be held to turn mouse control on or off.
+const TOGGLE_HOLD: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseSettings {
+    pub enabled: bool,
+    /// Mirror the horizontal axis, so moving the hand right moves the cursor
+    /// right when facing the camera.
+    pub mirror: bool,
+    /// Exponential smoothing factor in `0..1`; higher is smoother but laggier.
+    pub smoothing: f32,
+    /// Cursor movements shorter than this many screen pixels are ignored.
+    pub dead_zone: f32,
+    /// Fraction of the frame cut off on every side, so screen edges are
+    /// reachable without the hand leaving the camera view.
+    pub margin: f32,
+    /// Thumb-to-index fingertip distance, relative to palm length, below
+    /// which the hand counts as pinching.
+    pub pinch_threshold: f32,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mirror: true,
+            smoothing: 0.6,
+            dead_zone: 3.0,
+            margin: 0.15,
+            pinch_threshold: 0.35,
+        }
+    }
+}
+
+/// Drives the system cursor from the first hand's index fingertip. A pinch
+/// of thumb and index fingertips presses the left button and releasing it
+/// lets go, so short pinches click and moving while pinched drags. The palm
+/// detector doesn't output fingertips, so without hand landmarks the cursor
+/// follows the first palm's index finger base and never clicks.
+///
+/// Holding a fist for a second turns it on or off, so it can be stopped
+/// while another window has the keyboard.
+pub struct MouseControl {
+    pub settings: MouseSettings,
+    toggle_gesture: FistToggle,
+    enigo: Option<Enigo>,
+    screen: (f32, f32),
+    smoothed: Option<(f32, f32)>,
+    cursor: Option<(f32, f32)>,
+    pressed: bool,
+}
+
+impl MouseControl {
+    pub fn new(settings: MouseSettings) -> Self {
+        let enigo = match Enigo::new(&Settings::default()) {
+            Ok(enigo) => Some(enigo),
+            Err(e) => {
+                warn!("Mouse control unavailable: {}", e);
+                None
+            }
+        };
+        let screen = enigo
+            .as_ref()
+            .and_then(|e| e.main_display().ok())
+            .unwrap_or((1920, 1080));
+        Self {
+            settings,
+            toggle_gesture: FistToggle::default(),
+            enigo,
+            screen: (screen.0 as f32, screen.1 as f32),
+            smoothed: None,
+            cursor: None,
+            pressed: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.settings.enabled = !self.settings.enabled;
+        if !self.settings.enabled {
+            self.lose_hand();
+        }
+    }
+
+    /// Feeds the first palm and hand detected in a frame of `frame_size`
+    /// pixels at `now`, if any.
+    pub fn update(
+        &mut self,
+        palm: Option<&Palm>,
+        hand: Option<&Hand>,
+        frame_size: (u32, u32),
+        now: Instant,
+    ) {
+        if self.enigo.is_none() {
+            return;
+        }
+        if self.toggle_gesture.update(hand, now) {
+            self.toggle();
+        }
+        if !self.settings.enabled {
+            return;
+        }
+        let (pointer, pinch) = match (hand, palm) {
+            (Some(hand), _) => {
+                let (x, y, _) = hand.landmarks[INDEX_TIP];
+                // A thumb folded over a fist isn't a pinch.
+                let pinch = (!hand.is_fist()).then(|| hand.pinch_ratio());
+                ((x, y), pinch)
+            }
+            (None, Some(palm)) => (palm.tips[INDEX_BASE], None),
+            (None, None) => {
+                self.lose_hand();
+                return;
+            }
+        };
+
+        let target = self.to_screen(pointer, frame_size);
+        let a = self.settings.smoothing.clamp(0.0, 0.99);
+        let (x, y) = match self.smoothed {
+            Some((sx, sy)) => (
+                sx + (1.0 - a) * (target.0 - sx),
+                sy + (1.0 - a) * (target.1 - sy),
+            ),
+            None => target,
+        };
+        self.smoothed = Some((x, y));
+
+        let moved = self
+            .cursor
+            .is_none_or(|(cx, cy)| (x - cx).hypot(y - cy) >= self.settings.dead_zone);
+        if moved {
+            self.cursor = Some((x, y));
+            self.send(|e| e.move_mouse(x as i32, y as i32, Coordinate::Abs));
+        }
+
+        // Hysteresis keeps a borderline pinch from chattering the button.
+        let threshold = self.settings.pinch_threshold;
+        if !self.pressed && pinch.is_some_and(|pinch| pinch < threshold) {
+            self.pressed = true;
+            self.send(|e| e.button(Button::Left, Direction::Press));
+        } else if self.pressed && pinch.is_none_or(|pinch| pinch > threshold * 1.2) {
+            self.pressed = false;
+            self.send(|e| e.button(Button::Left, Direction::Release));
+        }
+    }
+
+    fn lose_hand(&mut self) {
+        self.smoothed = None;
+        self.cursor = None;
+        if self.pressed {
+            self.pressed = false;
+            self.send(|e| e.button(Button::Left, Direction::Release));
+        }
+    }
+
+    fn to_screen(&self, (x, y): (f32, f32), (w, h): (u32, u32)) -> (f32, f32) {
+        let m = self.settings.margin.clamp(0.0, 0.45);
+        let mut nx = ((x / w as f32 - m) / (1.0 - 2.0 * m)).clamp(0.0, 1.0);
+        let ny = ((y / h as f32 - m) / (1.0 - 2.0 * m)).clamp(0.0, 1.0);
+        if self.settings.mirror {
+            nx = 1.0 - nx;
+        }
+        (nx * (self.screen.0 - 1.0), ny * (self.screen.1 - 1.0))
+    }
+
+    fn send(&mut self, event: impl FnOnce(&mut Enigo) -> enigo::InputResult<()>) {
+        if let Some(enigo) = self.enigo.as_mut() {
+            if let Err(e) = event(enigo) {
+                warn!("Mouse event failed: {}", e);
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.enigo.is_none() {
+            ui.label("No input backend available");
+            return;
+        }
+        let mut enabled = self.settings.enabled;
+        ui.checkbox(&mut enabled, "Enabled (F9)")
+            .on_hover_text("Or hold a fist for a second");
+        if enabled != self.settings.enabled {
+            self.toggle();
+        }
+        let s = &mut self.settings;
+        ui.checkbox(&mut s.mirror, "Mirror");
+        ui.add(egui::Slider::new(&mut s.smoothing, 0.0..=0.95).text("Smoothing"));
+        ui.add(egui::Slider::new(&mut s.dead_zone, 0.0..=20.0).text("Dead zone"));
+        ui.add(egui::Slider::new(&mut s.margin, 0.0..=0.4).text("Margin"));
+        ui.add(egui::Slider::new(&mut s.pinch_threshold, 0.1..=1.0).text("Pinch"));
+        ui.label(if self.pressed { "Pinching" } else { "Open" });
+        ui.weak("Clicking needs the hand landmarks pipeline");
+    }
+}
+
+/// Fires once a fist has been held for [`TOGGLE_HOLD`], then not again
+/// until the hand opens or leaves.
+#[derive(Default)]
+struct FistToggle {
+    /// When the fist was made, and whether it already fired.
+    since: Option<(Instant, bool)>,
+}
+
+impl FistToggle {
+    fn update(&mut self, hand: Option<&Hand>, now: Instant) -> bool {
+        if !hand.is_some_and(Hand::is_fist) {
+            self.since = None;
+            return false;
+        }
+        match self.since {
+            None => {
+                self.since = Some((now, false));
+                false
+            }
+            Some((since, false)) if now.duration_since(since) >= TOGGLE_HOLD => {
+                self.since = Some((since, true));
+                true
+            }
+            Some(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bbox::RotatedRect,
+        hand::{MIDDLE_BASE, MIDDLE_TIP, NUM_LANDMARKS, PINKY_TIP, RING_TIP, WRIST},
+        palm::Handedness,
+    };
+
+    /// A hand with its palm 100 pixels long and its fingertips `reach`
+    /// pixels from the wrist.
+    fn hand(reach: f32) -> Hand {
+        let mut landmarks = [(0.0, 0.0, 0.0); NUM_LANDMARKS];
+        landmarks[WRIST] = (100.0, 300.0, 0.0);
+        landmarks[MIDDLE_BASE] = (100.0, 200.0, 0.0);
+        for tip in [INDEX_TIP, MIDDLE_TIP, RING_TIP, PINKY_TIP] {
+            landmarks[tip] = (100.0, 300.0 - reach, 0.0);
+        }
+        Hand {
+            roi: RotatedRect {
+                cx: 100.0,
+                cy: 200.0,
+                w: 200.0,
+                h: 200.0,
+                angle: 0.0,
+            },
+            landmarks,
+            presence: 1.0,
+            handedness: Handedness::Right,
+        }
+    }
+
+    #[test]
+    fn held_fist_toggles_once() {
+        let (fist, open) = (hand(80.0), hand(190.0));
+        let mut toggle = FistToggle::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert!(!toggle.update(Some(&fist), at(0)));
+        assert!(!toggle.update(Some(&fist), at(500)));
+        assert!(toggle.update(Some(&fist), at(1000)));
+        assert!(!toggle.update(Some(&fist), at(2500)));
+        // Opening the hand, or losing it, starts over.
+        assert!(!toggle.update(Some(&open), at(2600)));
+        assert!(!toggle.update(Some(&fist), at(2700)));
+        assert!(!toggle.update(None, at(3000)));
+        assert!(!toggle.update(Some(&fist), at(3100)));
+        assert!(toggle.update(Some(&fist), at(4100)));
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::bbox::{BBox, Detection};
+
+/// How candidates overlapping a better one are dealt with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NmsMode {
+    /// Dropped.
+    #[default]
+    Hard,
+    /// Kept with their score lowered by how much they overlap, so a second
+    /// object close to the first can survive.
+    Soft,
+    /// Blended into the best one, weighted by score, as MediaPipe does.
+    /// Boxes and keypoints jitter less from frame to frame.
+    Weighted,
+}
+
+impl NmsMode {
+    pub const ALL: [Self; 3] = [Self::Hard, Self::Soft, Self::Weighted];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hard => "Hard",
+            Self::Soft => "Soft",
+            Self::Weighted => "Weighted",
+        }
+    }
+}
+
+/// Score-weighted mean of `value` over `cluster`.
+pub fn weighted_mean<T: Detection>(cluster: &[T], value: impl Fn(&T) -> (f32, f32)) -> (f32, f32) {
+    let total = cluster.iter().map(T::score).sum::<f32>().max(f32::EPSILON);
+    cluster.iter().fold((0.0, 0.0), |(x, y), c| {
+        let ((vx, vy), weight) = (value(c), c.score() / total);
+        (x + vx * weight, y + vy * weight)
+    })
+}
+
+/// Score-weighted mean box of `cluster`.
+pub fn weighted_bbox<T: Detection>(cluster: &[T]) -> BBox {
+    let (x, y) = weighted_mean(cluster, |c| (c.bbox().x, c.bbox().y));
+    let (w, h) = weighted_mean(cluster, |c| (c.bbox().w, c.bbox().h));
+    BBox { x, y, w, h }
+}
+
+/// Suppresses overlapping candidates as `mode` says, keeping those scoring
+/// at least `score_threshold`. Best first.
+pub fn suppress<T: Detection>(
+    candidates: Vec<T>,
+    mode: NmsMode,
+    score_threshold: f32,
+    iou_threshold: f32,
+) -> Vec<T> {
+    match mode {
+        NmsMode::Hard => non_max_suppression(candidates, score_threshold, iou_threshold),
+        NmsMode::Soft => soft_non_max_suppression(candidates, score_threshold, iou_threshold),
+        NmsMode::Weighted => {
+            weighted_non_max_suppression(candidates, score_threshold, iou_threshold)
+        }
+    }
+}
+
+/// Keeps the best-scoring candidates above `score_threshold`, dropping any
+/// that overlap an already kept one by `iou_threshold` or more.
+pub fn non_max_suppression<T: Detection>(
+    mut candidates: Vec<T>,
+    score_threshold: f32,
+    iou_threshold: f32,
+) -> Vec<T> {
+    candidates.retain(|c| c.score() >= score_threshold);
+    candidates.sort_by(|c1, c2| c2.score().total_cmp(&c1.score()));
+    let mut kept = vec![];
+    while !candidates.is_empty() {
+        let best = candidates.remove(0);
+        candidates.retain(|c| c.bbox().iou(best.bbox()) < iou_threshold);
+        kept.push(best);
+    }
+    kept
+}
+
+/// Like [`non_max_suppression`], but a candidate overlapping a kept one by
+/// `iou_threshold` or more has its score scaled by one minus the overlap,
+/// and is only dropped once that falls below `score_threshold`.
+pub fn soft_non_max_suppression<T: Detection>(
+    mut candidates: Vec<T>,
+    score_threshold: f32,
+    iou_threshold: f32,
+) -> Vec<T> {
+    candidates.retain(|c| c.score() >= score_threshold);
+    let mut kept = vec![];
+    while let Some(i) = (0..candidates.len())
+        .max_by(|&i, &j| candidates[i].score().total_cmp(&candidates[j].score()))
+    {
+        let best = candidates.swap_remove(i);
+        for candidate in &mut candidates {
+            let iou = candidate.bbox().iou(best.bbox());
+            if iou >= iou_threshold {
+                *candidate = candidate.with_score(candidate.score() * (1.0 - iou));
+            }
+        }
+        candidates.retain(|c| c.score() >= score_threshold);
+        kept.push(best);
+    }
+    kept
+}
+
+/// Like [`non_max_suppression`], but the best candidate and those it
+/// overlaps by `iou_threshold` or more are replaced by
+/// [`Detection::blend`] of them all.
+pub fn weighted_non_max_suppression<T: Detection>(
+    mut candidates: Vec<T>,
+    score_threshold: f32,
+    iou_threshold: f32,
+) -> Vec<T> {
+    candidates.retain(|c| c.score() >= score_threshold);
+    candidates.sort_by(|c1, c2| c2.score().total_cmp(&c1.score()));
+    let mut kept = vec![];
+    while !candidates.is_empty() {
+        let best = candidates[0];
+        let (mut cluster, rest): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .skip(1)
+            .partition(|c| c.bbox().iou(best.bbox()) >= iou_threshold);
+        cluster.insert(0, best);
+        kept.push(T::blend(&cluster));
+        candidates = rest;
+    }
+    kept
+}
+
+/// [`suppress`] within each class, so overlapping detections of
+/// different classes are all kept. The result is sorted by score.
+pub fn class_aware_non_max_suppression<T: Detection>(
+    candidates: Vec<T>,
+    class: impl Fn(&T) -> usize,
+    mode: NmsMode,
+    score_threshold: f32,
+    iou_threshold: f32,
+) -> Vec<T> {
+    let mut by_class = BTreeMap::<usize, Vec<T>>::new();
+    for candidate in candidates {
+        by_class
+            .entry(class(&candidate))
+            .or_default()
+            .push(candidate);
+    }
+    let mut kept = by_class
+        .into_values()
+        .flat_map(|c| suppress(c, mode, score_threshold, iou_threshold))
+        .collect::<Vec<_>>();
+    kept.sort_by(|c1, c2| c2.score().total_cmp(&c1.score()));
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbox::BBox;
+
+    #[derive(Clone, Copy, Debug)]
+    struct Candidate(f32, f32, usize);
+
+    impl Detection for Candidate {
+        fn bbox(&self) -> BBox {
+            BBox {
+                x: self.0,
+                y: 0.0,
+                w: 10.0,
+                h: 10.0,
+            }
+        }
+
+        fn score(&self) -> f32 {
+            self.1
+        }
+
+        fn with_score(self, score: f32) -> Self {
+            Self(self.0, score, self.2)
+        }
+
+        fn blend(cluster: &[Self]) -> Self {
+            let (x, _) = weighted_mean(cluster, |c| (c.0, 0.0));
+            Self(x, cluster[0].1, cluster[0].2)
+        }
+    }
+
+    #[test]
+    fn overlaps_only_suppress_within_class() {
+        let candidates = vec![
+            Candidate(0.0, 0.9, 0),
+            Candidate(1.0, 0.8, 0),
+            Candidate(1.0, 0.7, 1),
+            Candidate(50.0, 0.6, 0),
+            Candidate(0.0, 0.1, 1),
+        ];
+        let kept = class_aware_non_max_suppression(candidates, |c| c.2, NmsMode::Hard, 0.5, 0.5);
+        let kept = kept.iter().map(|c| (c.0, c.2)).collect::<Vec<_>>();
+        assert_eq!(kept, [(0.0, 0), (1.0, 1), (50.0, 0)]);
+    }
+
+    #[test]
+    fn soft_and_weighted_modes_keep_overlaps() {
+        // 10 wide boxes: 1 apart overlap by 9/11, 5 apart by 1/3.
+        let candidates = vec![
+            Candidate(0.0, 0.9, 0),
+            Candidate(1.0, 0.85, 0),
+            Candidate(5.0, 0.8, 0),
+        ];
+
+        let kept = soft_non_max_suppression(candidates.clone(), 0.3, 0.3);
+        let kept = kept.iter().map(|c| (c.0, c.1)).collect::<Vec<_>>();
+        // The close one falls under the score threshold, the other is kept
+        // with a third of its score taken off.
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0], (0.0, 0.9));
+        assert_eq!(kept[1].0, 5.0);
+        assert!((kept[1].1 - 0.8 * 2.0 / 3.0).abs() < 1e-6);
+
+        // The close one is blended into the best, keeping the best score.
+        let kept = weighted_non_max_suppression(candidates, 0.3, 0.5);
+        let kept = kept.iter().map(|c| (c.0, c.1)).collect::<Vec<_>>();
+        assert_eq!(kept.len(), 2);
+        assert!((kept[0].0 - 0.85 / 1.75).abs() < 1e-6);
+        assert_eq!(kept[0].1, 0.9);
+        assert_eq!(kept[1], (5.0, 0.8));
+    }
+}
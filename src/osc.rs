@@ -0,0 +1,284 @@
+//! Sends hand keypoints as OSC over UDP, for TouchDesigner, Max,
+//! SuperCollider and the like.
+//!
+//! Every frame goes out as one bundle holding `/hand/count n` followed by
+//! `/hand/{id}/landmark/{n} x y z` per keypoint. Coordinates are normalized to
+//! the frame; z is always 0 as the palm detector is 2D. Motion gestures go
+//! out as they happen as `/gesture/{name} track x y`, e.g.
+//! `/gesture/swipe_left 3 0.42 0.5`, with the hand's track identifier and
+//! where it was, and so do `/gesture/hand_near` and `/gesture/hand_far` of
+//! hand distance. Face meshes go out every frame as one more bundle,
+//! `/face/count n` followed by `/face/{id}/eye_aspect_ratio right left`,
+//! `/face/{id}/mouth_openness v` and `/face/{id}/blink b`, with `b` 1 while
+//! both eyes are closed. With two hand metrics on, every frame also sends
+//! `/two_hands/count n`, 2 or 0, and with two hands `/two_hands/distance d`,
+//! `/two_hands/angle a` in radians, `/two_hands/center x y`,
+//! `/two_hands/pinch b`, `/two_hands/zoom z` and `/two_hands/rotation r`,
+//! see [`TwoHandMetrics`].
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::{face_mesh::FaceMesh, motion::MotionEvent, palm::Palm, two_hands::TwoHandMetrics};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Float(f32),
+}
+
+/// Appends `s` null-terminated and padded to a multiple of four bytes.
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(s.as_bytes());
+    let pad = 4 - s.len() % 4;
+    buf.extend(std::iter::repeat_n(0, pad));
+}
+
+pub fn encode_message(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut buf = vec![];
+    push_str(&mut buf, address);
+    let tags = args
+        .iter()
+        .map(|arg| match arg {
+            OscArg::Int(_) => 'i',
+            OscArg::Float(_) => 'f',
+        })
+        .collect::<String>();
+    push_str(&mut buf, &format!(",{}", tags));
+    for arg in args {
+        match arg {
+            OscArg::Int(v) => buf.extend_from_slice(&v.to_be_bytes()),
+            OscArg::Float(v) => buf.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+    buf
+}
+
+/// Wraps `messages` in a bundle to be dispatched immediately.
+pub fn encode_bundle(messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut buf = vec![];
+    push_str(&mut buf, "#bundle");
+    buf.extend_from_slice(&1u64.to_be_bytes());
+    for message in messages {
+        buf.extend_from_slice(&(message.len() as i32).to_be_bytes());
+        buf.extend_from_slice(message);
+    }
+    buf
+}
+
+pub struct OscOutput {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    socket: Option<UdpSocket>,
+    target: Option<SocketAddr>,
+    error: String,
+}
+
+impl OscOutput {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            enabled: false,
+            host: host.into(),
+            port,
+            socket: None,
+            target: None,
+            error: String::new(),
+        }
+    }
+
+    /// Binds the socket and resolves the target after the settings change.
+    fn connect(&mut self) {
+        self.target = None;
+        let result = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())
+            .and_then(|mut addrs| addrs.next().ok_or("no address".to_owned()))
+            .and_then(|target| {
+                if self.socket.is_none() {
+                    self.socket = Some(UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?);
+                }
+                Ok(target)
+            });
+        match result {
+            Ok(target) => {
+                self.target = Some(target);
+                self.error.clear();
+            }
+            Err(e) => self.error = e,
+        }
+    }
+
+    pub fn send_palms(&mut self, palms: &[Palm], (w, h): (u32, u32)) {
+        if !self.enabled {
+            return;
+        }
+        if self.target.is_none() {
+            self.connect();
+        }
+        let (Some(socket), Some(target)) = (&self.socket, self.target) else {
+            return;
+        };
+
+        let mut messages = vec![encode_message(
+            "/hand/count",
+            &[OscArg::Int(palms.len() as i32)],
+        )];
+        for (id, palm) in palms.iter().enumerate() {
+            for (n, (x, y)) in palm.tips.iter().enumerate() {
+                messages.push(encode_message(
+                    &format!("/hand/{}/landmark/{}", id, n),
+                    &[
+                        OscArg::Float(x / w as f32),
+                        OscArg::Float(y / h as f32),
+                        OscArg::Float(0.0),
+                    ],
+                ));
+            }
+        }
+        if let Err(e) = socket.send_to(&encode_bundle(&messages), target) {
+            self.error = e.to_string();
+        }
+    }
+
+    pub fn send_faces(&mut self, meshes: &[FaceMesh]) {
+        if !self.enabled {
+            return;
+        }
+        if self.target.is_none() {
+            self.connect();
+        }
+        let (Some(socket), Some(target)) = (&self.socket, self.target) else {
+            return;
+        };
+        let mut messages = vec![encode_message(
+            "/face/count",
+            &[OscArg::Int(meshes.len() as i32)],
+        )];
+        for (id, mesh) in meshes.iter().enumerate() {
+            let e = mesh.expression;
+            messages.push(encode_message(
+                &format!("/face/{}/eye_aspect_ratio", id),
+                &e.eye_aspect_ratio.map(OscArg::Float),
+            ));
+            messages.push(encode_message(
+                &format!("/face/{}/mouth_openness", id),
+                &[OscArg::Float(e.mouth_openness)],
+            ));
+            messages.push(encode_message(
+                &format!("/face/{}/blink", id),
+                &[OscArg::Int(e.blinking() as i32)],
+            ));
+        }
+        if let Err(e) = socket.send_to(&encode_bundle(&messages), target) {
+            self.error = e.to_string();
+        }
+    }
+
+    pub fn send_two_hands(&mut self, metrics: Option<&TwoHandMetrics>, (w, h): (u32, u32)) {
+        if !self.enabled {
+            return;
+        }
+        if self.target.is_none() {
+            self.connect();
+        }
+        let (Some(socket), Some(target)) = (&self.socket, self.target) else {
+            return;
+        };
+        let count = metrics.map_or(0, |_| 2);
+        let mut messages = vec![encode_message("/two_hands/count", &[OscArg::Int(count)])];
+        if let Some(m) = metrics {
+            let (x, y) = m.center();
+            for (address, args) in [
+                ("distance", vec![OscArg::Float(m.distance)]),
+                ("angle", vec![OscArg::Float(m.angle)]),
+                (
+                    "center",
+                    vec![OscArg::Float(x / w as f32), OscArg::Float(y / h as f32)],
+                ),
+                ("pinch", vec![OscArg::Int(m.pinching as i32)]),
+                ("zoom", vec![OscArg::Float(m.zoom)]),
+                ("rotation", vec![OscArg::Float(m.rotation)]),
+            ] {
+                messages.push(encode_message(&format!("/two_hands/{}", address), &args));
+            }
+        }
+        if let Err(e) = socket.send_to(&encode_bundle(&messages), target) {
+            self.error = e.to_string();
+        }
+    }
+
+    pub fn send_gestures(&mut self, events: &[MotionEvent]) {
+        if !self.enabled || events.is_empty() {
+            return;
+        }
+        if self.target.is_none() {
+            self.connect();
+        }
+        let (Some(socket), Some(target)) = (&self.socket, self.target) else {
+            return;
+        };
+        let messages = events
+            .iter()
+            .map(|event| {
+                let name = event.gesture.name().to_lowercase().replace(' ', "_");
+                encode_message(
+                    &format!("/gesture/{}", name),
+                    &[
+                        OscArg::Int(event.track as i32),
+                        OscArg::Float(event.position.0),
+                        OscArg::Float(event.position.1),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Err(e) = socket.send_to(&encode_bundle(&messages), target) {
+            self.error = e.to_string();
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        let host = ui
+            .horizontal(|ui| {
+                ui.label("Host");
+                ui.text_edit_singleline(&mut self.host)
+            })
+            .inner;
+        let port = ui.add(egui::DragValue::new(&mut self.port).prefix("Port "));
+        if host.changed() || port.changed() {
+            self.target = None;
+        }
+        if !self.error.is_empty() {
+            ui.colored_label(egui::Color32::RED, &self.error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_layout() {
+        let msg = encode_message("/hand/count", &[OscArg::Int(2)]);
+        assert_eq!(&msg[..12], b"/hand/count\0");
+        assert_eq!(&msg[12..16], b",i\0\0");
+        assert_eq!(&msg[16..], &[0, 0, 0, 2]);
+
+        let msg = encode_message("/a", &[OscArg::Float(1.0), OscArg::Float(0.5)]);
+        assert_eq!(&msg[..4], b"/a\0\0");
+        assert_eq!(&msg[4..8], b",ff\0");
+        assert_eq!(&msg[8..12], &1.0f32.to_be_bytes());
+        assert_eq!(&msg[12..], &0.5f32.to_be_bytes());
+    }
+
+    #[test]
+    fn bundle_layout() {
+        let msg = encode_message("/a", &[]);
+        let bundle = encode_bundle(std::slice::from_ref(&msg));
+        assert_eq!(&bundle[..8], b"#bundle\0");
+        assert_eq!(&bundle[8..16], &1u64.to_be_bytes());
+        assert_eq!(&bundle[16..20], &(msg.len() as i32).to_be_bytes());
+        assert_eq!(&bundle[20..], &msg[..]);
+    }
+}
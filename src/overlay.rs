@@ -0,0 +1,790 @@
+use ab_glyph::{FontRef, PxScale};
+use image::{imageops::FilterType, Rgb, RgbImage};
+use imageproc::{
+    drawing::{
+        draw_filled_circle_mut, draw_hollow_circle_mut, draw_line_segment_mut, draw_polygon_mut,
+        draw_text_mut,
+    },
+    point::Point,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+use crate::{
+    bbox::{BBox, RotatedRect},
+    canvas::AirCanvas,
+    depth::{DepthDisplay, DepthMap},
+    face_mesh::{FaceMesh, EYE_LANDMARKS, MOUTH_LANDMARKS},
+    hand::{Hand, HAND_EDGES},
+    iris::Face,
+    palm::Palm,
+    plugin::PluginDetection,
+    pose::{Pose, POSE_EDGES},
+    trails::{Heatmap, Trails},
+    two_hands::TwoHandMetrics,
+    worker::{Detections, WeakDetections},
+    yolo::Object,
+};
+
+/// Pose landmarks less likely to be visible than this are not drawn.
+const MIN_VISIBILITY: f32 = 0.5;
+
+/// Pairs of palm keypoints connected when drawing the skeleton.
+const PALM_EDGES: [(usize, usize); 10] = [
+    (0, 5),
+    (5, 6),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (2, 3),
+    (3, 4),
+    (6, 1),
+];
+
+/// Length of the dashes and gaps of weak detections' boxes, in pixels.
+const DASH: f32 = 6.0;
+
+/// Length of the gaze ray per unit of gaze offset, in face widths.
+const GAZE_RAY_SCALE: f32 = 3.0;
+
+/// What the overlay draws and how. Persisted across runs.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overlay {
+    pub boxes: bool,
+    /// Index of every palm keypoint next to it.
+    pub tip_indices: bool,
+    pub scores: bool,
+    /// Boxes and captions of palms, objects and faces colored by score,
+    /// from red to green, with the score shown.
+    pub confidence_colors: bool,
+    pub skeleton: bool,
+    /// Per-frame detection ids, the same ones OSC output uses.
+    pub ids: bool,
+    /// Rotated crop a hand landmark model would see, around each palm.
+    pub hand_rois: bool,
+    /// "L" or "R" in front of each palm's caption.
+    pub handedness: bool,
+    /// Fading paths of the palm centers.
+    pub trails: bool,
+    /// How far back trails reach, in seconds.
+    pub trail_seconds: f32,
+    /// Where palms spent time since the stream was opened or cleared.
+    pub heatmap: bool,
+    /// Where the depth estimation pipeline's maps are shown.
+    pub depth_display: DepthDisplay,
+    /// Opacity of depth maps blended over the frame.
+    pub depth_opacity: f32,
+    /// Height of text labels, in pixels.
+    pub label_height: f32,
+    pub thickness: f32,
+    pub label_color: [u8; 3],
+    pub box_color: [u8; 3],
+    pub skeleton_color: [u8; 3],
+    /// Palms of the candidate model when comparing palm models.
+    pub compare_color: [u8; 3],
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self {
+            boxes: false,
+            tip_indices: true,
+            scores: false,
+            confidence_colors: false,
+            skeleton: true,
+            ids: false,
+            hand_rois: false,
+            handedness: true,
+            trails: false,
+            trail_seconds: 2.0,
+            heatmap: false,
+            depth_display: DepthDisplay::default(),
+            depth_opacity: 0.6,
+            label_height: 28.0,
+            thickness: 1.0,
+            label_color: [255, 0, 255],
+            box_color: [255, 255, 0],
+            skeleton_color: [0, 255, 0],
+            compare_color: [0, 160, 255],
+        }
+    }
+}
+
+/// Draws detections onto camera frames. Holds everything that doesn't change
+/// between frames, so nothing is parsed or allocated per keypoint.
+pub struct OverlayRenderer {
+    font: FontRef<'static>,
+    settings: Overlay,
+    label_scale: PxScale,
+    labels: Vec<String>,
+}
+
+impl OverlayRenderer {
+    pub fn new(settings: Overlay) -> Self {
+        let font = FontRef::try_from_slice(include_bytes!("../DejaVuSans.ttf")).unwrap();
+        Self {
+            font,
+            settings,
+            label_scale: Self::label_scale(settings.label_height),
+            labels: (0..7).map(|i| i.to_string()).collect(),
+        }
+    }
+
+    fn label_scale(height: f32) -> PxScale {
+        PxScale {
+            x: height * 2.0,
+            y: height,
+        }
+    }
+
+    pub fn settings(&self) -> Overlay {
+        self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Overlay) {
+        self.label_scale = Self::label_scale(settings.label_height);
+        self.settings = settings;
+    }
+
+    fn text(&self, buf: &mut RgbImage, (x, y): (f32, f32), text: &str) {
+        self.colored_text(buf, (x, y), text, self.settings.label_color);
+    }
+
+    fn colored_text(&self, buf: &mut RgbImage, (x, y): (f32, f32), text: &str, color: [u8; 3]) {
+        draw_text_mut(
+            buf,
+            Rgb(color),
+            x as i32,
+            y as i32,
+            self.label_scale,
+            &self.font,
+            text,
+        );
+    }
+
+    /// Draws a line `thickness` pixels wide as a filled quad.
+    fn line(&self, buf: &mut RgbImage, from: (f32, f32), to: (f32, f32), color: [u8; 3]) {
+        wide_line(buf, from, to, self.settings.thickness, color);
+    }
+
+    fn bbox(&self, buf: &mut RgbImage, bbox: BBox) {
+        self.colored_bbox(buf, bbox, self.settings.box_color);
+    }
+
+    fn colored_bbox(&self, buf: &mut RgbImage, bbox: BBox, color: [u8; 3]) {
+        self.rotated_rect(buf, bbox.into(), color);
+    }
+
+    /// Box of a weak detection, dashed.
+    fn dashed_bbox(&self, buf: &mut RgbImage, bbox: BBox, color: [u8; 3]) {
+        let corners = RotatedRect::from(bbox).corners();
+        for (i, from) in corners.iter().enumerate() {
+            for (from, to) in dashes(*from, corners[(i + 1) % 4], DASH) {
+                self.line(buf, from, to, color);
+            }
+        }
+    }
+
+    /// Box and caption colors of a detection with `score`.
+    fn score_colors(&self, score: f32) -> ([u8; 3], [u8; 3]) {
+        if self.settings.confidence_colors {
+            let color = confidence_color(score);
+            (color, color)
+        } else {
+            (self.settings.box_color, self.settings.label_color)
+        }
+    }
+
+    fn rotated_rect(&self, buf: &mut RgbImage, rect: RotatedRect, color: [u8; 3]) {
+        let corners = rect.corners();
+        for (i, from) in corners.iter().enumerate() {
+            self.line(buf, *from, corners[(i + 1) % 4], color);
+        }
+    }
+
+    /// Draws the `id`th palm of the frame.
+    pub fn draw_palm(&self, buf: &mut RgbImage, id: usize, palm: &Palm) {
+        let settings = self.settings;
+        if settings.tip_indices {
+            for (tip, label) in palm.tips.iter().zip(&self.labels) {
+                self.text(buf, *tip, label);
+            }
+        }
+
+        if settings.skeleton {
+            for (f, t) in PALM_EDGES {
+                self.line(buf, palm.tips[f], palm.tips[t], settings.skeleton_color);
+            }
+        }
+
+        let (box_color, label_color) = self.score_colors(palm.score);
+        if settings.boxes {
+            self.colored_bbox(buf, palm.bbox, box_color);
+        }
+        if settings.hand_rois {
+            self.rotated_rect(buf, palm.hand_roi(), settings.box_color);
+        }
+        let side = Some(palm.handedness.label()).filter(|_| settings.handedness);
+        self.labeled_caption(buf, palm.bbox, side, id, Some(palm.score), label_color);
+    }
+
+    /// Estimated distance of a palm from the camera, below its box.
+    pub fn draw_distance(&self, buf: &mut RgbImage, palm: &Palm, distance: f32) {
+        let b = palm.bbox;
+        self.text(buf, (b.x, b.y + b.h), &format!("{:.0} cm", distance));
+    }
+
+    /// Line between two hands, with their zoom and rotation while both
+    /// pinch.
+    pub fn draw_two_hands(&self, buf: &mut RgbImage, metrics: &TwoHandMetrics) {
+        let [from, to] = metrics.centers;
+        let color = if metrics.pinching {
+            self.settings.box_color
+        } else {
+            self.settings.skeleton_color
+        };
+        self.line(buf, from, to, color);
+        if metrics.pinching {
+            let caption = format!(
+                "x{:.2} {:+.0} deg",
+                metrics.zoom,
+                metrics.rotation.to_degrees()
+            );
+            self.text(buf, metrics.center(), &caption);
+        }
+    }
+
+    /// Draws a palm of the candidate model in a comparison, in one color so
+    /// it stands apart from the reference palms.
+    pub fn draw_compared_palm(&self, buf: &mut RgbImage, palm: &Palm) {
+        let color = self.settings.compare_color;
+        if self.settings.skeleton {
+            for (f, t) in PALM_EDGES {
+                self.line(buf, palm.tips[f], palm.tips[t], color);
+            }
+        }
+        if self.settings.boxes {
+            self.colored_bbox(buf, palm.bbox, color);
+        }
+    }
+
+    /// Id and score above the top left corner of `bbox`, as enabled.
+    fn caption(&self, buf: &mut RgbImage, bbox: BBox, id: usize, score: Option<f32>) {
+        self.labeled_caption(buf, bbox, None, id, score, self.settings.label_color);
+    }
+
+    fn labeled_caption(
+        &self,
+        buf: &mut RgbImage,
+        bbox: BBox,
+        label: Option<&str>,
+        id: usize,
+        score: Option<f32>,
+        color: [u8; 3],
+    ) {
+        let mut caption = label.map(str::to_owned).into_iter().collect::<Vec<_>>();
+        if self.settings.ids {
+            caption.push(format!("#{}", id));
+        }
+        let scores = self.settings.scores || self.settings.confidence_colors;
+        if let Some(score) = score.filter(|_| scores) {
+            caption.push(format!("{:.2}", score));
+        }
+        if !caption.is_empty() {
+            let y = bbox.y - self.settings.label_height;
+            self.colored_text(buf, (bbox.x, y.max(0.0)), &caption.join(" "), color);
+        }
+    }
+
+    /// Draws the `id`th detection of a plugin: its box, keypoints as dots
+    /// with the skeleton, and its label.
+    pub fn draw_plugin_detection(
+        &self,
+        buf: &mut RgbImage,
+        id: usize,
+        detection: &PluginDetection,
+    ) {
+        let settings = self.settings;
+        let (box_color, label_color) = self.score_colors(detection.score);
+        self.colored_bbox(buf, detection.bbox, box_color);
+        if settings.skeleton {
+            for &(x, y) in &detection.keypoints {
+                draw_filled_circle_mut(
+                    buf,
+                    (x as i32, y as i32),
+                    3 + settings.thickness as i32 / 2,
+                    Rgb(settings.skeleton_color),
+                );
+            }
+        }
+        let label = Some(detection.label.as_str()).filter(|label| !label.is_empty());
+        self.labeled_caption(
+            buf,
+            detection.bbox,
+            label,
+            id,
+            Some(detection.score),
+            label_color,
+        );
+    }
+
+    /// Draws the `id`th pose of the frame.
+    pub fn draw_pose(&self, buf: &mut RgbImage, id: usize, pose: &Pose) {
+        let settings = self.settings;
+        if settings.boxes && pose.roi.w >= 1.0 && pose.roi.h >= 1.0 {
+            self.bbox(buf, pose.roi);
+        }
+        let visible = |i: usize| pose.landmarks[i].visibility >= MIN_VISIBILITY;
+        if settings.skeleton {
+            for (f, t) in POSE_EDGES {
+                if visible(f) && visible(t) {
+                    let (f, t) = (pose.landmarks[f], pose.landmarks[t]);
+                    self.line(buf, (f.x, f.y), (t.x, t.y), settings.skeleton_color);
+                }
+            }
+            for landmark in pose
+                .landmarks
+                .iter()
+                .filter(|l| l.visibility >= MIN_VISIBILITY)
+            {
+                draw_filled_circle_mut(
+                    buf,
+                    (landmark.x as i32, landmark.y as i32),
+                    3 + settings.thickness as i32 / 2,
+                    Rgb(settings.label_color),
+                );
+            }
+        }
+        self.caption(buf, pose.roi, id, None);
+    }
+
+    /// Draws the `id`th hand of the frame, joints nearer to the camera
+    /// bigger.
+    pub fn draw_hand(&self, buf: &mut RgbImage, id: usize, hand: &Hand) {
+        let settings = self.settings;
+        if settings.boxes {
+            self.bbox(buf, hand.bbox());
+        }
+        if settings.hand_rois {
+            self.rotated_rect(buf, hand.roi, settings.box_color);
+        }
+        if settings.skeleton {
+            let point = |i: usize| (hand.landmarks[i].0, hand.landmarks[i].1);
+            for (f, t) in HAND_EDGES {
+                self.line(buf, point(f), point(t), settings.skeleton_color);
+            }
+            for (x, y, z) in hand.landmarks {
+                let depth = (-z / hand.roi.w.max(1.0) * 8.0).clamp(-2.0, 2.0);
+                draw_filled_circle_mut(
+                    buf,
+                    (x as i32, y as i32),
+                    (3.0 + settings.thickness / 2.0 + depth).max(1.0) as i32,
+                    Rgb(settings.label_color),
+                );
+            }
+        }
+        let side = Some(hand.handedness.label()).filter(|_| settings.handedness);
+        self.labeled_caption(
+            buf,
+            hand.bbox(),
+            side,
+            id,
+            Some(hand.presence),
+            settings.label_color,
+        );
+    }
+
+    /// Draws the palm trails of a stream as of `now`, older parts fainter,
+    /// each in a color of its own.
+    pub fn draw_trails(&self, buf: &mut RgbImage, trails: &Trails, now: Instant) {
+        if !self.settings.trails {
+            return;
+        }
+        let radius = self.settings.thickness.max(2.0);
+        for (i, trail) in trails.trails().iter().enumerate() {
+            let color = class_color(i);
+            for ((from, _), (to, at)) in trail.iter().zip(trail.iter().skip(1)) {
+                let age = now.duration_since(*at).as_secs_f32();
+                let alpha = 1.0 - age / self.settings.trail_seconds;
+                let steps = (to.0 - from.0).hypot(to.1 - from.1).ceil().max(1.0);
+                for step in 0..steps as usize {
+                    let t = step as f32 / steps;
+                    let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+                    blend_disc(buf, point, radius, color, alpha);
+                }
+            }
+        }
+    }
+
+    /// Tints the frame by the heatmap, from blue where palms rarely were to
+    /// red where they were most.
+    pub fn draw_heatmap(&self, buf: &mut RgbImage, heatmap: &Heatmap) {
+        if !self.settings.heatmap {
+            return;
+        }
+        for (x, y, pixel) in buf.enumerate_pixels_mut() {
+            let heat = heatmap.value(x, y);
+            if heat < 0.01 {
+                continue;
+            }
+            let color = egui::ecolor::Hsva::new((1.0 - heat) * 0.66, 1.0, 1.0, 1.0).to_srgb();
+            let alpha = 0.2 + 0.4 * heat;
+            for (c, color) in pixel.0.iter_mut().zip(color) {
+                *c = (*c as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+            }
+        }
+    }
+
+    /// Blends the colorized depth map over the part of the frame it covers,
+    /// if depth is shown over the frame.
+    pub fn draw_depth(&self, buf: &mut RgbImage, map: &DepthMap) {
+        if self.settings.depth_display != DepthDisplay::Overlay {
+            return;
+        }
+        let area = map.area;
+        let (w, h) = (area.w.round() as u32, area.h.round() as u32);
+        if w == 0 || h == 0 {
+            return;
+        }
+        let colorized = image::imageops::resize(&map.colorized(), w, h, FilterType::Triangle);
+        let alpha = self.settings.depth_opacity;
+        let (x0, y0) = (area.x.round() as u32, area.y.round() as u32);
+        for (x, y, color) in colorized.enumerate_pixels() {
+            let Some(pixel) = buf.get_pixel_mut_checked(x0 + x, y0 + y) else {
+                continue;
+            };
+            for (c, color) in pixel.0.iter_mut().zip(color.0) {
+                *c = (*c as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+            }
+        }
+    }
+
+    /// Draws the strokes of the air canvas and the pen, filled while it
+    /// draws.
+    pub fn draw_canvas(&self, buf: &mut RgbImage, canvas: &AirCanvas) {
+        if !canvas.enabled {
+            return;
+        }
+        let radius = (canvas.width / 2.0).round() as i32;
+        for stroke in canvas.strokes() {
+            for pair in stroke.points.windows(2) {
+                wide_line(buf, pair[0], pair[1], canvas.width, stroke.color);
+            }
+            // Round ends and joints.
+            for &(x, y) in &stroke.points {
+                draw_filled_circle_mut(buf, (x as i32, y as i32), radius, Rgb(stroke.color));
+            }
+        }
+        if let Some(((x, y), color, drawing)) = canvas.pen() {
+            let center = (x as i32, y as i32);
+            if drawing {
+                draw_filled_circle_mut(buf, center, radius + 2, Rgb(color));
+            } else {
+                draw_hollow_circle_mut(buf, center, radius + 4, Rgb(color));
+            }
+        }
+    }
+
+    /// Draws the `id`th face of the frame with its eyes and gaze.
+    pub fn draw_face(&self, buf: &mut RgbImage, id: usize, face: &Face) {
+        let settings = self.settings;
+        let (box_color, label_color) = self.score_colors(face.score);
+        if settings.boxes {
+            self.colored_bbox(buf, face.bbox, box_color);
+        }
+        for eye in &face.eyes {
+            if settings.skeleton {
+                for (x, y) in eye.eyelids {
+                    draw_filled_circle_mut(
+                        buf,
+                        (x as i32, y as i32),
+                        1 + settings.thickness as i32 / 2,
+                        Rgb(settings.skeleton_color),
+                    );
+                }
+            }
+            let (ix, iy) = eye.iris;
+            draw_hollow_circle_mut(
+                buf,
+                (ix as i32, iy as i32),
+                eye.iris_radius.round() as i32,
+                Rgb(settings.label_color),
+            );
+            let length = face.bbox.w * GAZE_RAY_SCALE;
+            let end = (ix + eye.gaze.0 * length, iy + eye.gaze.1 * length);
+            self.line(buf, eye.iris, end, settings.label_color);
+        }
+        self.labeled_caption(buf, face.bbox, None, id, Some(face.score), label_color);
+    }
+
+    /// Draws the `id`th face mesh of the frame, with the points its
+    /// expression metrics come from joined up and the metrics as caption.
+    pub fn draw_mesh(&self, buf: &mut RgbImage, id: usize, mesh: &FaceMesh) {
+        let settings = self.settings;
+        if settings.boxes {
+            self.bbox(buf, mesh.bbox());
+        }
+        let point = |i: usize| (mesh.landmarks[i].0, mesh.landmarks[i].1);
+        if settings.skeleton {
+            for (x, y, _) in mesh.landmarks {
+                let (x, y) = (x as i32, y as i32);
+                if x >= 0 && y >= 0 && x < buf.width() as i32 && y < buf.height() as i32 {
+                    buf.put_pixel(x as u32, y as u32, Rgb(settings.skeleton_color));
+                }
+            }
+        }
+        let closed = mesh.expression.closed_eyes();
+        for (eye, closed) in EYE_LANDMARKS.iter().zip(closed) {
+            let color = if closed {
+                settings.box_color
+            } else {
+                settings.label_color
+            };
+            for (i, &from) in eye.iter().enumerate() {
+                self.line(buf, point(from), point(eye[(i + 1) % eye.len()]), color);
+            }
+        }
+        let [upper, lower, left, right] = MOUTH_LANDMARKS;
+        for (from, to) in [(left, upper), (upper, right), (right, lower), (lower, left)] {
+            self.line(buf, point(from), point(to), settings.label_color);
+        }
+        let e = mesh.expression;
+        let mut label = format!(
+            "EAR {:.2} {:.2} mouth {:.2}",
+            e.eye_aspect_ratio[0], e.eye_aspect_ratio[1], e.mouth_openness
+        );
+        if e.blinking() {
+            label.push_str(" blink");
+        }
+        self.labeled_caption(
+            buf,
+            mesh.bbox(),
+            Some(&label),
+            id,
+            Some(mesh.presence),
+            settings.label_color,
+        );
+    }
+
+    /// Draws the `id`th object of the frame in its class color. Its box and
+    /// class name are always drawn, they are the whole detection.
+    pub fn draw_object(&self, buf: &mut RgbImage, id: usize, object: &Object, names: &[String]) {
+        let color = if self.settings.confidence_colors {
+            confidence_color(object.score)
+        } else {
+            class_color(object.class)
+        };
+        self.colored_bbox(buf, object.bbox, color);
+        let name = match names.get(object.class) {
+            Some(name) => name.clone(),
+            None => object.class.to_string(),
+        };
+        self.labeled_caption(buf, object.bbox, Some(&name), id, Some(object.score), color);
+    }
+
+    /// Draws detections below their thresholds as dashed boxes with their
+    /// scores, so they can be told apart from those that count.
+    pub fn draw_weak(&self, buf: &mut RgbImage, weak: &WeakDetections, names: &[String]) {
+        let mut draw = |bbox: BBox, label: Option<&str>, score: f32| {
+            let (color, _) = self.score_colors(score);
+            self.dashed_bbox(buf, bbox, color);
+            let caption = match label {
+                Some(label) => format!("{} {:.2}", label, score),
+                None => format!("{:.2}", score),
+            };
+            let y = bbox.y - self.settings.label_height;
+            self.colored_text(buf, (bbox.x, y.max(0.0)), &caption, color);
+        };
+        for palm in &weak.palms {
+            draw(palm.bbox, None, palm.score);
+        }
+        for object in &weak.objects {
+            draw(
+                object.bbox,
+                names.get(object.class).map(String::as_str),
+                object.score,
+            );
+        }
+        for face in &weak.faces {
+            draw(face.bbox, None, face.score);
+        }
+    }
+
+    /// Draws everything detected in a frame, hands over faces over objects
+    /// over poses over palms.
+    pub fn draw_detections(&self, buf: &mut RgbImage, detections: &Detections, names: &[String]) {
+        if let Some(map) = &detections.depth {
+            self.draw_depth(buf, map);
+        }
+        self.draw_weak(buf, &detections.weak, names);
+        for (id, palm) in detections.palms.iter().enumerate() {
+            self.draw_palm(buf, id, palm);
+        }
+        for palm in &detections.compared {
+            self.draw_compared_palm(buf, palm);
+        }
+        for (id, pose) in detections.poses.iter().enumerate() {
+            self.draw_pose(buf, id, pose);
+        }
+        for (id, object) in detections.objects.iter().enumerate() {
+            self.draw_object(buf, id, object, names);
+        }
+        for (id, face) in detections.faces.iter().enumerate() {
+            self.draw_face(buf, id, face);
+        }
+        for (id, mesh) in detections.meshes.iter().enumerate() {
+            self.draw_mesh(buf, id, mesh);
+        }
+        for (id, hand) in detections.hands.iter().enumerate() {
+            self.draw_hand(buf, id, hand);
+        }
+        for (plugin, found) in &detections.plugins {
+            plugin.draw(self, buf, found);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let mut settings = self.settings;
+        ui.checkbox(&mut settings.boxes, "Bounding boxes");
+        ui.checkbox(&mut settings.tip_indices, "Tip indices");
+        ui.checkbox(&mut settings.scores, "Scores");
+        ui.checkbox(&mut settings.confidence_colors, "Color by confidence");
+        ui.checkbox(&mut settings.skeleton, "Skeleton");
+        ui.checkbox(&mut settings.ids, "Ids");
+        ui.checkbox(&mut settings.hand_rois, "Hand crops");
+        ui.checkbox(&mut settings.handedness, "Left/right hand");
+        ui.checkbox(&mut settings.trails, "Trails");
+        ui.add_enabled(
+            settings.trails,
+            egui::Slider::new(&mut settings.trail_seconds, 0.2..=10.0).text("Trail length (s)"),
+        );
+        ui.checkbox(&mut settings.heatmap, "Heatmap");
+        egui::ComboBox::from_label("Depth map")
+            .selected_text(settings.depth_display.name())
+            .show_ui(ui, |ui| {
+                for display in DepthDisplay::ALL {
+                    ui.selectable_value(&mut settings.depth_display, display, display.name());
+                }
+            });
+        ui.add_enabled(
+            settings.depth_display == DepthDisplay::Overlay,
+            egui::Slider::new(&mut settings.depth_opacity, 0.0..=1.0).text("Depth opacity"),
+        );
+        ui.add(egui::Slider::new(&mut settings.label_height, 8.0..=64.0).text("Label size"));
+        ui.add(egui::Slider::new(&mut settings.thickness, 1.0..=8.0).text("Line thickness"));
+        for (color, name) in [
+            (&mut settings.label_color, "Labels"),
+            (&mut settings.box_color, "Boxes"),
+            (&mut settings.skeleton_color, "Skeleton"),
+            (&mut settings.compare_color, "Compared model"),
+        ] {
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(color);
+                ui.label(name);
+            });
+        }
+        if ui.button("Reset").clicked() {
+            settings = Overlay::default();
+        }
+        if settings != self.settings {
+            self.set_settings(settings);
+        }
+    }
+}
+
+/// Draws a line `width` pixels wide as a filled quad.
+fn wide_line(buf: &mut RgbImage, from: (f32, f32), to: (f32, f32), width: f32, color: [u8; 3]) {
+    let half = width / 2.0;
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if half <= 0.5 || len < 1.0 {
+        draw_line_segment_mut(buf, from, to, Rgb(color));
+        return;
+    }
+    let (nx, ny) = (-dy / len * half, dx / len * half);
+    let corners = [
+        (from.0 + nx, from.1 + ny),
+        (to.0 + nx, to.1 + ny),
+        (to.0 - nx, to.1 - ny),
+        (from.0 - nx, from.1 - ny),
+    ]
+    .map(|(x, y)| Point::new(x.round() as i32, y.round() as i32));
+    if corners[0] == corners[3] {
+        draw_line_segment_mut(buf, from, to, Rgb(color));
+    } else {
+        draw_polygon_mut(buf, &corners, Rgb(color));
+    }
+}
+
+/// Mixes `color` into a disc of `buf` with opacity `alpha`.
+fn blend_disc(buf: &mut RgbImage, (cx, cy): (f32, f32), radius: f32, color: [u8; 3], alpha: f32) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let (w, h) = (buf.width() as i64, buf.height() as i64);
+    let r = radius.ceil() as i64;
+    let (px, py) = (cx.round() as i64, cy.round() as i64);
+    for y in (py - r).max(0)..(py + r + 1).min(h) {
+        for x in (px - r).max(0)..(px + r + 1).min(w) {
+            if ((x - px) as f32).hypot((y - py) as f32) > radius {
+                continue;
+            }
+            let pixel = buf.get_pixel_mut(x as u32, y as u32);
+            for (c, color) in pixel.0.iter_mut().zip(color) {
+                *c = (*c as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+            }
+        }
+    }
+}
+
+/// Pieces of the line from `from` to `to` to draw it dashed, `dash` long
+/// with gaps as long between them.
+fn dashes(from: (f32, f32), to: (f32, f32), dash: f32) -> Vec<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let len = dx.hypot(dy);
+    let at = |d: f32| (from.0 + dx * d / len, from.1 + dy * d / len);
+    (0..)
+        .map(|i| i as f32 * 2.0 * dash)
+        .take_while(|&start| start < len)
+        .map(|start| (at(start), at((start + dash).min(len))))
+        .collect()
+}
+
+/// Red for a score of 0 through yellow to green for 1.
+fn confidence_color(score: f32) -> [u8; 3] {
+    let hue = score.clamp(0.0, 1.0) / 3.0;
+    egui::ecolor::Hsva::new(hue, 1.0, 1.0, 1.0).to_srgb()
+}
+
+/// Distinct, stable color per class, spreading hues by the golden ratio.
+fn class_color(class: usize) -> [u8; 3] {
+    let hue = (class as f32 * 0.618_034).fract();
+    egui::ecolor::Hsva::new(hue, 0.85, 1.0, 1.0).to_srgb()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dashes_alternate_along_the_line() {
+        assert_eq!(
+            dashes((0.0, 0.0), (25.0, 0.0), 6.0),
+            [
+                ((0.0, 0.0), (6.0, 0.0)),
+                ((12.0, 0.0), (18.0, 0.0)),
+                ((24.0, 0.0), (25.0, 0.0)),
+            ]
+        );
+        assert_eq!(dashes((1.0, 1.0), (1.0, 1.0), 6.0), []);
+    }
+
+    #[test]
+    fn confidence_goes_from_red_to_green() {
+        assert_eq!(confidence_color(0.0), [255, 0, 0]);
+        assert_eq!(confidence_color(0.5), [255, 255, 0]);
+        assert_eq!(confidence_color(1.0), [0, 255, 0]);
+        assert_eq!(confidence_color(2.0), [0, 255, 0]);
+    }
+}
@@ -0,0 +1,372 @@
+use image::RgbImage;
+use ndarray::{Array4, ArrayD, ArrayView3};
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, path::Path};
+
+use crate::{
+    anchors::{Anchor, AnchorConfig},
+    bbox::{BBox, Detection, RotatedRect},
+    inference::{self, Session},
+    math::sigmoid,
+    nms::{suppress, weighted_bbox, weighted_mean, NmsMode},
+    preprocess::{Normalization, Resizer},
+};
+use tracing::{debug_span, error};
+
+/// Palm keypoints. The detector doesn't output fingertips.
+pub const WRIST: usize = 0;
+pub const INDEX_BASE: usize = 1;
+pub const MIDDLE_BASE: usize = 2;
+pub const PINKY_BASE: usize = 4;
+pub const THUMB: usize = 6;
+pub const KEYPOINT_NAMES: [&str; 7] = [
+    "wrist",
+    "index base",
+    "middle base",
+    "ring base",
+    "pinky base",
+    "thumb base",
+    "thumb",
+];
+
+/// Hand crop side relative to the palm box, and how far the crop is moved
+/// towards the fingers, in palm box heights. MediaPipe's values.
+const HAND_ROI_SCALE: f32 = 2.6;
+const HAND_ROI_SHIFT: f32 = 0.5;
+
+/// Which of the person's hands a palm is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Handedness {
+    #[serde(rename = "L")]
+    Left,
+    #[serde(rename = "R")]
+    Right,
+}
+
+impl Handedness {
+    /// Guesses the hand from the side the index finger is on, seen from the
+    /// wrist towards the fingers. Assumes an unmirrored frame with the palm
+    /// facing the camera; the back of a hand reads as the other hand.
+    pub fn of(tips: &[(f32, f32); 7]) -> Self {
+        let (wx, wy) = tips[WRIST];
+        let (mx, my) = tips[MIDDLE_BASE];
+        let (ix, iy) = tips[INDEX_BASE];
+        let (px, py) = tips[PINKY_BASE];
+        // Facing the camera with fingers up, a right thumb is on the image's
+        // right, so pinky-to-index turns clockwise from wrist-to-middle.
+        let cross = (mx - wx) * (iy - py) - (my - wy) * (ix - px);
+        if cross >= 0.0 {
+            Self::Right
+        } else {
+            Self::Left
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Left => "L",
+            Self::Right => "R",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Palm {
+    pub bbox: BBox,
+    pub tips: [(f32, f32); 7],
+    pub score: f32,
+    pub handedness: Handedness,
+}
+
+impl Detection for Palm {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn with_score(self, score: f32) -> Self {
+        Self { score, ..self }
+    }
+
+    fn blend(cluster: &[Self]) -> Self {
+        let tips = std::array::from_fn(|j| weighted_mean(cluster, |palm| palm.tips[j]));
+        Self {
+            bbox: weighted_bbox(cluster),
+            tips,
+            score: cluster[0].score,
+            handedness: Handedness::of(&tips),
+        }
+    }
+}
+
+impl Palm {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            bbox: self.bbox.shift(x, y),
+            tips: self.tips.map(|(xt, yt)| (xt + x, yt + y)),
+            ..*self
+        }
+    }
+
+    /// Thumb keypoint to index finger base distance relative to palm length.
+    /// The palm has no fingertip keypoints, so this only shrinks a little
+    /// when pinching; [`crate::hand::Hand::pinch_ratio`] measures a real
+    /// pinch.
+    pub fn pinch_ratio(&self) -> f32 {
+        let dist = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).hypot(a.1 - b.1);
+        let palm_length = dist(self.tips[WRIST], self.tips[MIDDLE_BASE]).max(1.0);
+        dist(self.tips[THUMB], self.tips[INDEX_BASE]) / palm_length
+    }
+
+    /// Clockwise angle in radians by which the wrist-to-middle-finger
+    /// direction is turned away from pointing straight up, in `-π..π`.
+    pub fn rotation(&self) -> f32 {
+        let (wx, wy) = self.tips[WRIST];
+        let (mx, my) = self.tips[MIDDLE_BASE];
+        let angle = (mx - wx).atan2(wy - my);
+        // atan2 already lands in -π..=π; fold π over so the range is half open.
+        if angle >= PI {
+            angle - 2.0 * PI
+        } else {
+            angle
+        }
+    }
+
+    /// Square crop containing the whole hand, turned so the fingers point
+    /// up. This is what a landmark model expects as input.
+    pub fn hand_roi(&self) -> RotatedRect {
+        let angle = self.rotation();
+        let (sin, cos) = angle.sin_cos();
+        let shift = self.bbox.h * HAND_ROI_SHIFT;
+        let side = self.bbox.w.max(self.bbox.h) * HAND_ROI_SCALE;
+        RotatedRect {
+            cx: self.bbox.x + self.bbox.w / 2.0 + shift * sin,
+            cy: self.bbox.y + self.bbox.h / 2.0 - shift * cos,
+            w: side,
+            h: side,
+            angle,
+        }
+    }
+
+    fn scale(&self, scale_x: f32, scale_y: f32) -> Self {
+        Self {
+            bbox: self.bbox.scale(scale_x, scale_y),
+            tips: self.tips.map(|(xt, yt)| (xt * scale_x, yt * scale_y)),
+            ..*self
+        }
+    }
+}
+
+/// Palm detection without the model: its input tensor, and the palms in its
+/// outputs. [`PalmDetector`] runs the model in between; the browser app
+/// runs it asynchronously.
+pub struct PalmDecoder {
+    anchor_config: AnchorConfig,
+    anchors: Vec<Anchor>,
+    resizer: Resizer,
+}
+
+impl Default for PalmDecoder {
+    fn default() -> Self {
+        let anchor_config = AnchorConfig::palm_detection();
+        Self {
+            anchors: anchor_config.generate(),
+            resizer: Resizer::new(anchor_config.input_width, anchor_config.input_height),
+            anchor_config,
+        }
+    }
+}
+
+impl PalmDecoder {
+    pub fn input(&mut self, img: &RgbImage) -> Array4<f32> {
+        self.resizer.resize(img, Normalization::UNIT)
+    }
+
+    /// Palms of an image of `size` pixels from the model `outputs` for it,
+    /// or an error if the outputs aren't a palm model's.
+    pub fn palms(
+        &self,
+        outputs: &[ArrayD<f32>],
+        size: (u32, u32),
+        thresholds: (f32, f32),
+        nms: NmsMode,
+    ) -> Result<Vec<Palm>, String> {
+        let n = self.anchors.len();
+        let output = |len: usize| {
+            let output = outputs.iter().find(|o| o.len() == len).ok_or_else(|| {
+                let lens = outputs
+                    .iter()
+                    .map(|o| o.len().to_string())
+                    .collect::<Vec<_>>();
+                format!(
+                    "palm model has no output of {} values, only {}",
+                    len,
+                    lens.join(", ")
+                )
+            })?;
+            let values = output
+                .as_slice()
+                .ok_or("palm model output isn't contiguous")?;
+            ArrayView3::from_shape((1, n, len / n), values).map_err(|e| e.to_string())
+        };
+        Ok(postprocess(
+            output(n * 18)?,
+            output(n)?,
+            &self.anchors,
+            (
+                self.anchor_config.input_width,
+                self.anchor_config.input_height,
+            ),
+            size,
+            thresholds,
+            nms,
+        ))
+    }
+}
+
+pub struct PalmDetector {
+    session: Session,
+    decoder: PalmDecoder,
+}
+
+impl PalmDetector {
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, String> {
+        inference::create_session(model_path).map(Self::from_session)
+    }
+
+    pub fn from_session(session: Session) -> Self {
+        Self {
+            session,
+            decoder: PalmDecoder::default(),
+        }
+    }
+
+    pub fn detect(
+        &mut self,
+        img: &RgbImage,
+        score_threshold: f32,
+        iou_threshold: f32,
+        nms: NmsMode,
+    ) -> Vec<Palm> {
+        let input = self.decoder.input(img);
+        let outputs = inference::run(&*self.session, input);
+        let thresholds = (score_threshold, iou_threshold);
+        self.decoder
+            .palms(&outputs, img.dimensions(), thresholds, nms)
+            .unwrap_or_else(|e| {
+                error!("Failed to decode palms: {}", e);
+                vec![]
+            })
+    }
+}
+
+/// Turns raw model outputs into the palms of a `width`×`height` image:
+/// decodes every anchor, keeps confident palms, suppressing overlaps as
+/// `nms` says, and scales them from model input to image pixels.
+pub fn postprocess(
+    regressors: ArrayView3<f32>,
+    scores: ArrayView3<f32>,
+    anchors: &[Anchor],
+    (input_w, input_h): (usize, usize),
+    (width, height): (u32, u32),
+    (score_threshold, iou_threshold): (f32, f32),
+    nms: NmsMode,
+) -> Vec<Palm> {
+    let _span = debug_span!("postprocess").entered();
+    let scale_x = width as f32 / input_w as f32;
+    let scale_y = height as f32 / input_h as f32;
+    let palms = decode(regressors, scores, anchors, (input_w, input_h));
+    suppress(palms, nms, score_threshold, iou_threshold)
+        .iter()
+        .map(|palm| palm.scale(scale_x, scale_y))
+        .collect()
+}
+
+/// Turns raw model outputs into one palm per anchor, in model input pixels.
+/// Keypoints are relative to the decoded box center, and the score logits
+/// become probabilities.
+pub fn decode(
+    regressors: ArrayView3<f32>,
+    scores: ArrayView3<f32>,
+    anchors: &[Anchor],
+    (input_w, input_h): (usize, usize),
+) -> Vec<Palm> {
+    anchors
+        .iter()
+        .enumerate()
+        .map(|(i, anchor)| {
+            let x = regressors[(0, i, 0)] * anchor.w + anchor.x_center * input_w as f32;
+            let y = regressors[(0, i, 1)] * anchor.h + anchor.y_center * input_h as f32;
+            let w = regressors[(0, i, 2)] * anchor.w;
+            let h = regressors[(0, i, 3)] * anchor.h;
+            let tips = std::array::from_fn(|j| {
+                (
+                    regressors[(0, i, 4 + j * 2)] + x,
+                    regressors[(0, i, 4 + j * 2 + 1)] + y,
+                )
+            });
+            Palm {
+                bbox: BBox {
+                    x: x - w / 2.0,
+                    y: y - h / 2.0,
+                    w,
+                    h,
+                },
+                tips,
+                score: sigmoid(scores[(0, i, 0)]),
+                handedness: Handedness::of(&tips),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::palm;
+
+    /// Palm of size 20 at `(50, 50)` whose fingers point along `(dx, dy)`.
+    fn pointing(dx: f32, dy: f32) -> Palm {
+        palm(50.0, 50.0)
+            .tip(WRIST, (50.0 - dx * 10.0, 50.0 - dy * 10.0))
+            .tip(MIDDLE_BASE, (50.0 + dx * 10.0, 50.0 + dy * 10.0))
+            .build()
+    }
+
+    #[test]
+    fn rotation() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+        assert!(close(pointing(0.0, -1.0).rotation(), 0.0));
+        assert!(close(pointing(1.0, 0.0).rotation(), PI / 2.0));
+        assert!(close(pointing(-1.0, 0.0).rotation(), -PI / 2.0));
+        assert!(close(pointing(0.0, 1.0).rotation(), -PI));
+    }
+
+    #[test]
+    fn hand_roi_extends_towards_fingers() {
+        let roi = pointing(0.0, -1.0).hand_roi();
+        assert!((roi.cx - 50.0).abs() < 1e-4 && (roi.cy - 40.0).abs() < 1e-4);
+        assert_eq!((roi.w, roi.h), (52.0, 52.0));
+
+        let roi = pointing(1.0, 0.0).hand_roi();
+        assert!((roi.cx - 60.0).abs() < 1e-4 && (roi.cy - 50.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn handedness_follows_index_side() {
+        // Fingers up, palm towards the camera: a right index is on the right.
+        let mut tips = pointing(0.0, -1.0).tips;
+        tips[INDEX_BASE] = (55.0, 42.0);
+        tips[PINKY_BASE] = (44.0, 44.0);
+        assert_eq!(Handedness::of(&tips), Handedness::Right);
+        let mirrored = tips.map(|(x, y)| (100.0 - x, y));
+        assert_eq!(Handedness::of(&mirrored), Handedness::Left);
+        // Turning the hand upside down doesn't change which hand it is.
+        let turned = tips.map(|(x, y)| (100.0 - x, 100.0 - y));
+        assert_eq!(Handedness::of(&turned), Handedness::Right);
+        assert_eq!(serde_json::to_string(&Handedness::Left).unwrap(), "\"L\"");
+    }
+}
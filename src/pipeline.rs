@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    depth::DepthEstimator,
+    face_mesh::FaceMesher,
+    hand::HandLandmarker,
+    inference::{self, Provider},
+    iris::IrisTracker,
+    models::{
+        COCO_LABELS, FACE_DETECTION_SHORT_RANGE, FACE_LANDMARK, HAND_LANDMARK_FULL, IRIS_LANDMARK,
+        MIDAS_SMALL, PALM_DETECTION_LITE, PALM_DETECTION_LITE_FP16, PALM_DETECTION_LITE_INT8,
+        POSE_DETECTION, POSE_LANDMARKS_FULL, YOLOV8N,
+    },
+    palm::PalmDetector,
+    plugin::{self, PluginId},
+    pose::PoseEstimator,
+    yolo::YoloDetector,
+};
+
+/// Numeric precision of the palm model's weights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Precision {
+    Fp32,
+    Fp16,
+    Int8,
+}
+
+impl Precision {
+    pub const ALL: [Self; 3] = [Self::Fp32, Self::Fp16, Self::Int8];
+    /// Precisions the fp32 palm model can be compared with.
+    pub const COMPARED: [Self; 2] = [Self::Fp16, Self::Int8];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fp32 => "fp32",
+            Self::Fp16 => "fp16",
+            Self::Int8 => "int8",
+        }
+    }
+
+    pub fn palm_model(self) -> &'static str {
+        match self {
+            Self::Fp32 => PALM_DETECTION_LITE,
+            Self::Fp16 => PALM_DETECTION_LITE_FP16,
+            Self::Int8 => PALM_DETECTION_LITE_INT8,
+        }
+    }
+}
+
+/// Which model chain runs on the camera frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PipelineKind {
+    Palm(Precision),
+    Pose,
+    Yolo,
+    /// The fp32 palm model next to one of the given precision, which is
+    /// never fp32 itself.
+    ComparePalm(Precision),
+    Iris,
+    /// Palm detection followed by 21 landmarks with depth per hand.
+    Hands(Precision),
+    /// Face detection followed by a 468-point mesh and expression metrics
+    /// per face.
+    FaceMesh,
+    /// Relative depth of every pixel.
+    Depth,
+    /// A registered [`plugin::Pipeline`].
+    Plugin(PluginId),
+}
+
+impl PipelineKind {
+    /// Every kind, with palm models of `precision`. The comparison uses
+    /// fp16 when `precision` is fp32, as comparing fp32 with itself tells
+    /// nothing.
+    pub fn all(precision: Precision) -> [Self; 8] {
+        [
+            Self::Palm(precision),
+            Self::Pose,
+            Self::Yolo,
+            Self::ComparePalm(match precision {
+                Precision::Fp32 => Precision::Fp16,
+                precision => precision,
+            }),
+            Self::Iris,
+            Self::Hands(precision),
+            Self::FaceMesh,
+            Self::Depth,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Palm(_) => "Palm detection",
+            Self::Pose => "Pose estimation",
+            Self::Yolo => "Object detection (YOLO)",
+            Self::ComparePalm(_) => "Palm model comparison",
+            Self::Iris => "Iris and gaze",
+            Self::Hands(_) => "Hand landmarks",
+            Self::FaceMesh => "Face mesh",
+            Self::Depth => "Depth estimation",
+            Self::Plugin(id) => id.name(),
+        }
+    }
+
+    pub fn precision(self) -> Option<Precision> {
+        match self {
+            Self::Palm(precision) | Self::ComparePalm(precision) | Self::Hands(precision) => {
+                Some(precision)
+            }
+            Self::Pose
+            | Self::Yolo
+            | Self::Iris
+            | Self::FaceMesh
+            | Self::Depth
+            | Self::Plugin(_) => None,
+        }
+    }
+
+    /// The kind with palm models of `precision`. Comparisons keep theirs
+    /// when `precision` is fp32.
+    pub fn with_precision(self, precision: Precision) -> Self {
+        match self {
+            Self::Palm(_) => Self::Palm(precision),
+            Self::ComparePalm(_) if precision == Precision::Fp32 => self,
+            Self::ComparePalm(_) => Self::ComparePalm(precision),
+            Self::Hands(_) => Self::Hands(precision),
+            Self::Pose
+            | Self::Yolo
+            | Self::Iris
+            | Self::FaceMesh
+            | Self::Depth
+            | Self::Plugin(_) => self,
+        }
+    }
+
+    /// Model files the pipeline is built from, in [`Pipeline::load`] order.
+    pub fn models(self) -> Vec<&'static str> {
+        match self {
+            Self::Palm(precision) => vec![precision.palm_model()],
+            Self::Pose => vec![POSE_DETECTION, POSE_LANDMARKS_FULL],
+            Self::Yolo => vec![YOLOV8N, COCO_LABELS],
+            Self::ComparePalm(precision) => vec![PALM_DETECTION_LITE, precision.palm_model()],
+            Self::Iris => vec![FACE_DETECTION_SHORT_RANGE, IRIS_LANDMARK],
+            Self::Hands(precision) => vec![precision.palm_model(), HAND_LANDMARK_FULL],
+            Self::FaceMesh => vec![FACE_DETECTION_SHORT_RANGE, FACE_LANDMARK],
+            Self::Depth => vec![MIDAS_SMALL],
+            Self::Plugin(id) => id.models(),
+        }
+    }
+}
+
+/// A pipeline to run, and on which provider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineSpec {
+    pub kind: PipelineKind,
+    /// Provider of this pipeline's models, if not the default one.
+    #[serde(default)]
+    pub provider: Option<Provider>,
+}
+
+impl PipelineSpec {
+    pub fn new(kind: PipelineKind) -> Self {
+        Self {
+            kind,
+            provider: None,
+        }
+    }
+
+    /// Kind, precision and provider, e.g. "Palm detection fp16 on CUDA".
+    pub fn label(&self) -> String {
+        let precision = self
+            .kind
+            .precision()
+            .map_or(String::new(), |p| format!(" {}", p.name()));
+        let provider = self.provider.map_or("default provider", Provider::name);
+        format!("{}{} on {}", self.kind.name(), precision, provider)
+    }
+
+    /// Builds the pipeline on its provider from the paths of
+    /// [`PipelineKind::models`].
+    pub fn load(self, paths: &[PathBuf]) -> Result<Pipeline, String> {
+        self.load_sharing(paths, &mut PalmDetectors::default())
+    }
+
+    /// Like [`Self::load`], taking palm detectors from `palms` rather than
+    /// loading them again.
+    pub fn load_sharing(
+        self,
+        paths: &[PathBuf],
+        palms: &mut PalmDetectors,
+    ) -> Result<Pipeline, String> {
+        let provider = self.provider.or_else(inference::default_provider);
+        inference::with_provider(self.provider, || {
+            Pipeline::load(self.kind, paths, &mut |path| palms.load(path, provider))
+        })
+    }
+}
+
+/// Builds `specs` from the paths of their [`PipelineKind::models`]. Each
+/// palm model is loaded once per provider, and shared by the pipelines
+/// using it.
+pub fn load_all(
+    specs: &[PipelineSpec],
+    paths: &[Vec<PathBuf>],
+) -> Result<Vec<(PipelineSpec, Pipeline)>, String> {
+    let mut palms = PalmDetectors::default();
+    specs
+        .iter()
+        .zip(paths)
+        .map(|(spec, paths)| {
+            spec.load_sharing(paths, &mut palms)
+                .map(|pipeline| (*spec, pipeline))
+                .map_err(|e| format!("{}: {}", spec.kind.name(), e))
+        })
+        .collect()
+}
+
+/// A palm detector that several pipelines may run from one node.
+pub type SharedPalmDetector = Arc<Mutex<PalmDetector>>;
+
+/// Palm detectors loaded so far, by model file and provider.
+#[derive(Default)]
+pub struct PalmDetectors(Vec<((PathBuf, Option<Provider>), SharedPalmDetector)>);
+
+impl PalmDetectors {
+    /// The detector of the model at `path` on `provider`, loading it unless
+    /// it was before.
+    fn load(
+        &mut self,
+        path: &Path,
+        provider: Option<Provider>,
+    ) -> Result<SharedPalmDetector, String> {
+        let key = (path.to_owned(), provider);
+        if let Some((_, detector)) = self.0.iter().find(|(k, _)| *k == key) {
+            return Ok(detector.clone());
+        }
+        let detector = Arc::new(Mutex::new(PalmDetector::new(path)?));
+        self.0.push((key, detector.clone()));
+        Ok(detector)
+    }
+}
+
+pub enum Pipeline {
+    Palm(SharedPalmDetector, Precision),
+    Pose(PoseEstimator),
+    Yolo(YoloDetector),
+    ComparePalm {
+        reference: SharedPalmDetector,
+        candidate: SharedPalmDetector,
+        precision: Precision,
+    },
+    Iris(IrisTracker),
+    Hands {
+        detector: SharedPalmDetector,
+        landmarker: HandLandmarker,
+        precision: Precision,
+    },
+    FaceMesh(FaceMesher),
+    Depth(DepthEstimator),
+    Plugin(PluginId, Box<dyn plugin::Pipeline>),
+}
+
+impl Pipeline {
+    /// Builds the pipeline from the paths of [`PipelineKind::models`],
+    /// getting palm detectors from `palm`.
+    pub fn load(
+        kind: PipelineKind,
+        paths: &[PathBuf],
+        palm: &mut dyn FnMut(&Path) -> Result<SharedPalmDetector, String>,
+    ) -> Result<Self, String> {
+        Ok(match kind {
+            PipelineKind::Palm(precision) => Self::Palm(palm(&paths[0])?, precision),
+            PipelineKind::Pose => Self::Pose(PoseEstimator::new(&paths[0], &paths[1])?),
+            PipelineKind::Yolo => Self::Yolo(YoloDetector::new(&paths[0], &paths[1])?),
+            PipelineKind::ComparePalm(Precision::Fp32) => {
+                return Err("can't compare the fp32 palm model with itself".to_owned())
+            }
+            PipelineKind::ComparePalm(precision) => Self::ComparePalm {
+                reference: palm(&paths[0])?,
+                candidate: palm(&paths[1])?,
+                precision,
+            },
+            PipelineKind::Iris => Self::Iris(IrisTracker::new(&paths[0], &paths[1])?),
+            PipelineKind::Hands(precision) => Self::Hands {
+                detector: palm(&paths[0])?,
+                landmarker: HandLandmarker::new(&paths[1])?,
+                precision,
+            },
+            PipelineKind::FaceMesh => Self::FaceMesh(FaceMesher::new(&paths[0], &paths[1])?),
+            PipelineKind::Depth => Self::Depth(DepthEstimator::new(&paths[0])?),
+            PipelineKind::Plugin(id) => Self::Plugin(id, id.load(paths)?),
+        })
+    }
+
+    pub fn kind(&self) -> PipelineKind {
+        match self {
+            Self::Palm(_, precision) => PipelineKind::Palm(*precision),
+            Self::Pose(_) => PipelineKind::Pose,
+            Self::Yolo(_) => PipelineKind::Yolo,
+            Self::ComparePalm { precision, .. } => PipelineKind::ComparePalm(*precision),
+            Self::Iris(_) => PipelineKind::Iris,
+            Self::Hands { precision, .. } => PipelineKind::Hands(*precision),
+            Self::FaceMesh(_) => PipelineKind::FaceMesh,
+            Self::Depth(_) => PipelineKind::Depth,
+            Self::Plugin(id, _) => PipelineKind::Plugin(*id),
+        }
+    }
+}
+
+/// Checkboxes for the pipelines to run, the palm model precision and the
+/// provider of each pipeline.
+pub fn specs_ui(ui: &mut egui::Ui, pipelines: &mut Vec<PipelineSpec>) {
+    let mut precision = pipelines
+        .iter()
+        .find_map(|spec| spec.kind.precision())
+        .unwrap_or(Precision::Fp32);
+    let plugins = plugin::registered().into_iter().map(PipelineKind::Plugin);
+    for kind in PipelineKind::all(precision).into_iter().chain(plugins) {
+        let position = pipelines.iter().position(|spec| spec.kind == kind);
+        let mut checked = position.is_some();
+        if ui.checkbox(&mut checked, kind.name()).changed() {
+            match position {
+                Some(i) => drop(pipelines.remove(i)),
+                None => {
+                    // Both palm pipelines fill in the same palms, so only
+                    // one of them runs.
+                    if kind.precision().is_some() {
+                        pipelines.retain(|spec| spec.kind.precision().is_none());
+                    }
+                    pipelines.push(PipelineSpec::new(kind));
+                }
+            }
+        }
+    }
+    if pipelines.iter().any(|spec| spec.kind.precision().is_some()) {
+        let comparing = pipelines
+            .iter()
+            .any(|spec| matches!(spec.kind, PipelineKind::ComparePalm(_)));
+        let precisions = if comparing {
+            &Precision::COMPARED[..]
+        } else {
+            &Precision::ALL[..]
+        };
+        egui::ComboBox::from_label("Palm model precision")
+            .selected_text(precision.name())
+            .show_ui(ui, |ui| {
+                for &p in precisions {
+                    ui.selectable_value(&mut precision, p, p.name());
+                }
+            });
+        for spec in pipelines.iter_mut() {
+            spec.kind = spec.kind.with_precision(precision);
+        }
+    }
+    for spec in pipelines.iter_mut() {
+        egui::ComboBox::from_label(spec.kind.name())
+            .selected_text(spec.provider.map_or("Default", Provider::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut spec.provider, None, "Default");
+                for p in Provider::ALL {
+                    ui.selectable_value(&mut spec.provider, Some(p), p.name());
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fp32_is_never_compared_with_itself() {
+        let fp32 = PipelineKind::ComparePalm(Precision::Fp32);
+        for precision in Precision::ALL {
+            assert!(!PipelineKind::all(precision).contains(&fp32));
+        }
+        let int8 = PipelineKind::ComparePalm(Precision::Int8);
+        assert_eq!(int8.with_precision(Precision::Fp32), int8);
+        let load = Pipeline::load(fp32, &[], &mut |_| unreachable!());
+        assert!(load.is_err());
+    }
+}
@@ -0,0 +1,403 @@
+//! Pipelines added without touching the built-in ones. A plugin is a type
+//! implementing [`Pipeline`], registered once at startup with [`register`].
+//! From then on it can be ticked in the "Pipelines" section, its models are
+//! downloaded with the others, it runs on the worker next to the built-in
+//! pipelines and its detections are drawn, all without changes to the app.
+//!
+//! Plugins are compiled in, or built as shared libraries exporting
+//! [`ENTRY_POINT`] and loaded from the `plugins` directory at startup.
+//! Rust has no stable ABI, so such a library has to be built against the
+//! same version of this crate by the same compiler.
+
+use image::{imageops::FilterType, RgbImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "native")]
+use std::path::Path;
+use std::{path::PathBuf, sync::Mutex};
+use tracing::warn;
+#[cfg(feature = "native")]
+use tracing::{error, info};
+
+use crate::{bbox::BBox, models::ModelSpec, overlay::OverlayRenderer};
+
+/// What a plugin wants its frames to look like.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputSpec {
+    /// Size the frame, or its ROI, is stretched to before
+    /// [`Pipeline::process`]. `None` passes it as is.
+    pub size: Option<(u32, u32)>,
+}
+
+/// A detection of a plugin: a labeled box with optional keypoints.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PluginDetection {
+    pub bbox: BBox,
+    pub label: String,
+    pub score: f32,
+    pub keypoints: Vec<(f32, f32)>,
+}
+
+impl PluginDetection {
+    /// Scales the detection by `(sx, sy)`, then shifts it by `(dx, dy)`.
+    pub fn to_frame(&self, (sx, sy): (f32, f32), (dx, dy): (f32, f32)) -> Self {
+        let b = self.bbox;
+        Self {
+            bbox: BBox {
+                x: b.x * sx + dx,
+                y: b.y * sy + dy,
+                w: b.w * sx,
+                h: b.h * sy,
+            },
+            label: self.label.clone(),
+            score: self.score,
+            keypoints: self
+                .keypoints
+                .iter()
+                .map(|&(x, y)| (x * sx + dx, y * sy + dy))
+                .collect(),
+        }
+    }
+}
+
+/// A self-contained pipeline: its models, how to run it and how to draw
+/// what it finds.
+pub trait Pipeline: Send {
+    /// Name in the UI and in saved profiles. Must be unique.
+    fn name() -> &'static str
+    where
+        Self: Sized;
+
+    /// Models to download before [`Pipeline::load`].
+    fn models() -> Vec<ModelSpec>
+    where
+        Self: Sized,
+    {
+        vec![]
+    }
+
+    /// Builds the plugin from the paths of [`Pipeline::models`], in order.
+    fn load(paths: &[PathBuf]) -> Result<Self, String>
+    where
+        Self: Sized;
+
+    fn input(&self) -> InputSpec {
+        InputSpec::default()
+    }
+
+    /// Detections in `frame`, in its pixels.
+    fn process(&mut self, frame: &RgbImage) -> Vec<PluginDetection>;
+
+    /// Draws detections on the annotated frame. Boxes, keypoints and
+    /// captions by default.
+    fn draw(overlay: &OverlayRenderer, buf: &mut RgbImage, detections: &[PluginDetection])
+    where
+        Self: Sized,
+    {
+        for (id, detection) in detections.iter().enumerate() {
+            overlay.draw_plugin_detection(buf, id, detection);
+        }
+    }
+}
+
+/// Runs `plugin` on `img`, resized as it asks, with detections in `img`
+/// pixels.
+pub fn process(plugin: &mut dyn Pipeline, img: &RgbImage) -> Vec<PluginDetection> {
+    let Some((w, h)) = plugin.input().size.filter(|&size| size != img.dimensions()) else {
+        return plugin.process(img);
+    };
+    let resized = image::imageops::resize(img, w, h, FilterType::Triangle);
+    let scale = (
+        img.width() as f32 / w as f32,
+        img.height() as f32 / h as f32,
+    );
+    plugin
+        .process(&resized)
+        .iter()
+        .map(|detection| detection.to_frame(scale, (0.0, 0.0)))
+        .collect()
+}
+
+type Load = fn(&[PathBuf]) -> Result<Box<dyn Pipeline>, String>;
+type Draw = fn(&OverlayRenderer, &mut RgbImage, &[PluginDetection]);
+
+#[derive(Clone)]
+struct Registration {
+    name: &'static str,
+    models: Vec<ModelSpec>,
+    load: Load,
+    draw: Draw,
+}
+
+impl Registration {
+    fn of<P: Pipeline + 'static>() -> Self {
+        Self {
+            name: P::name(),
+            models: P::models(),
+            load: |paths| Ok(Box::new(P::load(paths)?)),
+            draw: P::draw,
+        }
+    }
+}
+
+static REGISTRY: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+
+fn add(registration: Registration) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.retain(|r| r.name != registration.name);
+    registry.push(registration);
+}
+
+/// Makes `P` available as a pipeline. Registering a name again replaces
+/// the earlier plugin.
+pub fn register<P: Pipeline + 'static>() {
+    add(Registration::of::<P>());
+}
+
+/// Name of the function a plugin library exports to register its
+/// pipelines, as
+/// `#[no_mangle] pub fn ai_playground_plugins(registrar: &mut Registrar)`.
+pub const ENTRY_POINT: &str = "ai_playground_plugins";
+
+/// Collects the pipelines of a plugin library for [`load_library`].
+#[derive(Default)]
+pub struct Registrar(Vec<Registration>);
+
+impl Registrar {
+    /// Makes `P` available as a pipeline, as [`register`] does.
+    pub fn register<P: Pipeline + 'static>(&mut self) {
+        self.0.push(Registration::of::<P>());
+    }
+}
+
+/// Libraries plugins were loaded from. They stay loaded, as the code and
+/// names of their plugins live in them.
+#[cfg(feature = "native")]
+static LIBRARIES: Mutex<Vec<libloading::Library>> = Mutex::new(Vec::new());
+
+/// Registers the pipelines of the plugin library at `path`, through its
+/// [`ENTRY_POINT`]. The library has to be built against this version of
+/// the crate by the same compiler, as nothing checks that their types
+/// agree.
+#[cfg(feature = "native")]
+pub fn load_library(path: &Path) -> Result<Vec<PluginId>, String> {
+    // SAFETY: the library's initializers run as it loads, and its entry
+    // point is trusted to have the signature of `ENTRY_POINT`.
+    let library = unsafe { libloading::Library::new(path) }.map_err(|e| e.to_string())?;
+    let mut registrar = Registrar::default();
+    {
+        // SAFETY: as above.
+        let entry = unsafe { library.get::<fn(&mut Registrar)>(ENTRY_POINT.as_bytes()) }
+            .map_err(|e| e.to_string())?;
+        entry(&mut registrar);
+    }
+    LIBRARIES.lock().unwrap().push(library);
+    Ok(registrar
+        .0
+        .into_iter()
+        .map(|registration| {
+            let id = PluginId(registration.name);
+            add(registration);
+            id
+        })
+        .collect())
+}
+
+/// Loads every plugin library in `dir`, logging those that fail. Without
+/// the directory there are none.
+#[cfg(feature = "native")]
+pub fn load_libraries(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension() != Some(std::env::consts::DLL_EXTENSION.as_ref()) {
+            continue;
+        }
+        match load_library(&path) {
+            Ok(ids) => {
+                let names = ids.iter().map(|id| id.name()).collect::<Vec<_>>();
+                info!("Loaded {} from {}", names.join(", "), path.display());
+            }
+            Err(e) => error!("Failed to load plugins from {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Every registered plugin, in registration order.
+pub fn registered() -> Vec<PluginId> {
+    let registry = REGISTRY.lock().unwrap();
+    registry.iter().map(|r| PluginId(r.name)).collect()
+}
+
+/// Models of every registered plugin.
+pub fn model_specs() -> Vec<ModelSpec> {
+    let registry = REGISTRY.lock().unwrap();
+    registry.iter().flat_map(|r| r.models.clone()).collect()
+}
+
+fn registration(name: &str) -> Option<Registration> {
+    let registry = REGISTRY.lock().unwrap();
+    registry.iter().find(|r| r.name == name).cloned()
+}
+
+/// A plugin, by name. Profiles may name plugins this build doesn't
+/// register; they are kept, and fail to load.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PluginId(&'static str);
+
+impl PluginId {
+    pub fn name(self) -> &'static str {
+        self.0
+    }
+
+    /// File names of the plugin's models.
+    pub fn models(self) -> Vec<&'static str> {
+        registration(self.0).map_or(vec![], |r| {
+            r.models.iter().map(|spec| spec.file_name).collect()
+        })
+    }
+
+    pub fn load(self, paths: &[PathBuf]) -> Result<Box<dyn Pipeline>, String> {
+        match registration(self.0) {
+            Some(r) => (r.load)(paths),
+            None => Err(format!("Plugin {} is not available", self.0)),
+        }
+    }
+
+    pub fn draw(
+        self,
+        overlay: &OverlayRenderer,
+        buf: &mut RgbImage,
+        detections: &[PluginDetection],
+    ) {
+        match registration(self.0) {
+            Some(r) => (r.draw)(overlay, buf, detections),
+            None => warn!("Failed to draw {}: not registered", self.0),
+        }
+    }
+}
+
+impl Serialize for PluginId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+/// Names of plugins that aren't registered but were named by profiles,
+/// each kept once however often profiles are loaded.
+static UNKNOWN: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+impl<'de> Deserialize<'de> for PluginId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        if let Some(r) = registration(&name) {
+            return Ok(Self(r.name));
+        }
+        let mut unknown = UNKNOWN.lock().unwrap();
+        if let Some(&known) = unknown.iter().find(|known| **known == name) {
+            return Ok(Self(known));
+        }
+        let name = &*Box::leak(name.into_boxed_str());
+        unknown.push(name);
+        Ok(Self(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pipeline::{PipelineKind, PipelineSpec},
+        scheduler::Scheduler,
+        worker::Thresholds,
+    };
+
+    /// Finds the brightest pixel, at half resolution.
+    struct Brightest;
+
+    impl Pipeline for Brightest {
+        fn name() -> &'static str {
+            "Brightest pixel"
+        }
+
+        fn load(_: &[PathBuf]) -> Result<Self, String> {
+            Ok(Self)
+        }
+
+        fn input(&self) -> InputSpec {
+            InputSpec {
+                size: Some((50, 50)),
+            }
+        }
+
+        fn process(&mut self, frame: &RgbImage) -> Vec<PluginDetection> {
+            let (x, y, _) = frame
+                .enumerate_pixels()
+                .max_by_key(|(_, _, p)| p[0])
+                .unwrap();
+            vec![PluginDetection {
+                bbox: BBox {
+                    x: x as f32,
+                    y: y as f32,
+                    w: 1.0,
+                    h: 1.0,
+                },
+                label: "bright".into(),
+                score: 1.0,
+                keypoints: vec![(x as f32, y as f32)],
+            }]
+        }
+    }
+
+    #[test]
+    fn registered_plugins_run_as_pipelines() {
+        register::<Brightest>();
+        let id = registered()
+            .into_iter()
+            .find(|id| id.name() == "Brightest pixel")
+            .unwrap();
+        let kind = PipelineKind::Plugin(id);
+        let json = serde_json::to_string(&kind).unwrap();
+        assert_eq!(serde_json::from_str::<PipelineKind>(&json).unwrap(), kind);
+
+        let spec = PipelineSpec::new(kind);
+        let pipeline = spec.load(&[]).unwrap();
+        let mut scheduler = Scheduler::new(vec![(spec, pipeline)]);
+        let mut frame = RgbImage::new(100, 100);
+        frame.put_pixel(60, 20, image::Rgb([255, 255, 255]));
+        let roi = BBox {
+            x: 40.0,
+            y: 0.0,
+            w: 50.0,
+            h: 50.0,
+        };
+        let detections = scheduler.run(&frame, Some(roi), &Thresholds::default());
+        let (found_id, found) = &detections.plugins[0];
+        assert_eq!(*found_id, id);
+        // The 50×50 ROI is processed as is, so the pixel is found exactly.
+        assert_eq!(found[0].keypoints, [(60.0, 20.0)]);
+        // The whole frame is halved, so it is found to the nearest 2 pixels.
+        let detections = scheduler.run(&frame, None, &Thresholds::default());
+        let (x, y) = detections.plugins[0].1[0].keypoints[0];
+        assert!((x - 60.0).abs() <= 2.0 && (y - 20.0).abs() <= 2.0);
+    }
+
+    #[test]
+    fn unknown_plugins_fail_to_load() {
+        let id: PluginId = serde_json::from_str("\"Not a plugin\"").unwrap();
+        assert_eq!(id.name(), "Not a plugin");
+        assert!(id.models().is_empty());
+        assert!(id.load(&[]).is_err());
+        // Loading the name again reuses it.
+        let again: PluginId = serde_json::from_str("\"Not a plugin\"").unwrap();
+        assert!(std::ptr::eq(id.name(), again.name()));
+    }
+
+    #[cfg(feature = "native")]
+    #[test]
+    fn files_that_are_no_plugin_library_fail_to_load() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert!(load_library(&manifest).is_err());
+        assert!(load_library(Path::new("no_such_plugin.so")).is_err());
+    }
+}
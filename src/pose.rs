@@ -0,0 +1,356 @@
+//! Two-stage BlazePose: a person detector finds the body, then the landmark
+//! model regresses 33 keypoints inside a square crop around it.
+
+use image::RgbImage;
+use ndarray::{ArrayD, ArrayView3};
+use serde::{Serialize, Serializer};
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    anchors::{Anchor, AnchorConfig},
+    bbox::{BBox, Detection},
+    inference::{self, Session},
+    math::sigmoid,
+    nms::{suppress, weighted_bbox, weighted_mean, NmsMode},
+    preprocess::{crop_to_tensor, Normalization, Resizer},
+};
+
+pub const NUM_LANDMARKS: usize = 33;
+const LANDMARK_INPUT: usize = 256;
+/// Values per landmark in the landmark model output: x, y, z, visibility and
+/// presence. Depth is not used.
+const LANDMARK_STRIDE: usize = 5;
+/// Detector keypoints used to build the landmark crop.
+const HIP_CENTER: usize = 0;
+const BODY_CIRCLE: usize = 1;
+/// Values per anchor in the person detector's regressor output: the box,
+/// then 4 keypoints.
+const DETECTOR_STRIDE: usize = 12;
+
+/// The output of the landmark model holding the landmarks, which start
+/// with the [`NUM_LANDMARKS`] body ones. BlazePose follows them with 6
+/// auxiliary landmarks used for tracking, 39 in all; its other outputs are
+/// the world landmarks, with 3 values each, and the segmentation mask and
+/// heatmaps, whose lengths aren't multiples of [`LANDMARK_STRIDE`].
+fn landmark_output(outputs: &[ArrayD<f32>]) -> Option<&ArrayD<f32>> {
+    outputs
+        .iter()
+        .find(|o| o.len() >= NUM_LANDMARKS * LANDMARK_STRIDE && o.len() % LANDMARK_STRIDE == 0)
+}
+
+/// The regressor and score outputs of the person detector with `anchors`
+/// anchors, found by their lengths.
+fn detector_outputs(
+    outputs: &[ArrayD<f32>],
+    anchors: usize,
+) -> Result<(ArrayView3<'_, f32>, ArrayView3<'_, f32>), String> {
+    let output = |stride: usize| {
+        let len = anchors * stride;
+        let output = outputs.iter().find(|o| o.len() == len).ok_or_else(|| {
+            let lens = outputs
+                .iter()
+                .map(|o| o.len().to_string())
+                .collect::<Vec<_>>();
+            format!(
+                "person model has no output of {} values, only {}",
+                len,
+                lens.join(", ")
+            )
+        })?;
+        let values = output
+            .as_slice()
+            .ok_or("person model output isn't contiguous")?;
+        ArrayView3::from_shape((1, anchors, stride), values).map_err(|e| e.to_string())
+    };
+    Ok((output(DETECTOR_STRIDE)?, output(1)?))
+}
+
+/// Pairs of landmarks connected when drawing the skeleton, as in MediaPipe's
+/// `POSE_CONNECTIONS`.
+pub const POSE_EDGES: [(usize, usize); 35] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 7),
+    (0, 4),
+    (4, 5),
+    (5, 6),
+    (6, 8),
+    (9, 10),
+    (11, 12),
+    (11, 13),
+    (13, 15),
+    (15, 17),
+    (15, 19),
+    (15, 21),
+    (17, 19),
+    (12, 14),
+    (14, 16),
+    (16, 18),
+    (16, 20),
+    (16, 22),
+    (18, 20),
+    (11, 23),
+    (12, 24),
+    (23, 24),
+    (23, 25),
+    (24, 26),
+    (25, 27),
+    (26, 28),
+    (27, 29),
+    (28, 30),
+    (29, 31),
+    (30, 32),
+    (27, 31),
+    (28, 32),
+];
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Landmark {
+    pub x: f32,
+    pub y: f32,
+    /// Probability that the landmark is visible rather than occluded.
+    pub visibility: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Pose {
+    /// Region the landmarks were regressed in.
+    pub roi: BBox,
+    #[serde(serialize_with = "serialize_landmarks")]
+    pub landmarks: [Landmark; NUM_LANDMARKS],
+}
+
+impl Pose {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            roi: self.roi.shift(x, y),
+            landmarks: self.landmarks.map(|l| Landmark {
+                x: l.x + x,
+                y: l.y + y,
+                ..l
+            }),
+        }
+    }
+}
+
+/// serde only implements `Serialize` for arrays of up to 32 elements.
+fn serialize_landmarks<S: Serializer>(
+    landmarks: &[Landmark; NUM_LANDMARKS],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    landmarks.as_slice().serialize(serializer)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Person {
+    bbox: BBox,
+    keypoints: [(f32, f32); 4],
+    score: f32,
+}
+
+impl Detection for Person {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn with_score(self, score: f32) -> Self {
+        Self { score, ..self }
+    }
+
+    fn blend(cluster: &[Self]) -> Self {
+        Self {
+            bbox: weighted_bbox(cluster),
+            keypoints: std::array::from_fn(|j| weighted_mean(cluster, |p| p.keypoints[j])),
+            score: cluster[0].score,
+        }
+    }
+}
+
+pub struct PoseEstimator {
+    detector: Session,
+    landmarker: Session,
+    anchor_config: AnchorConfig,
+    anchors: Vec<Anchor>,
+    resizer: Resizer,
+}
+
+impl PoseEstimator {
+    pub fn new(
+        detector_path: impl AsRef<Path>,
+        landmarker_path: impl AsRef<Path>,
+    ) -> Result<Self, String> {
+        let anchor_config = AnchorConfig::pose_detection();
+        Ok(Self {
+            detector: inference::create_session(detector_path)?,
+            landmarker: inference::create_session(landmarker_path)?,
+            anchors: anchor_config.generate(),
+            resizer: Resizer::new(anchor_config.input_width, anchor_config.input_height),
+            anchor_config,
+        })
+    }
+
+    /// Finds the most confident person and regresses their landmarks.
+    pub fn estimate(&mut self, img: &RgbImage, score_threshold: f32, nms: NmsMode) -> Vec<Pose> {
+        let person = match self.detect_person(img, score_threshold, nms) {
+            Ok(Some(person)) => person,
+            Ok(None) => return vec![],
+            Err(e) => {
+                error!("Failed to detect a person: {}", e);
+                return vec![];
+            }
+        };
+
+        let (cx, cy) = person.keypoints[HIP_CENTER];
+        let (ex, ey) = person.keypoints[BODY_CIRCLE];
+        let side = 2.0 * (ex - cx).hypot(ey - cy) * 1.25;
+        let roi = BBox {
+            x: cx - side / 2.0,
+            y: cy - side / 2.0,
+            w: side,
+            h: side,
+        };
+
+        let input = crop_to_tensor(
+            img,
+            roi,
+            LANDMARK_INPUT,
+            LANDMARK_INPUT,
+            Normalization::UNIT,
+        );
+        let outputs = inference::run(&*self.landmarker, input);
+        let Some(raw) = landmark_output(&outputs) else {
+            error!(
+                "Pose landmark model has no {}-landmark output",
+                NUM_LANDMARKS
+            );
+            return vec![];
+        };
+        let presence = outputs
+            .iter()
+            .find(|o| o.len() == 1)
+            .map_or(1.0, |o| o.iter().next().copied().unwrap_or(1.0));
+        if presence < score_threshold {
+            return vec![];
+        }
+
+        let raw = raw
+            .iter()
+            .copied()
+            .take(NUM_LANDMARKS * LANDMARK_STRIDE)
+            .collect::<Vec<_>>();
+        let scale = roi.w / LANDMARK_INPUT as f32;
+        let mut landmarks = [Landmark::default(); NUM_LANDMARKS];
+        for (landmark, v) in landmarks.iter_mut().zip(raw.chunks_exact(LANDMARK_STRIDE)) {
+            *landmark = Landmark {
+                x: roi.x + v[0] * scale,
+                y: roi.y + v[1] * scale,
+                visibility: sigmoid(v[3]),
+            };
+        }
+        vec![Pose { roi, landmarks }]
+    }
+
+    fn detect_person(
+        &mut self,
+        img: &RgbImage,
+        score_threshold: f32,
+        nms: NmsMode,
+    ) -> Result<Option<Person>, String> {
+        let (input_w, input_h) = (
+            self.anchor_config.input_width,
+            self.anchor_config.input_height,
+        );
+        let input = self.resizer.resize(img, Normalization::SIGNED);
+        let outputs = inference::run(&*self.detector, input);
+        let (regressors, scores) = detector_outputs(&outputs, self.anchors.len())?;
+
+        let scale_x = img.width() as f32 / input_w as f32;
+        let scale_y = img.height() as f32 / input_h as f32;
+        let people = self
+            .anchors
+            .iter()
+            .enumerate()
+            .map(|(i, anchor)| {
+                let x = regressors[(0, i, 0)] * anchor.w + anchor.x_center * input_w as f32;
+                let y = regressors[(0, i, 1)] * anchor.h + anchor.y_center * input_h as f32;
+                let w = regressors[(0, i, 2)] * anchor.w;
+                let h = regressors[(0, i, 3)] * anchor.h;
+                let keypoint = |j: usize| {
+                    (
+                        (regressors[(0, i, 4 + j * 2)] * anchor.w
+                            + anchor.x_center * input_w as f32)
+                            * scale_x,
+                        (regressors[(0, i, 4 + j * 2 + 1)] * anchor.h
+                            + anchor.y_center * input_h as f32)
+                            * scale_y,
+                    )
+                };
+                Person {
+                    bbox: BBox {
+                        x: x - w / 2.0,
+                        y: y - h / 2.0,
+                        w,
+                        h,
+                    }
+                    .scale(scale_x, scale_y),
+                    keypoints: [keypoint(0), keypoint(1), keypoint(2), keypoint(3)],
+                    score: sigmoid(scores[(0, i, 0)]),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(suppress(people, nms, score_threshold, 0.3).first().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::IxDyn;
+
+    #[test]
+    fn landmarks_are_found_among_the_blazepose_outputs() {
+        // Outputs of pose_landmark_full, in name order.
+        let outputs = [
+            vec![1, 195],
+            vec![1, 1],
+            vec![1, 256, 256, 1],
+            vec![1, 64, 64, 39],
+            vec![1, 117],
+        ]
+        .map(|shape| ArrayD::zeros(IxDyn(&shape)));
+        assert_eq!(landmark_output(&outputs).map(|o| o.len()), Some(39 * 5));
+        // Exports with only the body landmarks are read too.
+        let outputs = [
+            ArrayD::zeros(IxDyn(&[1, 33 * 5])),
+            ArrayD::zeros(IxDyn(&[1, 1])),
+        ];
+        assert_eq!(landmark_output(&outputs).map(|o| o.len()), Some(33 * 5));
+        assert!(landmark_output(&outputs[1..]).is_none());
+    }
+
+    #[test]
+    fn detector_outputs_of_another_model_are_an_error() {
+        let anchors = AnchorConfig::pose_detection().generate().len();
+        let outputs = [
+            ArrayD::zeros(IxDyn(&[1, anchors, 1])),
+            ArrayD::zeros(IxDyn(&[1, anchors, DETECTOR_STRIDE])),
+        ];
+        let (regressors, scores) = detector_outputs(&outputs, anchors).unwrap();
+        assert_eq!(regressors.dim(), (1, anchors, DETECTOR_STRIDE));
+        assert_eq!(scores.dim(), (1, anchors, 1));
+        // A palm detector's outputs.
+        let outputs = [
+            ArrayD::zeros(IxDyn(&[1, 2016, 18])),
+            ArrayD::zeros(IxDyn(&[1, 2016, 1])),
+        ];
+        assert!(detector_outputs(&outputs, anchors).is_err());
+        assert!(detector_outputs(&[], anchors).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Low power mode, for leaving the playground running on a laptop. Once no
+//! hand has been seen in the first stream for a while, detection runs less
+//! often, models can move to the CPU, cameras drop to a lower resolution
+//! and the app repaints a few times a second only. A hand, or anything
+//! moving in front of the camera, wakes it up again.
+
+use image::{GrayImage, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::{inference::Provider, track::Schedule};
+
+/// Side of the thumbnails frames are compared at to notice motion.
+const THUMBNAIL: (u32, u32) = (64, 48);
+/// Gray level change of a thumbnail pixel that counts as motion.
+const CHANGE: u8 = 24;
+
+/// Persisted across runs.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub enabled: bool,
+    /// Seconds without hands before going idle.
+    pub idle_after: f32,
+    /// Detection runs on every `idle_every`th frame while idle.
+    pub idle_every: u32,
+    /// Repaints per second while idle.
+    pub idle_fps: f32,
+    /// Run the models on the CPU while idle.
+    pub cpu: bool,
+    /// Widest camera resolution while idle, if lowered.
+    pub max_width: Option<u32>,
+    /// Share of the frame that has to change to wake up.
+    pub wake_motion: f32,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_after: 10.0,
+            idle_every: 6,
+            idle_fps: 5.0,
+            cpu: true,
+            max_width: Some(640),
+            wake_motion: 0.02,
+        }
+    }
+}
+
+/// Whether the app is idle, and when it last saw a hand.
+pub struct PowerSaver {
+    pub settings: PowerSettings,
+    idle: bool,
+    last_active: Instant,
+    /// Thumbnail of the last frame, to compare the next one with.
+    previous: Option<GrayImage>,
+}
+
+impl PowerSaver {
+    pub fn new(settings: PowerSettings) -> Self {
+        Self {
+            settings,
+            idle: false,
+            last_active: Instant::now(),
+            previous: None,
+        }
+    }
+
+    pub fn idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Takes the first stream's new `frame`, seen at `now` with `hands` or
+    /// without, and returns whether the app is idle now if that changed.
+    pub fn update(&mut self, hands: bool, frame: &RgbImage, now: Instant) -> Option<bool> {
+        let (w, h) = THUMBNAIL;
+        let thumbnail = image::imageops::grayscale(&image::imageops::thumbnail(frame, w, h));
+        let previous = self.previous.replace(thumbnail);
+        let moved = match (&previous, &self.previous) {
+            (Some(previous), Some(current)) => {
+                motion(previous, current) >= self.settings.wake_motion
+            }
+            _ => false,
+        };
+        let active = hands || (self.idle && moved);
+        if active || !self.settings.enabled {
+            self.last_active = now;
+        }
+        let idle_after = Duration::from_secs_f32(self.settings.idle_after);
+        let idle = now.duration_since(self.last_active) >= idle_after;
+        if idle == self.idle {
+            return None;
+        }
+        self.idle = idle;
+        Some(idle)
+    }
+
+    /// `schedule`, detecting less often while idle.
+    pub fn schedule(&self, schedule: Schedule) -> Schedule {
+        if !self.idle {
+            return schedule;
+        }
+        Schedule {
+            every: self.settings.idle_every.max(schedule.interval(None)),
+            adaptive: false,
+            ..schedule
+        }
+    }
+
+    /// Default provider to load models on, given the chosen one.
+    pub fn provider(&self, provider: Option<Provider>) -> Option<Provider> {
+        if self.idle && self.settings.cpu {
+            Some(Provider::Cpu)
+        } else {
+            provider
+        }
+    }
+
+    /// Widest camera resolution to capture at.
+    pub fn max_width(&self) -> Option<u32> {
+        self.settings.max_width.filter(|_| self.idle)
+    }
+
+    /// How long to wait before the next repaint, or `None` for right away.
+    pub fn repaint_after(&self) -> Option<Duration> {
+        self.idle
+            .then(|| Duration::from_secs_f32(1.0 / self.settings.idle_fps.max(0.1)))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings;
+        ui.checkbox(&mut settings.enabled, "Enabled");
+        ui.add(
+            egui::Slider::new(&mut settings.idle_after, 2.0..=120.0)
+                .text("Idle after no hands for (s)"),
+        );
+        ui.add(egui::Slider::new(&mut settings.idle_every, 1..=30).text("Detect every Nth frame"));
+        ui.add(egui::Slider::new(&mut settings.idle_fps, 1.0..=30.0).text("Repaints per second"));
+        ui.checkbox(&mut settings.cpu, "Run models on the CPU");
+        let mut lower = settings.max_width.is_some();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut lower, "Lower camera resolution to");
+            let mut width = settings.max_width.unwrap_or(640);
+            ui.add_enabled(
+                lower,
+                egui::DragValue::new(&mut width)
+                    .range(160..=1920)
+                    .suffix(" px wide"),
+            );
+            settings.max_width = lower.then_some(width);
+        });
+        ui.add(
+            egui::Slider::new(&mut settings.wake_motion, 0.005..=0.2)
+                .logarithmic(true)
+                .text("Wake on motion of"),
+        );
+        if !settings.enabled {
+            return;
+        }
+        if self.idle {
+            ui.label("Idle, waiting for a hand or motion");
+        } else {
+            ui.label("Active");
+        }
+    }
+}
+
+/// Share of pixels that changed between two thumbnails.
+fn motion(previous: &GrayImage, current: &GrayImage) -> f32 {
+    let changed = previous
+        .pixels()
+        .zip(current.pixels())
+        .filter(|(a, b)| a[0].abs_diff(b[0]) >= CHANGE)
+        .count();
+    changed as f32 / previous.len().max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idles_without_hands_and_wakes_on_motion() {
+        let mut power = PowerSaver::new(PowerSettings {
+            enabled: true,
+            ..PowerSettings::default()
+        });
+        let start = power.last_active;
+        let at = |s: u64| start + Duration::from_secs(s);
+        let dark = RgbImage::new(64, 48);
+        let bright = RgbImage::from_pixel(64, 48, image::Rgb([255; 3]));
+        assert_eq!(power.update(true, &dark, at(1)), None);
+        assert_eq!(power.update(false, &dark, at(5)), None);
+        assert_eq!(power.update(false, &dark, at(11)), Some(true));
+        assert_eq!(power.max_width(), Some(640));
+        assert_eq!(power.provider(Some(Provider::Cuda)), Some(Provider::Cpu));
+        assert_eq!(power.schedule(Schedule::default()).interval(None), 6);
+        assert!(power.repaint_after().is_some());
+        assert_eq!(power.update(false, &dark, at(12)), None);
+        assert_eq!(power.update(false, &bright, at(13)), Some(false));
+        assert_eq!(power.provider(Some(Provider::Cuda)), Some(Provider::Cuda));
+        assert_eq!(power.repaint_after(), None);
+        // Motion alone doesn't keep it awake, only hands do.
+        assert_eq!(power.update(false, &dark, at(20)), None);
+        assert_eq!(power.update(false, &bright, at(24)), Some(true));
+    }
+}
@@ -0,0 +1,353 @@
+//! Conversion of camera frames into NHWC `f32` model inputs.
+
+use image::{imageops::FilterType, RgbImage};
+use ndarray::{s, Array4, ArrayView4};
+use wide::f32x8;
+
+use crate::bbox::{BBox, RotatedRect};
+
+/// Range that pixel values `0..=255` are mapped onto.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalization {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Normalization {
+    pub const UNIT: Self = Self { min: 0.0, max: 1.0 };
+    pub const SIGNED: Self = Self {
+        min: -1.0,
+        max: 1.0,
+    };
+
+    /// Factor and offset mapping a pixel value onto the range.
+    fn affine(&self) -> (f32, f32) {
+        ((self.max - self.min) / 255.0, self.min)
+    }
+
+    fn apply(&self, v: f32) -> f32 {
+        self.min + v / 255.0 * (self.max - self.min)
+    }
+}
+
+/// Stretches the whole image to `width`×`height`. Reference implementation
+/// of [`Resizer`] built on `image`'s resize.
+pub fn image_to_tensor(
+    img: &RgbImage,
+    width: usize,
+    height: usize,
+    norm: Normalization,
+) -> Array4<f32> {
+    let resized = image::imageops::resize(img, width as u32, height as u32, FilterType::Triangle);
+    Array4::from_shape_vec(
+        (1, height, width, 3),
+        resized.iter().map(|v| norm.apply(*v as f32)).collect(),
+    )
+    .unwrap()
+}
+
+/// Bilinearly samples `roi` (in image pixels) into a `width`×`height`
+/// tensor. Parts of the ROI outside the image are filled with zero pixels.
+pub fn crop_to_tensor(
+    img: &RgbImage,
+    roi: BBox,
+    width: usize,
+    height: usize,
+    norm: Normalization,
+) -> Array4<f32> {
+    rotated_crop_to_tensor(img, roi.into(), width, height, norm)
+}
+
+/// Like [`crop_to_tensor`], but the crop is turned by `rect.angle`, so that
+/// `rect`'s up direction becomes the tensor's up direction.
+pub fn rotated_crop_to_tensor(
+    img: &RgbImage,
+    rect: RotatedRect,
+    width: usize,
+    height: usize,
+    norm: Normalization,
+) -> Array4<f32> {
+    let (img_w, img_h) = (img.width() as i64, img.height() as i64);
+    let pixel = |x: i64, y: i64, c: usize| -> f32 {
+        if x < 0 || y < 0 || x >= img_w || y >= img_h {
+            0.0
+        } else {
+            img.get_pixel(x as u32, y as u32)[c] as f32
+        }
+    };
+    Array4::from_shape_fn((1, height, width, 3), |(_, oy, ox, c)| {
+        let (sx, sy) = rect.to_image(
+            (ox as f32 + 0.5) / width as f32,
+            (oy as f32 + 0.5) / height as f32,
+        );
+        let (sx, sy) = (sx - 0.5, sy - 0.5);
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = pixel(x0, y0, c) * (1.0 - fx) + pixel(x0 + 1, y0, c) * fx;
+        let bottom = pixel(x0, y0 + 1, c) * (1.0 - fx) + pixel(x0 + 1, y0 + 1, c) * fx;
+        norm.apply(top * (1.0 - fy) + bottom * fy)
+    })
+}
+
+/// Placement of an aspect-preserving resize inside a square model input,
+/// with the rest of the input padded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Letterbox {
+    pub size: usize,
+    /// Size of the resized image inside the input.
+    pub width: usize,
+    pub height: usize,
+    pub pad_x: usize,
+    pub pad_y: usize,
+}
+
+impl Letterbox {
+    pub fn fit(img_w: u32, img_h: u32, size: usize) -> Self {
+        let scale = size as f32 / img_w.max(img_h) as f32;
+        let width = ((img_w as f32 * scale).round() as usize).clamp(1, size);
+        let height = ((img_h as f32 * scale).round() as usize).clamp(1, size);
+        Self {
+            size,
+            width,
+            height,
+            pad_x: (size - width) / 2,
+            pad_y: (size - height) / 2,
+        }
+    }
+
+    /// Centers `resized`, a `width`×`height` tensor, on a `fill` background.
+    pub fn pad(&self, resized: ArrayView4<f32>, fill: f32) -> Array4<f32> {
+        let mut out = Array4::from_elem((1, self.size, self.size, 3), fill);
+        out.slice_mut(s![
+            ..,
+            self.pad_y..self.pad_y + self.height,
+            self.pad_x..self.pad_x + self.width,
+            ..
+        ])
+        .assign(&resized);
+        out
+    }
+
+    /// Maps a box in input pixels back onto the `img_w`×`img_h` image.
+    pub fn unmap(&self, bbox: BBox, img_w: u32, img_h: u32) -> BBox {
+        bbox.shift(-(self.pad_x as f32), -(self.pad_y as f32))
+            .scale(
+                img_w as f32 / self.width as f32,
+                img_h as f32 / self.height as f32,
+            )
+    }
+}
+
+/// Source samples and weights contributing to one output row or column.
+#[derive(Clone, Debug, PartialEq)]
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Triangle filter taps resampling `src` samples to `dst`, matching
+/// [`FilterType::Triangle`]: the filter widens when downscaling so every
+/// source sample contributes.
+fn triangle_taps(src: usize, dst: usize) -> Vec<Taps> {
+    let ratio = src as f32 / dst as f32;
+    let support = ratio.max(1.0);
+    (0..dst)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * ratio;
+            let left = ((center - support).floor() as isize).clamp(0, src as isize - 1) as usize;
+            let right = ((center + support).ceil() as isize).clamp(left as isize + 1, src as isize)
+                as usize;
+            let mut weights = (left..right)
+                .map(|x| (1.0 - ((x as f32 + 0.5 - center) / support).abs()).max(0.0))
+                .collect::<Vec<_>>();
+            let sum = weights.iter().sum::<f32>();
+            if sum > 0.0 {
+                weights.iter_mut().for_each(|w| *w /= sum);
+            } else {
+                weights
+                    .iter_mut()
+                    .for_each(|w| *w = 1.0 / (right - left) as f32);
+            }
+            Taps {
+                start: left,
+                weights,
+            }
+        })
+        .collect()
+}
+
+/// Fused resize and normalization into model input tensors. The vertical
+/// pass, which touches every source pixel, runs 8 lanes wide; filter taps and
+/// the intermediate row are kept between frames of the same size.
+pub struct Resizer {
+    width: usize,
+    height: usize,
+    src: (u32, u32),
+    rows: Vec<Taps>,
+    cols: Vec<Taps>,
+    /// One vertically filtered source row, as interleaved RGB.
+    row: Vec<f32>,
+}
+
+impl Resizer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            src: (0, 0),
+            rows: vec![],
+            cols: vec![],
+            row: vec![],
+        }
+    }
+
+    pub fn resize(&mut self, img: &RgbImage, norm: Normalization) -> Array4<f32> {
+        if img.dimensions() != self.src {
+            self.src = img.dimensions();
+            self.rows = triangle_taps(img.height() as usize, self.height);
+            self.cols = triangle_taps(img.width() as usize, self.width);
+            self.row = vec![0.0; img.width() as usize * 3];
+        }
+        let stride = img.width() as usize * 3;
+        let raw = img.as_raw();
+        let (scale, offset) = norm.affine();
+        let mut out = Vec::with_capacity(self.width * self.height * 3);
+
+        for taps in &self.rows {
+            self.row.fill(0.0);
+            for (k, &weight) in taps.weights.iter().enumerate() {
+                let src = &raw[(taps.start + k) * stride..][..stride];
+                blend_row(&mut self.row, src, weight);
+            }
+            for taps in &self.cols {
+                let mut px = [0.0f32; 3];
+                for (k, &weight) in taps.weights.iter().enumerate() {
+                    let x = (taps.start + k) * 3;
+                    px[0] += self.row[x] * weight;
+                    px[1] += self.row[x + 1] * weight;
+                    px[2] += self.row[x + 2] * weight;
+                }
+                out.extend(px.map(|v| v * scale + offset));
+            }
+        }
+        Array4::from_shape_vec((1, self.height, self.width, 3), out).unwrap()
+    }
+}
+
+/// `acc += src * weight`, eight samples at a time.
+fn blend_row(acc: &mut [f32], src: &[u8], weight: f32) {
+    let w = f32x8::splat(weight);
+    let mut acc_chunks = acc.chunks_exact_mut(8);
+    let mut src_chunks = src.chunks_exact(8);
+    for (a, s) in (&mut acc_chunks).zip(&mut src_chunks) {
+        let s: [u8; 8] = s.try_into().unwrap();
+        let a: &mut [f32; 8] = a.try_into().unwrap();
+        *a = f32x8::new(s.map(f32::from))
+            .mul_add(w, f32x8::new(*a))
+            .to_array();
+    }
+    for (a, s) in acc_chunks
+        .into_remainder()
+        .iter_mut()
+        .zip(src_chunks.remainder())
+    {
+        *a += *s as f32 * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(w: u32, h: u32) -> RgbImage {
+        RgbImage::from_fn(w, h, |x, y| {
+            image::Rgb([
+                (x * 255 / w) as u8,
+                (y * 255 / h) as u8,
+                ((x + y) % 256) as u8,
+            ])
+        })
+    }
+
+    #[test]
+    fn taps_are_normalized() {
+        for (src, dst) in [(1280, 192), (192, 192), (100, 192), (7, 3)] {
+            let taps = triangle_taps(src, dst);
+            assert_eq!(taps.len(), dst);
+            for t in taps {
+                assert!((t.weights.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+                assert!(t.start + t.weights.len() <= src);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_reference_resize() {
+        for (w, h) in [(640, 480), (150, 100)] {
+            let img = gradient(w, h);
+            let expected = image_to_tensor(&img, 192, 192, Normalization::UNIT);
+            let actual = Resizer::new(192, 192).resize(&img, Normalization::UNIT);
+            assert_eq!(expected.shape(), actual.shape());
+            let mean_error = (&expected - &actual).mapv(f32::abs).mean().unwrap();
+            assert!(mean_error < 2.0 / 255.0, "mean error {}", mean_error);
+        }
+    }
+
+    #[test]
+    fn applies_normalization() {
+        let img = RgbImage::from_pixel(10, 10, image::Rgb([0, 255, 255]));
+        let t = Resizer::new(4, 4).resize(&img, Normalization::SIGNED);
+        assert!((t[(0, 2, 2, 0)] + 1.0).abs() < 1e-5);
+        assert!((t[(0, 2, 2, 1)] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotated_crop() {
+        let img = gradient(40, 30);
+        let roi = BBox {
+            x: 10.0,
+            y: 5.0,
+            w: 16.0,
+            h: 16.0,
+        };
+        let upright = crop_to_tensor(&img, roi, 8, 8, Normalization::UNIT);
+
+        // Half a turn samples the same square upside down.
+        let rect = RotatedRect {
+            angle: std::f32::consts::PI,
+            ..roi.into()
+        };
+        let turned = rotated_crop_to_tensor(&img, rect, 8, 8, Normalization::UNIT);
+        for ((_, y, x, c), v) in turned.indexed_iter() {
+            let expected = upright[(0, 7 - y, 7 - x, c)];
+            assert!((v - expected).abs() < 1e-4, "{} != {}", v, expected);
+        }
+    }
+
+    #[test]
+    fn letterbox_round_trip() {
+        let letterbox = Letterbox::fit(1280, 720, 640);
+        assert_eq!((letterbox.width, letterbox.height), (640, 360));
+        assert_eq!((letterbox.pad_x, letterbox.pad_y), (0, 140));
+
+        let input = BBox {
+            x: 100.0,
+            y: 240.0,
+            w: 64.0,
+            h: 32.0,
+        };
+        let bbox = letterbox.unmap(input, 1280, 720);
+        assert_eq!(
+            (bbox.x, bbox.y, bbox.w, bbox.h),
+            (200.0, 200.0, 128.0, 64.0)
+        );
+
+        let padded = letterbox.pad(Array4::ones((1, 360, 640, 3)).view(), 0.5);
+        assert_eq!(padded.dim(), (1, 640, 640, 3));
+        assert_eq!(padded[(0, 139, 0, 0)], 0.5);
+        assert_eq!(padded[(0, 140, 0, 0)], 1.0);
+        assert_eq!(padded[(0, 499, 639, 2)], 1.0);
+        assert_eq!(padded[(0, 500, 0, 0)], 0.5);
+    }
+}
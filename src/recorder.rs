@@ -0,0 +1,255 @@
+//! Logs the detections of every frame to a file for offline analysis.
+//!
+//! `.csv` files get one row per palm, plus an empty row for frames without
+//! any, so gaps in detection show up. Any other extension gets one JSON
+//! object per line and frame.
+
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+use tracing::error;
+
+use crate::{palm::Palm, stream::StreamState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Jsonl,
+    Csv,
+}
+
+impl LogFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Jsonl,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    frame: u64,
+    /// Seconds since recording started.
+    time: f64,
+    source: &'a str,
+    hands: usize,
+    palms: &'a [Palm],
+}
+
+pub fn jsonl_line(frame: u64, time: f64, source: &str, palms: &[Palm]) -> String {
+    serde_json::to_string(&Line {
+        frame,
+        time,
+        source,
+        hands: palms.len(),
+        palms,
+    })
+    .unwrap()
+}
+
+pub fn csv_header() -> String {
+    let mut columns = [
+        "frame",
+        "time",
+        "source",
+        "hands",
+        "hand",
+        "handedness",
+        "x",
+        "y",
+        "w",
+        "h",
+        "score",
+    ]
+    .map(str::to_owned)
+    .to_vec();
+    for i in 0..7 {
+        columns.push(format!("kp{}_x", i));
+        columns.push(format!("kp{}_y", i));
+    }
+    columns.join(",")
+}
+
+pub fn csv_rows(frame: u64, time: f64, source: &str, palms: &[Palm]) -> Vec<String> {
+    let prefix = format!("{},{:.6},{},{}", frame, time, source, palms.len());
+    if palms.is_empty() {
+        let empty = ",".repeat(csv_header().matches(',').count() - 3);
+        return vec![prefix + &empty];
+    }
+    palms
+        .iter()
+        .enumerate()
+        .map(|(i, palm)| {
+            let b = palm.bbox;
+            let mut row = format!(
+                "{},{},{},{},{},{},{},{}",
+                prefix,
+                i,
+                palm.handedness.label(),
+                b.x,
+                b.y,
+                b.w,
+                b.h,
+                palm.score
+            );
+            for (x, y) in palm.tips {
+                row += &format!(",{},{}", x, y);
+            }
+            row
+        })
+        .collect()
+}
+
+pub struct Recorder {
+    pub path: String,
+    /// Open log and the format it is written in, while recording.
+    file: Option<(BufWriter<File>, LogFormat)>,
+    started: Instant,
+    frame: u64,
+    status: String,
+}
+
+impl Recorder {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+            started: Instant::now(),
+            frame: 0,
+            status: String::new(),
+        }
+    }
+
+    pub fn recording(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Appends to the file at [`Self::path`], adding a CSV header if it's new.
+    pub fn start(&mut self) {
+        let format = LogFormat::from_path(&self.path);
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|file| {
+                let empty = file.metadata()?.len() == 0;
+                let mut file = BufWriter::new(file);
+                if format == LogFormat::Csv && empty {
+                    writeln!(file, "{}", csv_header())?;
+                }
+                Ok(file)
+            });
+        match result {
+            Ok(file) => {
+                self.file = Some((file, format));
+                self.started = Instant::now();
+                self.frame = 0;
+                self.status = format!("Recording to {}", self.path);
+            }
+            Err(e) => {
+                error!("Failed to open {}: {}", self.path, e);
+                self.status = format!("Failed: {}", e);
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((mut file, _)) = self.file.take() {
+            if let Err(e) = file.flush() {
+                error!("Failed to write {}: {}", self.path, e);
+            }
+            self.status = format!("Recorded {} frames", self.frame);
+        }
+    }
+
+    /// Logs the current detections of all streams as one frame.
+    pub fn record(&mut self, streams: &[StreamState]) {
+        let Some((file, format)) = &mut self.file else {
+            return;
+        };
+        let time = self.started.elapsed().as_secs_f64();
+        let result = streams.iter().try_for_each(|stream| {
+            let source = stream.id.slug();
+            match format {
+                LogFormat::Jsonl => writeln!(
+                    file,
+                    "{}",
+                    jsonl_line(self.frame, time, &source, &stream.palms)
+                ),
+                LogFormat::Csv => csv_rows(self.frame, time, &source, &stream.palms)
+                    .iter()
+                    .try_for_each(|row| writeln!(file, "{}", row)),
+            }
+        });
+        self.frame += 1;
+        if let Err(e) = result {
+            error!("Failed to write {}: {}", self.path, e);
+            self.file = None;
+            self.status = format!("Failed: {}", e);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add_enabled_ui(!self.recording(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut self.path);
+            });
+        });
+        ui.label("Writes CSV for .csv files, JSON lines otherwise");
+        let label = if self.recording() { "Stop" } else { "Record" };
+        if ui.button(label).clicked() {
+            if self.recording() {
+                self.stop();
+            } else {
+                self.start();
+            }
+        }
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util;
+
+    /// A right palm in the box at (1, 2) of 3×4, all keypoints at (5, 6).
+    fn logged() -> Palm {
+        test_util::palm(2.5, 4.0)
+            .size(3.0, 4.0)
+            .tips((5.0, 6.0))
+            .score(0.5)
+            .build()
+    }
+
+    #[test]
+    fn csv_rows_match_header() {
+        let columns = csv_header().split(',').count();
+        let rows = csv_rows(3, 0.25, "camera0", &[logged(), logged()]);
+        assert_eq!(rows.len(), 2);
+        assert!(rows[1].starts_with("3,0.250000,camera0,2,1,R,1,2,3,4,0.5,5,6,"));
+        let empty = csv_rows(4, 0.5, "camera0", &[]);
+        assert_eq!(empty, ["4,0.500000,camera0,0".to_owned() + &",".repeat(21)]);
+        for row in rows.iter().chain(&empty) {
+            assert_eq!(row.split(',').count(), columns);
+        }
+    }
+
+    #[test]
+    fn jsonl_is_one_line() {
+        let line = jsonl_line(7, 1.5, "camera0", &[logged()]);
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["frame"], 7);
+        assert_eq!(value["hands"], 1);
+        assert_eq!(value["palms"][0]["score"], 0.5);
+        assert_eq!(value["palms"][0]["handedness"], "R");
+    }
+}
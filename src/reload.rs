@@ -0,0 +1,139 @@
+//! Notices when model files change on disk, so pipelines can be rebuilt
+//! without restarting the app.
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// A changed file is only reloaded once it has been left alone this long, so
+/// an export still being written isn't read half-finished.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// Whether `event` changed one of `files`, which must be canonical.
+fn is_change(event: &Event, files: &[PathBuf]) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|path| files.contains(path))
+}
+
+/// Watches the directories of the current model files. Editors and
+/// exporters often replace files instead of writing them in place, which
+/// watching the files themselves would miss.
+pub struct ModelWatcher {
+    pub enabled: bool,
+    watcher: Option<RecommendedWatcher>,
+    dirs: Vec<PathBuf>,
+    /// Canonical paths of the watched files, shared with the watcher thread.
+    files: Arc<Mutex<Vec<PathBuf>>>,
+    /// When a watched file last changed, if it wasn't reloaded since.
+    changed: Arc<Mutex<Option<Instant>>>,
+    error: Option<String>,
+}
+
+impl ModelWatcher {
+    pub fn new() -> Self {
+        let files = Arc::new(Mutex::new(vec![]));
+        let changed = Arc::new(Mutex::new(None));
+        let (watched, last_change) = (files.clone(), changed.clone());
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                if is_change(&event, &watched.lock().unwrap()) {
+                    *last_change.lock().unwrap() = Some(Instant::now());
+                }
+            }
+        });
+        let (watcher, error) = match watcher {
+            Ok(watcher) => (Some(watcher), None),
+            Err(e) => {
+                warn!("Model file watching unavailable: {}", e);
+                (None, Some(e.to_string()))
+            }
+        };
+        Self {
+            enabled: true,
+            watcher,
+            dirs: vec![],
+            files,
+            changed,
+            error,
+        }
+    }
+
+    /// Watches `paths` from now on, instead of the previous ones.
+    pub fn watch(&mut self, paths: &[PathBuf]) {
+        let files = paths
+            .iter()
+            .filter_map(|path| std::fs::canonicalize(path).ok())
+            .collect::<Vec<_>>();
+        if *self.files.lock().unwrap() == files {
+            return;
+        }
+        let mut dirs = files
+            .iter()
+            .filter_map(|file| file.parent().map(Path::to_owned))
+            .collect::<Vec<_>>();
+        dirs.sort();
+        dirs.dedup();
+        if let Some(watcher) = &mut self.watcher {
+            for dir in self.dirs.iter().filter(|dir| !dirs.contains(dir)) {
+                let _ = watcher.unwatch(dir);
+            }
+            for dir in dirs.iter().filter(|dir| !self.dirs.contains(dir)) {
+                if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    warn!("Failed to watch {}: {}", dir.display(), e);
+                    self.error = Some(e.to_string());
+                }
+            }
+        }
+        self.dirs = dirs;
+        *self.files.lock().unwrap() = files;
+        *self.changed.lock().unwrap() = None;
+    }
+
+    /// Whether a watched file changed and has settled since the last call
+    /// that returned `true`.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = self.changed.lock().unwrap();
+        if !self.enabled || !changed.is_some_and(|at| at.elapsed() >= SETTLE) {
+            return false;
+        }
+        *changed = None;
+        true
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.watcher.is_some() {
+            ui.checkbox(&mut self.enabled, "Reload when model files change");
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+    }
+}
+
+impl Default for ModelWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, ModifyKind};
+
+    #[test]
+    fn only_changes_to_watched_files_count() {
+        let model = PathBuf::from("/models/palm.onnx");
+        let files = [model.clone()];
+        let modify =
+            |path: &str| Event::new(EventKind::Modify(ModifyKind::Any)).add_path(path.into());
+        assert!(is_change(&modify("/models/palm.onnx"), &files));
+        assert!(!is_change(&modify("/models/pose.onnx"), &files));
+        let access = Event::new(EventKind::Access(AccessKind::Any)).add_path(model);
+        assert!(!is_change(&access, &files));
+    }
+}
@@ -0,0 +1,327 @@
+//! Replays a recorded session: a video with the detection log recorded
+//! alongside it (see [`crate::recorder`]). The logged palms are drawn over
+//! the video without running any model, so overlay settings can be tried
+//! out on the same session again, on machines without a GPU too.
+
+use image::{ImageFormat, RgbImage};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{
+    network::JpegSplitter,
+    palm::Palm,
+    source::{FrameSource, SourceId},
+    worker::Detections,
+};
+
+/// Rate videos are decoded at, so that the time of every frame is known.
+const FRAME_RATE: f64 = 30.0;
+const READ_CHUNK: usize = 64 << 10;
+
+/// One line of a JSON lines detection log.
+#[derive(Deserialize)]
+struct Line {
+    time: f64,
+    source: String,
+    palms: Vec<Palm>,
+}
+
+/// Palms of every logged frame, by source.
+#[derive(Debug, Default)]
+pub struct DetectionLog {
+    /// Times in seconds since recording started, ascending.
+    frames: BTreeMap<String, Vec<(f64, Vec<Palm>)>>,
+}
+
+impl DetectionLog {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut log = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line: Line =
+                serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            log.frames
+                .entry(line.source)
+                .or_default()
+                .push((line.time, line.palms));
+        }
+        if log.frames.is_empty() {
+            return Err("no detections in the log".into());
+        }
+        for frames in log.frames.values_mut() {
+            frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        Ok(log)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Names of the logged streams.
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        self.frames.keys().map(String::as_str)
+    }
+
+    /// Palms of `source` in the last frame logged at or before `time`.
+    pub fn palms_at(&self, source: &str, time: f64) -> &[Palm] {
+        let Some(frames) = self.frames.get(source) else {
+            return &[];
+        };
+        match frames.partition_point(|(at, _)| *at <= time) {
+            0 => &[],
+            i => &frames[i - 1].1,
+        }
+    }
+}
+
+/// Frames of a video file, decoded by ffmpeg at [`FRAME_RATE`] and played
+/// back in real time. Loops at the end.
+pub struct VideoFile {
+    path: PathBuf,
+    ffmpeg: Child,
+    splitter: JpegSplitter,
+    chunk: Vec<u8>,
+    /// Index of the frame ffmpeg sends next.
+    next: u64,
+    /// When frame 0 is due, for pacing.
+    started: Instant,
+}
+
+impl VideoFile {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        if !path.is_file() {
+            return Err(format!("{} is not a file", path.display()));
+        }
+        let ffmpeg = Self::decode(&path, 0)?;
+        Ok(Self {
+            path,
+            ffmpeg,
+            splitter: JpegSplitter::default(),
+            chunk: vec![0; READ_CHUNK],
+            next: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// Has ffmpeg decode `path` as MJPEG from frame `from` on.
+    fn decode(path: &Path, from: u64) -> Result<Child, String> {
+        let start = format!("{:.3}", from as f64 / FRAME_RATE);
+        let rate = format!("fps={}", FRAME_RATE);
+        Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-ss", &start, "-i"])
+            .arg(path)
+            .args(["-an", "-vf", &rate, "-c:v", "mjpeg", "-q:v", "3"])
+            .args(["-f", "image2pipe", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start ffmpeg, is it installed? {}", e))
+    }
+
+    fn restart(&mut self, from: u64) -> Result<(), String> {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+        self.ffmpeg = Self::decode(&self.path, from)?;
+        self.splitter = JpegSplitter::default();
+        self.next = from;
+        self.started = Instant::now() - Self::time_of(from);
+        Ok(())
+    }
+
+    fn time_of(frame: u64) -> Duration {
+        Duration::from_secs_f64(frame as f64 / FRAME_RATE)
+    }
+
+    /// Time of the frame read last.
+    pub fn position(&self) -> Duration {
+        Self::time_of(self.next.saturating_sub(1))
+    }
+}
+
+impl Drop for VideoFile {
+    fn drop(&mut self) {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+    }
+}
+
+impl FrameSource for VideoFile {
+    fn id(&self) -> SourceId {
+        SourceId::Replay(self.path.clone())
+    }
+
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        loop {
+            if let Some(jpeg) = self.splitter.next_image() {
+                match image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg) {
+                    Ok(image) => {
+                        *frame = image.into_rgb8();
+                        self.next += 1;
+                        return Ok(());
+                    }
+                    Err(e) => warn!("Skipping a broken frame: {}", e),
+                }
+                continue;
+            }
+            let n = self.ffmpeg.stdout.as_mut().unwrap().read(&mut self.chunk)?;
+            if n > 0 {
+                self.splitter.push(&self.chunk[..n]);
+            } else if self.next > 0 {
+                self.restart(0)?;
+            } else {
+                return Err(format!("no frames in {}", self.path.display()).into());
+            }
+        }
+    }
+
+    /// Reads the next frame once it is due. A replay that fell behind, e.g.
+    /// while paused, goes on from where it is instead of catching up.
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        let now = Instant::now();
+        let due = self.started + Self::time_of(self.next);
+        if now < due {
+            return Ok(false);
+        }
+        if now > due + Self::time_of(1) {
+            self.started = now - Self::time_of(self.next);
+        }
+        self.read(frame).map(|()| true)
+    }
+
+    fn seek(&mut self, frames: isize) {
+        let current = self.next.saturating_sub(1) as isize;
+        let target = (current + frames).max(0) as u64;
+        if let Err(e) = self.restart(target) {
+            warn!("Failed to seek in {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+}
+
+/// A video replayed with the detections logged while it was recorded.
+pub struct Replay {
+    video: VideoFile,
+    log: DetectionLog,
+    /// Logged stream shown.
+    pub source: String,
+    /// Log time at the start of the video, in seconds, for videos and logs
+    /// not started together.
+    pub offset: f64,
+}
+
+impl Replay {
+    pub fn open(video: impl Into<PathBuf>, log: impl AsRef<Path>) -> Result<Self, String> {
+        let log = DetectionLog::load(log)?;
+        let source = log.sources().next().unwrap_or_default().to_owned();
+        Ok(Self {
+            video: VideoFile::open(video)?,
+            log,
+            source,
+            offset: 0.0,
+        })
+    }
+}
+
+impl FrameSource for Replay {
+    fn id(&self) -> SourceId {
+        self.video.id()
+    }
+
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        self.video.read(frame)
+    }
+
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        self.video.try_read(frame)
+    }
+
+    fn seek(&mut self, frames: isize) {
+        self.video.seek(frames);
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    fn recorded(&self) -> Option<Detections> {
+        let time = self.video.position().as_secs_f64() + self.offset;
+        Some(Detections {
+            palms: self.log.palms_at(&self.source, time).to_vec(),
+            ..Detections::default()
+        })
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let sources = self.log.sources().map(str::to_owned).collect::<Vec<_>>();
+        if sources.len() > 1 {
+            egui::ComboBox::from_id_source("replay_source")
+                .selected_text(&self.source)
+                .show_ui(ui, |ui| {
+                    for source in sources {
+                        ui.selectable_value(&mut self.source, source.clone(), source);
+                    }
+                });
+        }
+        ui.add(
+            egui::DragValue::new(&mut self.offset)
+                .speed(0.01)
+                .suffix(" s"),
+        )
+        .on_hover_text("Log time at the start of the video");
+        ui.label(format!("{:.2} s", self.video.position().as_secs_f32()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{palm::Handedness, recorder::jsonl_line, test_util::palm};
+
+    /// A left palm whose 3×4 box starts at `x`.
+    fn from(x: f32) -> Palm {
+        palm(x + 1.5, 4.0)
+            .size(3.0, 4.0)
+            .handedness(Handedness::Left)
+            .build()
+    }
+
+    #[test]
+    fn logged_palms_are_found_by_time() {
+        let text = [
+            jsonl_line(0, 0.0, "camera0", &[from(1.0)]),
+            jsonl_line(0, 0.0, "camera1", &[]),
+            jsonl_line(1, 0.5, "camera0", &[from(2.0), from(3.0)]),
+            jsonl_line(1, 0.5, "camera1", &[from(4.0)]),
+        ]
+        .join("\n");
+        let log = DetectionLog::parse(&text).unwrap();
+        assert_eq!(log.sources().collect::<Vec<_>>(), ["camera0", "camera1"]);
+        let xs = |palms: &[Palm]| palms.iter().map(|p| p.bbox.x).collect::<Vec<_>>();
+        assert_eq!(xs(log.palms_at("camera0", -0.1)), [0.0; 0]);
+        assert_eq!(xs(log.palms_at("camera0", 0.49)), [1.0]);
+        assert_eq!(xs(log.palms_at("camera0", 0.5)), [2.0, 3.0]);
+        assert_eq!(xs(log.palms_at("camera1", 9.0)), [4.0]);
+        assert_eq!(xs(log.palms_at("camera2", 9.0)), [0.0; 0]);
+        assert_eq!(log.palms_at("camera0", 0.5)[0].handedness, Handedness::Left);
+
+        assert!(DetectionLog::parse("{\"time\": 1}").is_err());
+        assert!(DetectionLog::parse("").is_err());
+    }
+}
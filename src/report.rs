@@ -0,0 +1,13 @@
+//! Output of the command-line modes, `--bench` and `eval`. Reports go to
+//! stdout line by line, apart from the log, so they can be piped on.
+
+use std::{
+    fmt::Arguments,
+    io::{self, Write},
+};
+
+/// Writes `line` and a newline to stdout. Fails once stdout is closed, e.g.
+/// when piped into `head`, rather than panicking like `println!`.
+pub fn report(line: Arguments) -> Result<(), String> {
+    writeln!(io::stdout().lock(), "{}", line).map_err(|e| format!("Failed to write report: {}", e))
+}
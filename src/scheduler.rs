@@ -0,0 +1,515 @@
+//! Runs several pipelines on the same frame. Their stages form a small
+//! dependency graph: the ROI crop they all share comes first, then the
+//! models, then stages combining model outputs. Stages whose inputs are
+//! ready run at the same time, batched per execution provider: each
+//! provider works through its stages on one thread, so GPU models queue
+//! for the device while CPU models run beside them.
+//!
+//! The crop, the models and the merging of their outputs are separate
+//! steps, [`Scheduler::crop`], [`Scheduler::infer`] and
+//! [`Postprocessor::detections`], so the detection worker can run each on
+//! a thread of its own.
+
+use image::RgbImage;
+use std::{sync::Arc, thread};
+
+use crate::{
+    bbox::BBox,
+    compare::Agreement,
+    depth::{DepthEstimator, DepthMap},
+    face_mesh::{FaceMesh, FaceMesher},
+    hand::{Hand, HandLandmarker},
+    inference::{self, Provider},
+    iris::{Face, IrisTracker},
+    palm::Palm,
+    pipeline::{Pipeline, PipelineSpec, SharedPalmDetector},
+    plugin::{self, PluginDetection, PluginId},
+    pose::{Pose, PoseEstimator},
+    worker::{Detections, Thresholds},
+    yolo::{Object, YoloDetector},
+};
+
+/// Node of the crop every detector reads.
+const CROP: usize = 0;
+
+enum Stage {
+    /// The ROI crop, made by [`Scheduler::crop`] before the models run.
+    Crop,
+    Palm(SharedPalmDetector),
+    Pose(PoseEstimator),
+    Yolo(YoloDetector),
+    Iris(IrisTracker),
+    FaceMesh(FaceMesher),
+    Depth(DepthEstimator),
+    Plugin(Box<dyn plugin::Pipeline>),
+    /// Landmarks of the hands around the palms of its dependency.
+    Hands(HandLandmarker),
+    /// How well the palms of the first dependency match those of the second.
+    Agreement,
+}
+
+enum Output {
+    /// `None` when detecting on the whole frame.
+    Crop(Option<RgbImage>),
+    Palms(Vec<Palm>),
+    Poses(Vec<Pose>),
+    Objects(Vec<Object>),
+    Faces(Vec<Face>),
+    Meshes(Vec<FaceMesh>),
+    Depth(Option<DepthMap>),
+    Plugin(Vec<PluginDetection>),
+    Hands(Vec<Hand>),
+    Agreement(Agreement),
+}
+
+struct Node {
+    stage: Stage,
+    deps: Vec<usize>,
+    /// Provider the stage runs on. Stages of the same lane run one after
+    /// the other.
+    lane: Option<Provider>,
+}
+
+impl Node {
+    /// Runs the stage on `roi` of `frame`, or all of it, given the outputs
+    /// of its dependencies. Detections are in frame coordinates.
+    fn run(
+        &mut self,
+        frame: &RgbImage,
+        roi: Option<BBox>,
+        outputs: &[Option<Output>],
+        thresholds: &Thresholds,
+    ) -> Output {
+        let img = match &outputs[CROP] {
+            Some(Output::Crop(Some(crop))) => crop,
+            _ => frame,
+        };
+        let (dx, dy) = roi.map_or((0.0, 0.0), |roi| (roi.x, roi.y));
+        // Weak palms are only drawn; nothing downstream runs on them.
+        let palms = |dep: usize| match &outputs[dep] {
+            Some(Output::Palms(palms)) => palms
+                .iter()
+                .filter(|palm| palm.score >= thresholds.palm_score)
+                .copied()
+                .collect(),
+            _ => vec![],
+        };
+        match &mut self.stage {
+            Stage::Crop => unreachable!("the crop is made before inference"),
+            Stage::Palm(detector) => Output::Palms(
+                detector
+                    .lock()
+                    .unwrap()
+                    .detect(
+                        img,
+                        thresholds.lowered(thresholds.palm_score),
+                        thresholds.palm_iou,
+                        thresholds.nms,
+                    )
+                    .iter()
+                    .map(|palm| palm.shift(dx, dy))
+                    .collect(),
+            ),
+            Stage::Pose(estimator) => Output::Poses(
+                estimator
+                    .estimate(img, thresholds.pose_score, thresholds.nms)
+                    .iter()
+                    .map(|pose| pose.shift(dx, dy))
+                    .collect(),
+            ),
+            Stage::Yolo(detector) => Output::Objects(
+                detector
+                    .detect(
+                        img,
+                        thresholds.lowered(thresholds.object_score),
+                        thresholds.object_iou,
+                        thresholds.nms,
+                    )
+                    .iter()
+                    .map(|object| object.shift(dx, dy))
+                    .collect(),
+            ),
+            Stage::Iris(tracker) => Output::Faces(
+                tracker
+                    .track(
+                        img,
+                        thresholds.lowered(thresholds.face_score),
+                        thresholds.nms,
+                    )
+                    .iter()
+                    .map(|face| face.shift(dx, dy))
+                    .collect(),
+            ),
+            Stage::FaceMesh(mesher) => Output::Meshes(
+                mesher
+                    .track(img, thresholds.face_score, thresholds.nms)
+                    .iter()
+                    .map(|mesh| mesh.shift(dx, dy))
+                    .collect(),
+            ),
+            Stage::Depth(estimator) => {
+                Output::Depth(estimator.estimate(img).map(|map| map.shift(dx, dy)))
+            }
+            Stage::Plugin(plugin) => Output::Plugin(
+                plugin::process(plugin.as_mut(), img)
+                    .iter()
+                    .map(|detection| detection.to_frame((1.0, 1.0), (dx, dy)))
+                    .collect(),
+            ),
+            // Palms are in frame coordinates, and hands may reach out of
+            // the ROI, so landmarks are found on the whole frame.
+            Stage::Hands(landmarker) => Output::Hands(landmarker.landmarks(
+                frame,
+                &palms(self.deps[0]),
+                thresholds.hand_presence,
+            )),
+            Stage::Agreement => Output::Agreement(Agreement::of_frame(
+                &palms(self.deps[0]),
+                &palms(self.deps[1]),
+                0.5,
+            )),
+        }
+    }
+}
+
+/// Stages of a wave that run on one provider, with their nodes.
+type Lane<'a> = (Option<Provider>, Vec<(usize, &'a mut Node)>);
+
+/// Nodes holding a pipeline's results.
+#[derive(Clone)]
+enum Sink {
+    Palms(usize),
+    Compare {
+        reference: usize,
+        candidate: usize,
+        agreement: usize,
+    },
+    Poses(usize),
+    Objects(usize),
+    Faces(usize),
+    Meshes(usize),
+    Depth(usize),
+    Plugin(PluginId, usize),
+    Hands {
+        palms: usize,
+        hands: usize,
+    },
+}
+
+/// Groups the nodes of a graph, given the dependencies of each, into waves
+/// that only depend on earlier waves. Panics on a cycle.
+fn waves(deps: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut placed = vec![false; deps.len()];
+    let mut waves = vec![];
+    while placed.contains(&false) {
+        let ready = (0..deps.len())
+            .filter(|&i| !placed[i] && deps[i].iter().all(|&dep| placed[dep]))
+            .collect::<Vec<_>>();
+        assert!(!ready.is_empty(), "stages depend on each other in a cycle");
+        for &i in &ready {
+            placed[i] = true;
+        }
+        waves.push(ready);
+    }
+    waves
+}
+
+/// Pipelines merged into one graph of stages.
+pub struct Scheduler {
+    specs: Vec<PipelineSpec>,
+    nodes: Vec<Node>,
+    sinks: Vec<Sink>,
+    waves: Vec<Vec<usize>>,
+    class_names: Arc<Vec<String>>,
+}
+
+impl Scheduler {
+    /// Builds the graph of `pipelines`, each loaded as its spec says. Palm
+    /// detectors shared by several pipelines, see
+    /// [`crate::pipeline::load_all`], run once for all of them.
+    pub fn new(pipelines: Vec<(PipelineSpec, Pipeline)>) -> Self {
+        let mut scheduler = Self {
+            specs: vec![],
+            nodes: vec![Node {
+                stage: Stage::Crop,
+                deps: vec![],
+                lane: None,
+            }],
+            sinks: vec![],
+            waves: vec![],
+            class_names: Arc::default(),
+        };
+        let mut palm_nodes = vec![];
+        for (spec, pipeline) in pipelines {
+            let lane = spec.provider.or_else(inference::default_provider);
+            let mut palm = |scheduler: &mut Self, detector: SharedPalmDetector| match palm_nodes
+                .iter()
+                .find(|(shared, _)| Arc::ptr_eq(shared, &detector))
+            {
+                Some(&(_, node)) => node,
+                None => {
+                    let node = scheduler.add(Stage::Palm(detector.clone()), vec![CROP], lane);
+                    palm_nodes.push((detector, node));
+                    node
+                }
+            };
+            let sink = match pipeline {
+                Pipeline::Palm(detector, _) => Sink::Palms(palm(&mut scheduler, detector)),
+                Pipeline::ComparePalm {
+                    reference,
+                    candidate,
+                    ..
+                } => {
+                    let reference = palm(&mut scheduler, reference);
+                    let candidate = palm(&mut scheduler, candidate);
+                    Sink::Compare {
+                        reference,
+                        candidate,
+                        agreement: scheduler.add(
+                            Stage::Agreement,
+                            vec![reference, candidate],
+                            None,
+                        ),
+                    }
+                }
+                Pipeline::Pose(estimator) => {
+                    Sink::Poses(scheduler.add(Stage::Pose(estimator), vec![CROP], lane))
+                }
+                Pipeline::Yolo(detector) => {
+                    scheduler.class_names = detector.labels();
+                    Sink::Objects(scheduler.add(Stage::Yolo(detector), vec![CROP], lane))
+                }
+                Pipeline::Iris(tracker) => {
+                    Sink::Faces(scheduler.add(Stage::Iris(tracker), vec![CROP], lane))
+                }
+                Pipeline::FaceMesh(mesher) => {
+                    Sink::Meshes(scheduler.add(Stage::FaceMesh(mesher), vec![CROP], lane))
+                }
+                Pipeline::Depth(estimator) => {
+                    Sink::Depth(scheduler.add(Stage::Depth(estimator), vec![CROP], lane))
+                }
+                Pipeline::Plugin(id, plugin) => {
+                    Sink::Plugin(id, scheduler.add(Stage::Plugin(plugin), vec![CROP], lane))
+                }
+                Pipeline::Hands {
+                    detector,
+                    landmarker,
+                    ..
+                } => {
+                    let palms = palm(&mut scheduler, detector);
+                    Sink::Hands {
+                        palms,
+                        hands: scheduler.add(Stage::Hands(landmarker), vec![palms], lane),
+                    }
+                }
+            };
+            scheduler.specs.push(spec);
+            scheduler.sinks.push(sink);
+        }
+        let deps = scheduler
+            .nodes
+            .iter()
+            .map(|node| node.deps.clone())
+            .collect::<Vec<_>>();
+        scheduler.waves = waves(&deps);
+        scheduler
+    }
+
+    fn add(&mut self, stage: Stage, deps: Vec<usize>, lane: Option<Provider>) -> usize {
+        self.nodes.push(Node { stage, deps, lane });
+        self.nodes.len() - 1
+    }
+
+    pub fn specs(&self) -> &[PipelineSpec] {
+        &self.specs
+    }
+
+    /// Names of the object detector's classes, if there is one.
+    pub fn class_names(&self) -> Arc<Vec<String>> {
+        self.class_names.clone()
+    }
+
+    /// Runs every pipeline on `roi` of `frame`, or all of it, with
+    /// detections of all of them in frame coordinates.
+    pub fn run(
+        &mut self,
+        frame: &RgbImage,
+        roi: Option<BBox>,
+        thresholds: &Thresholds,
+    ) -> Detections {
+        let outputs = self.infer(frame, Self::crop(frame, roi), roi, thresholds);
+        self.postprocessor().detections(&outputs, thresholds)
+    }
+
+    /// Cuts `roi` out of `frame` for the models, or `None` to detect on the
+    /// whole frame.
+    pub fn crop(frame: &RgbImage, roi: Option<BBox>) -> Option<RgbImage> {
+        roi.map(|roi| {
+            image::imageops::crop_imm(
+                frame,
+                roi.x as u32,
+                roi.y as u32,
+                roi.w as u32,
+                roi.h as u32,
+            )
+            .to_image()
+        })
+    }
+
+    /// Runs the models on `crop`, made from `roi` of `frame` by
+    /// [`Self::crop`].
+    pub fn infer(
+        &mut self,
+        frame: &RgbImage,
+        crop: Option<RgbImage>,
+        roi: Option<BBox>,
+        thresholds: &Thresholds,
+    ) -> Outputs {
+        let mut outputs = self.nodes.iter().map(|_| None).collect::<Vec<_>>();
+        outputs[CROP] = Some(Output::Crop(crop));
+        for wave in &self.waves {
+            let mut lanes: Vec<Lane> = vec![];
+            for (i, node) in self.nodes.iter_mut().enumerate() {
+                if !wave.contains(&i) || i == CROP {
+                    continue;
+                }
+                match lanes.iter_mut().find(|(lane, _)| *lane == node.lane) {
+                    Some((_, stages)) => stages.push((i, node)),
+                    None => lanes.push((node.lane, vec![(i, node)])),
+                }
+            }
+            let done = &outputs;
+            let run_lane = move |stages: Vec<(usize, &mut Node)>| {
+                stages
+                    .into_iter()
+                    .map(|(i, node)| (i, node.run(frame, roi, done, thresholds)))
+                    .collect::<Vec<_>>()
+            };
+            let finished = if lanes.len() == 1 {
+                run_lane(lanes.pop().unwrap().1)
+            } else {
+                thread::scope(|scope| {
+                    let lanes = lanes
+                        .into_iter()
+                        .map(|(_, stages)| scope.spawn(move || run_lane(stages)))
+                        .collect::<Vec<_>>();
+                    lanes
+                        .into_iter()
+                        .flat_map(|lane| lane.join().unwrap())
+                        .collect()
+                })
+            };
+            for (i, output) in finished {
+                outputs[i] = Some(output);
+            }
+        }
+        Outputs(outputs)
+    }
+
+    /// What turns the outputs of [`Self::infer`] into detections.
+    pub fn postprocessor(&self) -> Postprocessor {
+        Postprocessor {
+            sinks: self.sinks.clone(),
+        }
+    }
+}
+
+/// Outputs of every stage for one frame.
+pub struct Outputs(Vec<Option<Output>>);
+
+/// Collects the detections of every pipeline from the outputs of its
+/// stages, apart from the [`Scheduler`] running the models.
+pub struct Postprocessor {
+    sinks: Vec<Sink>,
+}
+
+impl Postprocessor {
+    /// Detections of every pipeline in `outputs`, with weak ones split off.
+    pub fn detections(&self, outputs: &Outputs, thresholds: &Thresholds) -> Detections {
+        let mut detections = self.merge(&outputs.0);
+        detections.split_weak(thresholds);
+        detections
+    }
+
+    /// Collects the results of every pipeline.
+    fn merge(&self, outputs: &[Option<Output>]) -> Detections {
+        let palms = |i: usize| match &outputs[i] {
+            Some(Output::Palms(palms)) => palms.as_slice(),
+            _ => &[],
+        };
+        let mut detections = Detections::default();
+        for sink in &self.sinks {
+            match *sink {
+                Sink::Palms(i) => detections.palms.extend_from_slice(palms(i)),
+                Sink::Compare {
+                    reference,
+                    candidate,
+                    agreement,
+                } => {
+                    detections.palms.extend_from_slice(palms(reference));
+                    detections.compared.extend_from_slice(palms(candidate));
+                    if let Some(Output::Agreement(agreement)) = &outputs[agreement] {
+                        detections.agreement = *agreement;
+                    }
+                }
+                Sink::Poses(i) => {
+                    if let Some(Output::Poses(poses)) = &outputs[i] {
+                        detections.poses.extend_from_slice(poses);
+                    }
+                }
+                Sink::Objects(i) => {
+                    if let Some(Output::Objects(objects)) = &outputs[i] {
+                        detections.objects.extend_from_slice(objects);
+                    }
+                }
+                Sink::Faces(i) => {
+                    if let Some(Output::Faces(faces)) = &outputs[i] {
+                        detections.faces.extend_from_slice(faces);
+                    }
+                }
+                Sink::Meshes(i) => {
+                    if let Some(Output::Meshes(meshes)) = &outputs[i] {
+                        detections.meshes.extend_from_slice(meshes);
+                    }
+                }
+                Sink::Depth(i) => {
+                    if let Some(Output::Depth(map)) = &outputs[i] {
+                        detections.depth.clone_from(map);
+                    }
+                }
+                Sink::Plugin(id, i) => {
+                    if let Some(Output::Plugin(found)) = &outputs[i] {
+                        detections.plugins.push((id, found.clone()));
+                    }
+                }
+                Sink::Hands { palms: p, hands } => {
+                    detections.palms.extend_from_slice(palms(p));
+                    if let Some(Output::Hands(hands)) = &outputs[hands] {
+                        detections.hands.extend_from_slice(hands);
+                    }
+                }
+            }
+        }
+        detections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waves_follow_dependencies() {
+        // A crop, three stages reading it and one combining two of them.
+        let deps = [vec![], vec![0], vec![0], vec![1, 2], vec![0]];
+        assert_eq!(waves(&deps), [vec![0], vec![1, 2, 4], vec![3]]);
+        // Nodes may come before their dependencies.
+        assert_eq!(waves(&[vec![1], vec![]]), [vec![1], vec![0]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle")]
+    fn cycles_are_rejected() {
+        waves(&[vec![1], vec![0]]);
+    }
+}
@@ -0,0 +1,222 @@
+//! Selfie segmentation and video-call style background effects.
+
+use image::{imageops::FilterType, GrayImage, RgbImage};
+use std::path::Path;
+use tracing::error;
+
+use crate::{
+    inference::{self, Session},
+    models::{ModelManager, SELFIE_SEGMENTATION},
+    preprocess::{Normalization, Resizer},
+};
+
+const INPUT_SIZE: usize = 256;
+/// The background is blurred at this fraction of the frame size, which is
+/// much cheaper and indistinguishable at video-call blur strengths.
+const BLUR_DOWNSCALE: u32 = 4;
+
+/// MediaPipe's selfie segmentation: a person-vs-background probability per
+/// pixel.
+pub struct Segmenter {
+    session: Session,
+    resizer: Resizer,
+}
+
+impl Segmenter {
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, String> {
+        Ok(Self {
+            session: inference::create_session(model_path)?,
+            resizer: Resizer::new(INPUT_SIZE, INPUT_SIZE),
+        })
+    }
+
+    /// Foreground mask scaled to the size of `img`, 255 where a person is.
+    pub fn mask(&mut self, img: &RgbImage) -> GrayImage {
+        let input = self.resizer.resize(img, Normalization::UNIT);
+        let mut outputs = inference::run(&*self.session, input);
+        let mask = outputs.swap_remove(0);
+        let mask = GrayImage::from_raw(
+            INPUT_SIZE as u32,
+            INPUT_SIZE as u32,
+            mask.iter()
+                .map(|p| (p.clamp(0.0, 1.0) * 255.0) as u8)
+                .collect(),
+        )
+        .unwrap();
+        image::imageops::resize(&mask, img.width(), img.height(), FilterType::Triangle)
+    }
+}
+
+/// Blends `background` into `frame` wherever `mask` is below 255.
+pub fn composite(frame: &mut RgbImage, mask: &GrayImage, background: &RgbImage) {
+    for ((fg, m), bg) in frame
+        .pixels_mut()
+        .zip(mask.pixels())
+        .zip(background.pixels())
+    {
+        let alpha = m[0] as u32;
+        for c in 0..3 {
+            fg[c] = ((fg[c] as u32 * alpha + bg[c] as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackgroundMode {
+    Off,
+    Blur,
+    Color,
+    Image,
+}
+
+/// Replaces everything but the people in a frame.
+pub struct Background {
+    pub mode: BackgroundMode,
+    pub blur_sigma: f32,
+    pub color: [u8; 3],
+    pub image_path: String,
+    /// Loaded replacement image, and its copy resized to the last frame.
+    image: Option<(RgbImage, RgbImage)>,
+    segmenter: Option<Segmenter>,
+    error: Option<String>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            mode: BackgroundMode::Off,
+            blur_sigma: 8.0,
+            color: [0, 177, 64],
+            image_path: String::new(),
+            image: None,
+            segmenter: None,
+            error: None,
+        }
+    }
+}
+
+impl Background {
+    /// Loads the segmentation model once a mode that needs it is selected.
+    pub fn load(&mut self, models: &mut ModelManager) {
+        if self.mode == BackgroundMode::Off || self.segmenter.is_some() {
+            return;
+        }
+        let Some(path) = models.require(SELFIE_SEGMENTATION) else {
+            return;
+        };
+        match Segmenter::new(path) {
+            Ok(segmenter) => self.segmenter = Some(segmenter),
+            Err(e) => {
+                error!("Failed to load the segmentation model: {}", e);
+                self.mode = BackgroundMode::Off;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, frame: &mut RgbImage) {
+        if self.mode == BackgroundMode::Off {
+            return;
+        }
+        let Some(segmenter) = &mut self.segmenter else {
+            return;
+        };
+        if self.mode == BackgroundMode::Image && self.image.is_none() {
+            return;
+        }
+        let mask = segmenter.mask(frame);
+        let (w, h) = frame.dimensions();
+        match self.mode {
+            BackgroundMode::Off => {}
+            BackgroundMode::Blur => {
+                let small = image::imageops::resize(
+                    frame,
+                    (w / BLUR_DOWNSCALE).max(1),
+                    (h / BLUR_DOWNSCALE).max(1),
+                    FilterType::Triangle,
+                );
+                let blurred =
+                    image::imageops::blur(&small, self.blur_sigma / BLUR_DOWNSCALE as f32);
+                let background = image::imageops::resize(&blurred, w, h, FilterType::Triangle);
+                composite(frame, &mask, &background);
+            }
+            BackgroundMode::Color => {
+                let background = RgbImage::from_pixel(w, h, image::Rgb(self.color));
+                composite(frame, &mask, &background);
+            }
+            BackgroundMode::Image => {
+                if let Some((original, resized)) = &mut self.image {
+                    if resized.dimensions() != (w, h) {
+                        *resized = image::imageops::resize(original, w, h, FilterType::Triangle);
+                    }
+                    composite(frame, &mask, resized);
+                }
+            }
+        }
+    }
+
+    fn load_image(&mut self) {
+        self.image = None;
+        self.error = None;
+        match image::open(&self.image_path) {
+            Ok(img) => {
+                let img = img.to_rgb8();
+                self.image = Some((img, RgbImage::new(0, 0)));
+            }
+            Err(e) => {
+                error!("Failed to load background {}: {}", self.image_path, e);
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        for (mode, name) in [
+            (BackgroundMode::Off, "Off"),
+            (BackgroundMode::Blur, "Blur"),
+            (BackgroundMode::Color, "Solid color"),
+            (BackgroundMode::Image, "Image"),
+        ] {
+            ui.radio_value(&mut self.mode, mode, name);
+        }
+        match self.mode {
+            BackgroundMode::Off => {}
+            BackgroundMode::Blur => {
+                ui.add(egui::Slider::new(&mut self.blur_sigma, 1.0..=32.0).text("Strength"));
+            }
+            BackgroundMode::Color => {
+                ui.color_edit_button_srgb(&mut self.color);
+            }
+            BackgroundMode::Image => {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.image_path);
+                    if ui.button("Load").clicked() {
+                        self.load_image();
+                    }
+                });
+            }
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+        if self.mode != BackgroundMode::Off && self.segmenter.is_none() {
+            ui.label("Waiting for the segmentation model");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_blends_by_mask() {
+        let mut frame = RgbImage::from_pixel(3, 1, image::Rgb([200, 100, 0]));
+        let mask = GrayImage::from_raw(3, 1, vec![255, 0, 128]).unwrap();
+        let background = RgbImage::from_pixel(3, 1, image::Rgb([0, 100, 255]));
+        composite(&mut frame, &mask, &background);
+        assert_eq!(frame.get_pixel(0, 0).0, [200, 100, 0]);
+        assert_eq!(frame.get_pixel(1, 0).0, [0, 100, 255]);
+        assert_eq!(frame.get_pixel(2, 0).0, [100, 100, 127]);
+    }
+}
@@ -0,0 +1,235 @@
+//! Publishes the annotated feed for other apps, as a virtual webcam that
+//! video call and streaming software can pick like any other camera.
+//!
+//! Only v4l2loopback on Linux is supported. OBS' virtual camera on Windows
+//! and macOS is fed through its own shared memory and system extension
+//! protocols, which are left out; there is no virtual camera on those
+//! platforms.
+
+use image::RgbImage;
+#[cfg(target_os = "linux")]
+use tracing::error;
+
+/// Somewhere frames can be sent to.
+pub trait VideoSink: Send {
+    /// Sends one frame, of the size the sink was opened with.
+    fn write(&mut self, frame: &RgbImage) -> Result<(), String>;
+}
+
+/// Converts `frame` to YUYV 4:2:2 with BT.601 limited range, the format
+/// webcam consumers accept most widely. An odd last column is dropped.
+pub fn rgb_to_yuyv(frame: &RgbImage, out: &mut Vec<u8>) {
+    let (w, h) = frame.dimensions();
+    out.clear();
+    out.reserve((w / 2 * 4 * h) as usize);
+    let y = |p: &[i32]| (((66 * p[0] + 129 * p[1] + 25 * p[2] + 128) >> 8) + 16) as u8;
+    for row in frame.as_raw().chunks_exact(w as usize * 3) {
+        for pair in row.chunks_exact(6) {
+            let pair = <[u8; 6]>::try_from(pair).unwrap().map(i32::from);
+            let (left, right) = pair.split_at(3);
+            let [r, g, b] = [0, 1, 2].map(|c| (left[c] + right[c]) / 2);
+            let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+            let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+            out.extend_from_slice(&[y(left), u as u8, y(right), v as u8]);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod v4l2 {
+    use std::{
+        fs::{File, OpenOptions},
+        io::Write,
+        os::fd::AsRawFd,
+    };
+
+    use image::RgbImage;
+
+    use super::{rgb_to_yuyv, VideoSink};
+
+    const BUF_TYPE_VIDEO_OUTPUT: u32 = 2;
+    const FIELD_NONE: u32 = 1;
+    const COLORSPACE_SRGB: u32 = 8;
+    const PIX_FMT_YUYV: u32 = u32::from_le_bytes(*b"YUYV");
+
+    /// `struct v4l2_pix_format`.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PixFormat {
+        width: u32,
+        height: u32,
+        pixelformat: u32,
+        field: u32,
+        bytesperline: u32,
+        sizeimage: u32,
+        colorspace: u32,
+        priv_: u32,
+        flags: u32,
+        ycbcr_enc: u32,
+        quantization: u32,
+        xfer_func: u32,
+    }
+
+    /// `struct v4l2_format` with the `pix` member of its format union. The
+    /// union is 200 bytes and pointer aligned.
+    #[repr(C)]
+    struct Format {
+        kind: u32,
+        _align: [usize; 0],
+        pix: PixFormat,
+        _rest: [u8; 200 - std::mem::size_of::<PixFormat>()],
+    }
+
+    /// `VIDIOC_S_FMT`, `_IOWR('V', 5, struct v4l2_format)`.
+    const VIDIOC_S_FMT: u64 =
+        3 << 30 | (std::mem::size_of::<Format>() as u64) << 16 | (b'V' as u64) << 8 | 5;
+
+    /// An output device of the v4l2loopback kernel module.
+    pub struct Loopback {
+        file: File,
+        buf: Vec<u8>,
+    }
+
+    impl Loopback {
+        pub fn open(device: &str, width: u32, height: u32) -> Result<Self, String> {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(device)
+                .map_err(|e| format!("{}: {}", device, e))?;
+            let width = width & !1;
+            let mut format = Format {
+                kind: BUF_TYPE_VIDEO_OUTPUT,
+                _align: [],
+                pix: PixFormat {
+                    width,
+                    height,
+                    pixelformat: PIX_FMT_YUYV,
+                    field: FIELD_NONE,
+                    bytesperline: width * 2,
+                    sizeimage: width * 2 * height,
+                    colorspace: COLORSPACE_SRGB,
+                    ..Default::default()
+                },
+                _rest: [0; 200 - std::mem::size_of::<PixFormat>()],
+            };
+            // SAFETY: `format` matches the kernel's layout and outlives the call.
+            let result = unsafe { libc::ioctl(file.as_raw_fd(), VIDIOC_S_FMT as _, &mut format) };
+            if result < 0 {
+                let e = std::io::Error::last_os_error();
+                return Err(format!("{} is no v4l2loopback device: {}", device, e));
+            }
+            Ok(Self { file, buf: vec![] })
+        }
+    }
+
+    impl VideoSink for Loopback {
+        fn write(&mut self, frame: &RgbImage) -> Result<(), String> {
+            rgb_to_yuyv(frame, &mut self.buf);
+            self.file.write_all(&self.buf).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Default device of [`open_virtual_camera`].
+#[cfg(target_os = "linux")]
+pub const DEFAULT_DEVICE: &str = "/dev/video10";
+
+/// Opens the virtual camera `device` for frames of `width`×`height`.
+#[cfg(target_os = "linux")]
+pub fn open_virtual_camera(
+    device: &str,
+    width: u32,
+    height: u32,
+) -> Result<Box<dyn VideoSink>, String> {
+    Ok(Box::new(v4l2::Loopback::open(device, width, height)?))
+}
+
+/// Feeds frames to a virtual camera while it is started, reopening it when
+/// the frame size changes.
+#[cfg(target_os = "linux")]
+pub struct VirtualCamera {
+    pub device: String,
+    pub enabled: bool,
+    /// Open sink and the frame size it was opened with.
+    sink: Option<(Box<dyn VideoSink>, (u32, u32))>,
+    error: Option<String>,
+}
+
+#[cfg(target_os = "linux")]
+impl VirtualCamera {
+    pub fn new(device: impl Into<String>) -> Self {
+        Self {
+            device: device.into(),
+            enabled: false,
+            sink: None,
+            error: None,
+        }
+    }
+
+    pub fn send(&mut self, frame: &RgbImage) {
+        if !self.enabled {
+            self.sink = None;
+            return;
+        }
+        let size = frame.dimensions();
+        if self.sink.as_ref().map(|(_, opened)| *opened) != Some(size) {
+            match open_virtual_camera(&self.device, size.0, size.1) {
+                Ok(sink) => {
+                    self.sink = Some((sink, size));
+                    self.error = None;
+                }
+                Err(e) => {
+                    error!("Failed to open virtual camera: {}", e);
+                    self.enabled = false;
+                    self.error = Some(e);
+                    return;
+                }
+            }
+        }
+        if let Some((sink, _)) = &mut self.sink {
+            if let Err(e) = sink.write(frame) {
+                error!("Failed to write to virtual camera: {}", e);
+                self.sink = None;
+                self.enabled = false;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Device");
+            ui.add_enabled(
+                self.sink.is_none(),
+                egui::TextEdit::singleline(&mut self.device),
+            );
+        });
+        ui.checkbox(&mut self.enabled, "Send annotated feed");
+        if let Some(e) = &self.error {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yuyv_uses_limited_range() {
+        let mut frame = RgbImage::new(3, 2);
+        frame.put_pixel(0, 0, image::Rgb([255, 255, 255]));
+        frame.put_pixel(1, 0, image::Rgb([255, 255, 255]));
+        let mut out = vec![];
+        rgb_to_yuyv(&frame, &mut out);
+        // The odd third column is dropped.
+        assert_eq!(out.len(), 2 * 4);
+        assert_eq!(out[..4], [235, 128, 235, 128]);
+        assert_eq!(out[4..], [16, 128, 16, 128]);
+
+        frame.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        frame.put_pixel(1, 0, image::Rgb([255, 0, 0]));
+        rgb_to_yuyv(&frame, &mut out);
+        assert_eq!(out[..4], [82, 90, 82, 240]);
+    }
+}
@@ -0,0 +1,118 @@
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+use crate::{
+    face_mesh::FaceMesh,
+    hand::Hand,
+    iris::Face,
+    palm::Palm,
+    plugin::{PluginDetection, PluginId},
+    pose::Pose,
+    stream::StreamState,
+    yolo::Object,
+};
+
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    /// Milliseconds since the Unix epoch.
+    timestamp: u128,
+    source: String,
+    width: u32,
+    height: u32,
+    palms: &'a [Palm],
+    poses: &'a [Pose],
+    objects: &'a [Object],
+    faces: &'a [Face],
+    meshes: &'a [FaceMesh],
+    hands: &'a [Hand],
+    plugins: &'a [(PluginId, Vec<PluginDetection>)],
+}
+
+/// Saves raw frames, annotated frames and their detections for building
+/// evaluation datasets.
+pub struct Snapshotter {
+    pub dir: String,
+    /// Timestamp of a requested snapshot that hasn't been taken yet.
+    pending: Option<u128>,
+    status: String,
+}
+
+impl Snapshotter {
+    pub fn new(dir: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            pending: None,
+            status: String::new(),
+        }
+    }
+
+    pub fn request(&mut self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.pending = Some(now.as_millis());
+    }
+
+    /// Timestamp of the snapshot to take this frame, if any.
+    pub fn take_pending(&mut self) -> Option<u128> {
+        self.pending.take()
+    }
+
+    /// Saves the raw and annotated frame of `stream` and its detections.
+    pub fn save(&mut self, timestamp: u128, stream: &StreamState) {
+        let source = stream.id.slug();
+        let (raw, annotated) = (&stream.frame, &stream.annotated);
+        let base = format!("{}_{}", timestamp, source);
+        let dir = PathBuf::from(&self.dir);
+        let sidecar = Sidecar {
+            timestamp,
+            source,
+            width: raw.width(),
+            height: raw.height(),
+            palms: &stream.palms,
+            poses: &stream.poses,
+            objects: &stream.objects,
+            faces: &stream.faces,
+            meshes: &stream.meshes,
+            hands: &stream.hands,
+            plugins: &stream.plugins,
+        };
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                raw.save(dir.join(format!("{}_raw.png", base)))
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|_| {
+                annotated
+                    .save(dir.join(format!("{}_annotated.png", base)))
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|_| {
+                let json = serde_json::to_string_pretty(&sidecar).unwrap();
+                std::fs::write(dir.join(format!("{}.json", base)), json).map_err(|e| e.to_string())
+            });
+        self.status = match result {
+            Ok(()) => format!("Saved {}", base),
+            Err(e) => {
+                error!("Failed to save snapshot: {}", e);
+                format!("Failed: {}", e)
+            }
+        };
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Directory");
+            ui.text_edit_singleline(&mut self.dir);
+        });
+        if ui.button("Snapshot (F12)").clicked() {
+            self.request();
+        }
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+    }
+}
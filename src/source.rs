@@ -0,0 +1,726 @@
+use image::{RgbImage, RgbaImage};
+#[cfg(feature = "native")]
+use nokhwa::{
+    utils::{
+        ApiBackend, CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType,
+    },
+    Camera, NokhwaError,
+};
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+#[cfg(feature = "native")]
+use tracing::{info, warn};
+
+use crate::worker::Detections;
+#[cfg(feature = "native")]
+use crate::{
+    color::{self, Mode, PixelFormat},
+    network::without_credentials,
+};
+
+/// How long [`LatestFrame::read`] waits for a frame before giving up.
+const FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+/// Wait before looking for an unplugged camera again, doubled after every
+/// further attempt up to [`MAX_RECONNECT`].
+#[cfg(feature = "native")]
+const MIN_RECONNECT: Duration = Duration::from_millis(500);
+#[cfg(feature = "native")]
+const MAX_RECONNECT: Duration = Duration::from_secs(8);
+
+/// Identifies where a stream's frames come from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceId {
+    #[cfg(feature = "native")]
+    Camera(CameraIndex),
+    Monitor(u32),
+    Window(u32),
+    Folder(PathBuf),
+    /// URL of a network stream.
+    #[cfg(feature = "native")]
+    Network(String),
+    /// Video of a replayed session.
+    Replay(PathBuf),
+    /// The browser's camera.
+    UserMedia,
+}
+
+impl SourceId {
+    /// Short name without spaces, for file names.
+    pub fn slug(&self) -> String {
+        match self {
+            #[cfg(feature = "native")]
+            SourceId::Camera(index) => format!("camera{}", index),
+            SourceId::Monitor(id) => format!("monitor{}", id),
+            SourceId::Window(id) => format!("window{}", id),
+            SourceId::Folder(dir) => {
+                let name = dir.file_name().unwrap_or_default().to_string_lossy();
+                let name = name.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+                format!("folder-{}", name)
+            }
+            #[cfg(feature = "native")]
+            SourceId::Network(url) => {
+                let url = without_credentials(url);
+                let host = url.split("://").last().unwrap_or_default();
+                let host = host.split(['/', ':']).next().unwrap_or_default();
+                let host = host.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+                format!("network-{}", host)
+            }
+            SourceId::Replay(video) => {
+                let name = video.file_stem().unwrap_or_default().to_string_lossy();
+                let name = name.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+                format!("replay-{}", name)
+            }
+            SourceId::UserMedia => "browser-camera".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for SourceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "native")]
+            SourceId::Camera(index) => write!(f, "Camera {}", index),
+            SourceId::Monitor(id) => write!(f, "Monitor {}", id),
+            SourceId::Window(id) => write!(f, "Window {}", id),
+            SourceId::Folder(dir) => write!(f, "{}", dir.display()),
+            #[cfg(feature = "native")]
+            SourceId::Network(url) => write!(f, "{}", without_credentials(url)),
+            SourceId::Replay(video) => write!(f, "Replay of {}", video.display()),
+            SourceId::UserMedia => write!(f, "Browser camera"),
+        }
+    }
+}
+
+/// Something that produces frames for a stream.
+pub trait FrameSource {
+    fn id(&self) -> SourceId;
+
+    /// Writes the next frame into `frame`, reallocating it if the size changed.
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>>;
+
+    /// Like [`Self::read`], but returns `false` instead of waiting if no new
+    /// frame is ready. Sources that can't tell just read.
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        self.read(frame).map(|()| true)
+    }
+
+    /// Moves `frames` frames forward or back, for stepping through a paused
+    /// stream. Live sources can only go on to their next frame.
+    fn seek(&mut self, _frames: isize) {}
+
+    /// Captures at most `width` pixels wide from now on, or as wide as
+    /// possible for `None`, for sources that can choose.
+    fn set_max_width(&mut self, _width: Option<u32>) {}
+
+    /// Whether [`Self::seek`] can go back.
+    fn seekable(&self) -> bool {
+        false
+    }
+
+    /// Frames produced but never read, because newer ones replaced them.
+    fn dropped(&self) -> u64 {
+        0
+    }
+
+    /// Detections recorded with the frame last read, for sources replaying
+    /// a session. Streams show them instead of running their pipelines.
+    fn recorded(&self) -> Option<Detections> {
+        None
+    }
+
+    /// Source specific controls, shown above the stream.
+    fn ui(&mut self, _ui: &mut egui::Ui) {}
+}
+
+#[cfg(feature = "native")]
+pub struct CameraSource {
+    index: CameraIndex,
+    /// Name of the device, to find it again once it is plugged back in,
+    /// possibly under another index.
+    name: String,
+    /// `None` from losing the device until it is reopened.
+    camera: Option<Camera>,
+    backoff: Backoff,
+    max_width: Option<u32>,
+}
+
+#[cfg(feature = "native")]
+impl CameraSource {
+    pub fn open(index: CameraIndex) -> Result<Self, NokhwaError> {
+        let camera = Self::start(index.clone(), None)?;
+        Ok(Self {
+            index,
+            name: camera.info().human_name(),
+            camera: Some(camera),
+            backoff: Backoff::new(MIN_RECONNECT, MAX_RECONNECT),
+            max_width: None,
+        })
+    }
+
+    /// Opens the camera in the mode [`color::negotiate`] picks among those
+    /// it offers.
+    fn start(index: CameraIndex, max_width: Option<u32>) -> Result<Camera, NokhwaError> {
+        let requested =
+            RequestedFormat::with_formats(RequestedFormatType::AbsoluteHighestFrameRate, &FORMATS);
+        let mut camera = Camera::new(index, requested)?;
+        let name = camera.info().human_name();
+        match camera.compatible_camera_formats() {
+            Ok(formats) => {
+                let modes = formats.iter().map(mode).collect::<Vec<_>>();
+                if let Some(best) = color::negotiate(&modes, max_width) {
+                    let exact = RequestedFormatType::Exact(formats[best]);
+                    camera.set_camera_requset(RequestedFormat::with_formats(exact, &FORMATS))?;
+                }
+            }
+            Err(e) => warn!("Failed to list the formats of camera {}: {}", name, e),
+        }
+        camera.open_stream()?;
+        let Mode { format, size, fps } = mode(&camera.camera_format());
+        info!(
+            "Camera {} captures {} {}x{} at {} fps",
+            name,
+            format.name(),
+            size.0,
+            size.1,
+            fps
+        );
+        Ok(camera)
+    }
+
+    /// Lists the cameras again and opens the one named like the lost
+    /// device, preferring its old index if several are.
+    fn reconnect(&self) -> Result<Camera, Box<dyn Error>> {
+        let cameras = nokhwa::query(ApiBackend::Auto)?;
+        let info = cameras
+            .iter()
+            .filter(|info| info.human_name() == self.name)
+            .min_by_key(|info| *info.index() != self.index)
+            .ok_or("not plugged in")?;
+        Ok(Self::start(info.index().clone(), self.max_width)?)
+    }
+}
+
+#[cfg(feature = "native")]
+impl FrameSource for CameraSource {
+    fn id(&self) -> SourceId {
+        SourceId::Camera(self.index.clone())
+    }
+
+    /// Reads the next frame. A camera that fails is taken as unplugged and
+    /// looked for again on every further read, waiting longer each time.
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        let camera = match &mut self.camera {
+            Some(camera) => camera,
+            None => {
+                thread::sleep(self.backoff.wait());
+                let camera = self
+                    .reconnect()
+                    .map_err(|e| format!("camera disconnected: {}", e))?;
+                info!("Reconnected to camera {}", self.name);
+                self.backoff.reset();
+                self.camera.insert(camera)
+            }
+        };
+        let buffer = match camera.frame() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                warn!("Lost camera {}: {}", self.name, e);
+                self.camera = None;
+                return Err(e.into());
+            }
+        };
+        let resolution = buffer.resolution();
+        let size = (resolution.width(), resolution.height());
+        let format = pixel_format(buffer.source_frame_format());
+        Ok(color::to_rgb(format, buffer.buffer(), size, frame)?)
+    }
+
+    fn set_max_width(&mut self, width: Option<u32>) {
+        if width == self.max_width {
+            return;
+        }
+        self.max_width = width;
+        // The format only changes on a closed stream.
+        self.camera = None;
+        match Self::start(self.index.clone(), width) {
+            Ok(camera) => self.camera = Some(camera),
+            Err(e) => warn!("Failed to reopen camera {}: {}", self.name, e),
+        }
+    }
+}
+
+/// Every format cameras are opened with; [`color::to_rgb`] converts them.
+#[cfg(feature = "native")]
+const FORMATS: [FrameFormat; 6] = [
+    FrameFormat::RAWRGB,
+    FrameFormat::RAWBGR,
+    FrameFormat::YUYV,
+    FrameFormat::NV12,
+    FrameFormat::GRAY,
+    FrameFormat::MJPEG,
+];
+
+#[cfg(feature = "native")]
+fn pixel_format(format: FrameFormat) -> PixelFormat {
+    match format {
+        FrameFormat::RAWRGB => PixelFormat::Rgb,
+        FrameFormat::RAWBGR => PixelFormat::Bgr,
+        FrameFormat::YUYV => PixelFormat::Yuyv,
+        FrameFormat::NV12 => PixelFormat::Nv12,
+        FrameFormat::GRAY => PixelFormat::Gray,
+        FrameFormat::MJPEG => PixelFormat::Mjpeg,
+    }
+}
+
+#[cfg(feature = "native")]
+fn mode(format: &CameraFormat) -> Mode {
+    Mode {
+        format: pixel_format(format.format()),
+        size: (format.width(), format.height()),
+        fps: format.frame_rate(),
+    }
+}
+
+/// Captures a whole monitor or a single window.
+#[cfg(feature = "native")]
+#[derive(Clone)]
+pub enum ScreenSource {
+    Monitor(xcap::Monitor),
+    Window(xcap::Window),
+}
+
+#[cfg(feature = "native")]
+impl ScreenSource {
+    /// Human readable name for the screen picker.
+    pub fn label(&self) -> String {
+        match self {
+            ScreenSource::Monitor(monitor) => format!(
+                "{} ({}x{})",
+                monitor.name(),
+                monitor.width(),
+                monitor.height()
+            ),
+            ScreenSource::Window(window) => format!("{} - {}", window.app_name(), window.title()),
+        }
+    }
+
+    /// All monitors followed by all visible windows.
+    pub fn all() -> Result<Vec<Self>, xcap::XCapError> {
+        let monitors = xcap::Monitor::all()?.into_iter().map(ScreenSource::Monitor);
+        let windows = xcap::Window::all()?
+            .into_iter()
+            .filter(|window| !window.is_minimized() && window.width() > 0 && window.height() > 0)
+            .map(ScreenSource::Window);
+        Ok(monitors.chain(windows).collect())
+    }
+}
+
+#[cfg(feature = "native")]
+impl FrameSource for ScreenSource {
+    fn id(&self) -> SourceId {
+        match self {
+            ScreenSource::Monitor(monitor) => SourceId::Monitor(monitor.id()),
+            ScreenSource::Window(window) => SourceId::Window(window.id()),
+        }
+    }
+
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        let captured = match self {
+            ScreenSource::Monitor(monitor) => monitor.capture_image()?,
+            ScreenSource::Window(window) => window.capture_image()?,
+        };
+        drop_alpha(&captured, frame);
+        Ok(())
+    }
+}
+
+/// Steps through the images of a directory, for looking at a fixed test set
+/// instead of a camera. Every read returns the current image again.
+pub struct ImageFolder {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+    index: usize,
+    /// Decoded current image, or why it couldn't be decoded.
+    current: Option<Result<RgbImage, String>>,
+    /// Whether a decode error was reported by the last read.
+    reported: bool,
+}
+
+impl ImageFolder {
+    /// Lists the images in `dir`, ordered by file name.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref().to_owned();
+        let mut files = std::fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+            .collect::<Vec<_>>();
+        if files.is_empty() {
+            return Err(format!("no images in {}", dir.display()));
+        }
+        files.sort();
+        Ok(Self {
+            dir,
+            files,
+            index: 0,
+            current: None,
+            reported: false,
+        })
+    }
+
+    pub fn current_path(&self) -> &Path {
+        &self.files[self.index]
+    }
+
+    /// Moves `step` images forward or back, wrapping around at the ends.
+    pub fn go(&mut self, step: isize) {
+        let n = self.files.len() as isize;
+        self.index = (self.index as isize + step).rem_euclid(n) as usize;
+        self.current = None;
+        self.reported = false;
+    }
+}
+
+impl FrameSource for ImageFolder {
+    fn id(&self) -> SourceId {
+        SourceId::Folder(self.dir.clone())
+    }
+
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        match self.try_read(frame)? {
+            true => Ok(()),
+            false => Err(format!("can't read {}", self.current_path().display()).into()),
+        }
+    }
+
+    /// Reports an unreadable image once, then returns `false` until another
+    /// image is selected.
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        let path = &self.files[self.index];
+        let current = self.current.get_or_insert_with(|| {
+            image::open(path)
+                .map(|img| img.to_rgb8())
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        });
+        match current {
+            Ok(img) => {
+                frame.clone_from(img);
+                Ok(true)
+            }
+            Err(_) if self.reported => Ok(false),
+            Err(e) => {
+                self.reported = true;
+                Err(e.clone().into())
+            }
+        }
+    }
+
+    fn seek(&mut self, frames: isize) {
+        self.go(frames);
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    /// Previous and next buttons, also on Page Up and Page Down.
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let (back, forward) = ui.input(|i| {
+            (
+                i.key_pressed(egui::Key::PageUp),
+                i.key_pressed(egui::Key::PageDown),
+            )
+        });
+        if ui.button("◀").clicked() || back {
+            self.go(-1);
+        }
+        if ui.button("▶").clicked() || forward {
+            self.go(1);
+        }
+        let name = self.current_path().file_name().unwrap_or_default();
+        ui.label(format!(
+            "{}/{} {}",
+            self.index + 1,
+            self.files.len(),
+            name.to_string_lossy()
+        ));
+    }
+}
+
+/// Wait before retrying something that failed, doubled after every further
+/// failure up to a maximum.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max,
+            next: min,
+        }
+    }
+
+    /// How long to wait before this attempt.
+    pub fn wait(&mut self) -> Duration {
+        let wait = self.next;
+        self.next = (wait * 2).min(self.max);
+        wait
+    }
+
+    /// Starts over from the shortest wait, after a success.
+    pub fn reset(&mut self) {
+        self.next = self.min;
+    }
+}
+
+pub(crate) fn drop_alpha(src: &RgbaImage, dst: &mut RgbImage) {
+    if dst.dimensions() != src.dimensions() {
+        *dst = RgbImage::new(src.width(), src.height());
+    }
+    for (d, s) in dst.pixels_mut().zip(src.pixels()) {
+        d.0 = [s[0], s[1], s[2]];
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    /// Newest frame that hasn't been read yet.
+    frame: Option<RgbImage>,
+    /// Buffer handed back by the reader, to decode the next frame into.
+    spare: Option<RgbImage>,
+    dropped: u64,
+    error: Option<String>,
+}
+
+/// Reads a source on its own thread, keeping only the newest frame. A slow
+/// consumer gets the latest frame instead of working through a queue of
+/// stale ones, which would make latency grow without bound.
+pub struct LatestFrame {
+    id: SourceId,
+    slot: Arc<(Mutex<Slot>, Condvar)>,
+    stop: Arc<AtomicBool>,
+    /// Width to pass to [`FrameSource::set_max_width`], 0 for `None`.
+    max_width: Arc<AtomicU32>,
+}
+
+impl LatestFrame {
+    /// Opens the source with `open` on the capture thread, so it doesn't
+    /// need to be `Send`.
+    pub fn spawn<F>(open: F) -> Result<Self, String>
+    where
+        F: FnOnce() -> Result<Box<dyn FrameSource>, String> + Send + 'static,
+    {
+        let slot = Arc::new((Mutex::new(Slot::default()), Condvar::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let max_width = Arc::new(AtomicU32::new(0));
+        let (id_tx, id_rx) = mpsc::channel();
+        let (thread_slot, thread_stop) = (slot.clone(), stop.clone());
+        let thread_max_width = max_width.clone();
+        thread::spawn(move || {
+            let mut source = match open() {
+                Ok(source) => source,
+                Err(e) => return drop(id_tx.send(Err(e))),
+            };
+            let _ = id_tx.send(Ok(source.id()));
+            let (slot, fresh) = &*thread_slot;
+            let mut back = RgbImage::new(0, 0);
+            let mut width = 0;
+            while !thread_stop.load(Ordering::Relaxed) {
+                let wanted = thread_max_width.load(Ordering::Relaxed);
+                if wanted != width {
+                    width = wanted;
+                    source.set_max_width((width != 0).then_some(width));
+                }
+                let result = source.read(&mut back);
+                let mut slot = slot.lock().unwrap();
+                match result {
+                    Ok(()) => {
+                        slot.error = None;
+                        back = match slot.frame.replace(back) {
+                            Some(stale) => {
+                                slot.dropped += 1;
+                                stale
+                            }
+                            None => slot.spare.take().unwrap_or_default(),
+                        };
+                    }
+                    Err(e) => slot.error = Some(e.to_string()),
+                }
+                fresh.notify_all();
+                if slot.error.is_some() {
+                    drop(slot);
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        });
+        let id = id_rx.recv().map_err(|e| e.to_string())??;
+        Ok(Self {
+            id,
+            slot,
+            stop,
+            max_width,
+        })
+    }
+}
+
+impl FrameSource for LatestFrame {
+    fn id(&self) -> SourceId {
+        self.id.clone()
+    }
+
+    /// Waits for a frame newer than the last one read.
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        let (slot, fresh) = &*self.slot;
+        let (mut slot, timeout) = fresh
+            .wait_timeout_while(slot.lock().unwrap(), FRAME_TIMEOUT, |slot| {
+                slot.frame.is_none() && slot.error.is_none()
+            })
+            .unwrap();
+        if let Some(mut newest) = slot.frame.take() {
+            std::mem::swap(frame, &mut newest);
+            slot.spare = Some(newest);
+            return Ok(());
+        }
+        if let Some(e) = slot.error.take() {
+            return Err(e.into());
+        }
+        debug_assert!(timeout.timed_out());
+        Err("timed out waiting for a frame".into())
+    }
+
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        let mut slot = self.slot.0.lock().unwrap();
+        if let Some(mut newest) = slot.frame.take() {
+            std::mem::swap(frame, &mut newest);
+            slot.spare = Some(newest);
+            return Ok(true);
+        }
+        match slot.error.take() {
+            Some(e) => Err(e.into()),
+            None => Ok(false),
+        }
+    }
+
+    fn dropped(&self) -> u64 {
+        self.slot.0.lock().unwrap().dropped
+    }
+
+    /// Passed on to the source on the capture thread before its next read.
+    fn set_max_width(&mut self, width: Option<u32>) {
+        self.max_width.store(width.unwrap_or(0), Ordering::Relaxed);
+    }
+}
+
+impl Drop for LatestFrame {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Produces frames whose single pixel counts up, one per millisecond.
+    struct Counter(u8);
+
+    impl FrameSource for Counter {
+        fn id(&self) -> SourceId {
+            SourceId::Monitor(0)
+        }
+
+        fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+            thread::sleep(Duration::from_millis(1));
+            self.0 = self.0.wrapping_add(1);
+            *frame = RgbImage::from_pixel(1, 1, image::Rgb([self.0; 3]));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn slow_reader_gets_newest_frame() {
+        let mut source = LatestFrame::spawn(|| Ok(Box::new(Counter(0)) as _)).unwrap();
+        let mut frame = RgbImage::new(0, 0);
+        source.read(&mut frame).unwrap();
+        let first = frame.get_pixel(0, 0)[0];
+        thread::sleep(Duration::from_millis(50));
+        source.read(&mut frame).unwrap();
+        assert!(frame.get_pixel(0, 0)[0].wrapping_sub(first) > 1);
+        assert!(source.dropped() > 0);
+    }
+
+    /// Produces one frame, then nothing.
+    struct Once(bool);
+
+    impl FrameSource for Once {
+        fn id(&self) -> SourceId {
+            SourceId::Monitor(0)
+        }
+
+        fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+            if std::mem::replace(&mut self.0, true) {
+                thread::sleep(Duration::from_secs(60));
+            }
+            *frame = RgbImage::new(1, 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn try_read_does_not_wait() {
+        let mut source = LatestFrame::spawn(|| Ok(Box::new(Once(false)) as _)).unwrap();
+        let mut frame = RgbImage::new(0, 0);
+        source.read(&mut frame).unwrap();
+        let start = std::time::Instant::now();
+        assert!(!source.try_read(&mut frame).unwrap());
+        assert!(start.elapsed() < FRAME_TIMEOUT / 2);
+    }
+
+    #[test]
+    fn image_folder_steps_through_images() {
+        let dir = std::env::temp_dir().join(format!("image-folder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, width) in [("b.png", 2), ("a.png", 1), ("c.png", 3)] {
+            RgbImage::new(width, 1).save(dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+        let mut folder = ImageFolder::open(&dir).unwrap();
+        assert_eq!(folder.files.len(), 3);
+        let mut frame = RgbImage::new(0, 0);
+        let mut widths = vec![];
+        for step in [1, 1, 1, -1] {
+            folder.read(&mut frame).unwrap();
+            widths.push(frame.width());
+            folder.go(step);
+        }
+        assert_eq!(widths, [1, 2, 3, 1]);
+        folder.read(&mut frame).unwrap();
+        assert_eq!(frame.width(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_until_reset() {
+        let ms = Duration::from_millis;
+        let mut backoff = Backoff::new(ms(500), ms(1500));
+        let waits = [(); 4].map(|_| backoff.wait());
+        assert_eq!(waits, [ms(500), ms(1000), ms(1500), ms(1500)]);
+        backoff.reset();
+        assert_eq!(backoff.wait(), ms(500));
+    }
+}
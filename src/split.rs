@@ -0,0 +1,278 @@
+//! Split-screen comparison: the frames of the first stream run through two
+//! independently configured sets of pipelines, e.g. CPU against CUDA or a
+//! lite against a full model. Both sides get the same frame and show their
+//! results together, each with its own latency.
+
+use egui::{ColorImage, TextureHandle};
+use image::RgbImage;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bench::percentile,
+    overlay::OverlayRenderer,
+    pipeline::{self, Pipeline, PipelineSpec},
+    scheduler::Scheduler,
+    worker::{DetectionWorker, Detections, Thresholds},
+};
+
+/// Latencies kept per side for the statistics.
+const LATENCY_WINDOW: usize = 100;
+
+/// Recent detection latencies of one side.
+#[derive(Clone, Debug, Default)]
+pub struct Latencies(VecDeque<Duration>);
+
+impl Latencies {
+    pub fn push(&mut self, latency: Duration) {
+        if self.0.len() == LATENCY_WINDOW {
+            self.0.pop_front();
+        }
+        self.0.push_back(latency);
+    }
+
+    /// Median and 95th percentile, once there are any.
+    pub fn p50_p95(&self) -> Option<(Duration, Duration)> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted = self.0.iter().copied().collect::<Vec<_>>();
+        sorted.sort();
+        Some((percentile(&sorted, 0.5), percentile(&sorted, 0.95)))
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// One set of pipelines and what it detected in the frame shown.
+#[derive(Default)]
+pub struct Side {
+    /// Pipelines to run, with their providers.
+    pub pipelines: Vec<PipelineSpec>,
+    worker: Option<DetectionWorker>,
+    detections: Detections,
+    pub latencies: Latencies,
+    error: Option<String>,
+    annotated: Option<RgbImage>,
+    texture: Option<TextureHandle>,
+}
+
+impl Side {
+    /// Pipelines the side runs, if any.
+    pub fn loaded(&self) -> &[PipelineSpec] {
+        self.worker.as_ref().map_or(&[], DetectionWorker::specs)
+    }
+
+    fn busy(&self) -> bool {
+        self.worker.as_ref().is_some_and(DetectionWorker::busy)
+    }
+}
+
+#[derive(Default)]
+pub struct SplitComparison {
+    pub enabled: bool,
+    pub sides: [Side; 2],
+    /// Frame both sides are detecting on, and when it was submitted.
+    in_flight: Option<(Instant, RgbImage)>,
+    /// When the frame shown was submitted.
+    shown: Option<Instant>,
+    start: Option<Instant>,
+}
+
+impl SplitComparison {
+    /// Whether `side` should be (re)built from its pipeline specs.
+    pub fn needs_load(&self, side: usize) -> bool {
+        let side = &self.sides[side];
+        self.enabled && side.error.is_none() && side.loaded() != side.pipelines.as_slice()
+    }
+
+    /// Runs `pipelines` on `side` from now on, or shows why they failed to
+    /// load. The frame in flight is given up, so both sides start afresh.
+    pub fn set_pipelines(
+        &mut self,
+        side: usize,
+        pipelines: Result<Vec<(PipelineSpec, Pipeline)>, String>,
+    ) {
+        let side = &mut self.sides[side];
+        match pipelines {
+            Ok(pipelines) => {
+                side.worker = (!pipelines.is_empty())
+                    .then(|| DetectionWorker::spawn(Scheduler::new(pipelines)));
+                side.error = None;
+            }
+            Err(e) => {
+                side.worker = None;
+                side.error = Some(e);
+            }
+        }
+        side.detections = Detections::default();
+        side.latencies.clear();
+        self.in_flight = None;
+    }
+
+    /// Collects the results of both sides and, once neither is busy, shows
+    /// them together on the frame they were detected on. Then submits
+    /// `frame` to both at the same time if it is `fresh`. Never waits.
+    pub fn update(
+        &mut self,
+        ctx: &egui::Context,
+        frame: &RgbImage,
+        fresh: bool,
+        thresholds: Thresholds,
+        overlay: &OverlayRenderer,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        for side in &mut self.sides {
+            let Some(finished) = side.worker.as_mut().and_then(DetectionWorker::poll) else {
+                continue;
+            };
+            // Results of a frame given up on are dropped.
+            if self
+                .in_flight
+                .as_ref()
+                .is_some_and(|(at, _)| *at == finished.at)
+            {
+                side.latencies.push(finished.elapsed);
+                side.detections = finished.detections;
+            }
+        }
+        if self.sides.iter().any(Side::busy) {
+            return;
+        }
+        if let Some((at, frame)) = self.in_flight.take() {
+            for side in &mut self.sides {
+                let mut annotated = side.annotated.take().unwrap_or_default();
+                annotated.clone_from(&frame);
+                let names = side
+                    .worker
+                    .as_ref()
+                    .map(DetectionWorker::class_names)
+                    .unwrap_or_default();
+                overlay.draw_detections(&mut annotated, &side.detections, &names);
+                let size = [annotated.width() as usize, annotated.height() as usize];
+                let image = ColorImage::from_rgb(size, annotated.as_raw());
+                match &mut side.texture {
+                    Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                    None => {
+                        side.texture =
+                            Some(ctx.load_texture("split", image, egui::TextureOptions::LINEAR))
+                    }
+                }
+                side.annotated = Some(annotated);
+            }
+            self.shown = Some(at);
+        }
+        if !fresh || self.sides.iter().all(|side| side.worker.is_none()) {
+            return;
+        }
+        let at = Instant::now();
+        self.start.get_or_insert(at);
+        let mut submitted = true;
+        for worker in self
+            .sides
+            .iter_mut()
+            .filter_map(|side| side.worker.as_mut())
+        {
+            submitted &= worker.submit(frame, None, thresholds, at);
+        }
+        // A side that didn't take the frame would never finish it.
+        if submitted {
+            self.in_flight = Some((at, frame.clone()));
+        }
+    }
+
+    /// Checkbox and the pipelines of both sides. Turning the comparison on
+    /// starts both sides with `current`.
+    pub fn settings_ui(&mut self, ui: &mut egui::Ui, current: &[PipelineSpec]) {
+        if ui.checkbox(&mut self.enabled, "Enabled").changed()
+            && self.enabled
+            && self.sides.iter().all(|side| side.pipelines.is_empty())
+        {
+            for side in &mut self.sides {
+                side.pipelines = current.to_vec();
+            }
+        }
+        if !self.enabled {
+            return;
+        }
+        for (i, (side, name)) in self.sides.iter_mut().zip(["A", "B"]).enumerate() {
+            ui.separator();
+            ui.strong(format!("Side {}", name));
+            ui.push_id(i, |ui| {
+                let before = side.pipelines.clone();
+                pipeline::specs_ui(ui, &mut side.pipelines);
+                if side.pipelines != before {
+                    side.error = None;
+                }
+            });
+        }
+    }
+
+    /// Both sides next to each other, each with its pipelines, the time of
+    /// the frame shown and its latency.
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let time = match (self.shown, self.start) {
+            (Some(shown), Some(start)) => {
+                format!(
+                    "Frame at {:.3} s",
+                    shown.duration_since(start).as_secs_f32()
+                )
+            }
+            _ => "Waiting for a frame".to_owned(),
+        };
+        ui.columns(2, |columns| {
+            for ((ui, side), name) in columns.iter_mut().zip(&self.sides).zip(["A", "B"]) {
+                ui.strong(format!("Side {}", name));
+                if side.pipelines.is_empty() {
+                    ui.label("No pipelines");
+                }
+                for spec in &side.pipelines {
+                    ui.label(spec.label());
+                }
+                if let Some(e) = &side.error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, e);
+                }
+                ui.label(&time);
+                match side.latencies.p50_p95() {
+                    Some((p50, p95)) => ui.label(format!(
+                        "Latency p50 {:.1} ms, p95 {:.1} ms ({:.1} fps)",
+                        p50.as_secs_f64() * 1e3,
+                        p95.as_secs_f64() * 1e3,
+                        1.0 / p50.as_secs_f64().max(1e-6),
+                    )),
+                    None => ui.label("No latency yet"),
+                };
+                if let Some(texture) = &side.texture {
+                    let size = texture.size_vec2();
+                    let scale = (ui.available_width() / size.x)
+                        .min(ui.available_height() / size.y)
+                        .max(0.0);
+                    ui.image((texture.id(), size * scale));
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latencies_keep_a_window() {
+        let mut latencies = Latencies::default();
+        assert_eq!(latencies.p50_p95(), None);
+        for ms in (1..=150).rev() {
+            latencies.push(Duration::from_millis(ms));
+        }
+        // Only the last 100, 1 to 100 ms, count.
+        let ms = |ms| Duration::from_millis(ms);
+        assert_eq!(latencies.p50_p95(), Some((ms(50), ms(95))));
+    }
+}
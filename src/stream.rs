@@ -0,0 +1,806 @@
+use egui::{
+    pos2, vec2, Align2, Color32, ColorImage, FontId, Pos2, Rect, Sense, Stroke, TextureHandle,
+};
+use image::RgbImage;
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::{debug_span, info, warn};
+
+use crate::{
+    bbox::BBox,
+    compare::Agreement,
+    depth::DepthMap,
+    enhance::Enhancement,
+    face_mesh::FaceMesh,
+    hand::Hand,
+    iris::{self, Face},
+    palm::{self, Palm},
+    pipeline::{Pipeline, PipelineSpec},
+    plugin::{PluginDetection, PluginId},
+    pose::Pose,
+    scheduler::Scheduler,
+    source::{FrameSource, SourceId},
+    track::{Extrapolator, Schedule},
+    trails::Trails,
+    worker::{DetectionWorker, Finished, Thresholds, WeakDetections},
+    yolo::Object,
+};
+
+/// ROIs are never made smaller than this many pixels per side.
+const MIN_ROI: f32 = 32.0;
+
+/// One frame source together with its detection pipelines and display texture.
+pub struct StreamState {
+    pub id: SourceId,
+    source: Box<dyn FrameSource>,
+    /// Loaded once its models are available; until then frames are shown as is.
+    worker: Option<DetectionWorker>,
+    handle: TextureHandle,
+    /// Newest frame of the source, as captured.
+    pub frame: RgbImage,
+    /// `frame` after enhancement, as detection sees it.
+    pub enhanced: RgbImage,
+    /// Enhancement `enhanced` was made with.
+    enhancement: Enhancement,
+    /// Copy of `enhanced` with effects and detections drawn on, as shown.
+    pub annotated: RgbImage,
+    /// Texture upload buffer. egui releases its reference once the frame is
+    /// painted, so the allocation is reused on the next update.
+    texture: Arc<ColorImage>,
+    /// Detections from the most recently finished frame, predicted forward to
+    /// the current frame if interpolation is on.
+    pub palms: Vec<Palm>,
+    pub poses: Vec<Pose>,
+    pub objects: Vec<Object>,
+    pub faces: Vec<Face>,
+    pub meshes: Vec<FaceMesh>,
+    pub hands: Vec<Hand>,
+    /// Depth of the most recently finished frame. Not predicted forward.
+    pub depth: Option<DepthMap>,
+    /// Detections of each plugin pipeline, as last detected.
+    pub plugins: Vec<(PluginId, Vec<PluginDetection>)>,
+    /// Palms of the candidate model when comparing palm models, as last
+    /// detected, and how well the models agreed since the comparison started.
+    pub compared: Vec<Palm>,
+    agreement: Agreement,
+    /// Detections below their thresholds, as last detected.
+    pub weak: WeakDetections,
+    /// Paths and heatmap of the palms since the stream was opened.
+    pub trails: Trails,
+    palm_track: Extrapolator<Palm>,
+    pose_track: Extrapolator<Pose>,
+    object_track: Extrapolator<Object>,
+    face_track: Extrapolator<Face>,
+    mesh_track: Extrapolator<FaceMesh>,
+    hand_track: Extrapolator<Hand>,
+    /// Smoothed time detection takes per frame.
+    inference_time: Option<Duration>,
+    /// Current detection interval and frames skipped since the last run.
+    interval: u32,
+    skipped: u32,
+    /// Region of the frame detection runs on, in frame pixels. Detections are
+    /// mapped back to full-frame coordinates.
+    roi: Option<BBox>,
+    /// Show only the ROI, magnified, instead of the whole frame.
+    zoom: bool,
+    /// Frame position where an ROI drag started.
+    drag_start: Option<(f32, f32)>,
+    /// Keeps the current frame instead of reading new ones. It is detected
+    /// again when the thresholds or the ROI change.
+    paused: bool,
+    /// Frames to move by before reading the next one while paused.
+    step: Option<isize>,
+    /// When the current frame was captured, and the frame, thresholds and
+    /// ROI detection was last started with.
+    frame_at: Instant,
+    submitted: Option<(Instant, Thresholds, Option<BBox>)>,
+    /// Why the source failed, until it delivers a frame again. A placeholder
+    /// is shown instead of the stale frame meanwhile.
+    lost: Option<String>,
+}
+
+impl StreamState {
+    pub fn open(
+        ctx: &egui::Context,
+        mut source: Box<dyn FrameSource>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let id = source.id();
+        let mut frame = RgbImage::new(0, 0);
+        source.read(&mut frame)?;
+        let mut texture = Arc::new(ColorImage::default());
+        Self::upload_frame(&frame, &mut texture);
+        let handle = ctx.load_texture(
+            id.to_string(),
+            texture.clone(),
+            egui::TextureOptions::LINEAR,
+        );
+        Ok(Self {
+            id,
+            source,
+            worker: None,
+            handle,
+            annotated: frame.clone(),
+            enhanced: frame.clone(),
+            enhancement: Enhancement::default(),
+            frame,
+            texture,
+            palms: vec![],
+            poses: vec![],
+            objects: vec![],
+            faces: vec![],
+            meshes: vec![],
+            hands: vec![],
+            depth: None,
+            plugins: vec![],
+            compared: vec![],
+            agreement: Agreement::default(),
+            weak: WeakDetections::default(),
+            trails: Trails::default(),
+            palm_track: Extrapolator::default(),
+            pose_track: Extrapolator::default(),
+            object_track: Extrapolator::default(),
+            face_track: Extrapolator::default(),
+            mesh_track: Extrapolator::default(),
+            hand_track: Extrapolator::default(),
+            inference_time: None,
+            interval: 1,
+            skipped: 0,
+            roi: None,
+            zoom: false,
+            drag_start: None,
+            paused: false,
+            step: None,
+            frame_at: Instant::now(),
+            submitted: None,
+            lost: None,
+        })
+    }
+
+    fn upload_frame(frame: &RgbImage, texture: &mut Arc<ColorImage>) {
+        let size = [frame.width() as usize, frame.height() as usize];
+        if Arc::get_mut(texture).is_none_or(|img| img.size != size) {
+            *texture = Arc::new(ColorImage::new(size, Color32::BLACK));
+        }
+        let img = Arc::get_mut(texture).unwrap();
+        for (dst, src) in img.pixels.iter_mut().zip(frame.pixels()) {
+            *dst = Color32::from_rgb(src[0], src[1], src[2]);
+        }
+    }
+
+    /// Whether the stream replays a session, showing its recorded detections
+    /// instead of running pipelines.
+    pub fn replaying(&self) -> bool {
+        self.source.recorded().is_some()
+    }
+
+    /// Pipelines the stream runs, if any.
+    pub fn pipelines(&self) -> &[PipelineSpec] {
+        self.worker.as_ref().map_or(&[], DetectionWorker::specs)
+    }
+
+    /// Captures at most `width` pixels wide from now on, if the source can
+    /// choose.
+    pub fn set_max_width(&mut self, width: Option<u32>) {
+        self.source.set_max_width(width);
+    }
+
+    /// Runs `pipelines` together on a worker thread from now on. The
+    /// previous worker finishes its current frame in the background and
+    /// exits.
+    pub fn set_pipelines(&mut self, pipelines: Vec<(PipelineSpec, Pipeline)>) {
+        self.worker =
+            (!pipelines.is_empty()).then(|| DetectionWorker::spawn(Scheduler::new(pipelines)));
+        self.palms.clear();
+        self.poses.clear();
+        self.objects.clear();
+        self.faces.clear();
+        self.meshes.clear();
+        self.hands.clear();
+        self.depth = None;
+        self.plugins.clear();
+        self.compared.clear();
+        self.agreement = Agreement::default();
+        self.weak = WeakDetections::default();
+        self.palm_track.clear();
+        self.pose_track.clear();
+        self.object_track.clear();
+        self.face_track.clear();
+        self.mesh_track.clear();
+        self.hand_track.clear();
+        self.inference_time = None;
+        self.submitted = None;
+    }
+
+    /// Names of the classes in [`Self::objects`].
+    pub fn class_names(&self) -> Arc<Vec<String>> {
+        self.worker
+            .as_ref()
+            .map(DetectionWorker::class_names)
+            .unwrap_or_default()
+    }
+
+    /// Takes the newest frame if there is one, enhances it, collects
+    /// finished detections and, if `schedule` says so, hands the enhanced
+    /// frame or its ROI to the detection worker along with `thresholds`.
+    /// Never waits for either.
+    /// While paused, only a frame stepped to is taken, and it is detected
+    /// regardless of `schedule`. Returns whether the frame or detections
+    /// changed, i.e. whether [`Self::annotated`] needs redrawing. If the
+    /// source fails, the previous frame and detections are kept, but shown
+    /// as disconnected until the source delivers again.
+    pub fn capture(
+        &mut self,
+        schedule: &Schedule,
+        thresholds: Thresholds,
+        enhancement: Enhancement,
+    ) -> bool {
+        let _span = debug_span!("capture", source = %self.id).entered();
+        let step = self.step.take();
+        let fresh = if self.paused && step.is_none() {
+            false
+        } else {
+            if let Some(frames) = step.filter(|&frames| frames != 0) {
+                self.source.seek(frames);
+            }
+            match self.source.try_read(&mut self.frame) {
+                Ok(fresh) => {
+                    if fresh && self.lost.take().is_some() {
+                        info!("{} is back", self.id);
+                    }
+                    fresh
+                }
+                Err(e) => {
+                    if self.lost.is_none() {
+                        warn!("Failed to read from {}: {}", self.id, e);
+                    }
+                    self.lost = Some(e.to_string());
+                    false
+                }
+            }
+        };
+        if step.is_some() && !fresh {
+            // A live source has no new frame yet; take the next one.
+            self.step = Some(0);
+        }
+        let finished = self.worker.as_mut().and_then(DetectionWorker::poll);
+        let detected = finished.is_some();
+        if let Some(finished) = finished {
+            self.finish(finished, schedule);
+        }
+        let enhanced = fresh || enhancement != self.enhancement;
+        if enhanced {
+            self.enhancement = enhancement;
+            self.enhanced.clone_from(&self.frame);
+            enhancement.apply(&mut self.enhanced);
+            // A paused frame is detected again as enhanced now.
+            self.submitted = None;
+        }
+        if let Some(recorded) = self.source.recorded().filter(|_| fresh) {
+            // Replayed sessions bring their detections along.
+            self.frame_at = Instant::now();
+            self.palms = recorded.palms;
+            self.poses = recorded.poses;
+            self.objects = recorded.objects;
+            self.faces = recorded.faces;
+            self.meshes = recorded.meshes;
+            self.hands = recorded.hands;
+            return true;
+        }
+        if !fresh {
+            if self.paused {
+                self.redetect(thresholds);
+            }
+            return detected || enhanced;
+        }
+
+        let now = Instant::now();
+        self.frame_at = now;
+        if schedule.interpolate && !detected && !self.paused {
+            self.palms = self.palm_track.predict(now);
+            self.poses = self.pose_track.predict(now);
+            self.objects = self.object_track.predict(now);
+            self.faces = self.face_track.predict(now);
+            self.meshes = self.mesh_track.predict(now);
+            self.hands = self.hand_track.predict(now);
+        }
+        self.interval = schedule.interval(self.inference_time);
+        self.roi = self.roi.map(|roi| self.clamp_roi(roi));
+        if self.paused {
+            self.redetect(thresholds);
+        } else if self.skipped + 1 < self.interval {
+            self.skipped += 1;
+        } else if let Some(worker) = &mut self.worker {
+            if worker.submit(&self.enhanced, self.roi, thresholds, now) {
+                self.skipped = 0;
+                self.submitted = Some((now, thresholds, self.roi));
+            }
+        }
+        true
+    }
+
+    /// Starts detection on the paused frame, unless it was already started
+    /// with the same thresholds and ROI. Retried until the worker is free.
+    fn redetect(&mut self, thresholds: Thresholds) {
+        let request = (self.frame_at, thresholds, self.roi);
+        if self.submitted == Some(request) {
+            return;
+        }
+        if let Some(worker) = &mut self.worker {
+            if worker.submit(&self.enhanced, self.roi, thresholds, self.frame_at) {
+                self.submitted = Some(request);
+            }
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.step = None;
+    }
+
+    /// Moves a paused stream `frames` frames forward or back. Only sources
+    /// that can seek go back; live ones move on to their next frame.
+    pub fn step(&mut self, frames: isize) {
+        if self.paused && (frames > 0 || self.source.seekable()) {
+            self.step = Some(frames);
+        }
+    }
+
+    /// Box coordinates, scores and keypoints of the detections at the frame
+    /// point `(x, y)`, for inspecting a paused frame.
+    fn inspect(&self, (x, y): (f32, f32)) -> String {
+        let contains = |b: BBox| x >= b.x && x <= b.x + b.w && y >= b.y && y <= b.y + b.h;
+        let point =
+            |name: &str, (px, py): (f32, f32)| format!("  {:<12} {:>7.1} {:>7.1}", name, px, py);
+        let bbox = |b: BBox| format!("  box x {:.1} y {:.1} w {:.1} h {:.1}", b.x, b.y, b.w, b.h);
+        let mut lines = vec![format!("Frame point {:.1} {:.1}", x, y)];
+        if let Some(nearness) = self.depth.as_ref().and_then(|map| map.at((x, y))) {
+            lines.push(format!("Nearness {:.3}", nearness));
+        }
+        for (id, palm) in self.palms.iter().enumerate() {
+            if !contains(palm.bbox) {
+                continue;
+            }
+            lines.push(format!(
+                "Palm {} {}, score {:.3}",
+                id,
+                palm.handedness.label(),
+                palm.score
+            ));
+            lines.push(bbox(palm.bbox));
+            for (name, tip) in palm::KEYPOINT_NAMES.iter().zip(palm.tips) {
+                lines.push(point(name, tip));
+            }
+        }
+        for (id, pose) in self.poses.iter().enumerate() {
+            if !contains(pose.roi) {
+                continue;
+            }
+            lines.push(format!("Pose {}", id));
+            lines.push(format!(
+                "  roi x {:.1} y {:.1} w {:.1} h {:.1}",
+                pose.roi.x, pose.roi.y, pose.roi.w, pose.roi.h
+            ));
+            // All 33 landmarks would not fit; show the one under the pointer.
+            let nearest = pose.landmarks.iter().enumerate().min_by(|(_, a), (_, b)| {
+                let da = (a.x - x).hypot(a.y - y);
+                let db = (b.x - x).hypot(b.y - y);
+                da.total_cmp(&db)
+            });
+            if let Some((i, l)) = nearest {
+                lines.push(format!(
+                    "{}, visibility {:.3}",
+                    point(&format!("landmark {}", i), (l.x, l.y)),
+                    l.visibility
+                ));
+            }
+        }
+        let names = self.class_names();
+        for (id, object) in self.objects.iter().enumerate() {
+            if !contains(object.bbox) {
+                continue;
+            }
+            let name = names.get(object.class).map_or("?", String::as_str);
+            lines.push(format!(
+                "Object {} {} (class {}), score {:.3}",
+                id, name, object.class, object.score
+            ));
+            lines.push(bbox(object.bbox));
+        }
+        for (id, face) in self.faces.iter().enumerate() {
+            if !contains(face.bbox) {
+                continue;
+            }
+            lines.push(format!("Face {}, score {:.3}", id, face.score));
+            lines.push(bbox(face.bbox));
+            for (name, keypoint) in iris::KEYPOINT_NAMES.iter().zip(face.keypoints) {
+                lines.push(point(name, keypoint));
+            }
+            for (name, eye) in ["right iris", "left iris"].iter().zip(face.eyes) {
+                lines.push(format!(
+                    "{}, radius {:.1}, gaze {:.2} {:.2}",
+                    point(name, eye.iris),
+                    eye.iris_radius,
+                    eye.gaze.0,
+                    eye.gaze.1
+                ));
+            }
+        }
+        for (id, mesh) in self.meshes.iter().enumerate() {
+            let b = mesh.bbox();
+            if !contains(b) {
+                continue;
+            }
+            let e = mesh.expression;
+            lines.push(format!("Face mesh {}, presence {:.3}", id, mesh.presence));
+            lines.push(bbox(b));
+            lines.push(format!(
+                "eye aspect ratio {:.2} {:.2}, mouth openness {:.2}",
+                e.eye_aspect_ratio[0], e.eye_aspect_ratio[1], e.mouth_openness
+            ));
+        }
+        for (id, hand) in self.hands.iter().enumerate() {
+            let b = hand.bbox();
+            if !contains(b) {
+                continue;
+            }
+            lines.push(format!(
+                "Hand {} {}, presence {:.3}",
+                id,
+                hand.handedness.label(),
+                hand.presence
+            ));
+            lines.push(bbox(b));
+            let nearest = hand.landmarks.iter().enumerate().min_by(|(_, a), (_, b)| {
+                let da = (a.0 - x).hypot(a.1 - y);
+                let db = (b.0 - x).hypot(b.1 - y);
+                da.total_cmp(&db)
+            });
+            if let Some((i, &(lx, ly, lz))) = nearest {
+                lines.push(format!(
+                    "{}, depth {:.1}",
+                    point(&format!("landmark {}", i), (lx, ly)),
+                    lz
+                ));
+            }
+        }
+        for (plugin, found) in &self.plugins {
+            for (id, detection) in found.iter().enumerate() {
+                if !contains(detection.bbox) {
+                    continue;
+                }
+                lines.push(format!(
+                    "{} {} {}, score {:.3}",
+                    plugin.name(),
+                    id,
+                    detection.label,
+                    detection.score
+                ));
+                lines.push(bbox(detection.bbox));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn finish(&mut self, finished: Finished, schedule: &Schedule) {
+        let elapsed = finished.elapsed;
+        self.inference_time = Some(match self.inference_time {
+            Some(smoothed) => smoothed.mul_f32(0.8) + elapsed.mul_f32(0.2),
+            None => elapsed,
+        });
+        let detections = finished.detections;
+        self.compared = detections.compared;
+        self.weak = detections.weak;
+        self.depth = detections.depth;
+        self.plugins = detections.plugins;
+        self.agreement.add(&detections.agreement);
+        self.palm_track.observe(&detections.palms, finished.at);
+        self.pose_track.observe(&detections.poses, finished.at);
+        self.object_track.observe(&detections.objects, finished.at);
+        self.face_track.observe(&detections.faces, finished.at);
+        self.mesh_track.observe(&detections.meshes, finished.at);
+        self.hand_track.observe(&detections.hands, finished.at);
+        if schedule.interpolate && !self.paused {
+            // The frame was captured a while ago; catch up to now.
+            let now = Instant::now();
+            self.palms = self.palm_track.predict(now);
+            self.poses = self.pose_track.predict(now);
+            self.objects = self.object_track.predict(now);
+            self.faces = self.face_track.predict(now);
+            self.meshes = self.mesh_track.predict(now);
+            self.hands = self.hand_track.predict(now);
+        } else {
+            self.palms = detections.palms;
+            self.poses = detections.poses;
+            self.objects = detections.objects;
+            self.faces = detections.faces;
+            self.meshes = detections.meshes;
+            self.hands = detections.hands;
+        }
+    }
+
+    fn full_frame(&self) -> BBox {
+        BBox {
+            x: 0.0,
+            y: 0.0,
+            w: self.frame.width() as f32,
+            h: self.frame.height() as f32,
+        }
+    }
+
+    /// Snaps `roi` to whole pixels inside the frame.
+    fn clamp_roi(&self, roi: BBox) -> BBox {
+        let full = self.full_frame();
+        let w = roi.w.round().clamp(MIN_ROI.min(full.w), full.w);
+        let h = roi.h.round().clamp(MIN_ROI.min(full.h), full.h);
+        BBox {
+            x: roi.x.round().clamp(0.0, full.w - w),
+            y: roi.y.round().clamp(0.0, full.h - h),
+            w,
+            h,
+        }
+    }
+
+    /// Scales the ROI by `factor` around the frame point `(px, py)`.
+    fn zoom_roi(&mut self, factor: f32, (px, py): (f32, f32)) {
+        let roi = self.roi.unwrap_or(self.full_frame());
+        self.roi = Some(self.clamp_roi(BBox {
+            x: px - (px - roi.x) * factor,
+            y: py - (py - roi.y) * factor,
+            w: roi.w * factor,
+            h: roi.h * factor,
+        }));
+    }
+
+    fn pan_roi(&mut self, dx: f32, dy: f32) {
+        if let Some(roi) = self.roi {
+            self.roi = Some(self.clamp_roi(roi.shift(dx * roi.w, dy * roi.h)));
+        }
+    }
+
+    /// Uploads the annotated frame to the GPU texture.
+    pub fn present(&mut self) {
+        Self::upload_frame(&self.annotated, &mut self.texture);
+        self.handle
+            .set(self.texture.clone(), egui::TextureOptions::LINEAR);
+    }
+
+    /// Shows the frame. Dragging draws the ROI, double-clicking clears it,
+    /// scrolling or `+`/`-` zoom it and the arrow keys pan it.
+    pub fn ui(&mut self, ui: &mut egui::Ui, max_size: egui::Vec2) {
+        ui.vertical(|ui| {
+            let header = ui
+                .horizontal(|ui| {
+                    ui.label(self.id.to_string());
+                    let mut paused = self.paused;
+                    if ui
+                        .toggle_value(&mut paused, "Pause")
+                        .on_hover_text("Space")
+                        .changed()
+                    {
+                        self.set_paused(paused);
+                    }
+                    if self.paused {
+                        // The source's own controls would move it behind
+                        // the paused frame's back.
+                        let back = ui.add_enabled(self.source.seekable(), egui::Button::new("◀"));
+                        if back.on_hover_text("Previous frame (,)").clicked() {
+                            self.step(-1);
+                        }
+                        if ui.button("▶").on_hover_text("Next frame (.)").clicked() {
+                            self.step(1);
+                        }
+                    } else {
+                        self.source.ui(ui);
+                    }
+                    if let Some(e) = self.worker.as_ref().and_then(DetectionWorker::failed) {
+                        ui.colored_label(Color32::RED, format!("Detection stopped: {}", e))
+                            .on_hover_text("Pick the pipelines again to restart it");
+                    }
+                    let dropped = self.source.dropped();
+                    if self.interval > 1 || dropped > 0 {
+                        ui.weak(format!("detect 1/{}, dropped {}", self.interval, dropped));
+                    }
+                    if self.agreement.frames > 0 {
+                        let a = self.agreement;
+                        let iou = a
+                            .mean_iou()
+                            .map_or("-".to_owned(), |iou| format!("{:.3}", iou));
+                        ui.weak(format!(
+                            "IoU {} over {} frames, missed {}, extra {}",
+                            iou, a.frames, a.missed, a.extra
+                        ));
+                    }
+                    ui.checkbox(&mut self.zoom, "Zoom to ROI");
+                    if ui.button("Clear ROI").clicked() {
+                        self.roi = None;
+                    }
+                })
+                .response
+                .rect
+                .height();
+
+            let full = self.full_frame();
+            let view = match self.roi {
+                Some(roi) if self.zoom => roi,
+                _ => full,
+            };
+            let uv = Rect::from_min_max(
+                pos2(view.x / full.w, view.y / full.h),
+                pos2((view.x + view.w) / full.w, (view.y + view.h) / full.h),
+            );
+            let txt = egui::load::SizedTexture::new(self.handle.id(), vec2(view.w, view.h));
+            let max_size = max_size - vec2(0.0, header + ui.spacing().item_spacing.y);
+            let response = ui.add(
+                egui::Image::from_texture(txt)
+                    .uv(uv)
+                    .max_size(max_size)
+                    .sense(Sense::click_and_drag()),
+            );
+
+            let rect = response.rect;
+            if let Some(e) = &self.lost {
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, Color32::from_gray(24));
+                painter.text(
+                    rect.center(),
+                    Align2::CENTER_CENTER,
+                    format!("{} disconnected\n{}\nReconnecting…", self.id, e),
+                    FontId::proportional(16.0),
+                    Color32::LIGHT_GRAY,
+                );
+            }
+            let to_frame = |p: Pos2| {
+                (
+                    view.x + (p.x - rect.min.x) / rect.width() * view.w,
+                    view.y + (p.y - rect.min.y) / rect.height() * view.h,
+                )
+            };
+            let to_screen = |(x, y): (f32, f32)| {
+                pos2(
+                    rect.min.x + (x - view.x) / view.w * rect.width(),
+                    rect.min.y + (y - view.y) / view.h * rect.height(),
+                )
+            };
+            let corners = |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| BBox {
+                x: x0.min(x1),
+                y: y0.min(y1),
+                w: (x1 - x0).abs(),
+                h: (y1 - y0).abs(),
+            };
+
+            if response.drag_started() {
+                self.drag_start = response.interact_pointer_pos().map(to_frame);
+            }
+            if let (Some(start), Some(pos)) = (self.drag_start, response.interact_pointer_pos()) {
+                let drawn = corners(start, to_frame(pos));
+                if response.drag_stopped() {
+                    self.drag_start = None;
+                    if drawn.w >= MIN_ROI && drawn.h >= MIN_ROI {
+                        self.roi = Some(self.clamp_roi(drawn));
+                    }
+                } else {
+                    ui.painter().rect_stroke(
+                        Rect::from_two_pos(
+                            to_screen((drawn.x, drawn.y)),
+                            to_screen((drawn.x + drawn.w, drawn.y + drawn.h)),
+                        ),
+                        0.0,
+                        Stroke::new(2.0, Color32::YELLOW),
+                    );
+                }
+            }
+            if response.double_clicked() {
+                self.roi = None;
+            }
+
+            if let Some(hover) = response.hover_pos() {
+                let (pause, back, forward) = ui.input(|i| {
+                    (
+                        i.key_pressed(egui::Key::Space),
+                        i.key_pressed(egui::Key::Comma),
+                        i.key_pressed(egui::Key::Period),
+                    )
+                });
+                if pause {
+                    self.set_paused(!self.paused);
+                }
+                if back {
+                    self.step(-1);
+                }
+                if forward {
+                    self.step(1);
+                }
+                if self.paused && self.drag_start.is_none() {
+                    let text = self.inspect(to_frame(hover));
+                    response.clone().on_hover_ui_at_pointer(|ui| {
+                        ui.monospace(text);
+                    });
+                }
+                let (scroll, zoom_in, zoom_out, pan) = ui.input(|i| {
+                    let key = |k| i.key_pressed(k) as i32 as f32;
+                    (
+                        i.smooth_scroll_delta.y,
+                        i.key_pressed(egui::Key::Plus) || i.key_pressed(egui::Key::Equals),
+                        i.key_pressed(egui::Key::Minus),
+                        (
+                            key(egui::Key::ArrowRight) - key(egui::Key::ArrowLeft),
+                            key(egui::Key::ArrowDown) - key(egui::Key::ArrowUp),
+                        ),
+                    )
+                });
+                let center = to_frame(hover);
+                if scroll != 0.0 {
+                    self.zoom_roi((-scroll * 0.002).exp(), center);
+                }
+                if zoom_in {
+                    self.zoom_roi(0.9, center);
+                }
+                if zoom_out {
+                    self.zoom_roi(1.1, center);
+                }
+                if pan != (0.0, 0.0) {
+                    self.pan_roi(pan.0 * 0.1, pan.1 * 0.1);
+                }
+            }
+
+            if let Some(roi) = self.roi.filter(|_| !self.zoom) {
+                ui.painter().rect_stroke(
+                    Rect::from_two_pos(
+                        to_screen((roi.x, roi.y)),
+                        to_screen((roi.x + roi.w, roi.y + roi.h)),
+                    ),
+                    0.0,
+                    Stroke::new(2.0, Color32::YELLOW),
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Produces frames whose single pixel counts up.
+    struct Counter(u8);
+
+    impl FrameSource for Counter {
+        fn id(&self) -> SourceId {
+            SourceId::Monitor(0)
+        }
+
+        fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+            self.0 += 1;
+            *frame = RgbImage::from_pixel(1, 1, image::Rgb([self.0; 3]));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn paused_stream_only_steps_forward() {
+        let ctx = egui::Context::default();
+        let mut stream = StreamState::open(&ctx, Box::new(Counter(0))).unwrap();
+        let (schedule, thresholds) = (Schedule::default(), Thresholds::default());
+        let enhancement = Enhancement::default();
+        let pixel = |stream: &StreamState| stream.frame.get_pixel(0, 0)[0];
+        assert!(stream.capture(&schedule, thresholds, enhancement));
+        assert_eq!(pixel(&stream), 2);
+
+        stream.set_paused(true);
+        assert!(!stream.capture(&schedule, thresholds, enhancement));
+        stream.step(-1);
+        assert!(!stream.capture(&schedule, thresholds, enhancement));
+        assert_eq!(pixel(&stream), 2);
+        stream.step(1);
+        assert!(stream.capture(&schedule, thresholds, enhancement));
+        assert!(!stream.capture(&schedule, thresholds, enhancement));
+        assert_eq!(pixel(&stream), 3);
+
+        stream.set_paused(false);
+        assert!(stream.capture(&schedule, thresholds, enhancement));
+        assert_eq!(pixel(&stream), 4);
+    }
+}
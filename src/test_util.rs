@@ -0,0 +1,75 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{
+    bbox::BBox,
+    palm::{Handedness, Palm},
+};
+
+/// A palm centered on `(x, y)`: a 20×20 box, every keypoint at the center
+/// and a score of 1. Change what a test needs, then [`PalmBuilder::build`].
+pub(crate) fn palm(x: f32, y: f32) -> PalmBuilder {
+    PalmBuilder {
+        center: (x, y),
+        size: (20.0, 20.0),
+        tips: [(x, y); 7],
+        score: 1.0,
+        handedness: None,
+    }
+}
+
+pub(crate) struct PalmBuilder {
+    center: (f32, f32),
+    size: (f32, f32),
+    tips: [(f32, f32); 7],
+    score: f32,
+    handedness: Option<Handedness>,
+}
+
+impl PalmBuilder {
+    /// Resizes the box around the same center.
+    pub(crate) fn size(mut self, w: f32, h: f32) -> Self {
+        self.size = (w, h);
+        self
+    }
+
+    /// Moves keypoint `i`, e.g. [`crate::palm::WRIST`], to `at`.
+    pub(crate) fn tip(mut self, i: usize, at: (f32, f32)) -> Self {
+        self.tips[i] = at;
+        self
+    }
+
+    /// Moves every keypoint to `at`.
+    pub(crate) fn tips(mut self, at: (f32, f32)) -> Self {
+        self.tips = [at; 7];
+        self
+    }
+
+    pub(crate) fn score(mut self, score: f32) -> Self {
+        self.score = score;
+        self
+    }
+
+    pub(crate) fn handedness(mut self, handedness: Handedness) -> Self {
+        self.handedness = Some(handedness);
+        self
+    }
+
+    /// The palm. Unless set, its handedness is read from the keypoints,
+    /// which is right when they all coincide.
+    pub(crate) fn build(self) -> Palm {
+        let ((x, y), (w, h)) = (self.center, self.size);
+        Palm {
+            bbox: BBox {
+                x: x - w / 2.0,
+                y: y - h / 2.0,
+                w,
+                h,
+            },
+            tips: self.tips,
+            score: self.score,
+            handedness: self
+                .handedness
+                .unwrap_or_else(|| Handedness::of(&self.tips)),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Running detection on a subset of frames and filling in the rest.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::{
+    bbox::BBox, face_mesh::FaceMesh, hand::Hand, iris::Face, palm::Palm, pose::Pose, yolo::Object,
+};
+
+/// Detections that can be moved to where they are predicted to be.
+pub trait Track: Copy {
+    fn bbox(&self) -> BBox;
+    fn shift(&self, dx: f32, dy: f32) -> Self;
+}
+
+impl Track for Palm {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        Palm::shift(self, dx, dy)
+    }
+}
+
+impl Track for Pose {
+    fn bbox(&self) -> BBox {
+        self.roi
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        Pose::shift(self, dx, dy)
+    }
+}
+
+impl Track for Object {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        Object::shift(self, dx, dy)
+    }
+}
+
+impl Track for Face {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        Face::shift(self, dx, dy)
+    }
+}
+
+impl Track for FaceMesh {
+    fn bbox(&self) -> BBox {
+        FaceMesh::bbox(self)
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        FaceMesh::shift(self, dx, dy)
+    }
+}
+
+impl Track for Hand {
+    fn bbox(&self) -> BBox {
+        Hand::bbox(self)
+    }
+
+    fn shift(&self, dx: f32, dy: f32) -> Self {
+        Hand::shift(self, dx, dy)
+    }
+}
+
+fn center(bbox: BBox) -> (f32, f32) {
+    (bbox.x + bbox.w / 2.0, bbox.y + bbox.h / 2.0)
+}
+
+/// Predicts detections between detector runs by moving each one along the
+/// velocity it had between the last two runs.
+pub struct Extrapolator<T> {
+    /// Latest detections with their velocity in pixels per second.
+    last: Vec<(T, (f32, f32))>,
+    at: Option<Instant>,
+}
+
+impl<T> Default for Extrapolator<T> {
+    fn default() -> Self {
+        Self {
+            last: vec![],
+            at: None,
+        }
+    }
+}
+
+impl<T: Track> Extrapolator<T> {
+    /// Records fresh detections. Each is matched to the nearest previous one
+    /// that is closer than the previous one's size.
+    pub fn observe(&mut self, detections: &[T], now: Instant) {
+        let dt = self
+            .at
+            .map(|at| now.duration_since(at).as_secs_f32())
+            .filter(|dt| *dt > 0.0);
+        let previous = std::mem::take(&mut self.last);
+        self.last = detections
+            .iter()
+            .map(|detection| {
+                let (x, y) = center(detection.bbox());
+                let velocity = dt
+                    .and_then(|dt| {
+                        previous
+                            .iter()
+                            .map(|(p, _)| (p.bbox(), center(p.bbox())))
+                            .map(|(bbox, (px, py))| (bbox, (px, py), (x - px).hypot(y - py)))
+                            .filter(|(bbox, _, dist)| *dist < bbox.w.max(bbox.h))
+                            .min_by(|a, b| a.2.total_cmp(&b.2))
+                            .map(|(_, (px, py), _)| ((x - px) / dt, (y - py) / dt))
+                    })
+                    .unwrap_or((0.0, 0.0));
+                (*detection, velocity)
+            })
+            .collect();
+        self.at = Some(now);
+    }
+
+    pub fn predict(&self, now: Instant) -> Vec<T> {
+        let dt = self
+            .at
+            .map_or(0.0, |at| now.saturating_duration_since(at).as_secs_f32());
+        self.last
+            .iter()
+            .map(|(detection, (vx, vy))| detection.shift(vx * dt, vy * dt))
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.last.clear();
+        self.at = None;
+    }
+}
+
+/// How often the detector runs on a stream's frames.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    /// Run detection on every `every`th frame.
+    pub every: u32,
+    /// Choose `every` from the measured inference time instead, so that the
+    /// stream keeps up with `target_fps`.
+    pub adaptive: bool,
+    pub target_fps: f32,
+    /// Move the last detections along their velocity on skipped frames,
+    /// rather than showing them where they were.
+    pub interpolate: bool,
+}
+
+/// Upper bound for the adaptive detection interval.
+const MAX_EVERY: u32 = 10;
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            every: 1,
+            adaptive: false,
+            target_fps: 30.0,
+            interpolate: true,
+        }
+    }
+}
+
+impl Schedule {
+    /// Detection interval for a detector taking `inference` per frame.
+    pub fn interval(&self, inference: Option<Duration>) -> u32 {
+        if !self.adaptive {
+            return self.every.max(1);
+        }
+        let Some(inference) = inference else {
+            return 1;
+        };
+        let frames = inference.as_secs_f32() * self.target_fps;
+        (frames.ceil() as u32).clamp(1, MAX_EVERY)
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.adaptive, "Adaptive");
+        if self.adaptive {
+            ui.add(egui::Slider::new(&mut self.target_fps, 5.0..=60.0).text("Target FPS"));
+        } else {
+            ui.add(
+                egui::Slider::new(&mut self.every, 1..=MAX_EVERY).text("Detect every Nth frame"),
+            );
+        }
+        ui.checkbox(&mut self.interpolate, "Interpolate skipped frames");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::palm;
+
+    /// A palm whose 20×20 box starts at `x`.
+    fn from(x: f32) -> Palm {
+        palm(x + 10.0, 10.0).build()
+    }
+
+    #[test]
+    fn extrapolates_velocity() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut extrapolator = Extrapolator::default();
+        extrapolator.observe(&[from(0.0), from(100.0)], at(0));
+        extrapolator.observe(&[from(110.0), from(10.0)], at(100));
+
+        let predicted = extrapolator.predict(at(150));
+        assert_eq!(predicted.len(), 2);
+        for (palm, x) in predicted.iter().zip([115.0, 15.0]) {
+            assert!((palm.bbox.x - x).abs() < 1e-3, "{} != {}", palm.bbox.x, x);
+        }
+    }
+
+    #[test]
+    fn far_detections_start_still() {
+        let start = Instant::now();
+        let mut extrapolator = Extrapolator::default();
+        extrapolator.observe(&[from(0.0)], start);
+        extrapolator.observe(&[from(200.0)], start + Duration::from_millis(100));
+        let predicted = extrapolator.predict(start + Duration::from_secs(1));
+        assert_eq!(predicted[0].bbox.x, 200.0);
+    }
+
+    #[test]
+    fn adaptive_interval() {
+        let schedule = Schedule {
+            adaptive: true,
+            target_fps: 30.0,
+            ..Default::default()
+        };
+        assert_eq!(schedule.interval(None), 1);
+        assert_eq!(schedule.interval(Some(Duration::from_millis(20))), 1);
+        assert_eq!(schedule.interval(Some(Duration::from_millis(70))), 3);
+        assert_eq!(schedule.interval(Some(Duration::from_secs(5))), MAX_EVERY);
+    }
+}
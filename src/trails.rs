@@ -0,0 +1,204 @@
+//! Where hands have been: recent paths of each palm center, and how long
+//! palms stayed in each part of the frame over the whole session.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::palm::Palm;
+
+/// Side of a heatmap cell, in frame pixels.
+const HEAT_CELL: u32 = 4;
+
+/// Recent positions of one palm center, oldest first.
+pub type Trail = VecDeque<((f32, f32), Instant)>;
+
+/// Time palms spent around every part of the frame, on a coarse grid.
+#[derive(Clone, Debug, Default)]
+pub struct Heatmap {
+    cols: u32,
+    rows: u32,
+    cells: Vec<f32>,
+    max: f32,
+}
+
+impl Heatmap {
+    /// Adds a blob of `radius` pixels at `(x, y)` that is heaviest at its
+    /// center, for a frame of `width`×`height`.
+    pub fn add(&mut self, (x, y): (f32, f32), radius: f32, (width, height): (u32, u32)) {
+        let (cols, rows) = (width.div_ceil(HEAT_CELL), height.div_ceil(HEAT_CELL));
+        if (cols, rows) != (self.cols, self.rows) {
+            *self = Self {
+                cols,
+                rows,
+                cells: vec![0.0; (cols * rows) as usize],
+                max: 0.0,
+            };
+        }
+        let cell = HEAT_CELL as f32;
+        let radius = (radius / cell).max(1.0);
+        let (cx, cy) = (x / cell, y / cell);
+        let rows = (cy - radius).max(0.0) as u32..((cy + radius).ceil() as u32).min(self.rows);
+        for row in rows {
+            let cols = (cx - radius).max(0.0) as u32..((cx + radius).ceil() as u32).min(self.cols);
+            for col in cols {
+                let d = (col as f32 + 0.5 - cx).hypot(row as f32 + 0.5 - cy);
+                if d < radius {
+                    let value = &mut self.cells[(row * self.cols + col) as usize];
+                    *value += 1.0 - d / radius;
+                    self.max = self.max.max(*value);
+                }
+            }
+        }
+    }
+
+    /// Heat at the frame pixel `(x, y)` relative to the hottest cell.
+    pub fn value(&self, x: u32, y: u32) -> f32 {
+        let (col, row) = (x / HEAT_CELL, y / HEAT_CELL);
+        if col >= self.cols || row >= self.rows || self.max <= 0.0 {
+            return 0.0;
+        }
+        self.cells[(row * self.cols + col) as usize] / self.max
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Follows palm centers from frame to frame. Each palm continues the trail
+/// ending closest to it, if it is within the palm's size.
+#[derive(Default)]
+pub struct Trails {
+    trails: Vec<Trail>,
+    /// Identifier of each trail, unique since the trails were created.
+    ids: Vec<u64>,
+    next_id: u64,
+    pub heatmap: Heatmap,
+}
+
+impl Trails {
+    /// Adds the palms of a `frame_size` frame at `now` and forgets
+    /// positions older than `max_age`.
+    pub fn update(
+        &mut self,
+        palms: &[Palm],
+        frame_size: (u32, u32),
+        now: Instant,
+        max_age: Duration,
+    ) {
+        let mut extended = vec![false; self.trails.len()];
+        let mut started = vec![];
+        for palm in palms {
+            let b = palm.bbox;
+            let center = (b.x + b.w / 2.0, b.y + b.h / 2.0);
+            let reach = b.w.max(b.h);
+            self.heatmap.add(center, reach / 2.0, frame_size);
+            let nearest = self
+                .trails
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !extended[*i])
+                .filter_map(|(i, trail)| {
+                    let ((x, y), _) = trail.back()?;
+                    Some((i, (x - center.0).hypot(y - center.1)))
+                })
+                .filter(|(_, d)| *d < reach)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match nearest {
+                Some((i, _)) => {
+                    self.trails[i].push_back((center, now));
+                    extended[i] = true;
+                }
+                None => started.push(Trail::from([(center, now)])),
+            }
+        }
+        for trail in started {
+            self.trails.push(trail);
+            self.ids.push(self.next_id);
+            self.next_id += 1;
+        }
+        for trail in &mut self.trails {
+            while trail
+                .front()
+                .is_some_and(|(_, at)| now.duration_since(*at) > max_age)
+            {
+                trail.pop_front();
+            }
+        }
+        let mut trails = self.trails.iter();
+        self.ids.retain(|_| !trails.next().unwrap().is_empty());
+        self.trails.retain(|trail| !trail.is_empty());
+    }
+
+    pub fn trails(&self) -> &[Trail] {
+        &self.trails
+    }
+
+    /// The trails with their identifiers, which stay the same while a palm
+    /// is followed.
+    pub fn tracks(&self) -> impl Iterator<Item = (u64, &Trail)> {
+        self.ids.iter().copied().zip(&self.trails)
+    }
+
+    pub fn clear(&mut self) {
+        self.trails.clear();
+        self.ids.clear();
+        self.heatmap.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::palm;
+
+    #[test]
+    fn palms_keep_their_trails() {
+        let mut trails = Trails::default();
+        let start = Instant::now();
+        let age = Duration::from_secs(1);
+        let at = |ms| start + Duration::from_millis(ms);
+        trails.update(
+            &[palm(20.0, 50.0).build(), palm(80.0, 50.0).build()],
+            (100, 100),
+            at(0),
+            age,
+        );
+        // Listed the other way around, both moved a little.
+        trails.update(
+            &[palm(75.0, 50.0).build(), palm(25.0, 50.0).build()],
+            (100, 100),
+            at(100),
+            age,
+        );
+        let ends = trails
+            .trails()
+            .iter()
+            .map(|trail| (trail.len(), trail.back().unwrap().0))
+            .collect::<Vec<_>>();
+        assert_eq!(ends, [(2, (25.0, 50.0)), (2, (75.0, 50.0))]);
+
+        trails.update(&[palm(25.0, 50.0).build()], (100, 100), at(1050), age);
+        let lens = trails.trails().iter().map(Trail::len).collect::<Vec<_>>();
+        assert_eq!(lens, [2, 1]);
+        trails.update(&[], (100, 100), at(1200), age);
+        assert_eq!(trails.trails().len(), 1);
+        let ids = trails.tracks().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ids, [0]);
+    }
+
+    #[test]
+    fn heat_accumulates_where_palms_stay() {
+        let mut heatmap = Heatmap::default();
+        for _ in 0..3 {
+            heatmap.add((10.0, 10.0), 8.0, (100, 100));
+        }
+        heatmap.add((62.0, 62.0), 8.0, (100, 100));
+        assert_eq!(heatmap.value(9, 9), 1.0);
+        assert!((heatmap.value(61, 61) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(heatmap.value(40, 40), 0.0);
+        assert_eq!(heatmap.value(200, 10), 0.0);
+    }
+}
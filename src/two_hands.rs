@@ -0,0 +1,251 @@
+//! Metrics of two hands together: how far apart they are, the angle of the
+//! line between them, and while both pinch, how much they zoomed and
+//! turned since they started. Values are continuous, for the outputs to
+//! map onto parameters, e.g. zoom and rotate in TouchDesigner.
+
+use std::f32::consts::PI;
+
+use crate::{
+    hand::{self, Hand},
+    palm::Palm,
+};
+
+/// Pinch ratio below which a hand pinches, see [`Hand::pinch_ratio`]. It
+/// lets go again above the threshold plus a fifth, so a borderline pinch
+/// doesn't break off a zoom.
+const PINCH_THRESHOLD: f32 = 0.35;
+
+/// Two hands of a frame, the left one of the image first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TwoHandMetrics {
+    /// Palm centers, in frame pixels.
+    pub centers: [(f32, f32); 2],
+    /// Distance between the palms, relative to the frame diagonal.
+    pub distance: f32,
+    /// Clockwise angle of the line from the left to the right palm against
+    /// the horizontal, in radians from -π to π.
+    pub angle: f32,
+    /// Whether both hands pinch.
+    pub pinching: bool,
+    /// Distance over the distance when both started pinching; 1 while they
+    /// don't.
+    pub zoom: f32,
+    /// Clockwise turn of the line since both started pinching, in radians
+    /// from -π to π; 0 while they don't.
+    pub rotation: f32,
+}
+
+impl TwoHandMetrics {
+    pub fn center(&self) -> (f32, f32) {
+        let [(ax, ay), (bx, by)] = self.centers;
+        ((ax + bx) / 2.0, (ay + by) / 2.0)
+    }
+}
+
+/// Follows the first two hands of the first stream, or palms without hand
+/// landmarks.
+#[derive(Default)]
+pub struct TwoHands {
+    pub enabled: bool,
+    /// Whether the left and right hand of the image pinch.
+    pinching: [bool; 2],
+    /// Distance and angle when both started pinching.
+    grab: Option<(f32, f32)>,
+    metrics: Option<TwoHandMetrics>,
+}
+
+impl TwoHands {
+    /// Metrics of the last frame with two hands, if the last frame had them.
+    pub fn metrics(&self) -> Option<&TwoHandMetrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Measures the first two of `hands`, in a frame of `frame_size`
+    /// pixels, or of `palms` if there aren't two hands. Others are left
+    /// out.
+    pub fn update(&mut self, palms: &[Palm], hands: &[Hand], (w, h): (u32, u32)) {
+        // Palm centers and pinch ratios. Palms have no fingertips, so their
+        // pinch is only a rough stand-in, as for the air canvas.
+        let hand = |hand: &Hand| {
+            let (wx, wy, _) = hand.landmarks[hand::WRIST];
+            let (mx, my, _) = hand.landmarks[hand::MIDDLE_BASE];
+            (((wx + mx) / 2.0, (wy + my) / 2.0), hand.pinch_ratio())
+        };
+        let palm = |palm: &Palm| {
+            let b = palm.bbox;
+            ((b.x + b.w / 2.0, b.y + b.h / 2.0), palm.pinch_ratio())
+        };
+        let pair = match (hands, palms) {
+            ([a, b, ..], _) => Some([hand(a), hand(b)]),
+            (_, [a, b, ..]) => Some([palm(a), palm(b)]),
+            _ => None,
+        };
+        let (Some(mut pair), true) = (pair, self.enabled) else {
+            self.pinching = [false; 2];
+            self.grab = None;
+            self.metrics = None;
+            return;
+        };
+        if pair[0].0 .0 > pair[1].0 .0 {
+            pair.swap(0, 1);
+        }
+        let centers = pair.map(|(center, _)| center);
+        for (pinching, (_, ratio)) in self.pinching.iter_mut().zip(pair) {
+            if ratio < PINCH_THRESHOLD {
+                *pinching = true;
+            } else if ratio > PINCH_THRESHOLD * 1.2 {
+                *pinching = false;
+            }
+        }
+        let [(ax, ay), (bx, by)] = centers;
+        let distance = (bx - ax).hypot(by - ay) / (w as f32).hypot(h as f32);
+        let angle = (by - ay).atan2(bx - ax);
+        let pinching = self.pinching == [true; 2];
+        let (zoom, rotation) = if pinching {
+            let (start_distance, start_angle) = *self.grab.get_or_insert((distance, angle));
+            (
+                distance / start_distance.max(1e-6),
+                wrap(angle - start_angle),
+            )
+        } else {
+            self.grab = None;
+            (1.0, 0.0)
+        };
+        self.metrics = Some(TwoHandMetrics {
+            centers,
+            distance,
+            angle,
+            pinching,
+            zoom,
+            rotation,
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Enabled");
+        let Some(m) = &self.metrics else {
+            ui.weak("Show two hands");
+            return;
+        };
+        ui.label(format!("Distance {:.2}", m.distance));
+        ui.label(format!("Angle {:.0}°", m.angle.to_degrees()));
+        if m.pinching {
+            ui.label(format!(
+                "Zoom ×{:.2}, rotation {:.0}°",
+                m.zoom,
+                m.rotation.to_degrees()
+            ));
+        } else {
+            ui.weak("Pinch with both hands to zoom and rotate");
+        }
+    }
+}
+
+/// `angle` folded into -π..π.
+fn wrap(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bbox::RotatedRect,
+        hand::{INDEX_TIP, MIDDLE_BASE, NUM_LANDMARKS, THUMB_TIP, WRIST},
+        palm::Handedness,
+        test_util::palm,
+    };
+
+    /// A hand with its palm centered at `(x, y)`, its thumb and index
+    /// fingertips touching or apart.
+    fn hand(x: f32, y: f32, pinching: bool) -> Hand {
+        let mut landmarks = [(x, y, 0.0); NUM_LANDMARKS];
+        landmarks[WRIST] = (x, y + 10.0, 0.0);
+        landmarks[MIDDLE_BASE] = (x, y - 10.0, 0.0);
+        landmarks[INDEX_TIP] = (x + 5.0, y - 25.0, 0.0);
+        landmarks[THUMB_TIP] = if pinching {
+            (x + 6.0, y - 23.0, 0.0)
+        } else {
+            (x + 20.0, y - 5.0, 0.0)
+        };
+        Hand {
+            roi: RotatedRect {
+                cx: x,
+                cy: y - 10.0,
+                w: 60.0,
+                h: 60.0,
+                angle: 0.0,
+            },
+            landmarks,
+            presence: 1.0,
+            handedness: Handedness::Right,
+        }
+    }
+
+    /// An open palm centered at `(x, y)`.
+    fn open(x: f32, y: f32) -> Palm {
+        palm(x, y)
+            .tip(crate::palm::WRIST, (x, y + 10.0))
+            .tip(crate::palm::MIDDLE_BASE, (x, y - 10.0))
+            .tip(crate::palm::THUMB, (x + 15.0, y))
+            .build()
+    }
+
+    #[test]
+    fn pinching_hands_zoom_and_rotate_from_where_they_started() {
+        let mut hands = TwoHands {
+            enabled: true,
+            ..TwoHands::default()
+        };
+        let size = (300, 400);
+        // Their palms, which can't tell a pinch, are left out.
+        let palms = [open(0.0, 0.0), open(10.0, 10.0)];
+        // Right hand first; metrics still go left to right.
+        hands.update(
+            &palms,
+            &[hand(200.0, 200.0, false), hand(100.0, 200.0, false)],
+            size,
+        );
+        let m = *hands.metrics().unwrap();
+        assert_eq!(m.centers[0], (100.0, 200.0));
+        assert!((m.distance - 0.2).abs() < 1e-6);
+        assert_eq!((m.angle, m.zoom, m.pinching), (0.0, 1.0, false));
+
+        let pair = [hand(100.0, 200.0, true), hand(200.0, 200.0, true)];
+        hands.update(&palms, &pair, size);
+        assert_eq!(hands.metrics().unwrap().zoom, 1.0);
+        // Twice as far apart, turned a quarter clockwise.
+        let pair = [hand(150.0, 100.0, true), hand(150.0, 300.0, true)];
+        hands.update(&palms, &pair, size);
+        let m = *hands.metrics().unwrap();
+        assert!(m.pinching);
+        assert!((m.zoom - 2.0).abs() < 1e-4);
+        assert!((m.rotation - PI / 2.0).abs() < 1e-4);
+
+        // Letting go resets both.
+        let pair = [hand(150.0, 100.0, false), hand(150.0, 300.0, true)];
+        hands.update(&palms, &pair, size);
+        let m = *hands.metrics().unwrap();
+        assert_eq!((m.pinching, m.zoom, m.rotation), (false, 1.0, 0.0));
+        hands.update(&[], &[hand(150.0, 100.0, false)], size);
+        assert!(hands.metrics().is_none());
+    }
+
+    #[test]
+    fn palms_stand_in_without_hand_landmarks() {
+        let mut hands = TwoHands {
+            enabled: true,
+            ..TwoHands::default()
+        };
+        hands.update(&[open(200.0, 100.0), open(100.0, 100.0)], &[], (300, 400));
+        let m = hands.metrics().unwrap();
+        assert_eq!(m.centers, [(100.0, 100.0), (200.0, 100.0)]);
+        assert!(!m.pinching);
+    }
+
+    #[test]
+    fn rotation_wraps_around() {
+        assert!((wrap(1.5 * PI) + 0.5 * PI).abs() < 1e-5);
+        assert!((wrap(-1.5 * PI) - 0.5 * PI).abs() < 1e-5);
+    }
+}
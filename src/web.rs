@@ -0,0 +1,463 @@
+//! The browser app, built with the `web` feature for wasm32: palm detection
+//! on the camera from `getUserMedia`, drawn with the desktop overlay.
+//!
+//! Browsers can't load models from disk, so the model is fetched from its
+//! URL, and only the palm pipeline is offered. It runs on onnxruntime-web,
+//! which `index.html` loads, on WebGPU where the browser has it and on
+//! WebAssembly otherwise. Its sessions only run asynchronously, so the
+//! palms drawn are those of the last frame the model finished, while it
+//! works on the next one.
+
+use egui::{ColorImage, TextureHandle};
+use image::{RgbImage, RgbaImage};
+use js_sys::{Array, Float32Array, Object, Reflect, Uint8Array};
+use ndarray::{Array4, ArrayD, IxDyn};
+use std::{cell::RefCell, error::Error, future::Future, rc::Rc};
+use tracing::warn;
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, HtmlVideoElement, MediaStream,
+    MediaStreamConstraints, Response,
+};
+
+use crate::{
+    models::ModelSpec,
+    overlay::OverlayRenderer,
+    palm::PalmDecoder,
+    source::{drop_alpha, FrameSource, SourceId},
+    worker::{Detections, Thresholds},
+};
+
+#[wasm_bindgen]
+extern "C" {
+    /// `ort.InferenceSession` of onnxruntime-web.
+    #[wasm_bindgen(js_namespace = ort, js_name = InferenceSession)]
+    #[derive(Clone)]
+    type JsSession;
+
+    #[wasm_bindgen(static_method_of = JsSession, js_namespace = ort, js_class = "InferenceSession")]
+    fn create(model: &Uint8Array, options: &Object) -> js_sys::Promise;
+
+    #[wasm_bindgen(method)]
+    fn run(this: &JsSession, feeds: &Object) -> js_sys::Promise;
+
+    #[wasm_bindgen(method, getter, js_name = inputNames)]
+    fn input_names(this: &JsSession) -> Array;
+
+    #[wasm_bindgen(method, getter, js_name = outputNames)]
+    fn output_names(this: &JsSession) -> Array;
+
+    /// `ort.Tensor` of onnxruntime-web.
+    #[wasm_bindgen(js_namespace = ort, js_name = Tensor)]
+    type JsTensor;
+
+    #[wasm_bindgen(constructor, js_namespace = ort, js_class = "Tensor")]
+    fn new(ty: &str, data: &Float32Array, dims: &Array) -> JsTensor;
+
+    #[wasm_bindgen(method, getter)]
+    fn data(this: &JsTensor) -> Float32Array;
+
+    #[wasm_bindgen(method, getter)]
+    fn dims(this: &JsTensor) -> Array;
+}
+
+/// Execution providers of onnxruntime-web, fastest first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebProvider {
+    WebGpu,
+    Wasm,
+}
+
+impl WebProvider {
+    pub const ALL: [Self; 2] = [Self::WebGpu, Self::Wasm];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::WebGpu => "WebGPU",
+            Self::Wasm => "WebAssembly",
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::WebGpu => "webgpu",
+            Self::Wasm => "wasm",
+        }
+    }
+}
+
+/// A model loaded in onnxruntime-web.
+pub struct WebSession {
+    session: JsSession,
+    pub provider: WebProvider,
+}
+
+impl WebSession {
+    /// Loads `model` on the first of `providers` that can run it.
+    pub async fn load(model: &[u8], providers: &[WebProvider]) -> Result<Self, String> {
+        let model = Uint8Array::from(model);
+        let mut error = "no execution provider".to_owned();
+        for &provider in providers {
+            let options = Object::new();
+            let list = Array::of1(&JsValue::from_str(provider.id()));
+            Reflect::set(&options, &"executionProviders".into(), &list).map_err(js_error)?;
+            match JsFuture::from(JsSession::create(&model, &options)).await {
+                Ok(session) => {
+                    return Ok(Self {
+                        session: session.unchecked_into(),
+                        provider,
+                    })
+                }
+                Err(e) => {
+                    error = format!("{}: {}", provider.name(), js_error(e));
+                    warn!("Failed to load the model on {}", error);
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Runs the model on `input` and returns its outputs in name order, as
+    /// native sessions do.
+    pub fn run(&self, input: Array4<f32>) -> impl Future<Output = Result<Outputs, String>> {
+        let session = self.session.clone();
+        async move {
+            let dims = input
+                .shape()
+                .iter()
+                .map(|&d| JsValue::from(d as u32))
+                .collect::<Array>();
+            let data = Float32Array::from(input.as_slice().ok_or("input isn't contiguous")?);
+            let feeds = Object::new();
+            let name = session.input_names().get(0);
+            Reflect::set(&feeds, &name, &JsTensor::new("float32", &data, &dims))
+                .map_err(js_error)?;
+            let results = JsFuture::from(session.run(&feeds))
+                .await
+                .map_err(js_error)?;
+            let mut names = session
+                .output_names()
+                .iter()
+                .filter_map(|name| name.as_string())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names
+                .iter()
+                .map(|name| {
+                    let tensor: JsTensor = Reflect::get(&results, &name.into())
+                        .map_err(js_error)?
+                        .unchecked_into();
+                    let shape = tensor
+                        .dims()
+                        .iter()
+                        .map(|d| d.as_f64().unwrap_or(0.0) as usize)
+                        .collect::<Vec<_>>();
+                    ArrayD::from_shape_vec(IxDyn(&shape), tensor.data().to_vec())
+                        .map_err(|e| format!("output {}: {}", name, e))
+                })
+                .collect()
+        }
+    }
+}
+
+fn js_error(e: impl Into<JsValue>) -> String {
+    let e = e.into();
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
+
+/// The browser's camera, played in a video element that is never shown
+/// and copied out of it through a canvas.
+pub struct UserMediaSource {
+    video: HtmlVideoElement,
+    context: CanvasRenderingContext2d,
+    /// Playback time of the frame read last, to tell whether there is a
+    /// newer one.
+    time: f64,
+}
+
+impl UserMediaSource {
+    /// Asks for the camera and waits until it plays.
+    pub async fn open() -> Result<Self, String> {
+        let window = web_sys::window().ok_or("no window")?;
+        let document = window.document().ok_or("no document")?;
+        let devices = window.navigator().media_devices().map_err(js_error)?;
+        let constraints = MediaStreamConstraints::new();
+        constraints.set_video(&JsValue::TRUE);
+        let request = devices
+            .get_user_media_with_constraints(&constraints)
+            .map_err(js_error)?;
+        let stream: MediaStream = JsFuture::from(request)
+            .await
+            .map_err(js_error)?
+            .dyn_into()
+            .map_err(js_error)?;
+
+        let video: HtmlVideoElement = document
+            .create_element("video")
+            .map_err(js_error)?
+            .dyn_into()
+            .map_err(js_error)?;
+        video.set_muted(true);
+        video.set_src_object(Some(&stream));
+        JsFuture::from(video.play().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+
+        let canvas: HtmlCanvasElement = document
+            .create_element("canvas")
+            .map_err(js_error)?
+            .dyn_into()
+            .map_err(js_error)?;
+        let context = canvas
+            .get_context("2d")
+            .map_err(js_error)?
+            .ok_or("no 2D canvas context")?
+            .dyn_into()
+            .map_err(js_error)?;
+        Ok(Self {
+            video,
+            context,
+            time: -1.0,
+        })
+    }
+}
+
+impl FrameSource for UserMediaSource {
+    fn id(&self) -> SourceId {
+        SourceId::UserMedia
+    }
+
+    fn read(&mut self, frame: &mut RgbImage) -> Result<(), Box<dyn Error>> {
+        let (w, h) = (self.video.video_width(), self.video.video_height());
+        if w == 0 || h == 0 {
+            return Err("the camera has no frame yet".into());
+        }
+        let canvas = self.context.canvas().ok_or("no canvas")?;
+        if (canvas.width(), canvas.height()) != (w, h) {
+            canvas.set_width(w);
+            canvas.set_height(h);
+        }
+        self.context
+            .draw_image_with_html_video_element(&self.video, 0.0, 0.0)
+            .map_err(js_error)?;
+        let data = self
+            .context
+            .get_image_data(0.0, 0.0, w as f64, h as f64)
+            .map_err(js_error)?
+            .data();
+        let rgba = RgbaImage::from_raw(w, h, data.0).ok_or("short image data")?;
+        drop_alpha(&rgba, frame);
+        self.time = self.video.current_time();
+        Ok(())
+    }
+
+    fn try_read(&mut self, frame: &mut RgbImage) -> Result<bool, Box<dyn Error>> {
+        if self.video.current_time() == self.time {
+            return Ok(false);
+        }
+        self.read(frame).map(|()| true)
+    }
+}
+
+/// Downloads `url` into memory.
+pub async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let response: Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+    if !response.ok() {
+        return Err(format!("{}: HTTP {}", url, response.status()));
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+/// Result of a future still running, or what it came to.
+type Pending<T> = Rc<RefCell<Option<Result<T, String>>>>;
+
+/// Outputs of a model run, by output name.
+type Outputs = Vec<ArrayD<f32>>;
+
+fn spawn<T: 'static>(future: impl Future<Output = Result<T, String>> + 'static) -> Pending<T> {
+    let pending = Pending::default();
+    let slot = pending.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        *slot.borrow_mut() = Some(future.await);
+    });
+    pending
+}
+
+/// Shows what `pending` is waiting for, or why it failed.
+fn status<T>(ui: &mut egui::Ui, pending: &Pending<T>, waiting: &str) {
+    match &*pending.borrow() {
+        None => drop(ui.label(waiting)),
+        Some(Err(e)) => drop(ui.colored_label(egui::Color32::LIGHT_RED, e)),
+        Some(Ok(_)) => {}
+    }
+}
+
+/// Fetches the palm model and loads it on the first of `providers` that
+/// can run it.
+fn load_palm_model(providers: Vec<WebProvider>) -> Pending<WebSession> {
+    spawn(async move {
+        let model = fetch(&ModelSpec::palm_detection_lite().url).await?;
+        WebSession::load(&model, &providers).await
+    })
+}
+
+pub struct WebApp {
+    source: Pending<UserMediaSource>,
+    session: Pending<WebSession>,
+    /// Provider picked in the UI, or `None` for the fastest available.
+    provider: Option<WebProvider>,
+    decoder: PalmDecoder,
+    /// Model run in progress, and the size of the frame it runs on.
+    running: Option<(Pending<Outputs>, (u32, u32))>,
+    /// Why the last run found no palms, if it failed.
+    detect_error: Option<String>,
+    thresholds: Thresholds,
+    overlay: OverlayRenderer,
+    frame: RgbImage,
+    detections: Detections,
+    texture: Option<TextureHandle>,
+}
+
+impl WebApp {
+    pub fn new(_cc: &eframe::CreationContext) -> Self {
+        Self {
+            source: spawn(UserMediaSource::open()),
+            session: load_palm_model(WebProvider::ALL.to_vec()),
+            provider: None,
+            decoder: PalmDecoder::default(),
+            running: None,
+            detect_error: None,
+            thresholds: Thresholds::default(),
+            overlay: OverlayRenderer::new(Default::default()),
+            frame: RgbImage::new(0, 0),
+            detections: Detections::default(),
+            texture: None,
+        }
+    }
+
+    /// Takes the camera's newest frame, detects palms on it and draws them.
+    fn capture(&mut self, ctx: &egui::Context) {
+        let fresh = match &mut *self.source.borrow_mut() {
+            Some(Ok(source)) => source.try_read(&mut self.frame).unwrap_or_else(|e| {
+                warn!("Failed to read from the camera: {}", e);
+                false
+            }),
+            _ => false,
+        };
+        if !fresh {
+            return;
+        }
+        self.detect();
+        let mut annotated = self.frame.clone();
+        self.overlay
+            .draw_detections(&mut annotated, &self.detections, &[]);
+        let size = [annotated.width() as usize, annotated.height() as usize];
+        let image = ColorImage::from_rgb(size, annotated.as_raw());
+        match &mut self.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+            None => {
+                self.texture = Some(ctx.load_texture("camera", image, egui::TextureOptions::LINEAR))
+            }
+        }
+    }
+}
+
+impl WebApp {
+    /// Takes the palms of a finished model run, and starts a run on the
+    /// current frame once none is in progress.
+    fn detect(&mut self) {
+        if let Some((running, size)) = &self.running {
+            let size = *size;
+            let Some(result) = running.borrow_mut().take() else {
+                return;
+            };
+            self.running = None;
+            let t = self.thresholds;
+            let palms = result.and_then(|outputs| {
+                let thresholds = (t.palm_score, t.palm_iou);
+                self.decoder.palms(&outputs, size, thresholds, t.nms)
+            });
+            match palms {
+                Ok(palms) => {
+                    self.detections.palms = palms;
+                    self.detect_error = None;
+                }
+                Err(e) => self.detect_error = Some(e),
+            }
+        }
+        if let Some(Ok(session)) = &*self.session.borrow() {
+            let input = self.decoder.input(&self.frame);
+            self.running = Some((spawn(session.run(input)), self.frame.dimensions()));
+        }
+    }
+
+    fn provider_ui(&mut self, ui: &mut egui::Ui) {
+        let before = self.provider;
+        egui::ComboBox::from_label("Execution provider")
+            .selected_text(self.provider.map_or("Fastest available", WebProvider::name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.provider, None, "Fastest available");
+                for p in WebProvider::ALL {
+                    ui.selectable_value(&mut self.provider, Some(p), p.name());
+                }
+            });
+        if self.provider != before {
+            let providers = self.provider.map_or(WebProvider::ALL.to_vec(), |p| vec![p]);
+            self.session = load_palm_model(providers);
+            self.running = None;
+        }
+        if let Some(Ok(session)) = &*self.session.borrow() {
+            ui.label(format!("Running on {}", session.provider.name()));
+        }
+    }
+}
+
+impl eframe::App for WebApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::SidePanel::right("settings").show(ctx, |ui| {
+            ui.collapsing("Execution provider", |ui| self.provider_ui(ui));
+            ui.collapsing("Thresholds", |ui| self.thresholds.ui(ui));
+            ui.collapsing("Overlay", |ui| self.overlay.ui(ui));
+        });
+        self.capture(ctx);
+        egui::CentralPanel::default().show(ctx, |ui| {
+            status(ui, &self.source, "Waiting for the camera");
+            status(ui, &self.session, "Loading the palm model");
+            if let Some(e) = &self.detect_error {
+                ui.colored_label(egui::Color32::LIGHT_RED, e);
+            }
+            if let Some(texture) = &self.texture {
+                let size = texture.size_vec2();
+                let scale = (ui.available_width() / size.x).min(ui.available_height() / size.y);
+                ui.image((texture.id(), size * scale.max(0.0)));
+            }
+        });
+        ctx.request_repaint();
+    }
+}
+
+/// Runs the app on the canvas with the id `canvas_id`.
+#[cfg(target_arch = "wasm32")]
+pub fn start(canvas_id: &'static str) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let started = eframe::WebRunner::new()
+            .start(
+                canvas_id,
+                eframe::WebOptions::default(),
+                Box::new(|cc| Ok(Box::new(WebApp::new(cc)))),
+            )
+            .await;
+        if let Err(e) = started {
+            web_sys::console::error_1(&e);
+        }
+    });
+}
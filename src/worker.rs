@@ -0,0 +1,413 @@
+//! Runs the pipelines on threads of their own, so capture and the UI never
+//! wait for inference.
+//!
+//! Frames go through three stages, each on its own thread and connected by
+//! channels holding one frame: preprocessing cuts out the ROI, inference
+//! runs the models of the [`Scheduler`] and postprocessing merges their
+//! outputs into [`Detections`]. So the next frame is cropped while the
+//! models work on the current one, and the last one is merged meanwhile.
+//! Frames are copied into a ring of three buffers, handed back with each
+//! result, so none are allocated per frame. With all three in use, new
+//! frames are skipped rather than queued.
+
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    bbox::BBox,
+    compare::Agreement,
+    depth::DepthMap,
+    face_mesh::FaceMesh,
+    hand::Hand,
+    iris::Face,
+    nms::NmsMode,
+    palm::Palm,
+    pipeline::PipelineSpec,
+    plugin::{PluginDetection, PluginId},
+    pose::Pose,
+    scheduler::Scheduler,
+    yolo::Object,
+};
+use tracing::{debug_span, error};
+
+/// Frame buffers in the ring: one being preprocessed, one in inference and
+/// one in postprocessing or waiting to be collected.
+const BUFFERS: usize = 3;
+
+/// Display threshold weak detections are shown from when turned on.
+const DEFAULT_DISPLAY: f32 = 0.2;
+
+/// Minimum scores and NMS overlaps of the pipelines.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Thresholds {
+    pub palm_score: f32,
+    pub palm_iou: f32,
+    pub pose_score: f32,
+    pub object_score: f32,
+    pub object_iou: f32,
+    pub face_score: f32,
+    pub hand_presence: f32,
+    /// How every detector resolves overlapping candidates.
+    pub nms: NmsMode,
+    /// Score palms, objects and faces below their own threshold are still
+    /// found from and drawn faded, for tuning. `None` leaves them out.
+    pub display: Option<f32>,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            palm_score: 0.65,
+            palm_iou: 0.25,
+            pose_score: 0.5,
+            object_score: 0.25,
+            object_iou: 0.45,
+            face_score: 0.5,
+            hand_presence: 0.5,
+            nms: NmsMode::default(),
+            display: None,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Score a detector with threshold `score` runs at, so that weak
+    /// detections are found too when they are shown.
+    pub fn lowered(&self, score: f32) -> f32 {
+        self.display.map_or(score, |display| display.min(score))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        for (value, name) in [
+            (&mut self.palm_score, "Palm score"),
+            (&mut self.palm_iou, "Palm NMS IoU"),
+            (&mut self.pose_score, "Pose score"),
+            (&mut self.object_score, "Object score"),
+            (&mut self.object_iou, "Object NMS IoU"),
+            (&mut self.face_score, "Face score"),
+            (&mut self.hand_presence, "Hand presence"),
+        ] {
+            ui.add(egui::Slider::new(value, 0.0..=1.0).text(name));
+        }
+        let mut show = self.display.is_some();
+        if ui.checkbox(&mut show, "Show weak detections").changed() {
+            self.display = show.then_some(DEFAULT_DISPLAY);
+        }
+        if let Some(display) = &mut self.display {
+            ui.add(egui::Slider::new(display, 0.0..=1.0).text("Display threshold"));
+        }
+        egui::ComboBox::from_label("NMS")
+            .selected_text(self.nms.name())
+            .show_ui(ui, |ui| {
+                for mode in NmsMode::ALL {
+                    ui.selectable_value(&mut self.nms, mode, mode.name());
+                }
+            });
+        if ui.button("Reset").clicked() {
+            *self = Self::default();
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Detections {
+    pub palms: Vec<Palm>,
+    pub poses: Vec<Pose>,
+    pub objects: Vec<Object>,
+    pub faces: Vec<Face>,
+    pub meshes: Vec<FaceMesh>,
+    pub hands: Vec<Hand>,
+    pub depth: Option<DepthMap>,
+    /// Detections of each plugin pipeline.
+    pub plugins: Vec<(PluginId, Vec<PluginDetection>)>,
+    /// Palms of the candidate model, when comparing palm models.
+    pub compared: Vec<Palm>,
+    pub agreement: Agreement,
+    pub weak: WeakDetections,
+}
+
+/// Detections scoring below their detector's threshold but not below the
+/// display threshold. They are only drawn, to see what a lower threshold
+/// would let through; nothing else acts on them.
+#[derive(Clone, Debug, Default)]
+pub struct WeakDetections {
+    pub palms: Vec<Palm>,
+    pub objects: Vec<Object>,
+    pub faces: Vec<Face>,
+}
+
+impl Detections {
+    /// Moves palms, objects and faces below their thresholds to
+    /// [`Self::weak`]. Candidate palms of a comparison below the palm
+    /// threshold are dropped.
+    pub fn split_weak(&mut self, thresholds: &Thresholds) {
+        fn split<T>(all: &mut Vec<T>, weak: &mut Vec<T>, confident: impl Fn(&T) -> bool) {
+            let (strong, below) = std::mem::take(all).into_iter().partition(confident);
+            *all = strong;
+            weak.extend(below);
+        }
+        let t = thresholds;
+        let weak = &mut self.weak;
+        split(&mut self.palms, &mut weak.palms, |p| {
+            p.score >= t.palm_score
+        });
+        split(&mut self.objects, &mut weak.objects, |o| {
+            o.score >= t.object_score
+        });
+        split(&mut self.faces, &mut weak.faces, |f| {
+            f.score >= t.face_score
+        });
+        self.compared.retain(|p| p.score >= t.palm_score);
+    }
+}
+
+/// A frame on its way through the stages, with what they made of it so far.
+struct Job<T> {
+    /// One of the buffers of the ring.
+    frame: RgbImage,
+    roi: Option<BBox>,
+    thresholds: Thresholds,
+    at: Instant,
+    /// Time the stages took so far, not counting waits between them.
+    elapsed: Duration,
+    data: T,
+}
+
+impl<T> Job<T> {
+    /// Runs a stage on the job, adding the time it takes.
+    fn then<U>(self, stage: impl FnOnce(&Self) -> U) -> Job<U> {
+        let start = Instant::now();
+        let data = stage(&self);
+        Job {
+            frame: self.frame,
+            roi: self.roi,
+            thresholds: self.thresholds,
+            at: self.at,
+            elapsed: self.elapsed + start.elapsed(),
+            data,
+        }
+    }
+}
+
+/// Detections of a submitted frame.
+pub struct Finished {
+    pub detections: Detections,
+    /// When the frame was submitted.
+    pub at: Instant,
+    /// Time preprocessing, inference and postprocessing took.
+    pub elapsed: Duration,
+    frame: RgbImage,
+}
+
+/// Pipelines running on threads of their own, one per stage.
+pub struct DetectionWorker {
+    specs: Vec<PipelineSpec>,
+    class_names: Arc<Vec<String>>,
+    jobs: SyncSender<Job<()>>,
+    results: Receiver<Finished>,
+    /// Frames submitted and not finished yet.
+    in_flight: usize,
+    /// Buffers of the ring not in use, to copy the next job into.
+    free: Vec<RgbImage>,
+    threads: Vec<JoinHandle<()>>,
+    /// What the pipelines panicked with, once they did.
+    failed: Option<String>,
+}
+
+impl DetectionWorker {
+    pub fn spawn(mut scheduler: Scheduler) -> Self {
+        let specs = scheduler.specs().to_vec();
+        let class_names = scheduler.class_names();
+        let postprocessor = scheduler.postprocessor();
+        let (jobs, job_rx) = mpsc::sync_channel::<Job<()>>(1);
+        let (crop_tx, crop_rx) = mpsc::sync_channel(1);
+        let (output_tx, output_rx) = mpsc::sync_channel(1);
+        let (result_tx, results) = mpsc::sync_channel(BUFFERS);
+        // Each stage ends once the one before it does, and the first once
+        // the worker is dropped and the job channel closes.
+        let preprocess = thread::spawn(move || {
+            for job in job_rx {
+                let _span = debug_span!("preprocess").entered();
+                let job = job.then(|job| Scheduler::crop(&job.frame, job.roi));
+                if crop_tx.send(job).is_err() {
+                    break;
+                }
+            }
+        });
+        let inference = thread::spawn(move || {
+            for mut job in crop_rx {
+                let _span = debug_span!("inference").entered();
+                let crop = job.data.take();
+                let job =
+                    job.then(|job| scheduler.infer(&job.frame, crop, job.roi, &job.thresholds));
+                if output_tx.send(job).is_err() {
+                    break;
+                }
+            }
+        });
+        let postprocess = thread::spawn(move || {
+            for job in output_rx {
+                let _span = debug_span!("postprocess").entered();
+                let job = job.then(|job| postprocessor.detections(&job.data, &job.thresholds));
+                let finished = Finished {
+                    detections: job.data,
+                    at: job.at,
+                    elapsed: job.elapsed,
+                    frame: job.frame,
+                };
+                if result_tx.send(finished).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            specs,
+            class_names,
+            jobs,
+            results,
+            in_flight: 0,
+            free: (0..BUFFERS).map(|_| RgbImage::new(0, 0)).collect(),
+            threads: vec![preprocess, inference, postprocess],
+            failed: None,
+        }
+    }
+
+    pub fn specs(&self) -> &[PipelineSpec] {
+        &self.specs
+    }
+
+    pub fn class_names(&self) -> Arc<Vec<String>> {
+        self.class_names.clone()
+    }
+
+    /// Whether any submitted frame isn't finished yet.
+    pub fn busy(&self) -> bool {
+        self.in_flight > 0
+    }
+
+    /// What the pipelines panicked with, if they did. The worker detects
+    /// nothing more after that.
+    pub fn failed(&self) -> Option<&str> {
+        self.failed.as_deref()
+    }
+
+    /// Starts detection on a copy of `frame`, restricted to `roi` if set,
+    /// unless every buffer of the ring is in use or the preprocessing stage
+    /// hasn't taken the previous frame yet.
+    pub fn submit(
+        &mut self,
+        frame: &RgbImage,
+        roi: Option<BBox>,
+        thresholds: Thresholds,
+        at: Instant,
+    ) -> bool {
+        let Some(mut copy) = self.free.pop() else {
+            return false;
+        };
+        copy.clone_from(frame);
+        let job = Job {
+            frame: copy,
+            roi,
+            thresholds,
+            at,
+            elapsed: Duration::ZERO,
+            data: (),
+        };
+        match self.jobs.try_send(job) {
+            Ok(()) => {
+                self.in_flight += 1;
+                true
+            }
+            Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) => {
+                self.free.push(job.frame);
+                false
+            }
+        }
+    }
+
+    /// Result of the newest finished frame, if any finished since the last
+    /// call. Older ones are dropped.
+    pub fn poll(&mut self) -> Option<Finished> {
+        let mut newest = None;
+        loop {
+            match self.results.try_recv() {
+                Ok(mut finished) => {
+                    self.in_flight -= 1;
+                    self.free
+                        .push(std::mem::replace(&mut finished.frame, RgbImage::new(0, 0)));
+                    newest = Some(finished);
+                }
+                Err(TryRecvError::Empty) => return newest,
+                Err(TryRecvError::Disconnected) => {
+                    // A stage panicked; don't wait for it forever.
+                    self.in_flight = 0;
+                    self.collect_panic();
+                    return newest;
+                }
+            }
+        }
+    }
+
+    /// Logs and keeps what a stage panicked with. Stages before it may
+    /// still wait for frames, so only finished ones are joined.
+    fn collect_panic(&mut self) {
+        let (finished, running) = std::mem::take(&mut self.threads)
+            .into_iter()
+            .partition::<Vec<_>, _>(JoinHandle::is_finished);
+        self.threads = running;
+        for thread in finished {
+            if let Err(payload) = thread.join() {
+                let message = panic_message(&*payload);
+                error!("Detection worker panicked: {}", message);
+                self.failed = Some(message);
+            }
+        }
+    }
+}
+
+/// The message a panic was raised with, if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked without a message".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_come_back_through_every_stage() {
+        let mut worker = DetectionWorker::spawn(Scheduler::new(vec![]));
+        let frame = RgbImage::new(8, 8);
+        let at = Instant::now();
+        assert!(worker.submit(&frame, None, Thresholds::default(), at));
+        assert!(worker.busy());
+        let finished = loop {
+            if let Some(finished) = worker.poll() {
+                break finished;
+            }
+            assert!(at.elapsed() < Duration::from_secs(10));
+            thread::yield_now();
+        };
+        assert_eq!(finished.at, at);
+        assert!(!worker.busy());
+        // The buffer went back into the ring.
+        assert_eq!(worker.free.len(), BUFFERS);
+        assert!(worker.failed().is_none());
+    }
+}
@@ -0,0 +1,146 @@
+//! YOLOv8 / YOLO11 object detection, as exported to ONNX by Ultralytics.
+
+use image::RgbImage;
+use ndarray::Ix3;
+use serde::Serialize;
+use std::{path::Path, sync::Arc};
+use tracing::{debug_span, error};
+
+use crate::{
+    bbox::{BBox, Detection},
+    inference::{self, Session},
+    nms::{class_aware_non_max_suppression, weighted_bbox, NmsMode},
+    preprocess::{Letterbox, Normalization, Resizer},
+};
+
+const INPUT_SIZE: usize = 640;
+
+/// Gray the letterbox padding is filled with, as in Ultralytics' own
+/// preprocessing.
+const PAD: f32 = 114.0 / 255.0;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Object {
+    pub bbox: BBox,
+    /// Index into the detector's [`YoloDetector::labels`].
+    pub class: usize,
+    pub score: f32,
+}
+
+impl Detection for Object {
+    fn bbox(&self) -> BBox {
+        self.bbox
+    }
+
+    fn score(&self) -> f32 {
+        self.score
+    }
+
+    fn with_score(self, score: f32) -> Self {
+        Self { score, ..self }
+    }
+
+    fn blend(cluster: &[Self]) -> Self {
+        Self {
+            bbox: weighted_bbox(cluster),
+            ..cluster[0]
+        }
+    }
+}
+
+impl Object {
+    pub fn shift(&self, x: f32, y: f32) -> Self {
+        Self {
+            bbox: self.bbox.shift(x, y),
+            ..*self
+        }
+    }
+}
+
+pub struct YoloDetector {
+    session: Session,
+    labels: Arc<Vec<String>>,
+    letterbox: Letterbox,
+    resizer: Resizer,
+}
+
+impl YoloDetector {
+    /// `labels_path` holds one class name per line, in class index order.
+    pub fn new(
+        model_path: impl AsRef<Path>,
+        labels_path: impl AsRef<Path>,
+    ) -> Result<Self, String> {
+        let labels_path = labels_path.as_ref();
+        let labels = std::fs::read_to_string(labels_path)
+            .map(|text| {
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_else(|e| {
+                error!("Failed to read labels {}: {}", labels_path.display(), e);
+                vec![]
+            });
+        let letterbox = Letterbox::fit(INPUT_SIZE as u32, INPUT_SIZE as u32, INPUT_SIZE);
+        Ok(Self {
+            session: inference::create_session(model_path)?,
+            labels: Arc::new(labels),
+            resizer: Resizer::new(letterbox.width, letterbox.height),
+            letterbox,
+        })
+    }
+
+    /// Class names. Classes beyond the label file are shown by index.
+    pub fn labels(&self) -> Arc<Vec<String>> {
+        self.labels.clone()
+    }
+
+    pub fn detect(
+        &mut self,
+        img: &RgbImage,
+        score_threshold: f32,
+        iou_threshold: f32,
+        nms: NmsMode,
+    ) -> Vec<Object> {
+        let letterbox = Letterbox::fit(img.width(), img.height(), INPUT_SIZE);
+        if letterbox != self.letterbox {
+            self.letterbox = letterbox;
+            self.resizer = Resizer::new(letterbox.width, letterbox.height);
+        }
+        let resized = self.resizer.resize(img, Normalization::UNIT);
+        let input = letterbox.pad(resized.view(), PAD);
+        let mut outputs = inference::run(&*self.session, input);
+
+        let _span = debug_span!("postprocess").entered();
+        // (1, 4 + classes, candidates): box center and size in input
+        // pixels, then one score per class.
+        let output = outputs.swap_remove(0).into_dimensionality::<Ix3>().unwrap();
+        let classes = output.shape()[1] - 4;
+        let objects = (0..output.shape()[2])
+            .filter_map(|i| {
+                let (class, score) = (0..classes)
+                    .map(|c| (c, output[(0, 4 + c, i)]))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))?;
+                if score < score_threshold {
+                    return None;
+                }
+                let (w, h) = (output[(0, 2, i)], output[(0, 3, i)]);
+                let bbox = BBox {
+                    x: output[(0, 0, i)] - w / 2.0,
+                    y: output[(0, 1, i)] - h / 2.0,
+                    w,
+                    h,
+                };
+                Some(Object {
+                    bbox: letterbox.unmap(bbox, img.width(), img.height()),
+                    class,
+                    score,
+                })
+            })
+            .collect();
+
+        class_aware_non_max_suppression(objects, |o| o.class, nms, score_threshold, iou_threshold)
+    }
+}
@@ -0,0 +1,354 @@
+//! Models known to work with the pipelines, with what they take and give,
+//! to download and switch to from one place. A model is checked against
+//! what its pipeline expects before it is used, so a wrong export shows up
+//! as a message rather than as a panic mid-frame. Model files set by path
+//! are checked the same way before their pipelines load.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+use crate::{
+    inference::{self, ModelShapes},
+    models::{
+        ModelManager, ModelStatus, FACE_DETECTION_SHORT_RANGE, HAND_LANDMARK_FULL,
+        PALM_DETECTION_FULL, PALM_DETECTION_LITE, POSE_DETECTION, POSE_LANDMARKS_FULL,
+    },
+    pipeline::{PipelineKind, PipelineSpec, Precision},
+};
+
+pub struct ZooModel {
+    pub name: &'static str,
+    /// File the model manager knows it by.
+    pub file_name: &'static str,
+    /// Model file of `pipeline` this one is used as, which is its own file
+    /// unless it stands in for another model.
+    pub replaces: &'static str,
+    pub pipeline: PipelineKind,
+    /// Width and height of the NHWC input.
+    pub input: (usize, usize),
+    /// Values per frame of the outputs the pipeline reads, whatever their
+    /// shape.
+    pub outputs: &'static [usize],
+    pub description: &'static str,
+    pub license: &'static str,
+    /// Rough size of the file, in megabytes.
+    pub size: f32,
+}
+
+pub static MODELS: [ZooModel; 6] = [
+    ZooModel {
+        name: "Palm detection lite",
+        file_name: PALM_DETECTION_LITE,
+        replaces: PALM_DETECTION_LITE,
+        pipeline: PipelineKind::Palm(Precision::Fp32),
+        input: (192, 192),
+        // 2016 anchors, each with a box, 7 keypoints and a score.
+        outputs: &[2016 * 18, 2016],
+        description: "Palm boxes with 7 keypoints",
+        license: "Apache-2.0",
+        size: 2.0,
+    },
+    ZooModel {
+        name: "Palm detection full",
+        file_name: PALM_DETECTION_FULL,
+        replaces: PALM_DETECTION_LITE,
+        pipeline: PipelineKind::Palm(Precision::Fp32),
+        input: (192, 192),
+        outputs: &[2016 * 18, 2016],
+        description: "Palm boxes with 7 keypoints, more accurate and slower than lite",
+        license: "Apache-2.0",
+        size: 4.6,
+    },
+    ZooModel {
+        name: "Hand landmarks full",
+        file_name: HAND_LANDMARK_FULL,
+        replaces: HAND_LANDMARK_FULL,
+        pipeline: PipelineKind::Hands(Precision::Fp32),
+        input: (224, 224),
+        // 21 landmarks with depth, then presence and handedness.
+        outputs: &[21 * 3, 1],
+        description: "21 landmarks with depth, presence and handedness per hand",
+        license: "Apache-2.0",
+        size: 5.5,
+    },
+    ZooModel {
+        name: "Face detection short range",
+        file_name: FACE_DETECTION_SHORT_RANGE,
+        replaces: FACE_DETECTION_SHORT_RANGE,
+        pipeline: PipelineKind::FaceMesh,
+        input: (128, 128),
+        // 896 anchors, each with a box, 6 keypoints and a score.
+        outputs: &[896 * 16, 896],
+        description: "Face boxes with 6 keypoints, within 2 m of the camera",
+        license: "Apache-2.0",
+        size: 0.4,
+    },
+    ZooModel {
+        name: "Pose detection",
+        file_name: POSE_DETECTION,
+        replaces: POSE_DETECTION,
+        pipeline: PipelineKind::Pose,
+        input: (224, 224),
+        // 2254 anchors, each with a box, 4 keypoints and a score.
+        outputs: &[2254 * 12, 2254],
+        description: "Person boxes with hip and body circle keypoints",
+        license: "Apache-2.0",
+        size: 3.0,
+    },
+    ZooModel {
+        name: "Pose landmarks full",
+        file_name: POSE_LANDMARKS_FULL,
+        replaces: POSE_LANDMARKS_FULL,
+        pipeline: PipelineKind::Pose,
+        input: (256, 256),
+        // 33 landmarks and 6 auxiliary ones, with depth, visibility and
+        // presence.
+        outputs: &[39 * 5],
+        description: "33 body landmarks with visibility",
+        license: "Apache-2.0",
+        size: 6.4,
+    },
+];
+
+impl ZooModel {
+    /// Whether a model of `shapes` takes the input and gives the outputs
+    /// the pipeline expects, and what is off if not.
+    pub fn check(&self, shapes: &ModelShapes) -> Result<(), String> {
+        let [input] = &shapes.inputs[..] else {
+            return Err(format!("has {} inputs, expected one", shapes.inputs.len()));
+        };
+        let (w, h) = self.input;
+        let expected = [1, h as i64, w as i64, 3];
+        let fits = input.len() == expected.len()
+            && input
+                .iter()
+                .zip(expected)
+                .all(|(&actual, expected)| actual < 0 || actual == expected);
+        if !fits {
+            return Err(format!(
+                "takes {}, expected {}",
+                shape(input),
+                shape(&expected)
+            ));
+        }
+        let lens = shapes.outputs.iter().map(|o| len(o)).collect::<Vec<_>>();
+        for &expected in self.outputs {
+            if !lens
+                .iter()
+                .any(|&len| len.is_none_or(|len| len == expected))
+            {
+                return Err(format!(
+                    "has no output of {} values, only {}",
+                    expected,
+                    shapes
+                        .outputs
+                        .iter()
+                        .map(|o| shape(o))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The model of [`MODELS`] whose inputs and outputs a model used as
+/// `file_name` has to have, if there is one. Palm models of every
+/// precision have those of the fp32 one.
+pub fn expected(file_name: &str) -> Option<&'static ZooModel> {
+    let file_name = if Precision::ALL.iter().any(|p| p.palm_model() == file_name) {
+        PALM_DETECTION_LITE
+    } else {
+        file_name
+    };
+    MODELS.iter().find(|model| model.file_name == file_name)
+}
+
+/// Checks the model files `paths` of `specs`, as given for their
+/// [`PipelineKind::models`], against what their pipelines expect. Files no
+/// model of [`MODELS`] describes pass.
+pub fn check_files(specs: &[PipelineSpec], paths: &[Vec<PathBuf>]) -> Result<(), String> {
+    let mut checked: Vec<&Path> = vec![];
+    for (spec, paths) in specs.iter().zip(paths) {
+        for (file_name, path) in spec.kind.models().into_iter().zip(paths) {
+            if checked.contains(&path.as_path()) {
+                continue;
+            }
+            checked.push(path);
+            if let Some(model) = expected(file_name) {
+                inference::model_shapes(path)
+                    .and_then(|shapes| model.check(&shapes))
+                    .map_err(|e| format!("{} as {}: {}", path.display(), file_name, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Values per frame of an output of `dimensions`, or `None` if dimensions
+/// other than the batch are dynamic.
+fn len(dimensions: &[i64]) -> Option<usize> {
+    let (_batch, rest) = dimensions.split_first()?;
+    rest.iter().map(|&d| usize::try_from(d).ok()).product()
+}
+
+/// `dimensions` as e.g. "1×192×192×3", with "?" for dynamic ones.
+fn shape(dimensions: &[i64]) -> String {
+    dimensions
+        .iter()
+        .map(|&d| if d < 0 { "?".to_owned() } else { d.to_string() })
+        .collect::<Vec<_>>()
+        .join("×")
+}
+
+/// Checks downloaded models of [`MODELS`], and the one to switch to once
+/// it is ready.
+#[derive(Default)]
+pub struct ModelZoo {
+    /// Results of [`ZooModel::check`] by path, as loading a model to check
+    /// it takes a moment.
+    checked: HashMap<PathBuf, Result<(), String>>,
+    /// Index of the model to switch to.
+    pending: Option<usize>,
+}
+
+impl ModelZoo {
+    fn check(&mut self, model: &ZooModel, path: &PathBuf) -> &Result<(), String> {
+        self.checked.entry(path.clone()).or_insert_with(|| {
+            inference::model_shapes(path).and_then(|shapes| model.check(&shapes))
+        })
+    }
+
+    /// The model to switch to and its path, once it downloaded and passed
+    /// the check. Models that fail it are logged and dropped.
+    pub fn switch(&mut self, models: &ModelManager) -> Option<(&'static ZooModel, PathBuf)> {
+        let model = &MODELS[self.pending?];
+        match models.status(model.file_name)? {
+            ModelStatus::Ready(path) => {
+                self.pending = None;
+                match self.check(model, &path) {
+                    Ok(()) => Some((model, path)),
+                    Err(e) => {
+                        error!("Failed to use {}: {} {}", model.name, path.display(), e);
+                        None
+                    }
+                }
+            }
+            ModelStatus::Failed(_) | ModelStatus::NotDownloadable => {
+                self.pending = None;
+                None
+            }
+            ModelStatus::Missing | ModelStatus::Downloading { .. } => None,
+        }
+    }
+
+    /// Lists the models of [`MODELS`] that are downloaded or can be.
+    pub fn ui(&mut self, ui: &mut egui::Ui, models: &mut ModelManager) {
+        for (i, model) in MODELS.iter().enumerate() {
+            let status = models.status(model.file_name);
+            if let Some(ModelStatus::NotDownloadable) = status {
+                continue;
+            }
+            ui.strong(model.name);
+            ui.label(model.description);
+            ui.weak(format!(
+                "{}×{} input, {}, {}, about {:.1} MB",
+                model.input.0,
+                model.input.1,
+                model.pipeline.name(),
+                model.license,
+                model.size
+            ));
+            match &status {
+                Some(ModelStatus::Ready(path)) => match self.check(model, path) {
+                    Ok(()) => {
+                        ui.label("Downloaded, inputs and outputs match");
+                    }
+                    Err(e) => {
+                        ui.colored_label(egui::Color32::RED, format!("Doesn't fit: {}", e));
+                    }
+                },
+                Some(ModelStatus::Downloading { received, .. }) => {
+                    ui.label(format!("Downloading, {:.1} MB", *received as f32 / 1e6));
+                }
+                Some(ModelStatus::Failed(e)) => {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+                Some(ModelStatus::Missing | ModelStatus::NotDownloadable) | None => {}
+            }
+            let label = match status {
+                Some(ModelStatus::Ready(_)) => "Use",
+                _ => "Download and use",
+            };
+            let waiting = self.pending == Some(i);
+            if ui.add_enabled(!waiting, egui::Button::new(label)).clicked() {
+                // Failed downloads are only retried from the "Models"
+                // section, where their URL can be fixed.
+                models.require(model.file_name);
+                self.pending = Some(i);
+            }
+            ui.separator();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_files_are_expected_to_match_their_zoo_model() {
+        let name = |file_name| expected(file_name).map(|model| model.name);
+        assert_eq!(name(PALM_DETECTION_LITE), Some("Palm detection lite"));
+        assert_eq!(
+            name(Precision::Int8.palm_model()),
+            Some("Palm detection lite")
+        );
+        assert_eq!(name(POSE_DETECTION), Some("Pose detection"));
+        assert_eq!(name(crate::models::IRIS_LANDMARK), None);
+    }
+
+    #[test]
+    fn shapes_are_checked_against_the_pipeline() {
+        let palm = &MODELS[0];
+        let shapes = |input: Vec<i64>, outputs: Vec<Vec<i64>>| ModelShapes {
+            inputs: vec![input],
+            outputs,
+        };
+        let outputs = vec![vec![1, 2016, 18], vec![1, 2016, 1]];
+        assert_eq!(
+            palm.check(&shapes(vec![1, 192, 192, 3], outputs.clone())),
+            Ok(())
+        );
+        // A dynamic batch is fine too.
+        assert_eq!(
+            palm.check(&shapes(vec![-1, 192, 192, 3], outputs.clone())),
+            Ok(())
+        );
+        assert_eq!(
+            palm.check(&shapes(vec![-1, 3, 192, 192], outputs.clone())),
+            Err("takes ?×3×192×192, expected 1×192×192×3".to_owned())
+        );
+        let pose = vec![vec![1, 2254, 12], vec![1, 2254, 1]];
+        assert!(palm.check(&shapes(vec![1, 192, 192, 3], pose)).is_err());
+        // Outputs of dynamic length can't be told apart, so they pass.
+        let dynamic = vec![vec![1, -1, 18], vec![1, -1, 1]];
+        assert_eq!(palm.check(&shapes(vec![1, 192, 192, 3], dynamic)), Ok(()));
+        let pose_landmarks = &MODELS[5];
+        let outputs = vec![
+            vec![1, 195],
+            vec![1, 1],
+            vec![1, 256, 256, 1],
+            vec![1, 64, 64, 39],
+            vec![1, 117],
+        ];
+        assert_eq!(
+            pose_landmarks.check(&shapes(vec![1, 256, 256, 3], outputs)),
+            Ok(())
+        );
+    }
+}
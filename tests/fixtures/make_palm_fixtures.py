@@ -0,0 +1,134 @@
+"""Writes the palm postprocessing fixtures used by tests/postprocess.rs.
+
+Raw outputs of palm_detection_lite for a 192x192 input, with palms placed on
+known anchors, and the palms expected after decoding, thresholding, NMS,
+scaling to a 640x480 frame and shifting by an ROI offset. Some palms sit just
+either side of the score threshold, and some overlap a kept palm by just
+under and just over the NMS IoU threshold. Expected values
+are worked out here from the anchor layout rather than taken from the Rust
+code, so they catch regressions in it. Needs only the standard library.
+"""
+
+import json
+import math
+import struct
+from pathlib import Path
+
+INPUT = 192
+FRAME = (640, 480)
+ROI_OFFSET = (10.0, 20.0)
+ANCHORS = 2016
+STRIDE = 18
+SCORE_THRESHOLD = 0.6
+IOU_THRESHOLD = 0.25
+# Raw scores are logits, which the decoder turns into probabilities.
+BACKGROUND_SCORE = -5.0
+THRESHOLD_LOGIT = math.log(SCORE_THRESHOLD / (1 - SCORE_THRESHOLD))
+EPSILON = 1e-3
+# Horizontal offset at which two 40x40 boxes overlap by exactly the IoU
+# threshold: (40 - d) / (40 + d) = IOU_THRESHOLD.
+NMS_OFFSET = 40 * (1 - IOU_THRESHOLD) / (1 + IOU_THRESHOLD)
+
+
+def anchor_center(i):
+    """Center of anchor `i` in input pixels: 24x24 cells of 2 anchors at
+    stride 8, then 12x12 cells of 6 anchors at stride 16."""
+    if i < 24 * 24 * 2:
+        cell, cols = i // 2, 24
+    else:
+        cell, cols = (i - 24 * 24 * 2) // 6, 12
+    x, y = cell % cols, cell // cols
+    return (x + 0.5) / cols * INPUT, (y + 0.5) / cols * INPUT
+
+
+# Keypoints relative to the box center: wrist, index, middle, ring and pinky
+# bases, thumb base and thumb tip. Fingers up with the index on the right is
+# a right hand facing the camera.
+RIGHT_TIPS = [(0, 15), (8, -10), (0, -12), (-5, -11), (-10, -6), (12, 8), (16, 0)]
+LEFT_TIPS = [(-x, y) for x, y in RIGHT_TIPS]
+
+# Anchor, box offset and size, keypoints, raw score.
+PALMS = [
+    # Kept.
+    (252, (2, -4, 40, 44), RIGHT_TIPS, 3.0),
+    # Same cell and nearly the same box, less confident: suppressed by NMS.
+    (253, (3, -3, 40, 44), RIGHT_TIPS, 2.0),
+    # Kept.
+    (1488, (-6, 8, 64, 60), LEFT_TIPS, 1.5),
+    # Well below the score threshold.
+    (700, (0, 0, 30, 30), RIGHT_TIPS, -1.0),
+    # Just above the score threshold: kept.
+    (136, (0, 0, 12, 12), RIGHT_TIPS, THRESHOLD_LOGIT + EPSILON),
+    # Just below it: dropped.
+    (232, (0, 0, 12, 12), RIGHT_TIPS, THRESHOLD_LOGIT - EPSILON),
+    # Kept, with two less confident palms in the same cell beside it.
+    (1920, (0, 0, 40, 40), RIGHT_TIPS, 2.5),
+    # Overlapping it by just under the IoU threshold: kept.
+    (1922, (NMS_OFFSET + 0.2, 0, 40, 40), RIGHT_TIPS, 2.2),
+    # Overlapping it by just over the IoU threshold: suppressed.
+    (1921, (-NMS_OFFSET + 0.2, 0, 40, 40), RIGHT_TIPS, 2.0),
+]
+KEPT = [
+    (PALMS[0], "R"),
+    (PALMS[2], "L"),
+    (PALMS[4], "R"),
+    (PALMS[6], "R"),
+    (PALMS[7], "R"),
+]
+
+
+def sigmoid(x):
+    return 1 / (1 + math.exp(-x))
+
+
+def write_npy(path, shape, values):
+    header = "{'descr': '<f4', 'fortran_order': False, 'shape': %s, }" % (tuple(shape),)
+    # Magic, version and header length take 10 bytes; pad to 64 with a newline.
+    header += " " * (63 - (10 + len(header)) % 64) + "\n"
+    with open(path, "wb") as f:
+        f.write(b"\x93NUMPY\x01\x00" + struct.pack("<H", len(header)) + header.encode())
+        f.write(struct.pack("<%df" % len(values), *values))
+
+
+def main():
+    out = Path(__file__).parent
+    regressors = [0.0] * (ANCHORS * STRIDE)
+    scores = [BACKGROUND_SCORE] * ANCHORS
+    for anchor, (dx, dy, w, h), tips, score in PALMS:
+        row = [dx, dy, w, h] + [v for tip in tips for v in tip]
+        regressors[anchor * STRIDE:(anchor + 1) * STRIDE] = row
+        scores[anchor] = score
+    write_npy(out / "palm_regressors.npy", (1, ANCHORS, STRIDE), regressors)
+    write_npy(out / "palm_scores.npy", (1, ANCHORS, 1), scores)
+
+    sx, sy = FRAME[0] / INPUT, FRAME[1] / INPUT
+    ox, oy = ROI_OFFSET
+    expected = []
+    # NMS keeps palms from the most confident down.
+    kept = sorted(KEPT, key=lambda k: -k[0][3])
+    for (anchor, (dx, dy, w, h), tips, score), handedness in kept:
+        ax, ay = anchor_center(anchor)
+        cx, cy = ax + dx, ay + dy
+        expected.append({
+            "bbox": {
+                "x": (cx - w / 2) * sx + ox,
+                "y": (cy - h / 2) * sy + oy,
+                "w": w * sx,
+                "h": h * sy,
+            },
+            "tips": [[(cx + tx) * sx + ox, (cy + ty) * sy + oy] for tx, ty in tips],
+            "score": sigmoid(score),
+            "handedness": handedness,
+        })
+    fixture = {
+        "frame": FRAME,
+        "roi_offset": ROI_OFFSET,
+        "score_threshold": SCORE_THRESHOLD,
+        "iou_threshold": IOU_THRESHOLD,
+        "palms": expected,
+    }
+    (out / "palm_expected.json").write_text(json.dumps(fixture, indent=2) + "\n")
+
+
+if __name__ == "__main__":
+    main()
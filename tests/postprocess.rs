@@ -0,0 +1,142 @@
+//! Golden tests for palm postprocessing: fixed raw model outputs through
+//! anchor decoding, score thresholding, NMS, scaling and ROI shifting.
+//! `tests/fixtures/make_palm_fixtures.py` writes the fixtures and works out
+//! the expected palms independently of this crate.
+
+use ai_playground::{
+    anchors::AnchorConfig,
+    nms::{non_max_suppression, NmsMode},
+    palm,
+};
+use ndarray::{Array3, ArrayD, IxDyn};
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Reads a little-endian `f32` `.npy` file in C order.
+fn read_npy(path: &Path) -> ArrayD<f32> {
+    let bytes = std::fs::read(path).unwrap();
+    assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00", "{}", path.display());
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+    assert!(header.contains("'descr': '<f4'"), "{}", header);
+    assert!(header.contains("'fortran_order': False"), "{}", header);
+    let shape = header
+        .split_once("'shape': (")
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .unwrap()
+        .0
+        .split(',')
+        .filter_map(|dim| dim.trim().parse().ok())
+        .collect::<Vec<usize>>();
+    let data = bytes[10 + header_len..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    ArrayD::from_shape_vec(IxDyn(&shape), data).unwrap()
+}
+
+fn read_outputs() -> (Array3<f32>, Array3<f32>) {
+    let regressors = read_npy(&fixture("palm_regressors.npy"));
+    let scores = read_npy(&fixture("palm_scores.npy"));
+    (
+        regressors.into_dimensionality().unwrap(),
+        scores.into_dimensionality().unwrap(),
+    )
+}
+
+fn close(actual: f32, expected: &serde_json::Value) -> bool {
+    (actual - expected.as_f64().unwrap() as f32).abs() < 1e-3
+}
+
+#[test]
+fn palms_match_golden_file() {
+    let expected: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(fixture("palm_expected.json")).unwrap())
+            .unwrap();
+    let frame = (
+        expected["frame"][0].as_u64().unwrap() as u32,
+        expected["frame"][1].as_u64().unwrap() as u32,
+    );
+    let offset = &expected["roi_offset"];
+    let (dx, dy) = (
+        offset[0].as_f64().unwrap() as f32,
+        offset[1].as_f64().unwrap() as f32,
+    );
+
+    let config = AnchorConfig::palm_detection();
+    let (regressors, scores) = read_outputs();
+    let palms = palm::postprocess(
+        regressors.view(),
+        scores.view(),
+        &config.generate(),
+        (config.input_width, config.input_height),
+        frame,
+        (
+            expected["score_threshold"].as_f64().unwrap() as f32,
+            expected["iou_threshold"].as_f64().unwrap() as f32,
+        ),
+        NmsMode::Hard,
+    )
+    .iter()
+    .map(|palm| palm.shift(dx, dy))
+    .collect::<Vec<_>>();
+
+    let expected = expected["palms"].as_array().unwrap();
+    assert_eq!(palms.len(), expected.len(), "{:?}", palms);
+    for (palm, want) in palms.iter().zip(expected) {
+        let b = palm.bbox;
+        let bbox = &want["bbox"];
+        assert!(
+            close(b.x, &bbox["x"])
+                && close(b.y, &bbox["y"])
+                && close(b.w, &bbox["w"])
+                && close(b.h, &bbox["h"]),
+            "{:?} != {}",
+            b,
+            bbox
+        );
+        assert!(close(palm.score, &want["score"]));
+        for (tip, want) in palm.tips.iter().zip(want["tips"].as_array().unwrap()) {
+            assert!(
+                close(tip.0, &want[0]) && close(tip.1, &want[1]),
+                "{:?} != {}",
+                tip,
+                want
+            );
+        }
+        assert_eq!(palm.handedness.label(), want["handedness"]);
+    }
+}
+
+#[test]
+fn threshold_drops_unconfident_anchors_before_nms() {
+    let config = AnchorConfig::palm_detection();
+    let (regressors, scores) = read_outputs();
+    let palms = palm::decode(
+        regressors.view(),
+        scores.view(),
+        &config.generate(),
+        (config.input_width, config.input_height),
+    );
+    assert_eq!(palms.len(), 2016);
+    // Nothing overlaps by more than everything, so only the score counts.
+    let confident = non_max_suppression(palms.clone(), 0.6, 1.1);
+    assert_eq!(confident.len(), 7);
+    // One palm is just above 0.6, and one just below.
+    let borderline = |palms: &[palm::Palm]| {
+        palms
+            .iter()
+            .filter(|palm| (palm.score - 0.6).abs() < 1e-3)
+            .count()
+    };
+    assert_eq!(borderline(&confident), 1);
+    let all = non_max_suppression(palms, 0.2, 1.1);
+    assert_eq!(all.len(), 9);
+    assert_eq!(borderline(&all), 2);
+}